@@ -205,7 +205,7 @@ pub mod models {
 }
 
 use bzauth_rs::adaptors::diesel::{DieselAdapterOptions, DieselAdaptor};
-use bzauth_rs::auth::AuthOptions;
+use bzauth_rs::auth::{AccountLinking, AuthOptions};
 use bzauth_rs::providers::discord::DiscordProvider;
 use diesel::SqliteConnection;
 use diesel::r2d2::{ConnectionManager, Pool};
@@ -227,7 +227,7 @@ bzauth_rs::adapt_diesel! {
 #[tokio::main]
 async fn main() {
     use axum::{Extension, Router};
-    use bzauth_rs::auth::AuthSessionOptions;
+    use bzauth_rs::auth::{AuthSessionOptions, SessionStrategy};
     use bzauth_rs::providers::GoogleProvider;
     use bzauth_rs::runtimes::axum::runtime::{AxumRuntime, AxumRuntimeOptions};
     use tokio::net::TcpListener;
@@ -258,10 +258,20 @@ async fn main() {
         ],
         callbacks: None,
         session: AuthSessionOptions {
-            strategy: Some("database".to_string()),
+            strategy: Some(SessionStrategy::Database),
             ..Default::default()
         }
         .into(),
+        secret: std::env::var("AUTH_SECRET").ok(),
+        // The routes are mounted at /auth, so the redirect URIs are /auth/callback/{provider}
+        base_url: Some(
+            std::env::var("AUTH_URL").unwrap_or_else(|_| "http://localhost:3000/auth".to_string()),
+        ),
+        trust_proxy: false,
+        // Both providers tell whether the email is verified, so a user can sign in with either
+        account_linking: AccountLinking::VerifiedEmail,
+        // Both providers revoke the tokens of unlinked accounts and deleted users
+        token_revocation: None,
     };
     let AxumRuntime { routes, auth } =
         AxumRuntime::from_options(AxumRuntimeOptions { auth_options });
@@ -1,9 +1,12 @@
+use std::sync::Arc;
+
+use diesel::QueryResult;
 use diesel::r2d2::{ManageConnection, Pool};
 
 use super::traits::*;
 use crate::contracts::adapt::{
-    Adapt, AdaptAccount, AdaptSession, AdaptUser, AdaptVerificationToken, CreateSessionOptions,
-    ProviderAccountId, SessionUser, UseVerificationTokenOptions,
+    Adapt, AdaptAccount, AdaptResult, AdaptSession, AdaptUser, AdaptVerificationToken,
+    CreateSessionOptions, ProviderAccountId, SessionUser, UseVerificationTokenOptions,
 };
 
 pub struct DieselAdapterOptions<
@@ -24,6 +27,8 @@ pub struct DieselAdapterOptions<
     pub adaptor: Adaptor,
 }
 
+/// Adapts a diesel connection pool. Diesel's queries are synchronous, so every operation runs
+/// on tokio's blocking thread pool rather than stalling the runtime's workers.
 pub struct DieselAdaptor<M, Adaptor, UserModel, AccountModel, SessionModel, VerificationTokenModel>
 where
     M: ManageConnection,
@@ -32,13 +37,16 @@ where
     Adaptor: AdaptSessionOperation<M::Connection, Model = SessionModel>,
     Adaptor: AdaptVerificationTokenOperation<M::Connection, Model = VerificationTokenModel>,
 {
-    pub options: DieselAdapterOptions<
-        M,
-        Adaptor,
-        UserModel,
-        AccountModel,
-        SessionModel,
-        VerificationTokenModel,
+    // Shared with the blocking tasks, which must own what they use
+    pub options: Arc<
+        DieselAdapterOptions<
+            M,
+            Adaptor,
+            UserModel,
+            AccountModel,
+            SessionModel,
+            VerificationTokenModel,
+        >,
     >,
 }
 
@@ -61,7 +69,29 @@ where
             VerificationTokenModel,
         >,
     ) -> Self {
-        Self { options }
+        Self {
+            options: Arc::new(options),
+        }
+    }
+
+    /// Runs an operation of the database adaptor on a blocking thread. The connection is taken
+    /// from the pool on that thread too, as waiting for one to free up blocks as well.
+    async fn run<T, F>(&self, operation: F) -> AdaptResult<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Adaptor, &mut M::Connection) -> QueryResult<T> + Send + 'static,
+        UserModel: 'static,
+        AccountModel: 'static,
+        SessionModel: 'static,
+        VerificationTokenModel: 'static,
+    {
+        let options = Arc::clone(&self.options);
+        tokio::task::spawn_blocking(move || {
+            // Grab a connection from the pool, failing if none frees up before the pool's timeout
+            let mut conn = options.conn_pool.get()?;
+            Ok(operation(&options.adaptor, &mut conn)?)
+        })
+        .await?
     }
 }
 
@@ -83,279 +113,186 @@ where
     VerificationTokenModel: From<AdaptVerificationToken> + Send + 'static,
     Adaptor: AdaptVerificationTokenOperation<M::Connection, Model = VerificationTokenModel>,
 {
-    async fn create_user(&self, user: AdaptUser) -> AdaptUser {
-        // Grab a connection from the pool
-        let mut conn = self
-            .options
-            .conn_pool
-            .clone()
-            .get()
-            .expect("Failed to get connection from pool");
-
-        // Borrow the database adaptor
-        let adaptor = &self.options.adaptor;
+    async fn create_user(&self, user: AdaptUser) -> AdaptResult<AdaptUser> {
+        let user = UserModel::from(user);
 
         // Create the user in the database
-        let new_user = adaptor.create_user(&mut conn, &user.into());
+        let new_user = self
+            .run(move |adaptor, conn| adaptor.create_user(conn, &user))
+            .await?;
 
         // Return the created user
-        AdaptUser::from(new_user)
+        Ok(AdaptUser::from(new_user))
     }
 
-    async fn get_user(&self, id: String) -> Option<AdaptUser> {
-        // Grab a connection from the pool
-        let mut conn = self
-            .options
-            .conn_pool
-            .clone()
-            .get()
-            .expect("Failed to get connection from pool");
-        // Borrow the database adaptor
-        let adaptor = &self.options.adaptor;
+    async fn get_user(&self, id: String) -> AdaptResult<Option<AdaptUser>> {
         // Get the user from the database
-        let user = adaptor.find_user_by_id(&mut conn, &id);
+        let user = self
+            .run(move |adaptor, conn| adaptor.find_user_by_id(conn, &id))
+            .await?;
         // Return the user
-        user.map(|user| AdaptUser::from(user))
+        Ok(user.map(AdaptUser::from))
     }
 
-    async fn get_user_by_email(&self, email: String) -> Option<AdaptUser> {
-        // Grab a connection from the pool
-        let mut conn = self
-            .options
-            .conn_pool
-            .clone()
-            .get()
-            .expect("Failed to get connection from pool");
-        // Borrow the database adaptor
-        let adaptor = &self.options.adaptor;
+    async fn get_user_by_email(&self, email: String) -> AdaptResult<Option<AdaptUser>> {
         // Get the user from the database
-        let user = adaptor.find_user_by_email(&mut conn, &email);
+        let user = self
+            .run(move |adaptor, conn| adaptor.find_user_by_email(conn, &email))
+            .await?;
         // Return the user
-        user.map(|user| AdaptUser::from(user))
+        Ok(user.map(AdaptUser::from))
     }
-    async fn get_user_by_account(&self, provider: ProviderAccountId) -> Option<AdaptUser> {
-        // Grab a connection from the pool
-        let mut conn = self
-            .options
-            .conn_pool
-            .clone()
-            .get()
-            .expect("Failed to get connection from pool");
-        // Borrow the database adaptor
-        let adaptor = &self.options.adaptor;
+    async fn get_user_by_account(
+        &self,
+        provider: ProviderAccountId,
+    ) -> AdaptResult<Option<AdaptUser>> {
         // Get the account joined with the user
-        let account = adaptor.find_user_by_account(
-            &mut conn,
-            provider.provider_id,
-            provider.provider_account_id,
-        );
+        let account = self
+            .run(move |adaptor, conn| {
+                adaptor.find_user_by_account(
+                    conn,
+                    provider.provider_id,
+                    provider.provider_account_id,
+                )
+            })
+            .await?;
 
         // let first = |(a, b)| a;
         let pick_last = |(_, b)| b;
 
         // Return the user
-        account.map(pick_last).map(|user| AdaptUser::from(user))
+        Ok(account.map(pick_last).map(AdaptUser::from))
     }
     /// Id is required
-    async fn update_user(&self, user: AdaptUser) -> AdaptUser {
-        // Grab a connection from the pool
-        let mut conn = self
-            .options
-            .conn_pool
-            .clone()
-            .get()
-            .expect("Failed to get connection from pool");
+    async fn update_user(&self, user: AdaptUser) -> AdaptResult<AdaptUser> {
+        let user = UserModel::from(user);
 
-        // Borrow the database adaptor
-        let adaptor = &self.options.adaptor;
         // Update the user in the database
-        let updated_user = adaptor.update_user(&mut conn, &user.into());
+        let updated_user = self
+            .run(move |adaptor, conn| adaptor.update_user(conn, &user))
+            .await?;
         // Return the updated user
-        AdaptUser::from(updated_user)
+        Ok(AdaptUser::from(updated_user))
     }
-    async fn delete_user(&self, id: String) -> () {
-        // Grab a connection from the pool
-        let mut conn = self
-            .options
-            .conn_pool
-            .clone()
-            .get()
-            .expect("Failed to get connection from pool");
-
-        // Borrow the database adaptor
-        let adaptor = &self.options.adaptor;
-
+    async fn delete_user(&self, id: String) -> AdaptResult<()> {
         // Delete the user from the database
-        adaptor.delete_user(&mut conn, &id);
-
-        // Return the user
-        // AdaptUser::from(updated_user)
-        ()
+        self.run(move |adaptor, conn| adaptor.delete_user(conn, &id))
+            .await
     }
 
-    async fn get_account(&self, provider: ProviderAccountId) -> Option<AdaptAccount> {
-        // Grab a connection from the pool
-        let mut conn = self
-            .options
-            .conn_pool
-            .clone()
-            .get()
-            .expect("Failed to get connection from pool");
-        // Borrow the database adaptor
-        let adaptor = &self.options.adaptor;
+    async fn get_account(&self, provider: ProviderAccountId) -> AdaptResult<Option<AdaptAccount>> {
         // Get the account from the database
-        let account = adaptor.find_account_by_id(
-            &mut conn,
-            provider.provider_id,
-            provider.provider_account_id,
-        );
+        let account = self
+            .run(move |adaptor, conn| {
+                adaptor.find_account_by_id(conn, provider.provider_id, provider.provider_account_id)
+            })
+            .await?;
         // Return the account
-        account.map(|account| AdaptAccount::from(account))
+        Ok(account.map(AdaptAccount::from))
     }
 
-    async fn link_account(&self, account: AdaptAccount) -> Option<AdaptAccount> {
-        // Grab a connection from the pool
-        let mut conn = self
-            .options
-            .conn_pool
-            .clone()
-            .get()
-            .expect("Failed to get connection from pool");
-        // Borrow the database adaptor
-        let adaptor = &self.options.adaptor;
+    async fn get_accounts_by_user(&self, user_id: String) -> AdaptResult<Vec<AdaptAccount>> {
+        // Get the accounts of the user from the database
+        let accounts = self
+            .run(move |adaptor, conn| adaptor.find_accounts_by_user(conn, user_id))
+            .await?;
+        Ok(accounts.into_iter().map(AdaptAccount::from).collect())
+    }
+
+    async fn link_account(&self, account: AdaptAccount) -> AdaptResult<AdaptAccount> {
+        let account = AccountModel::from(account);
+
         // Link the account in the database
-        let new_account = adaptor.link_account(&mut conn, &account.into());
+        let new_account = self
+            .run(move |adaptor, conn| adaptor.link_account(conn, &account))
+            .await?;
         // Return the linked account
-        Some(AdaptAccount::from(new_account))
+        Ok(AdaptAccount::from(new_account))
     }
-    async fn unlink_account(&self, provider: ProviderAccountId) -> () {
-        // Grab a connection from the pool
-        let mut conn = self
-            .options
-            .conn_pool
-            .clone()
-            .get()
-            .expect("Failed to get connection from pool");
-        // Borrow the database adaptor
-        let adaptor = &self.options.adaptor;
+    async fn update_account(&self, account: AdaptAccount) -> AdaptResult<AdaptAccount> {
+        let account = AccountModel::from(account);
+
+        // Update the token of the account in the database
+        let updated_account = self
+            .run(move |adaptor, conn| adaptor.update_account(conn, &account))
+            .await?;
+        // Return the updated account
+        Ok(AdaptAccount::from(updated_account))
+    }
+    async fn unlink_account(&self, provider: ProviderAccountId) -> AdaptResult<()> {
         // Unlink the account from the database
-        adaptor.unlink_account(
-            &mut conn,
-            provider.provider_id,
-            provider.provider_account_id,
-        );
-        // Return the account
-        // AdaptAccount::from(updated_user)
-        ()
+        self.run(move |adaptor, conn| {
+            adaptor.unlink_account(conn, provider.provider_id, provider.provider_account_id)
+        })
+        .await
     }
 
-    async fn create_session(&self, options: CreateSessionOptions) -> Option<AdaptSession> {
-        // Grab a connection from the pool
-        let mut conn = self
-            .options
-            .conn_pool
-            .clone()
-            .get()
-            .expect("Failed to get connection from pool");
-        // Borrow the database adaptor
-        let adaptor = &self.options.adaptor;
+    async fn create_session(&self, options: CreateSessionOptions) -> AdaptResult<AdaptSession> {
+        let session = SessionModel::from(AdaptSession {
+            token: options.token,
+            user_id: options.user_id,
+            expires_in: options.expires_in,
+        });
+
         // Create the session in the database
-        let new_session = adaptor.create_session(
-            &mut conn,
-            AdaptSession {
-                token: options.token,
-                user_id: options.user_id,
-                expires_in: options.expires_in,
-            }
-            .into(),
-        );
+        let new_session = self
+            .run(move |adaptor, conn| adaptor.create_session(conn, session))
+            .await?;
         // Return the created session
-        Some(AdaptSession::from(new_session))
+        Ok(AdaptSession::from(new_session))
     }
-    async fn get_session_and_user(&self, token: String) -> Option<SessionUser> {
-        // Grab a connection from the pool
-        let mut conn = self
-            .options
-            .conn_pool
-            .clone()
-            .get()
-            .expect("Failed to get connection from pool");
-        // Borrow the database adaptor
-        let adaptor = &self.options.adaptor;
+    async fn get_session_and_user(&self, token: String) -> AdaptResult<Option<SessionUser>> {
         // Get the session from the database
-        let session_user = adaptor.find_session_and_user(&mut conn, &token);
+        let session_user = self
+            .run(move |adaptor, conn| adaptor.find_session_and_user(conn, &token))
+            .await?;
         // Return the session and user
-        session_user
+        Ok(session_user
             .map(|(session, user)| (AdaptSession::from(session), AdaptUser::from(user)))
-            .map(|(session, user)| SessionUser { session, user })
+            .map(|(session, user)| SessionUser { session, user }))
     }
     /// session_token required
-    async fn update_session(&self, session: AdaptSession) -> AdaptSession {
-        // Grab a connection from the pool
-        let mut conn = self
-            .options
-            .conn_pool
-            .clone()
-            .get()
-            .expect("Failed to get connection from pool");
-        // Borrow the database adaptor
-        let adaptor = &self.options.adaptor;
+    async fn update_session(&self, session: AdaptSession) -> AdaptResult<AdaptSession> {
+        let session = SessionModel::from(session);
+
         // Update the session in the database
-        let updated_session = adaptor.update_session(&mut conn, session.into());
+        let updated_session = self
+            .run(move |adaptor, conn| adaptor.update_session(conn, session))
+            .await?;
         // Return the updated session
-        AdaptSession::from(updated_session)
+        Ok(AdaptSession::from(updated_session))
     }
-    async fn delete_session(&self, token: String) -> () {
-        // Grab a connection from the pool
-        let mut conn = self
-            .options
-            .conn_pool
-            .clone()
-            .get()
-            .expect("Failed to get connection from pool");
-        // Borrow the database adaptor
-        let adaptor = &self.options.adaptor;
+    async fn delete_session(&self, token: String) -> AdaptResult<()> {
         // Delete the session from the database
-        adaptor.delete_session(&mut conn, &token);
-        // Return the session
-        // AdaptSession::from(updated_session)
-        ()
+        self.run(move |adaptor, conn| adaptor.delete_session(conn, &token))
+            .await
     }
 
-    fn create_verification_token(&self, token: AdaptVerificationToken) -> AdaptVerificationToken {
-        // Grab a connection from the pool
-        let mut conn = self
-            .options
-            .conn_pool
-            .clone()
-            .get()
-            .expect("Failed to get connection from pool");
-        // Borrow the database adaptor
-        let adaptor = &self.options.adaptor;
+    async fn create_verification_token(
+        &self,
+        token: AdaptVerificationToken,
+    ) -> AdaptResult<AdaptVerificationToken> {
+        let token = VerificationTokenModel::from(token);
+
         // Create the verification token in the database
-        let new_token = adaptor.create_verification_token(&mut conn, token.into());
+        let new_token = self
+            .run(move |adaptor, conn| adaptor.create_verification_token(conn, token))
+            .await?;
         // Return the created verification token
-        AdaptVerificationToken::from(new_token)
+        Ok(AdaptVerificationToken::from(new_token))
     }
-    fn use_verification_token(
+    async fn use_verification_token(
         &self,
         options: UseVerificationTokenOptions,
-    ) -> Option<AdaptVerificationToken> {
-        // Grab a connection from the pool
-        let mut conn = self
-            .options
-            .conn_pool
-            .clone()
-            .get()
-            .expect("Failed to get connection from pool");
-        // Borrow the database adaptor
-        let adaptor = &self.options.adaptor;
+    ) -> AdaptResult<Option<AdaptVerificationToken>> {
         // Use the verification token in the database
-        /* let token =  */
-        adaptor.use_verification_token(&mut conn, options.email.as_str(), options.token.as_str());
+        let token = self
+            .run(move |adaptor, conn| {
+                adaptor.use_verification_token(conn, &options.email, &options.token)
+            })
+            .await?;
         // Return the used verification token
-        // token.map(|token| AdaptVerificationToken::from(token))
-        None
+        Ok(token.map(AdaptVerificationToken::from))
     }
 }
 
@@ -1,13 +1,81 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+use diesel::QueryResult;
+
+/// Prepares a connection before each operation of the adaptor
+pub trait PrepareConnection {
+    fn prepare(&mut self) {}
+}
+
+#[cfg(feature = "backend_sqlite")]
+impl PrepareConnection for diesel::SqliteConnection {
+    fn prepare(&mut self) {
+        use diesel::RunQueryDsl;
+
+        // Turn on foreign key constraints, swallowing the error if it fails
+        let _ = diesel::sql_query("PRAGMA foreign_keys = ON;").execute(self);
+    }
+}
+
+// Foreign key constraints are always on in Postgres
+#[cfg(feature = "backend_postgres")]
+impl PrepareConnection for diesel::PgConnection {}
+
+/// The types of the timestamp columns of a model. `Timestamp` columns are read as UTC
+/// `NaiveDateTime`s, and Postgres' `Timestamptz` columns as `DateTime<Utc>`s.
+pub trait DieselTimestamp {
+    fn from_utc(timestamp: DateTime<Utc>) -> Self;
+    fn to_utc(&self) -> DateTime<Utc>;
+}
+
+impl DieselTimestamp for NaiveDateTime {
+    fn from_utc(timestamp: DateTime<Utc>) -> Self {
+        timestamp.naive_utc()
+    }
+    fn to_utc(&self) -> DateTime<Utc> {
+        self.and_utc()
+    }
+}
+
+impl DieselTimestamp for DateTime<Utc> {
+    fn from_utc(timestamp: DateTime<Utc>) -> Self {
+        timestamp
+    }
+    fn to_utc(&self) -> DateTime<Utc> {
+        *self
+    }
+}
+
+/// The timestamp `expires_in` seconds from now
+pub fn expires_at<T: DieselTimestamp>(expires_in: u64) -> T {
+    let expires_in = chrono::Duration::try_seconds(expires_in.min(i64::MAX as u64) as i64)
+        .unwrap_or(chrono::Duration::MAX);
+    T::from_utc(
+        Utc::now()
+            .checked_add_signed(expires_in)
+            .unwrap_or(DateTime::<Utc>::MAX_UTC),
+    )
+}
+
+/// The current timestamp
+pub fn timestamp_now<T: DieselTimestamp>() -> T {
+    T::from_utc(Utc::now())
+}
+
+/// The seconds left until the timestamp, 0 once it has passed
+pub fn expires_in<T: DieselTimestamp>(expires_at: &T) -> u64 {
+    (expires_at.to_utc() - Utc::now()).num_seconds().max(0) as u64
+}
+
 pub trait AdaptUserOperation<C>
 where
     Self: Send + Sync + 'static,
 {
     type Model;
-    fn create_user(&self, conn: &mut C, user: &Self::Model) -> Self::Model;
-    fn find_user_by_id(&self, conn: &mut C, id: &str) -> Option<Self::Model>;
-    fn find_user_by_email(&self, conn: &mut C, email: &str) -> Option<Self::Model>;
-    fn update_user(&self, conn: &mut C, user: &Self::Model) -> Self::Model;
-    fn delete_user(&self, conn: &mut C, id: &str);
+    fn create_user(&self, conn: &mut C, user: &Self::Model) -> QueryResult<Self::Model>;
+    fn find_user_by_id(&self, conn: &mut C, id: &str) -> QueryResult<Option<Self::Model>>;
+    fn find_user_by_email(&self, conn: &mut C, email: &str) -> QueryResult<Option<Self::Model>>;
+    fn update_user(&self, conn: &mut C, user: &Self::Model) -> QueryResult<Self::Model>;
+    fn delete_user(&self, conn: &mut C, id: &str) -> QueryResult<()>;
 }
 
 // Doing this to hide the uuid dependency from the public API
@@ -25,6 +93,7 @@ macro_rules! adapt_diesel_user {
                     username: user.name,
                     email: user.email,
                     image: user.image,
+                    email_verified: user.email_verified.map(|_| true),
                 }
                 .into()
             }
@@ -35,10 +104,14 @@ macro_rules! adapt_diesel_user {
                 let user: $crate::contracts::user::User = user.into();
 
                 $model_type {
-                    id: user.id.unwrap(),
+                    id: user.id.unwrap_or_else($crate::adaptors::diesel::new_uuid),
                     name: user.username,
                     email: user.email,
                     image: user.image,
+                    email_verified: user
+                        .email_verified
+                        .filter(|verified| *verified)
+                        .map(|_| $crate::adaptors::diesel::timestamp_now()),
                     ..Default::default()
                 }
             }
@@ -46,7 +119,11 @@ macro_rules! adapt_diesel_user {
 
         impl $crate::adaptors::diesel::AdaptUserOperation<$connection> for $table_struct {
             type Model = $model_type;
-            fn create_user(&self, conn: &mut $connection, user: &Self::Model) -> Self::Model {
+            fn create_user(
+                &self,
+                conn: &mut $connection,
+                user: &Self::Model,
+            ) -> diesel::QueryResult<Self::Model> {
                 // Create a user using the connection
                 use diesel::ExpressionMethods;
                 use diesel::RunQueryDsl;
@@ -55,11 +132,8 @@ macro_rules! adapt_diesel_user {
                     use $table_type::dsl::*;
                 }
 
-                // Turn on foreign key constraints, swallowing the error if it fails
-                let _ = diesel::sql_query("PRAGMA foreign_keys = ON;")
-                    .execute(conn);
-
-                let now = chrono::Utc::now().naive_utc();
+                // Apply the settings of the backend, e.g. foreign key constraints on SQLite
+                $crate::adaptors::diesel::PrepareConnection::prepare(conn);
 
                 let to_insert = (
                     id.eq(user.id.clone()),
@@ -67,67 +141,66 @@ macro_rules! adapt_diesel_user {
                     image.eq(user.image.clone()),
                     name.eq(user.name.clone()),
                     email_verified.eq(user.email_verified.clone()),
-                    created_at.eq(now),
-                    updated_at.eq(now),
+                    created_at.eq(diesel::dsl::now),
+                    updated_at.eq(diesel::dsl::now),
                 );
 
-                let user = diesel::insert_into(paste::paste!($table_type::table))
+                diesel::insert_into(paste::paste!($table_type::table))
                     .values(&to_insert)
                     .returning(paste::paste!($model_type::as_returning()))
-                    /* .on_conflict(paste::paste!($table_type::email))
-                    .do_nothing() */
                     .get_result(conn)
-                    .unwrap();
-
-                user
             }
 
-            fn find_user_by_id(&self, conn: &mut $connection, id: &str) -> Option<Self::Model> {
+            fn find_user_by_id(
+                &self,
+                conn: &mut $connection,
+                user_id: &str,
+            ) -> diesel::QueryResult<Option<Self::Model>> {
                 // Find a user by ID using the connection
                 use diesel::ExpressionMethods;
+                use diesel::OptionalExtension;
                 use diesel::QueryDsl;
                 use diesel::RunQueryDsl;
                 paste::paste! {
                     use $table_type::dsl::*;
                 }
 
-                // Turn on foreign key constraints, swallowing the error if it fails
-                let _ = diesel::sql_query("PRAGMA foreign_keys = ON;")
-                    .execute(conn);
+                // Apply the settings of the backend, e.g. foreign key constraints on SQLite
+                $crate::adaptors::diesel::PrepareConnection::prepare(conn);
 
-                let user = paste::paste!($table_type::table)
-                    .filter(id.eq(id))
+                paste::paste!($table_type::table)
+                    .filter(id.eq(user_id))
                     .first::<Self::Model>(conn)
-                    .ok();
-
-                user
+                    .optional()
             }
 
             fn find_user_by_email(
                 &self,
                 conn: &mut $connection,
-                email: &str,
-            ) -> Option<Self::Model> {
+                user_email: &str,
+            ) -> diesel::QueryResult<Option<Self::Model>> {
                 // Find a user by email using the connection
                 use diesel::ExpressionMethods;
+                use diesel::OptionalExtension;
                 use diesel::QueryDsl;
                 use diesel::RunQueryDsl;
                 paste::paste! {
                     use $table_type::dsl::*;
                 }
 
-                // Turn on foreign key constraints, swallowing the error if it fails
-                let _ = diesel::sql_query("PRAGMA foreign_keys = ON;")
-                    .execute(conn);
+                // Apply the settings of the backend, e.g. foreign key constraints on SQLite
+                $crate::adaptors::diesel::PrepareConnection::prepare(conn);
 
-                let user = paste::paste!($table_type::table)
-                    .filter(email.eq(email))
+                paste::paste!($table_type::table)
+                    .filter(email.eq(user_email))
                     .first::<Self::Model>(conn)
-                    .ok();
-
-                user
+                    .optional()
             }
-            fn update_user(&self, conn: &mut $connection, user: &Self::Model) -> Self::Model {
+            fn update_user(
+                &self,
+                conn: &mut $connection,
+                user: &Self::Model,
+            ) -> diesel::QueryResult<Self::Model> {
                 // Update a user using the connection
                 use diesel::ExpressionMethods;
                 use diesel::QueryDsl;
@@ -136,29 +209,30 @@ macro_rules! adapt_diesel_user {
                     use $table_type::dsl::*;
                 }
 
-                // Turn on foreign key constraints, swallowing the error if it fails
-                let _ = diesel::sql_query("PRAGMA foreign_keys = ON;")
-                    .execute(conn);
-
-                let now = chrono::Utc::now().naive_utc();
+                // Apply the settings of the backend, e.g. foreign key constraints on SQLite
+                $crate::adaptors::diesel::PrepareConnection::prepare(conn);
 
+                // The verification of the email is kept unless the user is verified again
                 let to_update = (
                     email.eq(user.email.clone()),
                     image.eq(user.image.clone()),
                     name.eq(user.name.clone()),
-                    email_verified.eq(user.email_verified.clone()),
-                    updated_at.eq(now),
+                    user.email_verified
+                        .clone()
+                        .map(|verified| email_verified.eq(verified)),
+                    updated_at.eq(diesel::dsl::now),
                 );
 
-                let user = diesel::update(paste::paste!($table_type::table))
+                diesel::update(paste::paste!($table_type::table))
                     .filter(id.eq(user.id.clone()))
                     .set(to_update)
                     .get_result(conn)
-                    .unwrap();
-
-                user
             }
-            fn delete_user(&self, conn: &mut $connection, id: &str) {
+            fn delete_user(
+                &self,
+                conn: &mut $connection,
+                user_id: &str,
+            ) -> diesel::QueryResult<()> {
                 // Delete a user using the connection
                 use diesel::ExpressionMethods;
                 use diesel::QueryDsl;
@@ -167,14 +241,13 @@ macro_rules! adapt_diesel_user {
                     use $table_type::dsl::*;
                 }
 
-                // Turn on foreign key constraints, swallowing the error if it fails
-                let _ = diesel::sql_query("PRAGMA foreign_keys = ON;")
-                    .execute(conn);
+                // Apply the settings of the backend, e.g. foreign key constraints on SQLite
+                $crate::adaptors::diesel::PrepareConnection::prepare(conn);
 
                 diesel::delete(paste::paste!($table_type::table))
-                    .filter(id.eq(id))
+                    .filter(id.eq(user_id))
                     .execute(conn)
-                    .ok();
+                    .map(|_| ())
             }
         }
     };
@@ -186,21 +259,29 @@ where
 {
     type Model;
     type User;
-    fn create_account(&self, conn: &mut C, account: &Self::Model) -> Self::Model;
-    fn link_account(&self, conn: &mut C, account: &Self::Model) -> Self::Model;
-    fn unlink_account(&self, conn: &mut C, provider_id: String, provider_account_id: String);
+    fn create_account(&self, conn: &mut C, account: &Self::Model) -> QueryResult<Self::Model>;
+    fn link_account(&self, conn: &mut C, account: &Self::Model) -> QueryResult<Self::Model>;
+    fn update_account(&self, conn: &mut C, account: &Self::Model) -> QueryResult<Self::Model>;
+    fn unlink_account(
+        &self,
+        conn: &mut C,
+        provider_id: String,
+        provider_account_id: String,
+    ) -> QueryResult<()>;
     fn find_user_by_account(
         &self,
         conn: &mut C,
         provider_id: String,
         provider_account_id: String,
-    ) -> Option<(Self::Model, Self::User)>;
+    ) -> QueryResult<Option<(Self::Model, Self::User)>>;
     fn find_account_by_id(
         &self,
         conn: &mut C,
         provider_id: String,
         provider_account_id: String,
-    ) -> Option<Self::Model>;
+    ) -> QueryResult<Option<Self::Model>>;
+    fn find_accounts_by_user(&self, conn: &mut C, user_id: String)
+    -> QueryResult<Vec<Self::Model>>;
 }
 
 #[macro_export]
@@ -213,19 +294,20 @@ macro_rules! adapt_diesel_account {
                     id: account.id.into(),
                     user_id: account.user_id.into(),
                     provider_id: account.provider_id.into(),
-                    provider_type: account.provider_type.try_into().expect("Invalid provider type"),
+                    provider_type: account
+                        .provider_type
+                        .try_into()
+                        .expect("Invalid provider type"),
                     provider_account_id: account.provider_account_id.into(),
                     // session_state: account.session_state.into(),
                     token: $crate::contracts::token::Token {
                         access_token: account.access_token.into(),
                         token_type: account.token_type.into(),
                         refresh_token: account.refresh_token.into(),
-                        expires_in: {
-                            let now = chrono::Utc::now().naive_utc();
-                            let expires_at = account.expires_at.unwrap_or(now);
-                            let expires_in = (expires_at - now).num_seconds();
-                            Some(expires_in as u64)
-                        },
+                        expires_in: account
+                            .expires_at
+                            .as_ref()
+                            .map($crate::adaptors::diesel::expires_in),
                         scope: account.scope.into(),
                         id_token: account.id_token.into(),
                         others: {
@@ -247,19 +329,18 @@ macro_rules! adapt_diesel_account {
                 let account: $crate::contracts::account::Account = account.into();
                 let token = account.token.clone().expect("Token is required");
                 $model_type {
-                    id: account.id.expect("ID is required"),
+                    id: account
+                        .id
+                        .unwrap_or_else($crate::adaptors::diesel::new_uuid),
                     provider_id: account.provider_id.expect("Provider ID is required"),
-                    provider_account_id: account.provider_account_id.expect("Provider Account ID is required"),
+                    provider_account_id: account
+                        .provider_account_id
+                        .expect("Provider Account ID is required"),
                     user_id: account.user_id.expect("User ID is required"),
                     provider_type: account.provider_type.into(),
                     access_token: token.access_token,
                     refresh_token: token.refresh_token,
-                    expires_at: {
-                        let now = chrono::Utc::now().naive_utc();
-                        let expires_at = token.expires_in.unwrap_or(0);
-                        let expires_at = now + chrono::Duration::seconds(expires_at as i64);
-                        Some(expires_at)
-                    },
+                    expires_at: token.expires_in.map($crate::adaptors::diesel::expires_at),
                     token_type: token.token_type,
                     scope: token.scope,
                     id_token: token.id_token,
@@ -279,7 +360,11 @@ macro_rules! adapt_diesel_account {
             type Model = $model_type;
             type User = $user_struct;
 
-            fn create_account(&self, conn: &mut $connection, account: &Self::Model) -> Self::Model {
+            fn create_account(
+                &self,
+                conn: &mut $connection,
+                account: &Self::Model,
+            ) -> diesel::QueryResult<Self::Model> {
                 // Create an account using the connection
                 use diesel::ExpressionMethods;
                 use diesel::QueryDsl;
@@ -289,11 +374,8 @@ macro_rules! adapt_diesel_account {
                     use $table_type::dsl::*;
                 }
 
-                // Turn on foreign key constraints, swallowing the error if it fails
-                let _ = diesel::sql_query("PRAGMA foreign_keys = ON;")
-                    .execute(conn);
-
-                let now = chrono::Utc::now().naive_utc();
+                // Apply the settings of the backend, e.g. foreign key constraints on SQLite
+                $crate::adaptors::diesel::PrepareConnection::prepare(conn);
 
                 let to_insert = (
                     id.eq(account.id.clone()),
@@ -301,27 +383,28 @@ macro_rules! adapt_diesel_account {
                     provider_account_id.eq(account.provider_account_id.clone()),
                     user_id.eq(account.user_id.clone()),
                     provider_type.eq(account.provider_type.clone()),
+                    refresh_token.eq(account.refresh_token.clone()),
                     access_token.eq(account.access_token.clone()),
+                    expires_at.eq(account.expires_at.clone()),
                     token_type.eq(account.token_type.clone()),
                     scope.eq(account.scope.clone()),
                     id_token.eq(account.id_token.clone()),
                     session_state.eq(account.session_state.clone()),
-                    created_at.eq(now),
-                    updated_at.eq(now),
+                    created_at.eq(diesel::dsl::now),
+                    updated_at.eq(diesel::dsl::now),
                 );
 
-                let account = diesel::insert_into(paste::paste!($table_type::table))
+                diesel::insert_into(paste::paste!($table_type::table))
                     .values(&to_insert)
                     .returning(paste::paste!($model_type::as_returning()))
-                    // .on_conflict(paste::paste!($table_type::user_id))
-                    // .do_nothing()
                     .get_result(conn)
-                    .unwrap();
-
-                account
             }
 
-            fn link_account(&self, conn: &mut $connection, account: &Self::Model) -> Self::Model {
+            fn link_account(
+                &self,
+                conn: &mut $connection,
+                account: &Self::Model,
+            ) -> diesel::QueryResult<Self::Model> {
                 // Link (create) an account using the connection
                 use diesel::ExpressionMethods;
                 use diesel::QueryDsl;
@@ -331,11 +414,8 @@ macro_rules! adapt_diesel_account {
                     use $table_type::dsl::*;
                 }
 
-                // Turn on foreign key constraints, swallowing the error if it fails
-                let _ = diesel::sql_query("PRAGMA foreign_keys = ON;")
-                    .execute(conn);
-
-                let now = chrono::Utc::now().naive_utc();
+                // Apply the settings of the backend, e.g. foreign key constraints on SQLite
+                $crate::adaptors::diesel::PrepareConnection::prepare(conn);
 
                 let to_insert = (
                     id.eq(account.id.clone()),
@@ -343,32 +423,65 @@ macro_rules! adapt_diesel_account {
                     provider_account_id.eq(account.provider_account_id.clone()),
                     user_id.eq(account.user_id.clone()),
                     provider_type.eq(account.provider_type.clone()),
+                    refresh_token.eq(account.refresh_token.clone()),
                     access_token.eq(account.access_token.clone()),
+                    expires_at.eq(account.expires_at.clone()),
                     token_type.eq(account.token_type.clone()),
                     scope.eq(account.scope.clone()),
                     id_token.eq(account.id_token.clone()),
                     session_state.eq(account.session_state.clone()),
-                    created_at.eq(now),
-                    updated_at.eq(now),
+                    created_at.eq(diesel::dsl::now),
+                    updated_at.eq(diesel::dsl::now),
                 );
 
-                let account = diesel::insert_into(paste::paste!($table_type::table))
+                diesel::insert_into(paste::paste!($table_type::table))
                     .values(&to_insert)
                     .returning(paste::paste!($model_type::as_returning()))
-                    // .on_conflict(paste::paste!($table_type::user_id))
-                    // .do_nothing()
                     .get_result(conn)
-                    .unwrap();
+            }
+
+            fn update_account(
+                &self,
+                conn: &mut $connection,
+                account: &Self::Model,
+            ) -> diesel::QueryResult<Self::Model> {
+                // Update the token of an account using the connection
+                use diesel::ExpressionMethods;
+                use diesel::QueryDsl;
+                use diesel::RunQueryDsl;
+                use diesel::SelectableHelper;
+                paste::paste! {
+                    use $table_type::dsl::*;
+                }
+
+                // Apply the settings of the backend, e.g. foreign key constraints on SQLite
+                $crate::adaptors::diesel::PrepareConnection::prepare(conn);
+
+                let to_update = (
+                    refresh_token.eq(account.refresh_token.clone()),
+                    access_token.eq(account.access_token.clone()),
+                    expires_at.eq(account.expires_at.clone()),
+                    token_type.eq(account.token_type.clone()),
+                    scope.eq(account.scope.clone()),
+                    id_token.eq(account.id_token.clone()),
+                    session_state.eq(account.session_state.clone()),
+                    updated_at.eq(diesel::dsl::now),
+                );
 
-                account
+                diesel::update(paste::paste!($table_type::table))
+                    .filter(provider_id.eq(account.provider_id.clone()))
+                    .filter(provider_account_id.eq(account.provider_account_id.clone()))
+                    .set(to_update)
+                    .returning(paste::paste!($model_type::as_returning()))
+                    .get_result(conn)
             }
 
             fn unlink_account(
                 &self,
                 conn: &mut $connection,
-                provider_id: String,
-                provider_account_id: String,
-            ) {
+                provider: String,
+                provider_account: String,
+            ) -> diesel::QueryResult<()> {
                 // Unlink (delete) an account using the connection
                 use diesel::ExpressionMethods;
                 use diesel::QueryDsl;
@@ -378,25 +491,25 @@ macro_rules! adapt_diesel_account {
                     use $table_type::dsl::*;
                 }
 
-                // Turn on foreign key constraints, swallowing the error if it fails
-                let _ = diesel::sql_query("PRAGMA foreign_keys = ON;")
-                    .execute(conn);
+                // Apply the settings of the backend, e.g. foreign key constraints on SQLite
+                $crate::adaptors::diesel::PrepareConnection::prepare(conn);
 
                 diesel::delete(paste::paste!($table_type::table))
-                    .filter(provider_id.eq(provider_id))
-                    .filter(provider_account_id.eq(provider_account_id))
+                    .filter(provider_id.eq(provider))
+                    .filter(provider_account_id.eq(provider_account))
                     .execute(conn)
-                    .ok();
+                    .map(|_| ())
             }
 
             fn find_user_by_account(
                 &self,
                 conn: &mut $connection,
-                provider_id: String,
-                provider_account_id: String,
-            ) -> Option<(Self::Model, Self::User)> {
+                provider: String,
+                provider_account: String,
+            ) -> diesel::QueryResult<Option<(Self::Model, Self::User)>> {
                 // Find a user by account using the connection
                 use diesel::ExpressionMethods;
+                use diesel::OptionalExtension;
                 use diesel::QueryDsl;
                 use diesel::RunQueryDsl;
                 use diesel::SelectableHelper;
@@ -404,32 +517,30 @@ macro_rules! adapt_diesel_account {
                     use $table_type::dsl::*;
                 }
 
-                // Turn on foreign key constraints, swallowing the error if it fails
-                let _ = diesel::sql_query("PRAGMA foreign_keys = ON;")
-                    .execute(conn);
+                // Apply the settings of the backend, e.g. foreign key constraints on SQLite
+                $crate::adaptors::diesel::PrepareConnection::prepare(conn);
 
-                let account_user = paste::paste!($table_type::table)
-                    .filter(provider_id.eq(provider_id))
-                    .filter(provider_account_id.eq(provider_account_id))
+                paste::paste!($table_type::table)
+                    .filter(provider_id.eq(provider))
+                    .filter(provider_account_id.eq(provider_account))
                     .inner_join(paste::paste!($user_table_type::table))
                     .select((
                         paste::paste!($model_type::as_returning()),
                         paste::paste!($user_struct::as_returning()),
                     ))
                     .first::<(Self::Model, Self::User)>(conn)
-                    .ok();
-
-                account_user
+                    .optional()
             }
 
             fn find_account_by_id(
                 &self,
                 conn: &mut $connection,
-                provider_id: String,
-                provider_account_id: String,
-            ) -> Option<Self::Model> {
+                provider: String,
+                provider_account: String,
+            ) -> diesel::QueryResult<Option<Self::Model>> {
                 // Find an account by ID using the connection
                 use diesel::ExpressionMethods;
+                use diesel::OptionalExtension;
                 use diesel::QueryDsl;
                 use diesel::RunQueryDsl;
                 use diesel::SelectableHelper;
@@ -437,17 +548,36 @@ macro_rules! adapt_diesel_account {
                     use $table_type::dsl::*;
                 }
 
-                // Turn on foreign key constraints, swallowing the error if it fails
-                let _ = diesel::sql_query("PRAGMA foreign_keys = ON;")
-                    .execute(conn);
+                // Apply the settings of the backend, e.g. foreign key constraints on SQLite
+                $crate::adaptors::diesel::PrepareConnection::prepare(conn);
 
-                let account = paste::paste!($table_type::table)
-                    .filter(provider_id.eq(provider_id))
-                    .filter(provider_account_id.eq(provider_account_id))
+                paste::paste!($table_type::table)
+                    .filter(provider_id.eq(provider))
+                    .filter(provider_account_id.eq(provider_account))
                     .first::<Self::Model>(conn)
-                    .ok();
+                    .optional()
+            }
+
+            fn find_accounts_by_user(
+                &self,
+                conn: &mut $connection,
+                user: String,
+            ) -> diesel::QueryResult<Vec<Self::Model>> {
+                // Find the accounts of a user using the connection
+                use diesel::ExpressionMethods;
+                use diesel::QueryDsl;
+                use diesel::RunQueryDsl;
+                paste::paste! {
+                    use $table_type::dsl::*;
+                }
 
-                account
+                // Apply the settings of the backend, e.g. foreign key constraints on SQLite
+                $crate::adaptors::diesel::PrepareConnection::prepare(conn);
+
+                paste::paste!($table_type::table)
+                    .filter(user_id.eq(user))
+                    .order((provider_id.asc(), provider_account_id.asc()))
+                    .load::<Self::Model>(conn)
             }
         }
     };
@@ -459,11 +589,14 @@ where
 {
     type Model;
     type User;
-    fn create_session(&self, conn: &mut C, user: Self::Model) -> Self::Model;
-    fn update_session(&self, conn: &mut C, user: Self::Model) -> Self::Model;
-    fn find_session_and_user(&self, conn: &mut C, token: &str)
-    -> Option<(Self::Model, Self::User)>;
-    fn delete_session(&self, conn: &mut C, token: &str);
+    fn create_session(&self, conn: &mut C, user: Self::Model) -> QueryResult<Self::Model>;
+    fn update_session(&self, conn: &mut C, user: Self::Model) -> QueryResult<Self::Model>;
+    fn find_session_and_user(
+        &self,
+        conn: &mut C,
+        token: &str,
+    ) -> QueryResult<Option<(Self::Model, Self::User)>>;
+    fn delete_session(&self, conn: &mut C, token: &str) -> QueryResult<()>;
 }
 
 #[macro_export]
@@ -474,12 +607,7 @@ macro_rules! adapt_diesel_session {
                 $crate::contracts::adapt::AdaptSession {
                     user_id: session.user_id,
                     token: session.token,
-                    expires_in: {
-                        let now = chrono::Utc::now().naive_utc();
-                        let expires_at = session.expires_at;
-                        let expires_in = (expires_at - now).num_seconds();
-                        expires_in as u64
-                    },
+                    expires_in: $crate::adaptors::diesel::expires_in(&session.expires_at),
                 }
             }
         }
@@ -489,12 +617,7 @@ macro_rules! adapt_diesel_session {
                 $model_type {
                     user_id: session.user_id,
                     token: session.token,
-                    expires_at: {
-                        let now = chrono::Utc::now().naive_utc();
-                        let expires_in = session.expires_in;
-                        let expires_at = now + chrono::Duration::seconds(expires_in as i64);
-                        expires_at
-                    },
+                    expires_at: $crate::adaptors::diesel::expires_at(session.expires_in),
                     ..Default::default()
                 }
             }
@@ -503,7 +626,11 @@ macro_rules! adapt_diesel_session {
         impl $crate::adaptors::diesel::AdaptSessionOperation<$connection> for $table_struct {
             type Model = $model_type;
             type User = $user_struct;
-            fn create_session(&self, conn: &mut $connection, session: Self::Model) -> Self::Model {
+            fn create_session(
+                &self,
+                conn: &mut $connection,
+                session: Self::Model,
+            ) -> diesel::QueryResult<Self::Model> {
                 // Create a session using the connection
                 use diesel::ExpressionMethods;
                 use diesel::QueryDsl;
@@ -513,33 +640,29 @@ macro_rules! adapt_diesel_session {
                     use $table_type::dsl::*;
                 }
 
-                // Turn on foreign key constraints, swallowing the error if it fails
-                let _ = diesel::sql_query("PRAGMA foreign_keys = ON;")
-                    .execute(conn);
-
-                let now = chrono::Utc::now().naive_utc();
+                // Apply the settings of the backend, e.g. foreign key constraints on SQLite
+                $crate::adaptors::diesel::PrepareConnection::prepare(conn);
 
                 let to_insert = (
                     id.eq($crate::adaptors::diesel::new_uuid()),
                     user_id.eq(session.user_id.clone()),
                     token.eq(session.token.clone()),
                     expires_at.eq(session.expires_at.clone()),
-                    created_at.eq(now),
-                    updated_at.eq(now),
+                    created_at.eq(diesel::dsl::now),
+                    updated_at.eq(diesel::dsl::now),
                 );
 
-                let session = diesel::insert_into(paste::paste!($table_type::table))
+                diesel::insert_into(paste::paste!($table_type::table))
                     .values(&to_insert)
                     .returning(paste::paste!($model_type::as_returning()))
-                    // .on_conflict(paste::paste!($table_type::user_id))
-                    // .do_nothing()
                     .get_result(conn)
-                    .unwrap();
-
-                session
             }
 
-            fn update_session(&self, conn: &mut $connection, session: Self::Model) -> Self::Model {
+            fn update_session(
+                &self,
+                conn: &mut $connection,
+                session: Self::Model,
+            ) -> diesel::QueryResult<Self::Model> {
                 // Update a session using the connection
                 use diesel::ExpressionMethods;
                 use diesel::QueryDsl;
@@ -549,34 +672,29 @@ macro_rules! adapt_diesel_session {
                     use $table_type::dsl::*;
                 }
 
-                // Turn on foreign key constraints, swallowing the error if it fails
-                let _ = diesel::sql_query("PRAGMA foreign_keys = ON;")
-                    .execute(conn);
-
-                let now = chrono::Utc::now().naive_utc();
+                // Apply the settings of the backend, e.g. foreign key constraints on SQLite
+                $crate::adaptors::diesel::PrepareConnection::prepare(conn);
 
                 let to_update = (
                     user_id.eq(session.user_id.clone()),
                     token.eq(session.token.clone()),
                     expires_at.eq(session.expires_at.clone()),
-                    updated_at.eq(now),
+                    updated_at.eq(diesel::dsl::now),
                 );
 
-                let session = diesel::update(paste::paste!($table_type::table))
+                diesel::update(paste::paste!($table_type::table))
                     .filter(token.eq(session.token.clone()))
                     .set(to_update)
                     .get_result(conn)
-                    .unwrap();
-
-                session
             }
             fn find_session_and_user(
                 &self,
                 conn: &mut $connection,
-                token: &str,
-            ) -> Option<(Self::Model, Self::User)> {
+                session_token: &str,
+            ) -> diesel::QueryResult<Option<(Self::Model, Self::User)>> {
                 // Find a session and user using the connection
                 use diesel::ExpressionMethods;
+                use diesel::OptionalExtension;
                 use diesel::QueryDsl;
                 use diesel::RunQueryDsl;
                 use diesel::SelectableHelper;
@@ -584,24 +702,25 @@ macro_rules! adapt_diesel_session {
                     use $table_type::dsl::*;
                 }
 
-                // Turn on foreign key constraints, swallowing the error if it fails
-                let _ = diesel::sql_query("PRAGMA foreign_keys = ON;")
-                    .execute(conn);
+                // Apply the settings of the backend, e.g. foreign key constraints on SQLite
+                $crate::adaptors::diesel::PrepareConnection::prepare(conn);
 
-                let session_user = paste::paste!($table_type::table)
-                    .filter(token.eq(token))
+                paste::paste!($table_type::table)
+                    .filter(token.eq(session_token))
                     .inner_join(paste::paste!($user_table_type::table))
                     .select((
                         paste::paste!($model_type::as_returning()),
                         paste::paste!($user_struct::as_returning()),
                     ))
                     .first::<(Self::Model, Self::User)>(conn)
-                    .ok();
-
-                session_user
+                    .optional()
             }
 
-            fn delete_session(&self, conn: &mut $connection, token: &str) {
+            fn delete_session(
+                &self,
+                conn: &mut $connection,
+                session_token: &str,
+            ) -> diesel::QueryResult<()> {
                 // Delete a session using the connection
                 use diesel::ExpressionMethods;
                 use diesel::QueryDsl;
@@ -611,14 +730,13 @@ macro_rules! adapt_diesel_session {
                     use $table_type::dsl::*;
                 }
 
-                // Turn on foreign key constraints, swallowing the error if it fails
-                let _ = diesel::sql_query("PRAGMA foreign_keys = ON;")
-                    .execute(conn);
+                // Apply the settings of the backend, e.g. foreign key constraints on SQLite
+                $crate::adaptors::diesel::PrepareConnection::prepare(conn);
 
                 diesel::delete(paste::paste!($table_type::table))
-                    .filter(token.eq(token))
+                    .filter(token.eq(session_token))
                     .execute(conn)
-                    .ok();
+                    .map(|_| ())
             }
         }
     };
@@ -629,8 +747,18 @@ where
     Self: Send + Sync + 'static,
 {
     type Model;
-    fn create_verification_token(&self, conn: &mut C, token: Self::Model) -> Self::Model;
-    fn use_verification_token(&self, conn: &mut C, email: &str, token: &str);
+    fn create_verification_token(
+        &self,
+        conn: &mut C,
+        token: Self::Model,
+    ) -> QueryResult<Self::Model>;
+    /// Deletes the token, returning it if it existed
+    fn use_verification_token(
+        &self,
+        conn: &mut C,
+        email: &str,
+        token: &str,
+    ) -> QueryResult<Option<Self::Model>>;
 }
 
 #[macro_export]
@@ -641,12 +769,7 @@ macro_rules! adapt_diesel_verification_token {
                 $crate::contracts::adapt::AdaptVerificationToken {
                     email: token.email,
                     token: token.token,
-                    expires_in: {
-                        let now = chrono::Utc::now().naive_utc();
-                        let expires_at = token.expires_at;
-                        let expires_in = (expires_at - now).num_seconds();
-                        expires_in as u64
-                    },
+                    expires_in: $crate::adaptors::diesel::expires_in(&token.expires_at),
                 }
                 .into()
             }
@@ -657,12 +780,7 @@ macro_rules! adapt_diesel_verification_token {
                 $model_type {
                     email: token.email,
                     token: token.token,
-                    expires_at: {
-                        let now = chrono::Utc::now().naive_utc();
-                        let expires_in = token.expires_in;
-                        let expires_at = now + chrono::Duration::seconds(expires_in as i64);
-                        expires_at
-                    },
+                    expires_at: $crate::adaptors::diesel::expires_at(token.expires_in),
                     ..Default::default()
                 }
             }
@@ -676,53 +794,54 @@ macro_rules! adapt_diesel_verification_token {
                 &self,
                 conn: &mut $connection,
                 verification_token: Self::Model,
-            ) -> Self::Model {
+            ) -> diesel::QueryResult<Self::Model> {
                 // Create a verification token using the connection
                 use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl, SelectableHelper};
                 paste::paste! {
                     use $table_type::dsl::*;
                 }
 
-                // Turn on foreign key constraints, swallowing the error if it fails
-                let _ = diesel::sql_query("PRAGMA foreign_keys = ON;").execute(conn);
-
-                let now = chrono::Utc::now().naive_utc();
+                // Apply the settings of the backend, e.g. foreign key constraints on SQLite
+                $crate::adaptors::diesel::PrepareConnection::prepare(conn);
 
                 let to_insert = (
+                    id.eq($crate::adaptors::diesel::new_uuid()),
                     email.eq(verification_token.email.clone()),
                     token.eq(verification_token.token.clone()),
                     expires_at.eq(verification_token.expires_at.clone()),
-                    created_at.eq(now),
+                    created_at.eq(diesel::dsl::now),
+                    updated_at.eq(diesel::dsl::now),
                 );
 
-                let verification_token = diesel::insert_into(paste::paste!($table_type::table))
+                diesel::insert_into(paste::paste!($table_type::table))
                     .values(&to_insert)
                     .returning(paste::paste!($model_type::as_returning()))
-                    .on_conflict(paste::paste!($table_type::email))
-                    .do_nothing()
                     .get_result(conn)
-                    .unwrap();
-
-                verification_token
             }
-            fn use_verification_token(&self, conn: &mut $connection, email: &str, token: &str) {
+            fn use_verification_token(
+                &self,
+                conn: &mut $connection,
+                token_email: &str,
+                token_value: &str,
+            ) -> diesel::QueryResult<Option<Self::Model>> {
                 // Use a verification token using the connection
                 // (Using a verification token means deleting it)
-                use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl, SelectableHelper};
+                use diesel::{
+                    ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl, SelectableHelper,
+                };
                 paste::paste! {
                     use $table_type::dsl::*;
                 }
 
-                // Turn on foreign key constraints, swallowing the error if it fails
-                let _ = diesel::sql_query("PRAGMA foreign_keys = ON;").execute(conn);
+                // Apply the settings of the backend, e.g. foreign key constraints on SQLite
+                $crate::adaptors::diesel::PrepareConnection::prepare(conn);
 
-                let now = chrono::Utc::now().naive_utc();
-                let to_delete = (email.eq(email), token.eq(token));
                 diesel::delete(paste::paste!($table_type::table))
-                    .filter(email.eq(email))
-                    .filter(token.eq(token))
-                    .execute(conn)
-                    .ok();
+                    .filter(email.eq(token_email))
+                    .filter(token.eq(token_value))
+                    .returning(paste::paste!($model_type::as_returning()))
+                    .get_result(conn)
+                    .optional()
             }
         }
     };
@@ -1,23 +1,128 @@
-#[derive(Debug, Clone)]
-pub struct AdaptorError {
-    pub message: String,
+use http::StatusCode;
+
+use crate::tools::CoreError;
+
+/// The errors raised by an adaptor. Records that are looked up but do not exist are not errors,
+/// and are returned as `None` instead.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AdaptorError {
+    /// The record to update does not exist
+    NotFound(String),
+    /// The record conflicts with an existing one, e.g. a unique constraint was violated
+    Conflict(String),
+    /// The store cannot be reached, e.g. no connection could be taken from the pool
+    Unavailable(String),
+    Other(String),
 }
 
 impl AdaptorError {
-    pub fn new(message: String) -> Self {
-        AdaptorError { message }
+    pub fn message(&self) -> &str {
+        match self {
+            AdaptorError::NotFound(message)
+            | AdaptorError::Conflict(message)
+            | AdaptorError::Unavailable(message)
+            | AdaptorError::Other(message) => message,
+        }
     }
 
-    pub fn with_message(mut self, message: String) -> Self {
-        self.message = message;
-        self
+    /// The HTTP status of a request failing with this error
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AdaptorError::NotFound(_) => StatusCode::NOT_FOUND,
+            AdaptorError::Conflict(_) => StatusCode::CONFLICT,
+            AdaptorError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            AdaptorError::Other(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
     }
 }
 
 impl std::fmt::Display for AdaptorError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "AdaptorError: {}", self.message)
+        match self {
+            AdaptorError::NotFound(message) => write!(f, "Record not found: {}", message),
+            AdaptorError::Conflict(message) => write!(f, "Conflicting record: {}", message),
+            AdaptorError::Unavailable(message) => write!(f, "Store unavailable: {}", message),
+            AdaptorError::Other(message) => write!(f, "Adaptor error: {}", message),
+        }
     }
 }
 
 impl std::error::Error for AdaptorError {}
+
+impl From<AdaptorError> for CoreError {
+    fn from(error: AdaptorError) -> Self {
+        tracing::error!("[adaptor] {}", error);
+
+        // The details of the store are kept out of the response
+        let message = match error {
+            AdaptorError::NotFound(_) => "Not found",
+            AdaptorError::Conflict(_) => "Conflict",
+            AdaptorError::Unavailable(_) => "Service unavailable",
+            AdaptorError::Other(_) => "Internal server error",
+        };
+        CoreError::new()
+            .with_message(message)
+            .with_status(error.status().into())
+    }
+}
+
+#[cfg(feature = "adapt_diesel")]
+impl From<diesel::result::Error> for AdaptorError {
+    fn from(error: diesel::result::Error) -> Self {
+        use diesel::result::{DatabaseErrorKind, Error};
+
+        match error {
+            Error::NotFound => AdaptorError::NotFound(error.to_string()),
+            Error::DatabaseError(
+                DatabaseErrorKind::UniqueViolation | DatabaseErrorKind::ForeignKeyViolation,
+                ref info,
+            ) => AdaptorError::Conflict(info.message().to_string()),
+            Error::DatabaseError(DatabaseErrorKind::ClosedConnection, ref info) => {
+                AdaptorError::Unavailable(info.message().to_string())
+            }
+            error => AdaptorError::Other(error.to_string()),
+        }
+    }
+}
+
+#[cfg(feature = "adapt_diesel")]
+impl From<diesel::r2d2::PoolError> for AdaptorError {
+    fn from(error: diesel::r2d2::PoolError) -> Self {
+        AdaptorError::Unavailable(error.to_string())
+    }
+}
+
+#[cfg(feature = "adapt_sqlx")]
+impl From<sqlx::Error> for AdaptorError {
+    fn from(error: sqlx::Error) -> Self {
+        use sqlx::Error;
+
+        match error {
+            Error::RowNotFound => AdaptorError::NotFound(error.to_string()),
+            Error::Database(ref database_error)
+                if database_error.is_unique_violation()
+                    || database_error.is_foreign_key_violation() =>
+            {
+                AdaptorError::Conflict(database_error.message().to_string())
+            }
+            Error::PoolTimedOut | Error::PoolClosed | Error::Io(_) | Error::Tls(_) => {
+                AdaptorError::Unavailable(error.to_string())
+            }
+            error => AdaptorError::Other(error.to_string()),
+        }
+    }
+}
+
+#[cfg(feature = "adapt_sqlx")]
+impl From<sqlx::migrate::MigrateError> for AdaptorError {
+    fn from(error: sqlx::migrate::MigrateError) -> Self {
+        AdaptorError::Other(error.to_string())
+    }
+}
+
+/// Raised when a blocking operation panics or is cancelled before it completes
+impl From<tokio::task::JoinError> for AdaptorError {
+    fn from(error: tokio::task::JoinError) -> Self {
+        AdaptorError::Other(error.to_string())
+    }
+}
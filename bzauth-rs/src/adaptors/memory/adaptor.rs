@@ -0,0 +1,264 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use tokio::io::AsyncWriteExt;
+
+use super::models::{MemoryAccount, MemorySession, MemorySnapshot, MemoryVerificationToken};
+use super::store::MemoryStore;
+use crate::adaptors::error::AdaptorError;
+use crate::contracts::adapt::{
+    Adapt, AdaptAccount, AdaptResult, AdaptSession, AdaptUser, AdaptVerificationToken,
+    CreateSessionOptions, ProviderAccountId, SessionUser, UseVerificationTokenOptions,
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct MemoryAdapterOptions {
+    /// A JSON file the store is loaded from and written to after every change, so that it
+    /// persists across restarts. Without it, the store is lost when dropped. The file holds the
+    /// session tokens, the provider tokens of the accounts and the password hashes, so on Unix it
+    /// is only readable by its owner (mode 0600).
+    pub snapshot_path: Option<PathBuf>,
+}
+
+/// Keeps everything in memory, for local development and tests. Clones share the same store.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryAdaptor {
+    store: Arc<RwLock<MemoryStore>>,
+    snapshot_path: Option<Arc<tokio::sync::Mutex<PathBuf>>>,
+}
+
+impl MemoryAdaptor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fails if the snapshot file exists but cannot be read
+    pub fn from_options(options: MemoryAdapterOptions) -> AdaptResult<Self> {
+        let Some(path) = options.snapshot_path else {
+            return Ok(Self::new());
+        };
+
+        let store = match std::fs::read_to_string(&path) {
+            Ok(raw) => {
+                let snapshot: MemorySnapshot = serde_json::from_str(&raw).map_err(|e| {
+                    AdaptorError::Other(format!("Invalid snapshot {}: {}", path.display(), e))
+                })?;
+                MemoryStore::from_snapshot(snapshot)?
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => MemoryStore::default(),
+            Err(e) => {
+                return Err(AdaptorError::Unavailable(format!(
+                    "Failed to read snapshot {}: {}",
+                    path.display(),
+                    e
+                )));
+            }
+        };
+
+        Ok(Self {
+            store: Arc::new(RwLock::new(store)),
+            snapshot_path: Some(Arc::new(tokio::sync::Mutex::new(path))),
+        })
+    }
+
+    /// The current contents of the store
+    pub fn snapshot(&self) -> AdaptResult<MemorySnapshot> {
+        Ok(self.read()?.to_snapshot())
+    }
+
+    fn read(&self) -> AdaptResult<RwLockReadGuard<'_, MemoryStore>> {
+        self.store
+            .read()
+            .map_err(|e| AdaptorError::Other(e.to_string()))
+    }
+
+    /// Locks the store for a change, evicting the expired sessions and verification tokens first
+    fn write(&self) -> AdaptResult<RwLockWriteGuard<'_, MemoryStore>> {
+        let mut store = self
+            .store
+            .write()
+            .map_err(|e| AdaptorError::Other(e.to_string()))?;
+        store.evict_expired();
+        Ok(store)
+    }
+
+    /// Writes the store to the snapshot file, if there is one
+    async fn persist(&self) -> AdaptResult<()> {
+        let Some(snapshot_path) = &self.snapshot_path else {
+            return Ok(());
+        };
+
+        // The snapshot is taken once the file is locked, so the last write has the latest changes
+        let path = snapshot_path.lock().await;
+        let raw = serde_json::to_string(&self.snapshot()?)
+            .map_err(|e| AdaptorError::Other(e.to_string()))?;
+
+        write_snapshot(&path, raw).await.map_err(|e| {
+            AdaptorError::Unavailable(format!(
+                "Failed to write snapshot {}: {}",
+                path.display(),
+                e
+            ))
+        })
+    }
+}
+
+/// Writes the snapshot aside and renames it once complete, so that a crash or a failed write never
+/// leaves half a snapshot
+async fn write_snapshot(path: &Path, raw: String) -> std::io::Result<()> {
+    let partial = path.with_extension("partial");
+    let mut options = tokio::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    // Only readable by its owner, as it holds tokens and password hashes
+    #[cfg(unix)]
+    options.mode(0o600);
+
+    let mut file = options.open(&partial).await?;
+    // A partial file left by an older version may have kept wider permissions
+    #[cfg(unix)]
+    file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))
+        .await?;
+    file.write_all(raw.as_bytes()).await?;
+    file.sync_all().await?;
+    drop(file);
+
+    tokio::fs::rename(&partial, path).await
+}
+
+#[async_trait::async_trait]
+impl Adapt for MemoryAdaptor {
+    async fn create_user(&self, user: AdaptUser) -> AdaptResult<AdaptUser> {
+        let user = self.write()?.insert_user(user)?;
+        self.persist().await?;
+        Ok(user)
+    }
+
+    async fn get_user(&self, id: String) -> AdaptResult<Option<AdaptUser>> {
+        Ok(self.read()?.user(&id).cloned())
+    }
+
+    async fn get_user_by_email(&self, email: String) -> AdaptResult<Option<AdaptUser>> {
+        Ok(self.read()?.user_by_email(&email).cloned())
+    }
+
+    async fn get_user_by_account(
+        &self,
+        provider: ProviderAccountId,
+    ) -> AdaptResult<Option<AdaptUser>> {
+        let store = self.read()?;
+        let user = store
+            .account(&provider.provider_id, &provider.provider_account_id)
+            .and_then(|account| account.account.user_id.as_deref())
+            .and_then(|user_id| store.user(user_id));
+        Ok(user.cloned())
+    }
+
+    /// Id is required
+    async fn update_user(&self, user: AdaptUser) -> AdaptResult<AdaptUser> {
+        let user = self.write()?.update_user(user)?;
+        self.persist().await?;
+        Ok(user)
+    }
+
+    async fn delete_user(&self, id: String) -> AdaptResult<()> {
+        self.write()?.remove_user(&id);
+        self.persist().await
+    }
+
+    async fn get_account(&self, provider: ProviderAccountId) -> AdaptResult<Option<AdaptAccount>> {
+        let store = self.read()?;
+        let account = store.account(&provider.provider_id, &provider.provider_account_id);
+        Ok(account.cloned().map(AdaptAccount::from))
+    }
+
+    async fn get_accounts_by_user(&self, user_id: String) -> AdaptResult<Vec<AdaptAccount>> {
+        let store = self.read()?;
+        let accounts = store.accounts_of_user(&user_id);
+        Ok(accounts
+            .into_iter()
+            .cloned()
+            .map(AdaptAccount::from)
+            .collect())
+    }
+
+    async fn link_account(&self, account: AdaptAccount) -> AdaptResult<AdaptAccount> {
+        let account = self.write()?.insert_account(MemoryAccount::from(account))?;
+        self.persist().await?;
+        Ok(AdaptAccount::from(account))
+    }
+
+    async fn update_account(&self, account: AdaptAccount) -> AdaptResult<AdaptAccount> {
+        let account = self.write()?.update_account(MemoryAccount::from(account))?;
+        self.persist().await?;
+        Ok(AdaptAccount::from(account))
+    }
+
+    async fn unlink_account(&self, provider: ProviderAccountId) -> AdaptResult<()> {
+        self.write()?
+            .remove_account(&provider.provider_id, &provider.provider_account_id);
+        self.persist().await
+    }
+
+    async fn create_session(&self, options: CreateSessionOptions) -> AdaptResult<AdaptSession> {
+        let session = MemorySession::from(AdaptSession {
+            token: options.token,
+            user_id: options.user_id,
+            expires_in: options.expires_in,
+        });
+        let session = self.write()?.insert_session(session)?;
+        self.persist().await?;
+        Ok(AdaptSession::from(session))
+    }
+
+    /// Expired sessions are evicted, and so are not found
+    async fn get_session_and_user(&self, token: String) -> AdaptResult<Option<SessionUser>> {
+        let store = self.read()?;
+        let Some(session) = store.session(&token) else {
+            return Ok(None);
+        };
+        Ok(store.user(&session.user_id).map(|user| SessionUser {
+            session: AdaptSession::from(session.clone()),
+            user: user.clone(),
+        }))
+    }
+
+    /// session_token required
+    async fn update_session(&self, session: AdaptSession) -> AdaptResult<AdaptSession> {
+        let session = self.write()?.update_session(MemorySession::from(session))?;
+        self.persist().await?;
+        Ok(AdaptSession::from(session))
+    }
+
+    async fn delete_session(&self, token: String) -> AdaptResult<()> {
+        self.write()?.remove_session(&token);
+        self.persist().await
+    }
+
+    async fn create_verification_token(
+        &self,
+        token: AdaptVerificationToken,
+    ) -> AdaptResult<AdaptVerificationToken> {
+        let token = self
+            .write()?
+            .insert_verification_token(MemoryVerificationToken::from(token))?;
+        self.persist().await?;
+        Ok(AdaptVerificationToken::from(token))
+    }
+
+    async fn use_verification_token(
+        &self,
+        options: UseVerificationTokenOptions,
+    ) -> AdaptResult<Option<AdaptVerificationToken>> {
+        let token = self
+            .write()?
+            .take_verification_token(&options.email, &options.token);
+        self.persist().await?;
+        Ok(token.map(AdaptVerificationToken::from))
+    }
+}
+
+impl From<MemoryAdaptor> for Box<dyn Adapt> {
+    fn from(value: MemoryAdaptor) -> Self {
+        Box::new(value)
+    }
+}
@@ -0,0 +1,6 @@
+pub mod adaptor;
+pub mod models;
+mod store;
+
+pub use adaptor::*; // Export adaptor
+pub use models::*; // Export the records and snapshot
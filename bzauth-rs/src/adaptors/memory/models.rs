@@ -0,0 +1,113 @@
+use serde::{Deserialize, Serialize};
+
+use crate::contracts::adapt::{AdaptAccount, AdaptSession, AdaptUser, AdaptVerificationToken};
+
+/// The current time, in seconds since the epoch
+pub(crate) fn now() -> i64 {
+    chrono::Utc::now().timestamp()
+}
+
+/// The time `expires_in` seconds from now
+pub(crate) fn expires_at(expires_in: u64) -> i64 {
+    now().saturating_add(expires_in.min(i64::MAX as u64) as i64)
+}
+
+/// The seconds left until `expires_at`, 0 once it has passed
+pub(crate) fn expires_in(expires_at: i64) -> u64 {
+    expires_at.saturating_sub(now()).max(0) as u64
+}
+
+/// A linked account. The expiry of its token is kept as a time, so that it counts down.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryAccount {
+    pub account: AdaptAccount,
+    pub token_expires_at: Option<i64>,
+}
+
+impl From<AdaptAccount> for MemoryAccount {
+    fn from(mut account: AdaptAccount) -> Self {
+        let token_expires_at = account
+            .token
+            .as_mut()
+            .and_then(|token| token.expires_in.take())
+            .map(expires_at);
+        MemoryAccount {
+            account,
+            token_expires_at,
+        }
+    }
+}
+
+impl From<MemoryAccount> for AdaptAccount {
+    fn from(memory: MemoryAccount) -> Self {
+        let mut account = memory.account;
+        if let Some(token) = account.token.as_mut() {
+            token.expires_in = memory.token_expires_at.map(expires_in);
+        }
+        account
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemorySession {
+    pub token: String,
+    pub user_id: String,
+    pub expires_at: i64,
+}
+
+impl From<AdaptSession> for MemorySession {
+    fn from(session: AdaptSession) -> Self {
+        MemorySession {
+            token: session.token,
+            user_id: session.user_id,
+            expires_at: expires_at(session.expires_in),
+        }
+    }
+}
+
+impl From<MemorySession> for AdaptSession {
+    fn from(session: MemorySession) -> Self {
+        AdaptSession {
+            token: session.token,
+            user_id: session.user_id,
+            expires_in: expires_in(session.expires_at),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryVerificationToken {
+    pub email: String,
+    pub token: String,
+    pub expires_at: i64,
+}
+
+impl From<AdaptVerificationToken> for MemoryVerificationToken {
+    fn from(token: AdaptVerificationToken) -> Self {
+        MemoryVerificationToken {
+            email: token.email,
+            token: token.token,
+            expires_at: expires_at(token.expires_in),
+        }
+    }
+}
+
+impl From<MemoryVerificationToken> for AdaptVerificationToken {
+    fn from(token: MemoryVerificationToken) -> Self {
+        AdaptVerificationToken {
+            email: token.email,
+            token: token.token,
+            expires_in: expires_in(token.expires_at),
+        }
+    }
+}
+
+/// The contents of the store, as written to the snapshot file
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MemorySnapshot {
+    pub users: Vec<AdaptUser>,
+    pub accounts: Vec<MemoryAccount>,
+    pub sessions: Vec<MemorySession>,
+    pub verification_tokens: Vec<MemoryVerificationToken>,
+}
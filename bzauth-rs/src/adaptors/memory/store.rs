@@ -0,0 +1,320 @@
+use std::collections::{BTreeSet, HashMap};
+
+use super::models::{MemoryAccount, MemorySession, MemorySnapshot, MemoryVerificationToken, now};
+use crate::adaptors::error::AdaptorError;
+use crate::contracts::adapt::{AdaptResult, AdaptUser};
+
+/// (provider_id, provider_account_id)
+type AccountKey = (String, String);
+/// (email, token)
+type VerificationTokenKey = (String, String);
+
+/// The records, with the indexes kept alongside them. Sessions and verification tokens are also
+/// ordered by expiry, so that the expired ones are evicted without scanning the rest.
+#[derive(Debug, Default)]
+pub(crate) struct MemoryStore {
+    users: HashMap<String, AdaptUser>,
+    user_ids_by_email: HashMap<String, String>,
+    accounts: HashMap<AccountKey, MemoryAccount>,
+    sessions: HashMap<String, MemorySession>,
+    session_expiries: BTreeSet<(i64, String)>,
+    verification_tokens: HashMap<VerificationTokenKey, MemoryVerificationToken>,
+    verification_token_expiries: BTreeSet<(i64, VerificationTokenKey)>,
+}
+
+fn account_key(account: &MemoryAccount) -> AdaptResult<AccountKey> {
+    let required = |field: &Option<String>, name: &str| {
+        field
+            .clone()
+            .ok_or_else(|| AdaptorError::Other(format!("Account has no {}", name)))
+    };
+    Ok((
+        required(&account.account.provider_id, "provider ID")?,
+        required(&account.account.provider_account_id, "provider account ID")?,
+    ))
+}
+
+impl MemoryStore {
+    pub(crate) fn from_snapshot(snapshot: MemorySnapshot) -> AdaptResult<Self> {
+        let mut store = MemoryStore::default();
+        for user in snapshot.users {
+            store.insert_user(user)?;
+        }
+        for account in snapshot.accounts {
+            store.insert_account(account)?;
+        }
+        for session in snapshot.sessions {
+            store.insert_session(session)?;
+        }
+        for token in snapshot.verification_tokens {
+            store.insert_verification_token(token)?;
+        }
+        store.evict_expired();
+        Ok(store)
+    }
+
+    pub(crate) fn to_snapshot(&self) -> MemorySnapshot {
+        MemorySnapshot {
+            users: self.users.values().cloned().collect(),
+            accounts: self.accounts.values().cloned().collect(),
+            sessions: self.sessions.values().cloned().collect(),
+            verification_tokens: self.verification_tokens.values().cloned().collect(),
+        }
+    }
+
+    /// Removes the sessions and verification tokens that have expired
+    pub(crate) fn evict_expired(&mut self) {
+        let now = now();
+        while let Some((expires_at, _)) = self.session_expiries.first()
+            && *expires_at <= now
+            && let Some((_, token)) = self.session_expiries.pop_first()
+        {
+            self.sessions.remove(&token);
+        }
+        while let Some((expires_at, _)) = self.verification_token_expiries.first()
+            && *expires_at <= now
+            && let Some((_, key)) = self.verification_token_expiries.pop_first()
+        {
+            self.verification_tokens.remove(&key);
+        }
+    }
+
+    pub(crate) fn user(&self, id: &str) -> Option<&AdaptUser> {
+        self.users.get(id)
+    }
+
+    pub(crate) fn user_by_email(&self, email: &str) -> Option<&AdaptUser> {
+        self.user_ids_by_email
+            .get(email)
+            .and_then(|id| self.users.get(id))
+    }
+
+    pub(crate) fn insert_user(&mut self, mut user: AdaptUser) -> AdaptResult<AdaptUser> {
+        let id = user
+            .id
+            .get_or_insert_with(|| uuid::Uuid::new_v4().to_string())
+            .clone();
+        if self.users.contains_key(&id) {
+            return Err(AdaptorError::Conflict(format!(
+                "User {} already exists",
+                id
+            )));
+        }
+        if let Some(email) = &user.email {
+            if self.user_ids_by_email.contains_key(email) {
+                return Err(AdaptorError::Conflict(format!(
+                    "Email {} is already taken",
+                    email
+                )));
+            }
+            self.user_ids_by_email.insert(email.clone(), id.clone());
+        }
+        self.users.insert(id, user.clone());
+        Ok(user)
+    }
+
+    pub(crate) fn update_user(&mut self, user: AdaptUser) -> AdaptResult<AdaptUser> {
+        let id = user
+            .id
+            .clone()
+            .ok_or_else(|| AdaptorError::Other("User has no ID".to_string()))?;
+        let Some(existing) = self.users.get(&id) else {
+            return Err(AdaptorError::NotFound(format!(
+                "User {} does not exist",
+                id
+            )));
+        };
+
+        // The verification of the email is kept unless the user is verified again
+        let user = AdaptUser {
+            email_verified: user.email_verified.or(existing.email_verified),
+            ..user
+        };
+        if existing.email != user.email {
+            if let Some(email) = &user.email
+                && self.user_ids_by_email.contains_key(email)
+            {
+                return Err(AdaptorError::Conflict(format!(
+                    "Email {} is already taken",
+                    email
+                )));
+            }
+            if let Some(email) = &existing.email {
+                self.user_ids_by_email.remove(email);
+            }
+            if let Some(email) = &user.email {
+                self.user_ids_by_email.insert(email.clone(), id.clone());
+            }
+        }
+        self.users.insert(id, user.clone());
+        Ok(user)
+    }
+
+    /// Removes the user along with its accounts and sessions
+    pub(crate) fn remove_user(&mut self, id: &str) {
+        let Some(user) = self.users.remove(id) else {
+            return;
+        };
+        if let Some(email) = &user.email {
+            self.user_ids_by_email.remove(email);
+        }
+        self.accounts
+            .retain(|_, account| account.account.user_id.as_deref() != Some(id));
+
+        let tokens: Vec<String> = self
+            .sessions
+            .values()
+            .filter(|session| session.user_id == id)
+            .map(|session| session.token.clone())
+            .collect();
+        for token in tokens {
+            self.remove_session(&token);
+        }
+    }
+
+    pub(crate) fn account(
+        &self,
+        provider_id: &str,
+        provider_account_id: &str,
+    ) -> Option<&MemoryAccount> {
+        self.accounts
+            .get(&(provider_id.to_string(), provider_account_id.to_string()))
+    }
+
+    /// The accounts of the user, ordered by provider
+    pub(crate) fn accounts_of_user(&self, user_id: &str) -> Vec<&MemoryAccount> {
+        let mut accounts: Vec<_> = self
+            .accounts
+            .iter()
+            .filter(|(_, account)| account.account.user_id.as_deref() == Some(user_id))
+            .collect();
+        accounts.sort_by_key(|(key, _)| *key);
+        accounts.into_iter().map(|(_, account)| account).collect()
+    }
+
+    pub(crate) fn insert_account(
+        &mut self,
+        mut account: MemoryAccount,
+    ) -> AdaptResult<MemoryAccount> {
+        let key = account_key(&account)?;
+        let user_id = account
+            .account
+            .user_id
+            .clone()
+            .ok_or_else(|| AdaptorError::Other("Account has no user ID".to_string()))?;
+        if !self.users.contains_key(&user_id) {
+            return Err(AdaptorError::Conflict(format!(
+                "User {} does not exist",
+                user_id
+            )));
+        }
+        if self.accounts.contains_key(&key) {
+            return Err(AdaptorError::Conflict(format!(
+                "Account {}/{} is already linked",
+                key.0, key.1
+            )));
+        }
+
+        account
+            .account
+            .id
+            .get_or_insert_with(|| uuid::Uuid::new_v4().to_string());
+        self.accounts.insert(key, account.clone());
+        Ok(account)
+    }
+
+    /// Replaces the token of the account, keeping everything else
+    pub(crate) fn update_account(&mut self, account: MemoryAccount) -> AdaptResult<MemoryAccount> {
+        let key = account_key(&account)?;
+        let Some(existing) = self.accounts.get_mut(&key) else {
+            return Err(AdaptorError::NotFound(format!(
+                "Account {}/{} does not exist",
+                key.0, key.1
+            )));
+        };
+
+        existing.account.token = account.account.token;
+        existing.token_expires_at = account.token_expires_at;
+        Ok(existing.clone())
+    }
+
+    pub(crate) fn remove_account(&mut self, provider_id: &str, provider_account_id: &str) {
+        self.accounts
+            .remove(&(provider_id.to_string(), provider_account_id.to_string()));
+    }
+
+    /// The session, unless it has expired
+    pub(crate) fn session(&self, token: &str) -> Option<&MemorySession> {
+        self.sessions
+            .get(token)
+            .filter(|session| session.expires_at > now())
+    }
+
+    pub(crate) fn insert_session(&mut self, session: MemorySession) -> AdaptResult<MemorySession> {
+        if !self.users.contains_key(&session.user_id) {
+            return Err(AdaptorError::Conflict(format!(
+                "User {} does not exist",
+                session.user_id
+            )));
+        }
+        if self.sessions.contains_key(&session.token) {
+            return Err(AdaptorError::Conflict("Session already exists".to_string()));
+        }
+
+        self.session_expiries
+            .insert((session.expires_at, session.token.clone()));
+        self.sessions.insert(session.token.clone(), session.clone());
+        Ok(session)
+    }
+
+    pub(crate) fn update_session(&mut self, session: MemorySession) -> AdaptResult<MemorySession> {
+        let Some(existing) = self.sessions.get_mut(&session.token) else {
+            return Err(AdaptorError::NotFound("Session does not exist".to_string()));
+        };
+
+        // Only the expiry of a session changes
+        self.session_expiries
+            .remove(&(existing.expires_at, existing.token.clone()));
+        existing.expires_at = session.expires_at;
+        self.session_expiries
+            .insert((existing.expires_at, existing.token.clone()));
+        Ok(existing.clone())
+    }
+
+    pub(crate) fn remove_session(&mut self, token: &str) {
+        if let Some(session) = self.sessions.remove(token) {
+            self.session_expiries
+                .remove(&(session.expires_at, session.token));
+        }
+    }
+
+    pub(crate) fn insert_verification_token(
+        &mut self,
+        token: MemoryVerificationToken,
+    ) -> AdaptResult<MemoryVerificationToken> {
+        let key = (token.email.clone(), token.token.clone());
+        if self.verification_tokens.contains_key(&key) {
+            return Err(AdaptorError::Conflict(
+                "Verification token already exists".to_string(),
+            ));
+        }
+
+        self.verification_token_expiries
+            .insert((token.expires_at, key.clone()));
+        self.verification_tokens.insert(key, token.clone());
+        Ok(token)
+    }
+
+    /// Removes the verification token, returning it unless it has expired
+    pub(crate) fn take_verification_token(
+        &mut self,
+        email: &str,
+        token: &str,
+    ) -> Option<MemoryVerificationToken> {
+        let key = (email.to_string(), token.to_string());
+        let token = self.verification_tokens.remove(&key)?;
+        self.verification_token_expiries
+            .remove(&(token.expires_at, key));
+        Some(token).filter(|token| token.expires_at > now())
+    }
+}
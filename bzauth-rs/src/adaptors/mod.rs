@@ -5,3 +5,6 @@ pub mod diesel;
 
 #[cfg(feature = "adapt_sqlx")]
 pub mod sqlx;
+
+#[cfg(feature = "adapt_memory")]
+pub mod memory;
@@ -1 +1,207 @@
-// TODO:
+use sqlx::pool::PoolConnection;
+use sqlx::{Database, Pool};
+
+use super::models::{SqlxAccount, SqlxSession, SqlxUser, SqlxVerificationToken};
+use super::traits::*;
+use crate::contracts::adapt::{
+    Adapt, AdaptAccount, AdaptResult, AdaptSession, AdaptUser, AdaptVerificationToken,
+    CreateSessionOptions, ProviderAccountId, SessionUser, UseVerificationTokenOptions,
+};
+
+/// The migrations creating the tables used by the adaptor on SQLite, embedded in the binary
+#[cfg(feature = "backend_sqlite")]
+pub static SQLITE_MIGRATOR: sqlx::migrate::Migrator = sqlx::migrate!("./migrations/sqlite");
+
+/// The migrations creating the tables used by the adaptor on Postgres, embedded in the binary
+#[cfg(feature = "backend_postgres")]
+pub static POSTGRES_MIGRATOR: sqlx::migrate::Migrator = sqlx::migrate!("./migrations/postgres");
+
+pub struct SqlxAdapterOptions<DB: Database> {
+    pub pool: Pool<DB>,
+}
+
+pub struct SqlxAdaptor<DB: Database> {
+    pub options: SqlxAdapterOptions<DB>,
+}
+
+impl<DB: Database> SqlxAdaptor<DB> {
+    pub fn from_options(options: SqlxAdapterOptions<DB>) -> Self {
+        Self { options }
+    }
+
+    /// Grabs a connection from the pool, failing if none frees up before the pool's timeout
+    async fn connection(&self) -> AdaptResult<PoolConnection<DB>> {
+        Ok(self.options.pool.acquire().await?)
+    }
+}
+
+#[cfg(feature = "backend_sqlite")]
+impl SqlxAdaptor<sqlx::Sqlite> {
+    /// Creates or updates the tables used by the adaptor. Migrations already applied to the
+    /// database are skipped, so this is safe to call on every start up.
+    pub async fn migrate(&self) -> AdaptResult<()> {
+        Ok(SQLITE_MIGRATOR.run(&self.options.pool).await?)
+    }
+}
+
+#[cfg(feature = "backend_postgres")]
+impl SqlxAdaptor<sqlx::Postgres> {
+    /// Creates or updates the tables used by the adaptor. Migrations already applied to the
+    /// database are skipped, so this is safe to call on every start up.
+    pub async fn migrate(&self) -> AdaptResult<()> {
+        Ok(POSTGRES_MIGRATOR.run(&self.options.pool).await?)
+    }
+}
+
+#[async_trait::async_trait]
+impl<DB> Adapt for SqlxAdaptor<DB>
+where
+    DB: Database,
+    DB::Connection: AdaptUserOperation
+        + AdaptAccountOperation
+        + AdaptSessionOperation
+        + AdaptVerificationTokenOperation,
+{
+    async fn create_user(&self, user: AdaptUser) -> AdaptResult<AdaptUser> {
+        let mut conn = self.connection().await?;
+        let new_user = conn.create_user(&SqlxUser::from(user)).await?;
+        Ok(AdaptUser::from(new_user))
+    }
+
+    async fn get_user(&self, id: String) -> AdaptResult<Option<AdaptUser>> {
+        let mut conn = self.connection().await?;
+        let user = conn.find_user_by_id(&id).await?;
+        Ok(user.map(AdaptUser::from))
+    }
+
+    async fn get_user_by_email(&self, email: String) -> AdaptResult<Option<AdaptUser>> {
+        let mut conn = self.connection().await?;
+        let user = conn.find_user_by_email(&email).await?;
+        Ok(user.map(AdaptUser::from))
+    }
+
+    async fn get_user_by_account(
+        &self,
+        provider: ProviderAccountId,
+    ) -> AdaptResult<Option<AdaptUser>> {
+        let mut conn = self.connection().await?;
+        let user = conn
+            .find_user_by_account(&provider.provider_id, &provider.provider_account_id)
+            .await?;
+        Ok(user.map(AdaptUser::from))
+    }
+
+    /// Id is required
+    async fn update_user(&self, user: AdaptUser) -> AdaptResult<AdaptUser> {
+        let mut conn = self.connection().await?;
+        let updated_user = conn.update_user(&SqlxUser::from(user)).await?;
+        Ok(AdaptUser::from(updated_user))
+    }
+
+    async fn delete_user(&self, id: String) -> AdaptResult<()> {
+        let mut conn = self.connection().await?;
+        Ok(conn.delete_user(&id).await?)
+    }
+
+    async fn get_account(&self, provider: ProviderAccountId) -> AdaptResult<Option<AdaptAccount>> {
+        let mut conn = self.connection().await?;
+        let account = conn
+            .find_account_by_id(&provider.provider_id, &provider.provider_account_id)
+            .await?;
+        account.map(AdaptAccount::try_from).transpose()
+    }
+
+    async fn get_accounts_by_user(&self, user_id: String) -> AdaptResult<Vec<AdaptAccount>> {
+        let mut conn = self.connection().await?;
+        let accounts = conn.find_accounts_by_user(&user_id).await?;
+        accounts.into_iter().map(AdaptAccount::try_from).collect()
+    }
+
+    async fn link_account(&self, account: AdaptAccount) -> AdaptResult<AdaptAccount> {
+        let mut conn = self.connection().await?;
+        let new_account = conn.link_account(&SqlxAccount::try_from(account)?).await?;
+        AdaptAccount::try_from(new_account)
+    }
+
+    async fn update_account(&self, account: AdaptAccount) -> AdaptResult<AdaptAccount> {
+        let mut conn = self.connection().await?;
+        let updated_account = conn
+            .update_account(&SqlxAccount::try_from(account)?)
+            .await?;
+        AdaptAccount::try_from(updated_account)
+    }
+
+    async fn unlink_account(&self, provider: ProviderAccountId) -> AdaptResult<()> {
+        let mut conn = self.connection().await?;
+        Ok(conn
+            .unlink_account(&provider.provider_id, &provider.provider_account_id)
+            .await?)
+    }
+
+    async fn create_session(&self, options: CreateSessionOptions) -> AdaptResult<AdaptSession> {
+        let mut conn = self.connection().await?;
+        let session = SqlxSession::from(AdaptSession {
+            token: options.token,
+            user_id: options.user_id,
+            expires_in: options.expires_in,
+        });
+        let new_session = conn.create_session(&session).await?;
+        Ok(AdaptSession::from(new_session))
+    }
+
+    async fn get_session_and_user(&self, token: String) -> AdaptResult<Option<SessionUser>> {
+        let mut conn = self.connection().await?;
+        let session_user = conn.find_session_and_user(&token).await?;
+        Ok(session_user.map(|(session, user)| SessionUser {
+            session: AdaptSession::from(session),
+            user: AdaptUser::from(user),
+        }))
+    }
+
+    /// session_token required
+    async fn update_session(&self, session: AdaptSession) -> AdaptResult<AdaptSession> {
+        let mut conn = self.connection().await?;
+        let updated_session = conn.update_session(&SqlxSession::from(session)).await?;
+        Ok(AdaptSession::from(updated_session))
+    }
+
+    async fn delete_session(&self, token: String) -> AdaptResult<()> {
+        let mut conn = self.connection().await?;
+        Ok(conn.delete_session(&token).await?)
+    }
+
+    async fn create_verification_token(
+        &self,
+        token: AdaptVerificationToken,
+    ) -> AdaptResult<AdaptVerificationToken> {
+        let mut conn = self.connection().await?;
+        let new_token = conn
+            .create_verification_token(&SqlxVerificationToken::from(token))
+            .await?;
+        Ok(AdaptVerificationToken::from(new_token))
+    }
+
+    async fn use_verification_token(
+        &self,
+        options: UseVerificationTokenOptions,
+    ) -> AdaptResult<Option<AdaptVerificationToken>> {
+        let mut conn = self.connection().await?;
+        let token = conn
+            .use_verification_token(&options.email, &options.token)
+            .await?;
+        Ok(token.map(AdaptVerificationToken::from))
+    }
+}
+
+impl<DB> From<SqlxAdaptor<DB>> for Box<dyn Adapt>
+where
+    DB: Database,
+    DB::Connection: AdaptUserOperation
+        + AdaptAccountOperation
+        + AdaptSessionOperation
+        + AdaptVerificationTokenOperation,
+{
+    fn from(value: SqlxAdaptor<DB>) -> Self {
+        Box::new(value)
+    }
+}
@@ -1 +1,7 @@
-// TODO: ...
+pub mod adaptor;
+pub mod models;
+pub mod traits;
+
+pub use adaptor::*; // Export adaptor
+pub use models::*; // Export the row models
+pub use traits::*; // re-export all traits from the traits module
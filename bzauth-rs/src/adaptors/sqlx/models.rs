@@ -0,0 +1,213 @@
+use chrono::NaiveDateTime;
+
+use crate::adaptors::error::AdaptorError;
+use crate::contracts::adapt::{AdaptAccount, AdaptSession, AdaptUser, AdaptVerificationToken};
+use crate::contracts::token::Token;
+
+/// A row of the `users` table
+#[derive(Debug, Clone, Default, sqlx::FromRow)]
+pub struct SqlxUser {
+    pub id: String,
+    pub name: Option<String>,
+    pub email: Option<String>,
+    pub email_verified: Option<NaiveDateTime>,
+    pub image: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+/// A row of the `accounts` table
+#[derive(Debug, Clone, Default, sqlx::FromRow)]
+pub struct SqlxAccount {
+    pub id: String,
+    pub provider_id: String,
+    pub provider_account_id: String,
+    pub user_id: String,
+    pub provider_type: String,
+    pub refresh_token: Option<String>,
+    pub access_token: Option<String>,
+    pub expires_at: Option<NaiveDateTime>,
+    pub token_type: Option<String>,
+    pub scope: Option<String>,
+    pub id_token: Option<String>,
+    pub session_state: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+/// A row of the `sessions` table
+#[derive(Debug, Clone, Default, sqlx::FromRow)]
+pub struct SqlxSession {
+    pub id: String,
+    pub user_id: String,
+    pub token: String,
+    pub expires_at: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+/// A row of the `verification_tokens` table
+#[derive(Debug, Clone, Default, sqlx::FromRow)]
+pub struct SqlxVerificationToken {
+    pub id: String,
+    pub email: String,
+    pub token: String,
+    pub expires_at: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+fn now() -> NaiveDateTime {
+    chrono::Utc::now().naive_utc()
+}
+
+fn expires_at(expires_in: u64) -> NaiveDateTime {
+    let expires_in = chrono::Duration::try_seconds(expires_in.min(i64::MAX as u64) as i64)
+        .unwrap_or(chrono::Duration::MAX);
+    now()
+        .checked_add_signed(expires_in)
+        .unwrap_or(NaiveDateTime::MAX)
+}
+
+fn expires_in(expires_at: NaiveDateTime) -> u64 {
+    (expires_at - now()).num_seconds().max(0) as u64
+}
+
+impl From<SqlxUser> for AdaptUser {
+    fn from(user: SqlxUser) -> Self {
+        AdaptUser {
+            id: Some(user.id),
+            username: user.name,
+            email: user.email,
+            image: user.image,
+            email_verified: user.email_verified.map(|_| true),
+        }
+    }
+}
+
+impl From<AdaptUser> for SqlxUser {
+    fn from(user: AdaptUser) -> Self {
+        let now = now();
+        SqlxUser {
+            id: user.id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string()),
+            name: user.username,
+            email: user.email,
+            image: user.image,
+            email_verified: user.email_verified.filter(|v| *v).map(|_| now),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+}
+
+impl TryFrom<SqlxAccount> for AdaptAccount {
+    type Error = AdaptorError;
+
+    fn try_from(account: SqlxAccount) -> Result<Self, Self::Error> {
+        let mut token = Token {
+            access_token: account.access_token,
+            token_type: account.token_type,
+            refresh_token: account.refresh_token,
+            expires_in: account.expires_at.map(expires_in),
+            scope: account.scope,
+            id_token: account.id_token,
+            ..Default::default()
+        };
+        if let Some(session_state) = account.session_state {
+            token
+                .others
+                .insert("session_state".to_string(), session_state);
+        }
+
+        Ok(AdaptAccount {
+            id: Some(account.id),
+            user_id: Some(account.user_id),
+            provider_id: Some(account.provider_id),
+            provider_type: account
+                .provider_type
+                .try_into()
+                .map_err(AdaptorError::Other)?,
+            provider_account_id: Some(account.provider_account_id),
+            token: Some(token),
+        })
+    }
+}
+
+impl TryFrom<AdaptAccount> for SqlxAccount {
+    type Error = AdaptorError;
+
+    fn try_from(account: AdaptAccount) -> Result<Self, Self::Error> {
+        let required = |field: Option<String>, name: &str| {
+            field.ok_or_else(|| AdaptorError::Other(format!("Account has no {}", name)))
+        };
+        let token = account.token.unwrap_or_default();
+        let now = now();
+
+        Ok(SqlxAccount {
+            id: account
+                .id
+                .unwrap_or_else(|| uuid::Uuid::new_v4().to_string()),
+            provider_id: required(account.provider_id, "provider ID")?,
+            provider_account_id: required(account.provider_account_id, "provider account ID")?,
+            user_id: required(account.user_id, "user ID")?,
+            provider_type: account.provider_type.into(),
+            refresh_token: token.refresh_token,
+            access_token: token.access_token,
+            expires_at: token.expires_in.map(expires_at),
+            token_type: token.token_type,
+            scope: token.scope,
+            id_token: token.id_token,
+            session_state: token.others.get("session_state").cloned(),
+            created_at: now,
+            updated_at: now,
+        })
+    }
+}
+
+impl From<SqlxSession> for AdaptSession {
+    fn from(session: SqlxSession) -> Self {
+        AdaptSession {
+            token: session.token,
+            user_id: session.user_id,
+            expires_in: expires_in(session.expires_at),
+        }
+    }
+}
+
+impl From<AdaptSession> for SqlxSession {
+    fn from(session: AdaptSession) -> Self {
+        let now = now();
+        SqlxSession {
+            id: uuid::Uuid::new_v4().to_string(),
+            user_id: session.user_id,
+            token: session.token,
+            expires_at: expires_at(session.expires_in),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+}
+
+impl From<SqlxVerificationToken> for AdaptVerificationToken {
+    fn from(token: SqlxVerificationToken) -> Self {
+        AdaptVerificationToken {
+            email: token.email,
+            token: token.token,
+            expires_in: expires_in(token.expires_at),
+        }
+    }
+}
+
+impl From<AdaptVerificationToken> for SqlxVerificationToken {
+    fn from(token: AdaptVerificationToken) -> Self {
+        let now = now();
+        SqlxVerificationToken {
+            id: uuid::Uuid::new_v4().to_string(),
+            email: token.email,
+            token: token.token,
+            expires_at: expires_at(token.expires_in),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+}
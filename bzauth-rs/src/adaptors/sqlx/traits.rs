@@ -1 +1,335 @@
-// TODO:
\ No newline at end of file
+use super::models::{SqlxAccount, SqlxSession, SqlxUser, SqlxVerificationToken};
+
+#[async_trait::async_trait]
+pub trait AdaptUserOperation
+where
+    Self: Send,
+{
+    async fn create_user(&mut self, user: &SqlxUser) -> sqlx::Result<SqlxUser>;
+    async fn find_user_by_id(&mut self, id: &str) -> sqlx::Result<Option<SqlxUser>>;
+    async fn find_user_by_email(&mut self, email: &str) -> sqlx::Result<Option<SqlxUser>>;
+    async fn update_user(&mut self, user: &SqlxUser) -> sqlx::Result<SqlxUser>;
+    async fn delete_user(&mut self, id: &str) -> sqlx::Result<()>;
+}
+
+#[async_trait::async_trait]
+pub trait AdaptAccountOperation
+where
+    Self: Send,
+{
+    async fn link_account(&mut self, account: &SqlxAccount) -> sqlx::Result<SqlxAccount>;
+    async fn update_account(&mut self, account: &SqlxAccount) -> sqlx::Result<SqlxAccount>;
+    async fn unlink_account(
+        &mut self,
+        provider_id: &str,
+        provider_account_id: &str,
+    ) -> sqlx::Result<()>;
+    async fn find_user_by_account(
+        &mut self,
+        provider_id: &str,
+        provider_account_id: &str,
+    ) -> sqlx::Result<Option<SqlxUser>>;
+    async fn find_account_by_id(
+        &mut self,
+        provider_id: &str,
+        provider_account_id: &str,
+    ) -> sqlx::Result<Option<SqlxAccount>>;
+    async fn find_accounts_by_user(&mut self, user_id: &str) -> sqlx::Result<Vec<SqlxAccount>>;
+}
+
+#[async_trait::async_trait]
+pub trait AdaptSessionOperation
+where
+    Self: Send,
+{
+    async fn create_session(&mut self, session: &SqlxSession) -> sqlx::Result<SqlxSession>;
+    async fn update_session(&mut self, session: &SqlxSession) -> sqlx::Result<SqlxSession>;
+    async fn find_session_and_user(
+        &mut self,
+        token: &str,
+    ) -> sqlx::Result<Option<(SqlxSession, SqlxUser)>>;
+    async fn delete_session(&mut self, token: &str) -> sqlx::Result<()>;
+}
+
+#[async_trait::async_trait]
+pub trait AdaptVerificationTokenOperation
+where
+    Self: Send,
+{
+    async fn create_verification_token(
+        &mut self,
+        token: &SqlxVerificationToken,
+    ) -> sqlx::Result<SqlxVerificationToken>;
+    /// Deletes the token, returning it if it existed
+    async fn use_verification_token(
+        &mut self,
+        email: &str,
+        token: &str,
+    ) -> sqlx::Result<Option<SqlxVerificationToken>>;
+}
+
+// The statements are shared by the backends, which all support `$n` parameters and `RETURNING`
+macro_rules! adapt_sqlx_operations {
+    ($connection:ty) => {
+        #[async_trait::async_trait]
+        impl AdaptUserOperation for $connection {
+            async fn create_user(&mut self, user: &SqlxUser) -> sqlx::Result<SqlxUser> {
+                sqlx::query_as(
+                    "INSERT INTO users (id, name, email, email_verified, image, created_at, \
+                     updated_at) VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING *",
+                )
+                .bind(&user.id)
+                .bind(&user.name)
+                .bind(&user.email)
+                .bind(user.email_verified)
+                .bind(&user.image)
+                .bind(user.created_at)
+                .bind(user.updated_at)
+                .fetch_one(self)
+                .await
+            }
+
+            async fn find_user_by_id(&mut self, id: &str) -> sqlx::Result<Option<SqlxUser>> {
+                sqlx::query_as("SELECT * FROM users WHERE id = $1")
+                    .bind(id)
+                    .fetch_optional(self)
+                    .await
+            }
+
+            async fn find_user_by_email(&mut self, email: &str) -> sqlx::Result<Option<SqlxUser>> {
+                sqlx::query_as("SELECT * FROM users WHERE email = $1")
+                    .bind(email)
+                    .fetch_optional(self)
+                    .await
+            }
+
+            async fn update_user(&mut self, user: &SqlxUser) -> sqlx::Result<SqlxUser> {
+                // The verification of the email is kept unless the user is verified again
+                sqlx::query_as(
+                    "UPDATE users SET name = $1, email = $2, image = $3, updated_at = $4, \
+                     email_verified = COALESCE($6, email_verified) WHERE id = $5 RETURNING *",
+                )
+                .bind(&user.name)
+                .bind(&user.email)
+                .bind(&user.image)
+                .bind(user.updated_at)
+                .bind(&user.id)
+                .bind(user.email_verified)
+                .fetch_one(self)
+                .await
+            }
+
+            async fn delete_user(&mut self, id: &str) -> sqlx::Result<()> {
+                // Accounts and sessions of the user are deleted by the foreign keys
+                sqlx::query("DELETE FROM users WHERE id = $1")
+                    .bind(id)
+                    .execute(self)
+                    .await
+                    .map(|_| ())
+            }
+        }
+
+        #[async_trait::async_trait]
+        impl AdaptAccountOperation for $connection {
+            async fn link_account(&mut self, account: &SqlxAccount) -> sqlx::Result<SqlxAccount> {
+                sqlx::query_as(
+                    "INSERT INTO accounts (id, provider_id, provider_account_id, user_id, \
+                     provider_type, refresh_token, access_token, expires_at, token_type, scope, \
+                     id_token, session_state, created_at, updated_at) \
+                     VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14) \
+                     RETURNING *",
+                )
+                .bind(&account.id)
+                .bind(&account.provider_id)
+                .bind(&account.provider_account_id)
+                .bind(&account.user_id)
+                .bind(&account.provider_type)
+                .bind(&account.refresh_token)
+                .bind(&account.access_token)
+                .bind(account.expires_at)
+                .bind(&account.token_type)
+                .bind(&account.scope)
+                .bind(&account.id_token)
+                .bind(&account.session_state)
+                .bind(account.created_at)
+                .bind(account.updated_at)
+                .fetch_one(self)
+                .await
+            }
+
+            async fn update_account(&mut self, account: &SqlxAccount) -> sqlx::Result<SqlxAccount> {
+                sqlx::query_as(
+                    "UPDATE accounts SET refresh_token = $1, access_token = $2, expires_at = $3, \
+                     token_type = $4, scope = $5, id_token = $6, session_state = $7, \
+                     updated_at = $8 WHERE provider_id = $9 AND provider_account_id = $10 \
+                     RETURNING *",
+                )
+                .bind(&account.refresh_token)
+                .bind(&account.access_token)
+                .bind(account.expires_at)
+                .bind(&account.token_type)
+                .bind(&account.scope)
+                .bind(&account.id_token)
+                .bind(&account.session_state)
+                .bind(account.updated_at)
+                .bind(&account.provider_id)
+                .bind(&account.provider_account_id)
+                .fetch_one(self)
+                .await
+            }
+
+            async fn unlink_account(
+                &mut self,
+                provider_id: &str,
+                provider_account_id: &str,
+            ) -> sqlx::Result<()> {
+                sqlx::query(
+                    "DELETE FROM accounts WHERE provider_id = $1 AND provider_account_id = $2",
+                )
+                .bind(provider_id)
+                .bind(provider_account_id)
+                .execute(self)
+                .await
+                .map(|_| ())
+            }
+
+            async fn find_user_by_account(
+                &mut self,
+                provider_id: &str,
+                provider_account_id: &str,
+            ) -> sqlx::Result<Option<SqlxUser>> {
+                sqlx::query_as(
+                    "SELECT users.* FROM users INNER JOIN accounts ON accounts.user_id = users.id \
+                     WHERE accounts.provider_id = $1 AND accounts.provider_account_id = $2",
+                )
+                .bind(provider_id)
+                .bind(provider_account_id)
+                .fetch_optional(self)
+                .await
+            }
+
+            async fn find_account_by_id(
+                &mut self,
+                provider_id: &str,
+                provider_account_id: &str,
+            ) -> sqlx::Result<Option<SqlxAccount>> {
+                sqlx::query_as(
+                    "SELECT * FROM accounts WHERE provider_id = $1 AND provider_account_id = $2",
+                )
+                .bind(provider_id)
+                .bind(provider_account_id)
+                .fetch_optional(self)
+                .await
+            }
+
+            async fn find_accounts_by_user(
+                &mut self,
+                user_id: &str,
+            ) -> sqlx::Result<Vec<SqlxAccount>> {
+                sqlx::query_as(
+                    "SELECT * FROM accounts WHERE user_id = $1 \
+                     ORDER BY provider_id, provider_account_id",
+                )
+                .bind(user_id)
+                .fetch_all(self)
+                .await
+            }
+        }
+
+        #[async_trait::async_trait]
+        impl AdaptSessionOperation for $connection {
+            async fn create_session(&mut self, session: &SqlxSession) -> sqlx::Result<SqlxSession> {
+                sqlx::query_as(
+                    "INSERT INTO sessions (id, user_id, token, expires_at, created_at, updated_at) \
+                     VALUES ($1, $2, $3, $4, $5, $6) RETURNING *",
+                )
+                .bind(&session.id)
+                .bind(&session.user_id)
+                .bind(&session.token)
+                .bind(session.expires_at)
+                .bind(session.created_at)
+                .bind(session.updated_at)
+                .fetch_one(self)
+                .await
+            }
+
+            async fn update_session(&mut self, session: &SqlxSession) -> sqlx::Result<SqlxSession> {
+                sqlx::query_as(
+                    "UPDATE sessions SET expires_at = $1, updated_at = $2 WHERE token = $3 \
+                     RETURNING *",
+                )
+                .bind(session.expires_at)
+                .bind(session.updated_at)
+                .bind(&session.token)
+                .fetch_one(self)
+                .await
+            }
+
+            async fn find_session_and_user(
+                &mut self,
+                token: &str,
+            ) -> sqlx::Result<Option<(SqlxSession, SqlxUser)>> {
+                let session: Option<SqlxSession> =
+                    sqlx::query_as("SELECT * FROM sessions WHERE token = $1")
+                        .bind(token)
+                        .fetch_optional(&mut *self)
+                        .await?;
+                let Some(session) = session else {
+                    return Ok(None);
+                };
+
+                let user = self.find_user_by_id(&session.user_id).await?;
+                Ok(user.map(|user| (session, user)))
+            }
+
+            async fn delete_session(&mut self, token: &str) -> sqlx::Result<()> {
+                sqlx::query("DELETE FROM sessions WHERE token = $1")
+                    .bind(token)
+                    .execute(self)
+                    .await
+                    .map(|_| ())
+            }
+        }
+
+        #[async_trait::async_trait]
+        impl AdaptVerificationTokenOperation for $connection {
+            async fn create_verification_token(
+                &mut self,
+                token: &SqlxVerificationToken,
+            ) -> sqlx::Result<SqlxVerificationToken> {
+                sqlx::query_as(
+                    "INSERT INTO verification_tokens (id, email, token, expires_at, created_at, \
+                     updated_at) VALUES ($1, $2, $3, $4, $5, $6) RETURNING *",
+                )
+                .bind(&token.id)
+                .bind(&token.email)
+                .bind(&token.token)
+                .bind(token.expires_at)
+                .bind(token.created_at)
+                .bind(token.updated_at)
+                .fetch_one(self)
+                .await
+            }
+
+            async fn use_verification_token(
+                &mut self,
+                email: &str,
+                token: &str,
+            ) -> sqlx::Result<Option<SqlxVerificationToken>> {
+                sqlx::query_as(
+                    "DELETE FROM verification_tokens WHERE email = $1 AND token = $2 RETURNING *",
+                )
+                .bind(email)
+                .bind(token)
+                .fetch_optional(self)
+                .await
+            }
+        }
+    };
+}
+
+#[cfg(feature = "backend_sqlite")]
+adapt_sqlx_operations!(sqlx::SqliteConnection);
+
+#[cfg(feature = "backend_postgres")]
+adapt_sqlx_operations!(sqlx::PgConnection);
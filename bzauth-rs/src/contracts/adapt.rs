@@ -3,6 +3,7 @@ use serde::{Deserialize, Serialize};
 use super::account::Account;
 use super::session::Session;
 use super::user::User;
+use crate::adaptors::error::AdaptorError;
 
 pub type AdaptUser = User;
 
@@ -30,16 +31,21 @@ pub struct SessionUser {
 pub struct AdaptSession {
     pub token: String,
     pub user_id: String,
+    /// The seconds left before the session expires, 0 once expired
     pub expires_in: u64,
 }
 impl AdaptSession {
+    pub fn is_expired(&self) -> bool {
+        self.expires_in == 0
+    }
+
     pub fn adapt_from(session: Session, token: String) -> Self {
         AdaptSession {
             token,
             user_id: session.user.unwrap().id.unwrap(),
             expires_in: {
                 let now = chrono::Utc::now().timestamp() as u64;
-                session.expires_at.unwrap_or(0) - now
+                session.expires_at.unwrap_or(0).saturating_sub(now)
             },
         }
     }
@@ -49,6 +55,7 @@ impl AdaptSession {
                 id: Some(self.user_id.clone()),
                 ..session.user.clone().unwrap()
             }),
+            account: session.account.clone(),
             expires_at: {
                 let now = chrono::Utc::now().timestamp() as u64;
                 Some(self.expires_in + now)
@@ -69,34 +76,48 @@ pub struct UseVerificationTokenOptions {
     pub token: String,
 }
 
+pub type AdaptResult<T> = Result<T, AdaptorError>;
+
+/// The store of users, accounts, sessions and verification tokens. Lookups of records that do not
+/// exist return `Ok(None)`, errors are kept for a store that cannot fulfil the request.
 #[async_trait::async_trait]
 pub trait Adapt
 where
     Self: Send + Sync,
 {
-    async fn create_user(&self, user: AdaptUser) -> AdaptUser;
-    async fn get_user(&self, id: String) -> Option<AdaptUser>;
-    async fn get_user_by_email(&self, email: String) -> Option<AdaptUser>;
-    async fn get_user_by_account(&self, provider: ProviderAccountId) -> Option<AdaptUser>;
+    async fn create_user(&self, user: AdaptUser) -> AdaptResult<AdaptUser>;
+    async fn get_user(&self, id: String) -> AdaptResult<Option<AdaptUser>>;
+    async fn get_user_by_email(&self, email: String) -> AdaptResult<Option<AdaptUser>>;
+    async fn get_user_by_account(
+        &self,
+        provider: ProviderAccountId,
+    ) -> AdaptResult<Option<AdaptUser>>;
     /// Id is required
-    async fn update_user(&self, user: AdaptUser) -> AdaptUser;
-    async fn delete_user(&self, id: String) -> ();
-
-    async fn get_account(&self, provider: ProviderAccountId) -> Option<AdaptAccount>;
-    async fn link_account(&self, account: AdaptAccount) -> Option<AdaptAccount>;
-    async fn unlink_account(&self, provider: ProviderAccountId) -> ();
-
-    async fn create_session(&self, options: CreateSessionOptions) -> Option<AdaptSession>;
-    async fn get_session_and_user(&self, token: String) -> Option<SessionUser>;
+    async fn update_user(&self, user: AdaptUser) -> AdaptResult<AdaptUser>;
+    async fn delete_user(&self, id: String) -> AdaptResult<()>;
+
+    async fn get_account(&self, provider: ProviderAccountId) -> AdaptResult<Option<AdaptAccount>>;
+    /// The accounts linked to the user, ordered by provider
+    async fn get_accounts_by_user(&self, user_id: String) -> AdaptResult<Vec<AdaptAccount>>;
+    async fn link_account(&self, account: AdaptAccount) -> AdaptResult<AdaptAccount>;
+    /// Provider and provider account ID are required. Only the token of the account is updated
+    async fn update_account(&self, account: AdaptAccount) -> AdaptResult<AdaptAccount>;
+    async fn unlink_account(&self, provider: ProviderAccountId) -> AdaptResult<()>;
+
+    async fn create_session(&self, options: CreateSessionOptions) -> AdaptResult<AdaptSession>;
+    async fn get_session_and_user(&self, token: String) -> AdaptResult<Option<SessionUser>>;
     /// session_token required
-    async fn update_session(&self, session: AdaptSession) -> AdaptSession;
-    async fn delete_session(&self, token: String) -> ();
+    async fn update_session(&self, session: AdaptSession) -> AdaptResult<AdaptSession>;
+    async fn delete_session(&self, token: String) -> AdaptResult<()>;
 
-    fn create_verification_token(&self, token: AdaptVerificationToken) -> AdaptVerificationToken;
-    fn use_verification_token(
+    async fn create_verification_token(
+        &self,
+        token: AdaptVerificationToken,
+    ) -> AdaptResult<AdaptVerificationToken>;
+    async fn use_verification_token(
         &self,
         options: UseVerificationTokenOptions,
-    ) -> Option<AdaptVerificationToken>;
+    ) -> AdaptResult<Option<AdaptVerificationToken>>;
 
     // TODO: Omitting WebAuthn methods for now
 }
@@ -6,6 +6,11 @@ use serde::{Deserialize, Serialize};
 use super::endpoint::Endpoint;
 use super::profile::Profile;
 use super::user::User;
+use crate::providers::credentials::CredentialsProvider;
+use crate::providers::email::EmailProvider;
+use crate::providers::error::ProviderError;
+use crate::tools::generators;
+use crate::tools::jose::{DEFAULT_CLOCK_SKEW, JwksCache};
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
 pub enum ProviderType {
@@ -56,11 +61,14 @@ impl std::fmt::Display for ProviderType {
     }
 }
 
-#[derive(Debug, Clone)]
+/// The checks performed during an OAuth2 flow to protect the authorisation code
+#[derive(Debug, Clone, PartialEq)]
 pub enum ProviderOAuth2Check {
     None,
     State,
     PKCE,
+    /// OpenID Connect only: binds the id_token to the sign-in with a nonce
+    Nonce,
 }
 
 pub trait Provide: Send + Sync + Any + 'static
@@ -76,6 +84,15 @@ where
     fn as_oauth2(&self) -> Option<&dyn ProvideOAuth2> {
         None
     }
+    fn as_oidc(&self) -> Option<&dyn ProvideOidc> {
+        None
+    }
+    fn as_email(&self) -> Option<&EmailProvider> {
+        None
+    }
+    fn as_credentials(&self) -> Option<&CredentialsProvider> {
+        None
+    }
 }
 dyn_clone::clone_trait_object!(Provide);
 
@@ -103,8 +120,21 @@ impl Provide for Box<dyn Provide> {
     fn as_oauth2(&self) -> Option<&dyn ProvideOAuth2> {
         self.as_ref().as_oauth2()
     }
+
+    fn as_oidc(&self) -> Option<&dyn ProvideOidc> {
+        self.as_ref().as_oidc()
+    }
+
+    fn as_email(&self) -> Option<&EmailProvider> {
+        self.as_ref().as_email()
+    }
+
+    fn as_credentials(&self) -> Option<&CredentialsProvider> {
+        self.as_ref().as_credentials()
+    }
 }
 
+#[async_trait::async_trait]
 pub trait ProvideOAuth2: Send + Sync + Any + ProvidesProfile + 'static
 where
     Self: DynClone,
@@ -119,9 +149,102 @@ where
     fn auth_endpoint(&self) -> Endpoint;
     fn token_endpoint(&self) -> Endpoint;
     fn profile_endpoint(&self) -> Endpoint;
+
+    /// The RFC 7009 endpoint the tokens of an account are revoked at. Defaults to none, leaving the
+    /// tokens to expire at the provider.
+    /// https://datatracker.ietf.org/doc/html/rfc7009
+    fn revocation_endpoint(&self) -> Option<Endpoint> {
+        None
+    }
+
+    /// The scopes requested during the authorisation flow. Defaults to none.
+    fn scopes(&self) -> Vec<String> {
+        vec![]
+    }
+
+    /// The checks to perform during the authorisation flow. Defaults to state only.
+    fn checks(&self) -> Vec<ProviderOAuth2Check> {
+        vec![ProviderOAuth2Check::State]
+    }
+
+    /// Returns the provider as an OpenID Connect provider, if it is one
+    fn as_oidc(&self) -> Option<&dyn ProvideOidc> {
+        None
+    }
+
+    /// Fetches the user's profile with the access token. Defaults to a bearer GET of the profile
+    /// endpoint; override it for providers that spread the profile over several endpoints.
+    async fn fetch_profile(&self, access_token: &str) -> Result<Profile, ProviderError> {
+        generators::generate_http_client()
+            .map_err(|_| ProviderError::ProfileFailed("Failed to create HTTP client".to_string()))?
+            .get(self.profile_endpoint().url())
+            .bearer_auth(access_token)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| ProviderError::ProfileFailed(e.to_string()))?
+            .json::<Profile>()
+            .await
+            .map_err(|e| ProviderError::ProfileFailed(e.to_string()))
+    }
 }
 dyn_clone::clone_trait_object!(ProvideOAuth2);
 
+/// The OpenID Provider metadata, as served from `/.well-known/openid-configuration`.
+/// https://openid.net/specs/openid-connect-discovery-1_0.html#ProviderMetadata
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OidcMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub userinfo_endpoint: Option<String>,
+    pub jwks_uri: String,
+    pub end_session_endpoint: Option<String>,
+    pub revocation_endpoint: Option<String>,
+    pub scopes_supported: Option<Vec<String>>,
+    pub id_token_signing_alg_values_supported: Option<Vec<String>>,
+
+    #[serde(flatten)]
+    pub others: serde_json::Value,
+}
+
+/// An OAuth2 provider whose endpoints are discovered from an OpenID Connect issuer. The endpoints
+/// of [ProvideOAuth2] are only valid once [ProvideOidc::discover] has succeeded.
+#[async_trait::async_trait]
+pub trait ProvideOidc: ProvideOAuth2 {
+    /// The issuer identifier, which must match the `iss` claim of the id_token
+    fn issuer(&self) -> String;
+
+    /// The accepted values of the `iss` claim. Defaults to the issuer.
+    fn issuers(&self) -> Vec<String> {
+        vec![self.issuer()]
+    }
+
+    /// Fetches the issuer's metadata, or returns the cached copy
+    async fn discover(&self) -> Result<OidcMetadata, ProviderError>;
+
+    /// The cache of the issuer's signing keys, shared between clones of the provider
+    fn jwks(&self) -> JwksCache;
+
+    /// The tolerated clock skew when checking the id_token lifetime (in seconds)
+    fn clock_skew(&self) -> i64 {
+        DEFAULT_CLOCK_SKEW
+    }
+
+    /// Whether the profile is fetched from the userinfo endpoint rather than read from the
+    /// verified id_token. Defaults to the id_token, saving a round-trip.
+    fn fetch_userinfo(&self) -> bool {
+        false
+    }
+
+    /// Whether signing out also ends the session at the issuer, through RP-initiated logout.
+    /// Only applies when the issuer advertises an `end_session_endpoint`. Defaults to false.
+    fn rp_initiated_logout(&self) -> bool {
+        false
+    }
+}
+
 pub trait ProvidesProfile: Send + Sync + Any + 'static
 where
     Self: DynClone,
@@ -156,6 +279,10 @@ impl<T: ProvideOAuth2> Provide for T {
     fn as_oauth2(&self) -> Option<&dyn ProvideOAuth2> {
         Some(self)
     }
+
+    fn as_oidc(&self) -> Option<&dyn ProvideOidc> {
+        ProvideOAuth2::as_oidc(self)
+    }
 }
 
 // impl<T: Provide> From<T> for Box<dyn Provide> {
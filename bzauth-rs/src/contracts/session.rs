@@ -1,7 +1,32 @@
+use serde::{Deserialize, Serialize};
+
+use super::account::Account;
+use super::provide::ProviderType;
 use super::user::User;
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Session {
     pub user: Option<User>,
+    /// The account used to sign in. Only kept by stateless sessions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account: Option<SessionAccount>,
     pub expires_at: Option<u64>,
 }
+
+/// A summary of the account used to sign in, without its tokens
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct SessionAccount {
+    pub provider_id: Option<String>,
+    pub provider_type: ProviderType,
+    pub provider_account_id: Option<String>,
+}
+
+impl From<&Account> for SessionAccount {
+    fn from(account: &Account) -> Self {
+        SessionAccount {
+            provider_id: account.provider_id.clone(),
+            provider_type: account.provider_type.clone(),
+            provider_account_id: account.provider_account_id.clone(),
+        }
+    }
+}
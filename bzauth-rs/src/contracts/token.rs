@@ -15,3 +15,16 @@ pub struct Token {
     #[serde(flatten)]
     pub others: HashMap<String, String>,
 }
+
+impl Token {
+    /// The token issued in place of `previous`. Providers may leave out the refresh token, scope
+    /// or id_token when they did not change, so those of `previous` are kept.
+    pub fn replacing(self, previous: Token) -> Token {
+        Token {
+            refresh_token: self.refresh_token.or(previous.refresh_token),
+            scope: self.scope.or(previous.scope),
+            id_token: self.id_token.or(previous.id_token),
+            ..self
+        }
+    }
+}
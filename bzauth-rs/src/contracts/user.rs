@@ -6,4 +6,7 @@ pub struct User {
     pub username: Option<String>,
     pub email: Option<String>,
     pub image: Option<String>,
+    /// Whether the email was verified, by the provider the user signed up with or by a sign-in link
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email_verified: Option<bool>,
 }
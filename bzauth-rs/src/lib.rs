@@ -7,3 +7,6 @@ pub mod adaptors;
 pub mod auth;
 pub mod providers;
 pub mod runtimes;
+
+#[cfg(feature = "testing")]
+pub mod testing;
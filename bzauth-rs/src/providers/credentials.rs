@@ -0,0 +1,322 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+/// The cost of the argon2id password hashes
+pub use argon2::Params as PasswordParams;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::{Algorithm, Argon2, Version};
+
+use super::error::ProviderError;
+use crate::adaptors::error::AdaptorError;
+use crate::contracts::account::Account;
+use crate::contracts::adapt::{Adapt, ProviderAccountId};
+use crate::contracts::provide::{Provide, ProviderType};
+use crate::contracts::token::Token;
+use crate::contracts::user::User;
+use crate::tools::CoreError;
+use crate::tools::awaitable::Awaitable;
+
+/// How long a failed sign-in takes at least, so that failures cannot tell which accounts exist
+pub const DEFAULT_CREDENTIALS_FAILURE_DELAY: Duration = Duration::from_millis(500);
+
+/// The `token_type` of the accounts holding a password hash, in place of an access token
+pub const PASSWORD_TOKEN_TYPE: &str = "password";
+
+/// The form posted to sign in with a [CredentialsProvider]
+#[derive(Clone, Default)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+    /// The other fields of the form, e.g. a one-time code
+    pub fields: HashMap<String, String>,
+}
+
+impl Credentials {
+    /// Reads the `username` and `password` fields, keeping the rest but the CSRF token
+    pub fn from_form(mut form: HashMap<String, String>) -> Self {
+        let username = form.remove("username").unwrap_or_default();
+        let password = form.remove("password").unwrap_or_default();
+        form.remove(crate::tools::request_extractors::CSRF_TOKEN_FIELD);
+
+        Self {
+            username: username.trim().to_string(),
+            password,
+            fields: form,
+        }
+    }
+}
+
+impl std::fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Credentials")
+            .field("username", &self.username)
+            .field("password", &"<redacted>")
+            .field("fields", &self.fields.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+/// Checks the credentials, returning the user they belong to
+pub type AuthorizeCallback = Arc<dyn Fn(Credentials) -> Awaitable<Option<User>> + Send + Sync>;
+
+/// Signs users in with a username and password, posted as a form to `/callback/{provider}`.
+///
+/// The credentials are checked by the `authorize` callback if there is one. Otherwise the
+/// passwords are kept through the adaptor, hashed with argon2id: each user has an account of the
+/// provider, named after their username, whose access token is the hash. See
+/// [CredentialsProvider::set_password].
+#[derive(Clone)]
+pub struct CredentialsProvider {
+    id: String,
+    name: String,
+    authorize: Option<AuthorizeCallback>,
+    password_params: PasswordParams,
+    failure_delay: Duration,
+    /// Verified against when there is no account, so that unknown users take as long to refuse
+    dummy_hash: Arc<OnceLock<String>>,
+}
+
+#[derive(Clone, Default)]
+pub struct CredentialsProviderOptions {
+    /// Overrides the provider ID, which is also the path of its routes. Defaults to `credentials`
+    pub id: Option<String>,
+    /// Defaults to `Credentials`
+    pub name: Option<String>,
+    /// Checks the credentials instead of the built-in password store
+    pub authorize: Option<AuthorizeCallback>,
+    /// The cost of the argon2id password hashes. Defaults to the argon2 defaults (19 MiB, 2
+    /// iterations, 1 lane)
+    pub password_params: Option<PasswordParams>,
+    /// How long a failed sign-in takes at least. Defaults to 500 ms, and must be longer than a
+    /// successful check takes for the failures to be indistinguishable
+    pub failure_delay: Option<Duration>,
+}
+
+impl CredentialsProvider {
+    /// Create a new CredentialsProvider using the built-in password store
+    pub fn new() -> Self {
+        Self::from_options(CredentialsProviderOptions::default())
+    }
+
+    /// Create a new CredentialsProvider checking the credentials with the callback
+    pub fn with_authorize(authorize: AuthorizeCallback) -> Self {
+        Self::from_options(CredentialsProviderOptions {
+            authorize: Some(authorize),
+            ..Default::default()
+        })
+    }
+
+    pub fn from_options(options: CredentialsProviderOptions) -> Self {
+        CredentialsProvider {
+            id: options.id.unwrap_or_else(|| "credentials".to_string()),
+            name: options.name.unwrap_or_else(|| "Credentials".to_string()),
+            authorize: options.authorize,
+            password_params: options.password_params.unwrap_or_default(),
+            failure_delay: options
+                .failure_delay
+                .unwrap_or(DEFAULT_CREDENTIALS_FAILURE_DELAY),
+            dummy_hash: Arc::new(OnceLock::new()),
+        }
+    }
+
+    pub fn failure_delay(&self) -> Duration {
+        self.failure_delay
+    }
+
+    /// Whether the credentials are checked against the built-in password store
+    pub fn uses_password_store(&self) -> bool {
+        self.authorize.is_none()
+    }
+
+    fn argon2(&self) -> Argon2<'static> {
+        Argon2::new(
+            Algorithm::Argon2id,
+            Version::V0x13,
+            self.password_params.clone(),
+        )
+    }
+
+    /// Hashes the password with argon2id and a random salt, as a PHC string
+    pub fn hash_password(&self, password: &str) -> Result<String, ProviderError> {
+        let salt: [u8; 16] = rand::random();
+        let salt =
+            SaltString::encode_b64(&salt).map_err(|e| ProviderError::HashFailed(e.to_string()))?;
+
+        self.argon2()
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+            .map_err(|e| ProviderError::HashFailed(e.to_string()))
+    }
+
+    /// Whether the password matches the PHC string. The parameters are read from the hash, so
+    /// older hashes still verify once the cost changes
+    pub fn verify_password(&self, password: &str, hash: &str) -> bool {
+        PasswordHash::new(hash)
+            .map(|hash| {
+                self.argon2()
+                    .verify_password(password.as_bytes(), &hash)
+                    .is_ok()
+            })
+            .unwrap_or(false)
+    }
+
+    /// Verifies the password on the blocking thread pool, as hashing is slow by design. Without a
+    /// hash, a dummy one is verified so that the check takes as long.
+    async fn verify_password_blocking(&self, password: String, hash: Option<String>) -> bool {
+        let provider = self.clone();
+        tokio::task::spawn_blocking(move || {
+            let known = hash.is_some();
+            let hash = hash.unwrap_or_else(|| {
+                provider
+                    .dummy_hash
+                    .get_or_init(|| provider.hash_password("").unwrap_or_default())
+                    .clone()
+            });
+            provider.verify_password(&password, &hash) && known
+        })
+        .await
+        .unwrap_or(false)
+    }
+
+    /// Sets the password of the user in the built-in password store, replacing any previous one.
+    /// The username must not belong to another user.
+    pub async fn set_password(
+        &self,
+        adaptor: &dyn Adapt,
+        user_id: &str,
+        username: &str,
+        password: &str,
+    ) -> Result<Account, CoreError> {
+        let username = username.trim().to_string();
+        let provider_account_id = || ProviderAccountId {
+            provider_id: self.id.clone(),
+            provider_account_id: username.clone(),
+        };
+
+        if let Some(account) = adaptor.get_account(provider_account_id()).await? {
+            if account.user_id.as_deref() != Some(user_id) {
+                return Err(AdaptorError::Conflict(format!(
+                    "Username {} is already taken",
+                    username
+                ))
+                .into());
+            }
+            adaptor.unlink_account(provider_account_id()).await?;
+        }
+
+        let provider = self.clone();
+        let password = password.to_string();
+        let hash = tokio::task::spawn_blocking(move || provider.hash_password(&password))
+            .await
+            .map_err(|e| ProviderError::HashFailed(e.to_string()))??;
+
+        let account = adaptor
+            .link_account(Account {
+                id: None,
+                user_id: Some(user_id.to_string()),
+                provider_id: Some(self.id.clone()),
+                provider_type: ProviderType::Credentials,
+                provider_account_id: Some(username),
+                token: Some(Token {
+                    access_token: Some(hash),
+                    token_type: Some(PASSWORD_TOKEN_TYPE.to_string()),
+                    ..Default::default()
+                }),
+            })
+            .await?;
+        Ok(account)
+    }
+
+    /// Checks the credentials, returning the user and the account they signed in with. The account
+    /// never holds the password hash.
+    pub async fn authorize(
+        &self,
+        adaptor: Option<&dyn Adapt>,
+        credentials: Credentials,
+    ) -> Result<Option<(User, Account)>, CoreError> {
+        let account = |user: &User| Account {
+            id: None,
+            user_id: user.id.clone(),
+            provider_id: Some(self.id.clone()),
+            provider_type: ProviderType::Credentials,
+            provider_account_id: Some(credentials.username.clone()),
+            token: None,
+        };
+
+        if let Some(authorize) = &self.authorize {
+            let user = authorize(credentials.clone()).await;
+            return Ok(user.map(|user| {
+                let account = account(&user);
+                (user, account)
+            }));
+        }
+
+        let adaptor = adaptor.ok_or_else(|| {
+            CoreError::new().with_message("An adaptor is required for the password store")
+        })?;
+        let stored = match credentials.username.is_empty() {
+            true => None,
+            false => {
+                adaptor
+                    .get_account(ProviderAccountId {
+                        provider_id: self.id.clone(),
+                        provider_account_id: credentials.username.clone(),
+                    })
+                    .await?
+            }
+        };
+        let hash = stored
+            .as_ref()
+            .and_then(|account| account.token.as_ref())
+            .filter(|token| token.token_type.as_deref() == Some(PASSWORD_TOKEN_TYPE))
+            .and_then(|token| token.access_token.clone());
+
+        let verified = self
+            .verify_password_blocking(credentials.password.clone(), hash)
+            .await;
+        let user_id = stored.and_then(|account| account.user_id);
+        let user = match (verified, user_id) {
+            (true, Some(user_id)) => adaptor.get_user(user_id).await?,
+            _ => None,
+        };
+
+        Ok(user.map(|user| {
+            let account = account(&user);
+            (user, account)
+        }))
+    }
+}
+
+impl Default for CredentialsProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Provide for CredentialsProvider {
+    fn id(&self) -> String {
+        self.id.clone()
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn provider_type(&self) -> ProviderType {
+        ProviderType::Credentials
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn as_credentials(&self) -> Option<&CredentialsProvider> {
+        Some(self)
+    }
+}
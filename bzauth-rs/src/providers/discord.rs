@@ -1,9 +1,9 @@
-use std::collections::HashMap;
-
 use super::error::ProviderError;
 use crate::contracts::endpoint::Endpoint;
 use crate::contracts::profile::Profile;
-use crate::contracts::provide::{ProvideOAuth2, ProviderType, ProvidesProfile};
+use crate::contracts::provide::{
+    ProvideOAuth2, ProviderOAuth2Check, ProviderType, ProvidesProfile,
+};
 use crate::contracts::user::User;
 
 pub struct DiscordProfile {
@@ -13,6 +13,8 @@ pub struct DiscordProfile {
     pub avatar: Option<String>,
     pub image_url: Option<String>,
     pub email: Option<String>,
+    /// Whether the email was verified with Discord
+    pub verified: Option<bool>,
 }
 
 #[derive(Debug, Clone)]
@@ -25,6 +27,9 @@ pub struct DiscordProvider {
     auth_endpoint: Endpoint,
     token_endpoint: Endpoint,
     profile_endpoint: Endpoint,
+    revocation_endpoint: Endpoint,
+    scopes: Vec<String>,
+    checks: Vec<ProviderOAuth2Check>,
     profile_resolver: fn(profile: DiscordProfile) -> Box<User>,
     _options: DiscordProviderOptions,
 }
@@ -33,6 +38,10 @@ pub struct DiscordProvider {
 pub struct DiscordProviderOptions {
     pub client_id: Option<String>,
     pub client_secret: Option<String>,
+    /// Overrides the default scopes (identify, email)
+    pub scopes: Option<Vec<String>>,
+    /// Overrides the default checks (state and PKCE)
+    pub checks: Option<Vec<ProviderOAuth2Check>>,
 }
 
 impl DiscordProvider {
@@ -46,6 +55,7 @@ impl DiscordProvider {
         Self::from_options(DiscordProviderOptions {
             client_id,
             client_secret,
+            ..Default::default()
         })
         .unwrap()
     }
@@ -66,16 +76,18 @@ impl DiscordProvider {
             provider_type: ProviderType::OAuth,
             client_id,
             client_secret,
-            auth_endpoint: Endpoint::from((
-                String::from("https://discord.com/oauth2/authorize"),
-                {
-                    let mut map = HashMap::<String, String>::new();
-                    map.insert(String::from("scope"), String::from("identify+email"));
-                    map
-                },
-            )),
+            auth_endpoint: "https://discord.com/oauth2/authorize".into(),
             token_endpoint: "https://discord.com/api/oauth2/token".into(),
             profile_endpoint: "https://discord.com/api/users/@me".into(),
+            revocation_endpoint: "https://discord.com/api/oauth2/token/revoke".into(),
+            scopes: options
+                .clone()
+                .scopes
+                .unwrap_or_else(|| ["identify", "email"].map(String::from).to_vec()),
+            checks: options
+                .clone()
+                .checks
+                .unwrap_or(vec![ProviderOAuth2Check::State, ProviderOAuth2Check::PKCE]),
             profile_resolver: |profile| {
                 let mut profile = profile;
                 profile.image_url = derive_avatar_image(&profile);
@@ -85,6 +97,7 @@ impl DiscordProvider {
                     username: Some(profile.username),
                     email: profile.email,
                     image: profile.image_url,
+                    email_verified: profile.verified,
                 })
             },
             _options: options,
@@ -110,11 +123,13 @@ fn derive_avatar_image(profile: &DiscordProfile) -> Option<String> {
             profile.id, avatar, extension, 1024
         ))
     } else {
+        // User IDs are 64-bit snowflakes, and users with a new username have no discriminator
         let default_avatar_number = if profile.discriminator == "0" {
-            profile.id.parse::<u32>().unwrap() >> 22
+            profile.id.parse::<u64>().map(|id| (id >> 22) % 6)
         } else {
-            profile.discriminator.parse::<u32>().unwrap() % 5
-        };
+            profile.discriminator.parse::<u64>().map(|d| d % 5)
+        }
+        .unwrap_or(0);
 
         Some(format!(
             "https://cdn.discordapp.com/embed/avatars/{}.png",
@@ -160,6 +175,17 @@ impl ProvideOAuth2 for DiscordProvider {
     fn profile_endpoint(&self) -> Endpoint {
         self.profile_endpoint.clone()
     }
+    fn revocation_endpoint(&self) -> Option<Endpoint> {
+        Some(self.revocation_endpoint.clone())
+    }
+
+    fn scopes(&self) -> Vec<String> {
+        self.scopes.clone()
+    }
+
+    fn checks(&self) -> Vec<ProviderOAuth2Check> {
+        self.checks.clone()
+    }
 }
 
 impl From<Profile> for DiscordProfile {
@@ -188,6 +214,10 @@ impl From<Profile> for DiscordProfile {
 
         let email = value.email;
 
+        let verified = value
+            .email_verified
+            .or_else(|| value.others.get("verified").and_then(|v| v.as_bool()));
+
         DiscordProfile {
             id,
             username,
@@ -195,6 +225,7 @@ impl From<Profile> for DiscordProfile {
             avatar,
             image_url: None, // Will be set later
             email,
+            verified,
         }
     }
 }
@@ -0,0 +1,9 @@
+pub mod provider;
+#[cfg(feature = "email_smtp")]
+pub mod smtp;
+pub mod transport;
+
+pub use provider::*; // Export the provider
+#[cfg(feature = "email_smtp")]
+pub use smtp::*; // Export the SMTP transport
+pub use transport::*; // Export the transport trait and the offline transports
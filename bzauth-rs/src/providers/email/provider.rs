@@ -0,0 +1,191 @@
+use std::any::Any;
+use std::sync::Arc;
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use sha2::{Digest, Sha256};
+use url::Url;
+
+use super::transport::{EmailMessage, EmailTransport};
+use crate::contracts::provide::{Provide, ProviderType};
+use crate::providers::error::ProviderError;
+
+/// The default lifetime of a sign-in link (in seconds): 24 hours
+pub const DEFAULT_EMAIL_MAX_AGE: u64 = 24 * 60 * 60;
+
+/// Signs users in with a single-use link sent to their email address. The tokens of the links are
+/// kept through the adaptor, so an adaptor is required.
+#[derive(Clone)]
+pub struct EmailProvider {
+    id: String,
+    name: String,
+    from: String,
+    max_age: u64,
+    verify_request_url: Option<String>,
+    transport: Arc<dyn EmailTransport>,
+}
+
+#[derive(Clone, Default)]
+pub struct EmailProviderOptions {
+    /// How the sign-in links are sent. Required
+    pub transport: Option<Arc<dyn EmailTransport>>,
+    /// The sender of the sign-in emails, e.g. `Example <no-reply@example.com>`. Required
+    pub from: Option<String>,
+    /// Overrides the provider ID, which is also the path of its routes. Defaults to `email`
+    pub id: Option<String>,
+    /// Defaults to `Email`
+    pub name: Option<String>,
+    /// How long a sign-in link stays valid (in seconds). Defaults to 24 hours
+    pub max_age: Option<u64>,
+    /// Where to send the user once the link is sent, e.g. a "check your inbox" page. Without it,
+    /// `/login/{provider}` answers with an empty 200 response
+    pub verify_request_url: Option<String>,
+}
+
+impl EmailProvider {
+    /// Create a new EmailProvider sending the links through the transport
+    ///
+    /// # Panics
+    ///
+    /// Panics if the sender is not an email address
+    pub fn new<T: EmailTransport, S: Into<String>>(transport: T, from: S) -> Self {
+        Self::from_options(EmailProviderOptions {
+            transport: Some(Arc::new(transport)),
+            from: Some(from.into()),
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    pub fn from_options(options: EmailProviderOptions) -> Result<Self, ProviderError> {
+        let transport = options.transport.ok_or(ProviderError::MissingTransport(
+            "EmailProviderOptions::transport is required".to_string(),
+        ))?;
+        let from = options
+            .from
+            .filter(|from| from.contains('@'))
+            .ok_or(ProviderError::InvalidEmail("Missing sender".to_string()))?;
+
+        Ok(EmailProvider {
+            id: options.id.unwrap_or_else(|| "email".to_string()),
+            name: options.name.unwrap_or_else(|| "Email".to_string()),
+            from,
+            max_age: options.max_age.unwrap_or(DEFAULT_EMAIL_MAX_AGE),
+            verify_request_url: options.verify_request_url,
+            transport,
+        })
+    }
+
+    /// How long a sign-in link stays valid (in seconds)
+    pub fn max_age(&self) -> u64 {
+        self.max_age
+    }
+
+    pub fn verify_request_url(&self) -> Option<&str> {
+        self.verify_request_url.as_deref()
+    }
+
+    /// Trims and lowercases the address, so that a user is found whichever way they type it, and
+    /// refuses anything that is not a single address
+    pub fn normalize_email(&self, email: &str) -> Result<String, ProviderError> {
+        let email = email.trim().to_lowercase();
+        let forbidden = |c: char| {
+            c.is_whitespace() || c.is_control() || matches!(c, ',' | ';' | '<' | '>' | '"')
+        };
+
+        let valid = email.len() <= 254
+            && !email.contains(forbidden)
+            && email.split_once('@').is_some_and(|(local, domain)| {
+                !local.is_empty()
+                    && !domain.is_empty()
+                    && !domain.contains('@')
+                    && !domain.starts_with('.')
+                    && !domain.ends_with('.')
+            });
+
+        match valid {
+            true => Ok(email),
+            false => Err(ProviderError::InvalidEmail(email)),
+        }
+    }
+
+    /// The link that signs the user in, under the callback route of the provider
+    pub fn sign_in_url(&self, auth_url: &str, token: &str, email: &str) -> String {
+        let mut url = format!("{}/callback/{}", auth_url, self.id);
+        if let Ok(mut parsed) = Url::parse(&url) {
+            parsed
+                .query_pairs_mut()
+                .append_pair("token", token)
+                .append_pair("email", email);
+            url = parsed.to_string();
+        }
+        url
+    }
+
+    /// Sends the sign-in link to the address
+    pub async fn send_sign_in_link(&self, email: &str, url: &str) -> Result<(), ProviderError> {
+        let host = Url::parse(url)
+            .ok()
+            .and_then(|u| u.host_str().map(String::from))
+            .unwrap_or_default();
+
+        self.transport
+            .send(EmailMessage {
+                to: email.to_string(),
+                from: self.from.clone(),
+                subject: format!("Sign in to {}", host),
+                text: format!("Sign in to {}\n{}\n", host, url),
+                html: format!(
+                    "<p>Sign in to <strong>{}</strong></p><p><a href=\"{}\">Sign in</a></p>\
+                     <p>If you did not request this email, you can safely ignore it.</p>",
+                    escape_html(&host),
+                    escape_html(url)
+                ),
+                url: url.to_string(),
+            })
+            .await
+    }
+}
+
+/// Hashes the token of a sign-in link with the secret. Only the hash is stored, so that the links
+/// cannot be rebuilt from the store.
+pub fn hash_verification_token(token: &str, secret: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(token.as_bytes());
+    hasher.update(secret.as_bytes());
+    URL_SAFE_NO_PAD.encode(hasher.finalize())
+}
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+impl Provide for EmailProvider {
+    fn id(&self) -> String {
+        self.id.clone()
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn provider_type(&self) -> ProviderType {
+        ProviderType::Email
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn as_email(&self) -> Option<&EmailProvider> {
+        Some(self)
+    }
+}
@@ -0,0 +1,101 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::providers::error::ProviderError;
+
+/// A sign-in email, ready to be sent
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EmailMessage {
+    pub to: String,
+    pub from: String,
+    pub subject: String,
+    pub text: String,
+    pub html: String,
+    /// The sign-in link, also found in the text and the HTML
+    pub url: String,
+}
+
+/// Delivers the sign-in emails of an [EmailProvider](super::EmailProvider)
+#[async_trait::async_trait]
+pub trait EmailTransport: Send + Sync + 'static {
+    async fn send(&self, message: EmailMessage) -> Result<(), ProviderError>;
+}
+
+/// Keeps the messages in memory rather than sending them, for tests. Clones share the same outbox.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryTransport {
+    outbox: Arc<Mutex<Vec<EmailMessage>>>,
+}
+
+impl MemoryTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The messages sent so far, oldest first
+    pub fn messages(&self) -> Vec<EmailMessage> {
+        self.outbox
+            .lock()
+            .map(|outbox| outbox.clone())
+            .unwrap_or_default()
+    }
+
+    /// The last message sent to the address, if any
+    pub fn last_message_to(&self, to: &str) -> Option<EmailMessage> {
+        self.messages()
+            .into_iter()
+            .rev()
+            .find(|message| message.to == to)
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailTransport for MemoryTransport {
+    async fn send(&self, message: EmailMessage) -> Result<(), ProviderError> {
+        self.outbox
+            .lock()
+            .map_err(|e| ProviderError::SendFailed(e.to_string()))?
+            .push(message);
+        Ok(())
+    }
+}
+
+/// Writes each message to a JSON file in a directory rather than sending it, for local development
+#[derive(Debug, Clone)]
+pub struct FileTransport {
+    dir: PathBuf,
+}
+
+impl FileTransport {
+    /// The directory is created on the first message if it does not exist
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &PathBuf {
+        &self.dir
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailTransport for FileTransport {
+    async fn send(&self, message: EmailMessage) -> Result<(), ProviderError> {
+        let raw = serde_json::to_string_pretty(&message)
+            .map_err(|e| ProviderError::SendFailed(e.to_string()))?;
+
+        // Named by time first, so that a listing shows the messages in order
+        let path = self.dir.join(format!(
+            "{}-{}.json",
+            chrono::Utc::now().format("%Y%m%d%H%M%S%6f"),
+            uuid::Uuid::new_v4().simple()
+        ));
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .map_err(|e| ProviderError::SendFailed(format!("{}: {}", self.dir.display(), e)))?;
+        tokio::fs::write(&path, raw)
+            .await
+            .map_err(|e| ProviderError::SendFailed(format!("{}: {}", path.display(), e)))
+    }
+}
@@ -1,5 +1,70 @@
+use http::StatusCode;
+
+use crate::tools::CoreError;
+
 #[derive(Debug, Clone)]
 pub enum ProviderError {
     MissingClientId(String),
     MissingClientSecret(String),
+    MissingIssuer(String),
+    MissingTransport(String),
+    DiscoveryFailed(String),
+    ProfileFailed(String),
+    /// The address given to the email provider is not a valid email address
+    InvalidEmail(String),
+    /// The email transport failed to send the sign-in link
+    SendFailed(String),
+    /// A password could not be hashed
+    HashFailed(String),
+    /// The provider could not refresh the access token, but may later
+    RefreshFailed(String),
+    /// The tokens of the account are gone or were refused by the provider, so the user has to sign
+    /// in with the provider again
+    ReauthorisationRequired(String),
+    /// The provider did not confirm the revocation of a token
+    RevocationFailed(String),
+}
+
+impl std::fmt::Display for ProviderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProviderError::MissingClientId(msg) => write!(f, "Missing client ID: {}", msg),
+            ProviderError::MissingClientSecret(msg) => write!(f, "Missing client secret: {}", msg),
+            ProviderError::MissingIssuer(msg) => write!(f, "Missing issuer: {}", msg),
+            ProviderError::MissingTransport(msg) => write!(f, "Missing email transport: {}", msg),
+            ProviderError::DiscoveryFailed(msg) => write!(f, "Discovery failed: {}", msg),
+            ProviderError::ProfileFailed(msg) => write!(f, "Failed to fetch profile: {}", msg),
+            ProviderError::InvalidEmail(msg) => write!(f, "Invalid email address: {}", msg),
+            ProviderError::SendFailed(msg) => write!(f, "Failed to send email: {}", msg),
+            ProviderError::HashFailed(msg) => write!(f, "Failed to hash password: {}", msg),
+            ProviderError::RefreshFailed(msg) => write!(f, "Failed to refresh token: {}", msg),
+            ProviderError::ReauthorisationRequired(msg) => {
+                write!(f, "Reauthorisation required: {}", msg)
+            }
+            ProviderError::RevocationFailed(msg) => write!(f, "Failed to revoke token: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ProviderError {}
+
+impl From<ProviderError> for CoreError {
+    fn from(error: ProviderError) -> Self {
+        let status = match error {
+            // The provider could not be reached or served an invalid response
+            ProviderError::DiscoveryFailed(_) | ProviderError::ProfileFailed(_) => {
+                StatusCode::BAD_GATEWAY
+            }
+            ProviderError::SendFailed(_)
+            | ProviderError::RefreshFailed(_)
+            | ProviderError::RevocationFailed(_) => StatusCode::BAD_GATEWAY,
+            ProviderError::ReauthorisationRequired(_) => StatusCode::UNAUTHORIZED,
+            ProviderError::InvalidEmail(_) => StatusCode::BAD_REQUEST,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+
+        CoreError::new()
+            .with_message(error.to_string())
+            .with_status(status.into())
+    }
 }
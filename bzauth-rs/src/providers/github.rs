@@ -1 +1,293 @@
+use http::header::ACCEPT;
+use serde::Deserialize;
 
+use super::error::ProviderError;
+use crate::contracts::endpoint::Endpoint;
+use crate::contracts::profile::Profile;
+use crate::contracts::provide::{
+    ProvideOAuth2, ProviderOAuth2Check, ProviderType, ProvidesProfile,
+};
+use crate::contracts::user::User;
+use crate::tools::generators;
+
+/// The media type of the GitHub REST API
+const GITHUB_API_MEDIA_TYPE: &str = "application/vnd.github+json";
+
+pub struct GithubProfile {
+    pub id: String,
+    pub login: String,
+    pub name: Option<String>,
+    pub avatar_url: Option<String>,
+    pub html_url: Option<String>,
+    pub email: Option<String>,
+    pub email_verified: Option<bool>,
+}
+
+/// An address of the user, as listed by `/user/emails`
+#[derive(Debug, Clone, Deserialize)]
+struct GithubEmail {
+    email: String,
+    primary: bool,
+    verified: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct GithubProvider {
+    id: String,
+    name: String,
+    provider_type: ProviderType,
+    client_id: String,
+    client_secret: String,
+    auth_endpoint: Endpoint,
+    token_endpoint: Endpoint,
+    profile_endpoint: Endpoint,
+    emails_endpoint: Endpoint,
+    scopes: Vec<String>,
+    checks: Vec<ProviderOAuth2Check>,
+    profile_resolver: fn(profile: GithubProfile) -> Box<User>,
+    _options: GithubProviderOptions,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct GithubProviderOptions {
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+    /// The URL of a GitHub Enterprise Server, e.g. `https://github.example.com`. Defaults to
+    /// github.com
+    pub base_url: Option<String>,
+    /// Overrides the default scopes (read:user, user:email)
+    pub scopes: Option<Vec<String>>,
+    /// Overrides the default checks (state and PKCE)
+    pub checks: Option<Vec<ProviderOAuth2Check>>,
+}
+
+impl GithubProvider {
+    /// Create a new GithubProvider with default options
+    ///
+    /// This will use the environment variables GITHUB_CLIENT_ID and GITHUB_CLIENT_SECRET
+    pub fn new() -> Self {
+        let client_id = std::env::var("GITHUB_CLIENT_ID").ok();
+        let client_secret = std::env::var("GITHUB_CLIENT_SECRET").ok();
+
+        Self::from_options(GithubProviderOptions {
+            client_id,
+            client_secret,
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    pub fn from_options(options: GithubProviderOptions) -> Result<Self, ProviderError> {
+        let client_id = options
+            .clone()
+            .client_id
+            .ok_or(ProviderError::MissingClientId("".to_string()))?;
+        let client_secret = options
+            .clone()
+            .client_secret
+            .ok_or(ProviderError::MissingClientSecret("".to_string()))?;
+
+        // GitHub Enterprise Server serves the API under the instance, rather than a subdomain
+        let (base_url, api_url) = match &options.base_url {
+            Some(base_url) => {
+                let base_url = base_url.trim_end_matches('/').to_string();
+                let api_url = format!("{}/api/v3", base_url);
+                (base_url, api_url)
+            }
+            None => (
+                "https://github.com".to_string(),
+                "https://api.github.com".to_string(),
+            ),
+        };
+
+        let provider = GithubProvider {
+            id: "github".to_string(),
+            name: "GitHub".to_string(),
+            provider_type: ProviderType::OAuth,
+            client_id,
+            client_secret,
+            auth_endpoint: format!("{}/login/oauth/authorize", base_url).into(),
+            token_endpoint: format!("{}/login/oauth/access_token", base_url).into(),
+            profile_endpoint: format!("{}/user", api_url).into(),
+            emails_endpoint: format!("{}/user/emails", api_url).into(),
+            scopes: options
+                .clone()
+                .scopes
+                .unwrap_or_else(|| ["read:user", "user:email"].map(String::from).to_vec()),
+            checks: options
+                .clone()
+                .checks
+                .unwrap_or(vec![ProviderOAuth2Check::State, ProviderOAuth2Check::PKCE]),
+            profile_resolver: |profile| {
+                Box::new(User {
+                    id: Some(profile.id),
+                    username: Some(profile.login),
+                    email: profile.email,
+                    image: profile.avatar_url,
+                    email_verified: profile.email_verified,
+                })
+            },
+            _options: options,
+        };
+
+        Ok(provider)
+    }
+
+    /// The endpoint listing the user's addresses, including private ones
+    pub fn emails_endpoint(&self) -> Endpoint {
+        self.emails_endpoint.clone()
+    }
+
+    async fn get<T: serde::de::DeserializeOwned>(
+        &self,
+        endpoint: &Endpoint,
+        access_token: &str,
+    ) -> Result<T, ProviderError> {
+        // The shared client sends the User-Agent header GitHub requires
+        generators::generate_http_client()
+            .map_err(|_| ProviderError::ProfileFailed("Failed to create HTTP client".to_string()))?
+            .get(endpoint.url())
+            .header(ACCEPT, GITHUB_API_MEDIA_TYPE)
+            .bearer_auth(access_token)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| ProviderError::ProfileFailed(e.to_string()))?
+            .json::<T>()
+            .await
+            .map_err(|e| ProviderError::ProfileFailed(e.to_string()))
+    }
+}
+
+impl Default for GithubProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait::async_trait]
+impl ProvideOAuth2 for GithubProvider {
+    fn id(&self) -> String {
+        self.id.clone()
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn provider_type(&self) -> ProviderType {
+        self.provider_type.clone()
+    }
+
+    fn client_id(&self) -> String {
+        self.client_id.clone()
+    }
+
+    fn client_secret(&self) -> String {
+        self.client_secret.clone()
+    }
+
+    // Endpoints
+    fn auth_endpoint(&self) -> Endpoint {
+        self.auth_endpoint.clone()
+    }
+    fn token_endpoint(&self) -> Endpoint {
+        self.token_endpoint.clone()
+    }
+    fn profile_endpoint(&self) -> Endpoint {
+        self.profile_endpoint.clone()
+    }
+
+    fn scopes(&self) -> Vec<String> {
+        self.scopes.clone()
+    }
+
+    fn checks(&self) -> Vec<ProviderOAuth2Check> {
+        self.checks.clone()
+    }
+
+    async fn fetch_profile(&self, access_token: &str) -> Result<Profile, ProviderError> {
+        let user: serde_json::Value = self.get(&self.profile_endpoint, access_token).await?;
+        let mut profile = profile_from_user(user);
+
+        // The public email is optional and may be unverified, so prefer the primary verified
+        // address. Listing them needs the `user:email` scope, without it the public email is kept
+        match self
+            .get::<Vec<GithubEmail>>(&self.emails_endpoint, access_token)
+            .await
+        {
+            Ok(emails) => {
+                if let Some(primary) = emails.iter().find(|e| e.primary && e.verified) {
+                    profile.email = Some(primary.email.clone());
+                    profile.email_verified = Some(true);
+                } else {
+                    profile.email_verified = profile.email.as_ref().map(|email| {
+                        emails
+                            .iter()
+                            .any(|e| e.verified && e.email.eq_ignore_ascii_case(email))
+                    });
+                }
+            }
+            Err(error) => {
+                tracing::debug!("[github] Failed to list the user's emails: {}", error);
+            }
+        }
+
+        Ok(profile)
+    }
+}
+
+/// Maps the `/user` response to the standard claims, keeping the rest in `others`
+fn profile_from_user(user: serde_json::Value) -> Profile {
+    let string = |key: &str| user.get(key).and_then(|v| v.as_str()).map(String::from);
+
+    // The ID is a number
+    let id = user.get("id").map(|id| match id {
+        serde_json::Value::String(id) => id.clone(),
+        id => id.to_string(),
+    });
+
+    Profile {
+        id: id.clone(),
+        sub: id,
+        name: string("name"),
+        preferred_username: string("login"),
+        profile: string("html_url"),
+        picture: string("avatar_url"),
+        website: string("blog").filter(|blog| !blog.is_empty()),
+        email: string("email"),
+        others: user,
+        ..Default::default()
+    }
+}
+
+impl From<Profile> for GithubProfile {
+    fn from(value: Profile) -> Self {
+        let login = value
+            .preferred_username
+            .or_else(|| {
+                value
+                    .others
+                    .get("login")
+                    .and_then(|v| v.as_str())
+                    .map(|s| s.to_string())
+            })
+            .unwrap_or_default();
+
+        GithubProfile {
+            id: value.id.or(value.sub).unwrap_or_default(),
+            login,
+            name: value.name,
+            avatar_url: value.picture,
+            html_url: value.profile,
+            email: value.email,
+            email_verified: value.email_verified,
+        }
+    }
+}
+
+impl ProvidesProfile for GithubProvider {
+    fn get_profile(&self, profile: Profile) -> Box<User> {
+        (self.profile_resolver)(profile.into())
+    }
+}
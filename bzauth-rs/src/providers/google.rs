@@ -1,10 +1,15 @@
-use std::collections::HashMap;
-
 use super::error::ProviderError;
 use crate::contracts::endpoint::Endpoint;
 use crate::contracts::profile::Profile;
-use crate::contracts::provide::{ProvideOAuth2, ProviderType, ProvidesProfile};
+use crate::contracts::provide::{
+    OidcMetadata, ProvideOAuth2, ProvideOidc, ProviderOAuth2Check, ProviderType, ProvidesProfile,
+};
 use crate::contracts::user::User;
+use crate::tools::jose::JwksCache;
+
+/// Google's issuer. Older id_tokens use the bare host as the issuer
+const GOOGLE_ISSUER: &str = "https://accounts.google.com";
+const GOOGLE_JWKS_URI: &str = "https://www.googleapis.com/oauth2/v3/certs";
 
 #[derive(Debug, Clone, Default)]
 pub struct GoogleProfile {
@@ -33,6 +38,10 @@ pub struct GoogleProvider {
     auth_endpoint: Endpoint,
     token_endpoint: Endpoint,
     userinfo_endpoint: Endpoint,
+    revocation_endpoint: Endpoint,
+    scopes: Vec<String>,
+    checks: Vec<ProviderOAuth2Check>,
+    jwks: JwksCache,
     _profile: fn(profile: GoogleProfile) -> Box<User>,
     _options: GoogleProviderOptions,
 }
@@ -41,6 +50,10 @@ pub struct GoogleProvider {
 pub struct GoogleProviderOptions {
     pub client_id: Option<String>,
     pub client_secret: Option<String>,
+    /// Overrides the default scopes (openid, email, profile)
+    pub scopes: Option<Vec<String>>,
+    /// Overrides the default checks (state and PKCE)
+    pub checks: Option<Vec<ProviderOAuth2Check>>,
 }
 
 impl GoogleProvider {
@@ -51,6 +64,7 @@ impl GoogleProvider {
         Self::from_options(GoogleProviderOptions {
             client_id: std::env::var("GOOGLE_CLIENT_ID").ok(),
             client_secret: std::env::var("GOOGLE_CLIENT_SECRET").ok(),
+            ..Default::default()
         })
         .unwrap()
     }
@@ -71,16 +85,19 @@ impl GoogleProvider {
             provider_type: ProviderType::OAuth,
             client_id,
             client_secret,
-            auth_endpoint: Endpoint::from((
-                String::from("https://accounts.google.com/o/oauth2/v2/auth"),
-                {
-                    let mut map = HashMap::<String, String>::new();
-                    map.insert(String::from("scope"), String::from("openid email profile"));
-                    map
-                },
-            )),
+            auth_endpoint: "https://accounts.google.com/o/oauth2/v2/auth".into(),
             token_endpoint: "https://oauth2.googleapis.com/token".into(),
             userinfo_endpoint: "https://openidconnect.googleapis.com/v1/userinfo".into(),
+            revocation_endpoint: "https://oauth2.googleapis.com/revoke".into(),
+            scopes: options
+                .clone()
+                .scopes
+                .unwrap_or_else(|| ["openid", "email", "profile"].map(String::from).to_vec()),
+            checks: options
+                .clone()
+                .checks
+                .unwrap_or(vec![ProviderOAuth2Check::State, ProviderOAuth2Check::PKCE]),
+            jwks: JwksCache::new(),
             _profile: |profile| {
                 Box::new(User {
                     // todo.
@@ -88,6 +105,7 @@ impl GoogleProvider {
                     username: Some(profile.given_name),
                     email: Some(profile.email),
                     image: Some(profile.picture),
+                    email_verified: Some(profile.email_verified),
                 })
             },
             _options: options,
@@ -135,42 +153,80 @@ impl ProvideOAuth2 for GoogleProvider {
     fn profile_endpoint(&self) -> Endpoint {
         self.userinfo_endpoint.clone()
     }
+    fn revocation_endpoint(&self) -> Option<Endpoint> {
+        Some(self.revocation_endpoint.clone())
+    }
+
+    fn scopes(&self) -> Vec<String> {
+        self.scopes.clone()
+    }
+
+    fn checks(&self) -> Vec<ProviderOAuth2Check> {
+        self.checks.clone()
+    }
+
+    fn as_oidc(&self) -> Option<&dyn ProvideOidc> {
+        Some(self)
+    }
+}
+
+/// Google is an OpenID Connect issuer with well-known endpoints, so the id_token is verified
+/// without a discovery round-trip
+#[async_trait::async_trait]
+impl ProvideOidc for GoogleProvider {
+    fn issuer(&self) -> String {
+        GOOGLE_ISSUER.to_string()
+    }
+
+    fn issuers(&self) -> Vec<String> {
+        vec![GOOGLE_ISSUER.to_string(), "accounts.google.com".to_string()]
+    }
+
+    async fn discover(&self) -> Result<OidcMetadata, ProviderError> {
+        Ok(OidcMetadata {
+            issuer: GOOGLE_ISSUER.to_string(),
+            authorization_endpoint: self.auth_endpoint.url(),
+            token_endpoint: self.token_endpoint.url(),
+            userinfo_endpoint: Some(self.userinfo_endpoint.url()),
+            revocation_endpoint: Some(self.revocation_endpoint.url()),
+            jwks_uri: GOOGLE_JWKS_URI.to_string(),
+            ..Default::default()
+        })
+    }
+
+    fn jwks(&self) -> JwksCache {
+        self.jwks.clone()
+    }
 }
 
 impl From<Profile> for GoogleProfile {
-    #[allow(unused_variables)]
     fn from(value: Profile) -> Self {
-        // Pick from others: aud, azp, exp, hd, iat, iss
-        let aud = value.others.get("aud").cloned().unwrap_or_default();
-        let azp = value.others.get("azp").cloned().unwrap_or_default();
-        let exp = value
-            .others
-            .get("exp")
-            .and_then(|v| v.as_i64())
-            .unwrap_or(0);
-        let iat = value
-            .others
-            .get("iat")
-            .and_then(|v| v.as_i64())
-            .unwrap_or(0);
-        let hd = value.others.get("hd").cloned().unwrap_or_default();
-        let iss = value.others.get("iss").cloned().unwrap_or_default();
+        // The id_token claims that are not part of the standard profile
+        let claim = |key: &str| value.others.get(key).cloned().unwrap_or_default();
+        let string_claim = |key: &str| claim(key).as_str().unwrap_or_default().to_string();
+        let aud = match claim("aud") {
+            serde_json::Value::Array(aud) => aud
+                .first()
+                .and_then(|a| a.as_str())
+                .unwrap_or_default()
+                .to_string(),
+            aud => aud.as_str().unwrap_or_default().to_string(),
+        };
 
         GoogleProfile {
-            // aud: value.aud,
-            // azp: value.azp,
-            email: value.email.unwrap(),
+            aud,
+            azp: string_claim("azp"),
+            email: value.email.unwrap_or_default(),
             email_verified: value.email_verified.unwrap_or(false),
-            // exp: value.exp,
+            exp: claim("exp").as_i64().unwrap_or(0),
             family_name: value.family_name.unwrap_or_default(),
             given_name: value.given_name.unwrap_or_default(),
-            // hd: value.hd.unwrap_or_default(),
-            // iat: value.iat,
-            // iss: value.iss,
+            hd: string_claim("hd"),
+            iat: claim("iat").as_i64().unwrap_or(0),
+            iss: string_claim("iss"),
             name: value.name.unwrap_or_default(),
             picture: value.picture.unwrap_or_default(),
             sub: value.sub.unwrap_or_default(),
-            ..Default::default()
         }
     }
 }
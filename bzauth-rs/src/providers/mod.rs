@@ -1,8 +1,14 @@
+pub mod credentials;
 pub mod discord;
+pub mod email;
 pub mod error;
 pub mod github;
 pub mod google;
+pub mod oidc;
 
+pub use credentials::CredentialsProvider;
 pub use discord::DiscordProvider;
-// use github::GithubProvider;
+pub use email::EmailProvider;
+pub use github::GithubProvider;
 pub use google::GoogleProvider;
+pub use oidc::OidcProvider;
@@ -0,0 +1,255 @@
+use std::sync::{Arc, RwLock};
+
+use super::error::ProviderError;
+use crate::contracts::endpoint::Endpoint;
+use crate::contracts::profile::Profile;
+use crate::contracts::provide::{
+    OidcMetadata, ProvideOAuth2, ProvideOidc, ProviderOAuth2Check, ProviderType, ProvidesProfile,
+};
+use crate::contracts::user::User;
+use crate::tools::jose::{DEFAULT_CLOCK_SKEW, JwksCache};
+
+/// The path of the discovery document, relative to the issuer
+pub const OIDC_DISCOVERY_PATH: &str = "/.well-known/openid-configuration";
+
+/// A generic OpenID Connect provider (Keycloak, Auth0, Okta, Authentik, Azure AD, ...), configured
+/// from the issuer's discovery document
+#[derive(Debug, Clone)]
+pub struct OidcProvider {
+    id: String,
+    name: String,
+    provider_type: ProviderType,
+    issuer: String,
+    client_id: String,
+    client_secret: String,
+    scopes: Vec<String>,
+    checks: Vec<ProviderOAuth2Check>,
+    metadata: Arc<RwLock<Option<OidcMetadata>>>,
+    jwks: JwksCache,
+    clock_skew: i64,
+    fetch_userinfo: bool,
+    rp_initiated_logout: bool,
+    profile_resolver: fn(profile: Profile) -> Box<User>,
+    _options: OidcProviderOptions,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct OidcProviderOptions {
+    /// Defaults to `oidc`. Set it when using more than one issuer
+    pub id: Option<String>,
+    /// Defaults to `OpenID Connect`
+    pub name: Option<String>,
+    /// The issuer URL, e.g. `https://auth.example.com/realms/main`
+    pub issuer: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+    /// Overrides the default scopes (openid, email, profile)
+    pub scopes: Option<Vec<String>>,
+    /// Overrides the default checks (state, PKCE and nonce)
+    pub checks: Option<Vec<ProviderOAuth2Check>>,
+    /// The tolerated clock skew when checking the id_token lifetime (in seconds). Defaults to 60
+    pub clock_skew: Option<i64>,
+    /// Fetches the profile from the userinfo endpoint instead of the id_token. Useful for issuers
+    /// that only put the subject in the id_token
+    pub fetch_userinfo: Option<bool>,
+    /// Signs the user out of the issuer too when signing out, if it advertises an
+    /// `end_session_endpoint`
+    pub rp_initiated_logout: Option<bool>,
+    /// Overrides how the standard claims are mapped to a user
+    pub profile: Option<fn(profile: Profile) -> Box<User>>,
+}
+
+impl OidcProvider {
+    /// Create a new OidcProvider with default options
+    ///
+    /// This will use the environment variables OIDC_ISSUER, OIDC_CLIENT_ID and OIDC_CLIENT_SECRET
+    pub fn new() -> Self {
+        Self::from_options(OidcProviderOptions {
+            issuer: std::env::var("OIDC_ISSUER").ok(),
+            client_id: std::env::var("OIDC_CLIENT_ID").ok(),
+            client_secret: std::env::var("OIDC_CLIENT_SECRET").ok(),
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    pub fn from_options(options: OidcProviderOptions) -> Result<Self, ProviderError> {
+        let issuer = options
+            .clone()
+            .issuer
+            .ok_or(ProviderError::MissingIssuer("".to_string()))?;
+        let client_id = options
+            .clone()
+            .client_id
+            .ok_or(ProviderError::MissingClientId("".to_string()))?;
+        let client_secret = options
+            .clone()
+            .client_secret
+            .ok_or(ProviderError::MissingClientSecret("".to_string()))?;
+
+        let provider = OidcProvider {
+            id: options.clone().id.unwrap_or_else(|| "oidc".to_string()),
+            name: options
+                .clone()
+                .name
+                .unwrap_or_else(|| "OpenID Connect".to_string()),
+            provider_type: ProviderType::OIDC,
+            issuer: issuer.trim_end_matches('/').to_string(),
+            client_id,
+            client_secret,
+            scopes: options
+                .clone()
+                .scopes
+                .unwrap_or_else(|| ["openid", "email", "profile"].map(String::from).to_vec()),
+            checks: options.clone().checks.unwrap_or(vec![
+                ProviderOAuth2Check::State,
+                ProviderOAuth2Check::PKCE,
+                ProviderOAuth2Check::Nonce,
+            ]),
+            metadata: Arc::new(RwLock::new(None)),
+            jwks: JwksCache::new(),
+            clock_skew: options.clock_skew.unwrap_or(DEFAULT_CLOCK_SKEW),
+            fetch_userinfo: options.fetch_userinfo.unwrap_or(false),
+            rp_initiated_logout: options.rp_initiated_logout.unwrap_or(false),
+            profile_resolver: options.profile.unwrap_or(|profile| {
+                Box::new(User {
+                    id: profile.sub,
+                    username: profile.preferred_username.or(profile.name),
+                    email: profile.email,
+                    image: profile.picture,
+                    email_verified: profile.email_verified,
+                })
+            }),
+            _options: options,
+        };
+
+        Ok(provider)
+    }
+
+    /// The cached metadata, if the issuer has been discovered
+    pub fn metadata(&self) -> Option<OidcMetadata> {
+        self.metadata.read().ok().and_then(|m| m.clone())
+    }
+
+    /// Reads an endpoint from the cached metadata. Empty until discovered.
+    fn endpoint(&self, f: impl FnOnce(OidcMetadata) -> Option<String>) -> Endpoint {
+        self.metadata().and_then(f).unwrap_or_default().into()
+    }
+}
+
+impl Default for OidcProvider {
+    /// Create a new OidcProvider with default options
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProvideOAuth2 for OidcProvider {
+    fn id(&self) -> String {
+        self.id.clone()
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn provider_type(&self) -> ProviderType {
+        self.provider_type.clone()
+    }
+
+    fn client_id(&self) -> String {
+        self.client_id.clone()
+    }
+
+    fn client_secret(&self) -> String {
+        self.client_secret.clone()
+    }
+
+    // Endpoints
+    fn auth_endpoint(&self) -> Endpoint {
+        self.endpoint(|m| Some(m.authorization_endpoint))
+    }
+    fn token_endpoint(&self) -> Endpoint {
+        self.endpoint(|m| Some(m.token_endpoint))
+    }
+    fn profile_endpoint(&self) -> Endpoint {
+        self.endpoint(|m| m.userinfo_endpoint)
+    }
+    fn revocation_endpoint(&self) -> Option<Endpoint> {
+        self.metadata()
+            .and_then(|m| m.revocation_endpoint)
+            .map(Endpoint::from)
+    }
+
+    fn scopes(&self) -> Vec<String> {
+        self.scopes.clone()
+    }
+
+    fn checks(&self) -> Vec<ProviderOAuth2Check> {
+        self.checks.clone()
+    }
+
+    fn as_oidc(&self) -> Option<&dyn ProvideOidc> {
+        Some(self)
+    }
+}
+
+#[async_trait::async_trait]
+impl ProvideOidc for OidcProvider {
+    fn issuer(&self) -> String {
+        self.issuer.clone()
+    }
+
+    async fn discover(&self) -> Result<OidcMetadata, ProviderError> {
+        if let Some(metadata) = self.metadata() {
+            return Ok(metadata);
+        }
+
+        let discovery_url = format!("{}{}", self.issuer, OIDC_DISCOVERY_PATH);
+        tracing::debug!("[oidc] Discovering issuer: {}", discovery_url);
+
+        let metadata = reqwest::get(&discovery_url)
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| ProviderError::DiscoveryFailed(e.to_string()))?
+            .json::<OidcMetadata>()
+            .await
+            .map_err(|e| ProviderError::DiscoveryFailed(e.to_string()))?;
+
+        // The issuer must match exactly, otherwise the id_token cannot be trusted
+        if metadata.issuer.trim_end_matches('/') != self.issuer {
+            return Err(ProviderError::DiscoveryFailed(format!(
+                "Issuer mismatch (expected={}, got={})",
+                self.issuer, metadata.issuer
+            )));
+        }
+
+        if let Ok(mut cached) = self.metadata.write() {
+            *cached = Some(metadata.clone());
+        }
+
+        Ok(metadata)
+    }
+
+    fn jwks(&self) -> JwksCache {
+        self.jwks.clone()
+    }
+
+    fn clock_skew(&self) -> i64 {
+        self.clock_skew
+    }
+
+    fn fetch_userinfo(&self) -> bool {
+        self.fetch_userinfo
+    }
+
+    fn rp_initiated_logout(&self) -> bool {
+        self.rp_initiated_logout
+    }
+}
+
+impl ProvidesProfile for OidcProvider {
+    fn get_profile(&self, profile: Profile) -> Box<User> {
+        (self.profile_resolver)(profile)
+    }
+}
@@ -55,12 +55,9 @@ where
     fn into_response(self) -> axum::response::Response {
         let mut response = axum::response::Response::default();
 
-        // Set the body
+        // Set the body (the payload is already serialised)
         if let Some(body) = self.payload {
-            *response.body_mut() = axum::body::Body::from(
-                serde_json::to_string(&body)
-                    .unwrap_or_else(|_| "Failed to serialize response".to_string()),
-            );
+            *response.body_mut() = axum::body::Body::from(body);
         }
 
         // Set the status code
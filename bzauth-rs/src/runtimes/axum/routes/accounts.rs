@@ -0,0 +1,26 @@
+use axum::extract::Request;
+
+use crate::runtimes::axum::extractors::auth::ExtractAuth;
+use crate::tools::request::CoreRequest;
+use crate::tools::response::CoreResponse;
+use crate::tools::{self, AccountsResponse, CoreError, TryFromAsync};
+
+#[axum::debug_handler]
+pub async fn accounts(
+    ExtractAuth(auth): ExtractAuth,
+    request: Request,
+) -> Result<CoreResponse<AccountsResponse>, CoreError> {
+    // Pass to internal handler
+    let core_request = CoreRequest::try_from_async(request).await?.with_auth(auth);
+    tools::accounts(core_request).await
+}
+
+#[axum::debug_handler]
+pub async fn unlink_account(
+    ExtractAuth(auth): ExtractAuth,
+    request: Request,
+) -> Result<CoreResponse<AccountsResponse>, CoreError> {
+    // Pass to internal handler
+    let core_request = CoreRequest::try_from_async(request).await?.with_auth(auth);
+    tools::unlink_account(core_request).await
+}
@@ -1,7 +1,13 @@
+pub mod accounts;
 pub mod authorise;
 pub mod callback;
 pub mod csrf;
+pub mod logout;
+pub mod session;
 
+pub use accounts::{accounts, unlink_account};
 pub use authorise::authorise;
 pub use callback::callback;
 pub use csrf::csrf;
+pub use logout::logout;
+pub use session::session;
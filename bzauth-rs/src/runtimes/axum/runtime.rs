@@ -6,7 +6,7 @@ use axum::{Json, Router};
 use serde::Serialize;
 use serde::ser::SerializeStruct;
 
-use super::routes::{authorise, callback, csrf};
+use super::routes::{accounts, authorise, callback, csrf, logout, session, unlink_account};
 use crate::auth::{Auth, AuthOptions};
 use crate::contracts::provide::Provide;
 
@@ -30,15 +30,21 @@ impl AxumRuntime {
     /// Create a new Axum runtime
     pub fn from_options(options: AxumRuntimeOptions) -> Self {
         let AxumRuntimeOptions { auth_options } = options;
-        let routes = AxumRuntime::create_router(&auth_options);
         let auth = Arc::new(Auth::from_options(auth_options));
+        let routes = AxumRuntime::create_router(&auth);
+
+        // Mount the routes at the base path, if any
+        let routes = match auth.base_path() {
+            "" => routes,
+            base_path => Router::new().nest(base_path, routes),
+        };
 
         // Create the runtime
         AxumRuntime { auth, routes }
     }
 
-    fn create_router(auth_options: &AuthOptions) -> Router {
-        let providers = auth_options.providers.clone();
+    fn create_router(auth: &Auth) -> Router {
+        let providers = auth.options.providers.clone();
         let providers_handler = move || async move { Json(providers) };
 
         Router::new()
@@ -66,9 +72,13 @@ impl AxumRuntime {
             // Ask for a csrf token
             .route("/csrf", get(csrf))
             // Get the session for the current user
-            .route("/session", get(|| async { "session endpoint" }))
-            // Logout endpoint that invalidates the session
-            .route("/logout", get(|| async { "Logout endpoint" }))
+            .route("/session", get(session))
+            // Ends the session of the current user
+            .route("/logout", post(logout))
+            // List the accounts linked to the current user
+            .route("/accounts", get(accounts))
+            // Unlink an account from the current user
+            .route("/accounts/{provider}/unlink", post(unlink_account))
             // Get a list of providers
             .route("/providers", get(providers_handler))
     }
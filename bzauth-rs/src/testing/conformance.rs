@@ -0,0 +1,543 @@
+//! The behaviour every [`Adapt`] implementation is expected to have. Run the whole suite with
+//! [`adaptor_conformance`] from a test of the implementation:
+//!
+//! ```ignore
+//! #[tokio::test]
+//! async fn conformance() {
+//!     bzauth_rs::testing::adaptor_conformance(&MyAdaptor::new()).await;
+//! }
+//! ```
+//!
+//! The checks panic on the first deviation. They create their records with unique IDs, emails and
+//! tokens, so the adaptor's store does not need to be empty, and can be shared with other tests.
+
+use crate::adaptors::error::AdaptorError;
+use crate::contracts::adapt::{
+    Adapt, AdaptAccount, AdaptUser, AdaptVerificationToken, CreateSessionOptions,
+    ProviderAccountId, UseVerificationTokenOptions,
+};
+use crate::contracts::provide::ProviderType;
+use crate::contracts::token::Token;
+
+const PROVIDER_ID: &str = "conformance";
+
+/// A string unique to this run of a check
+fn unique(prefix: &str) -> String {
+    format!("{}_{}", prefix, uuid::Uuid::new_v4().simple())
+}
+
+fn new_user() -> AdaptUser {
+    AdaptUser {
+        id: Some(unique("user")),
+        username: Some("John Doe".to_string()),
+        email: Some(format!("{}@email.com", unique("john.doe"))),
+        image: Some("https://example.com/john.doe.png".to_string()),
+        email_verified: None,
+    }
+}
+
+fn new_account(user: &AdaptUser) -> AdaptAccount {
+    AdaptAccount {
+        id: Some(unique("account")),
+        user_id: user.id.clone(),
+        provider_id: Some(PROVIDER_ID.to_string()),
+        provider_type: ProviderType::OAuth,
+        provider_account_id: Some(unique("provider_account")),
+        token: Some(Token {
+            access_token: Some("access_token".to_string()),
+            refresh_token: Some("refresh_token".to_string()),
+            token_type: Some("Bearer".to_string()),
+            expires_in: Some(3600),
+            ..Default::default()
+        }),
+    }
+}
+
+fn provider_account_id(account: &AdaptAccount) -> ProviderAccountId {
+    ProviderAccountId {
+        provider_id: account.provider_id.clone().unwrap(),
+        provider_account_id: account.provider_account_id.clone().unwrap(),
+    }
+}
+
+fn session_for(user: &AdaptUser, expires_in: u64) -> CreateSessionOptions {
+    CreateSessionOptions {
+        token: unique("session"),
+        user_id: user.id.clone().unwrap(),
+        expires_in,
+    }
+}
+
+async fn create_user<A: Adapt + ?Sized>(adaptor: &A) -> AdaptUser {
+    adaptor
+        .create_user(new_user())
+        .await
+        .expect("create_user failed")
+}
+
+/// Runs every check of the suite against the adaptor
+pub async fn adaptor_conformance<A: Adapt + ?Sized>(adaptor: &A) {
+    conformance_users(adaptor).await;
+    conformance_accounts(adaptor).await;
+    conformance_sessions(adaptor).await;
+    conformance_verification_tokens(adaptor).await;
+    conformance_delete_user(adaptor).await;
+}
+
+/// Users are found by ID and by email, emails are unique, only existing users are updated, and the
+/// verification of their email is kept
+pub async fn conformance_users<A: Adapt + ?Sized>(adaptor: &A) {
+    let user = new_user();
+    let created = adaptor
+        .create_user(user.clone())
+        .await
+        .expect("create_user failed");
+    assert_eq!(created.id, user.id, "create_user changed the ID");
+    assert_eq!(created.email, user.email, "create_user changed the email");
+
+    let id = user.id.clone().unwrap();
+    let email = user.email.clone().unwrap();
+    let found = adaptor.get_user(id.clone()).await.expect("get_user failed");
+    assert_eq!(
+        found.and_then(|u| u.email),
+        user.email,
+        "get_user did not find the user"
+    );
+    let found = adaptor
+        .get_user_by_email(email.clone())
+        .await
+        .expect("get_user_by_email failed");
+    assert_eq!(
+        found.and_then(|u| u.id),
+        user.id,
+        "get_user_by_email did not find the user"
+    );
+
+    let found = adaptor
+        .get_user(unique("unknown"))
+        .await
+        .expect("get_user failed");
+    assert!(found.is_none(), "get_user found an unknown user");
+    let found = adaptor
+        .get_user_by_email(format!("{}@email.com", unique("unknown")))
+        .await
+        .expect("get_user_by_email failed");
+    assert!(found.is_none(), "get_user_by_email found an unknown user");
+
+    // Emails are unique
+    let result = adaptor
+        .create_user(AdaptUser {
+            id: Some(unique("user")),
+            ..user.clone()
+        })
+        .await;
+    assert!(
+        matches!(result, Err(AdaptorError::Conflict(_))),
+        "create_user with a taken email should conflict, got {:?}",
+        result
+    );
+
+    // Users without an ID are given one
+    let created = adaptor
+        .create_user(AdaptUser {
+            id: None,
+            ..new_user()
+        })
+        .await
+        .expect("create_user without an ID failed");
+    let created_id = created.id.expect("create_user did not give the user an ID");
+    let found = adaptor.get_user(created_id).await.expect("get_user failed");
+    assert!(
+        found.is_some(),
+        "get_user did not find the user given an ID"
+    );
+
+    let updated = adaptor
+        .update_user(AdaptUser {
+            username: Some("Jane Doe".to_string()),
+            ..user.clone()
+        })
+        .await
+        .expect("update_user failed");
+    assert_eq!(updated.username.as_deref(), Some("Jane Doe"));
+    let found = adaptor.get_user(id).await.expect("get_user failed");
+    assert_eq!(
+        found.and_then(|u| u.username).as_deref(),
+        Some("Jane Doe"),
+        "update_user did not persist the change"
+    );
+
+    // The verification of the email is kept until the user is verified again
+    let verified = adaptor
+        .create_user(AdaptUser {
+            email_verified: Some(true),
+            ..new_user()
+        })
+        .await
+        .expect("create_user of a verified user failed");
+    assert_eq!(
+        verified.email_verified,
+        Some(true),
+        "create_user did not keep the verification"
+    );
+    adaptor
+        .update_user(AdaptUser {
+            username: Some("Jane Doe".to_string()),
+            email_verified: None,
+            ..verified.clone()
+        })
+        .await
+        .expect("update_user failed");
+    let found = adaptor
+        .get_user(verified.id.clone().unwrap())
+        .await
+        .expect("get_user failed");
+    assert_eq!(
+        found.and_then(|u| u.email_verified),
+        Some(true),
+        "update_user lost the verification"
+    );
+
+    let result = adaptor
+        .update_user(AdaptUser {
+            id: Some(unique("unknown")),
+            ..new_user()
+        })
+        .await;
+    assert!(
+        matches!(result, Err(AdaptorError::NotFound(_))),
+        "update_user of an unknown user should not be found, got {:?}",
+        result
+    );
+}
+
+/// Linked accounts lead to their user and keep their token, which updates replace, and each is
+/// linked only once
+pub async fn conformance_accounts<A: Adapt + ?Sized>(adaptor: &A) {
+    let user = create_user(adaptor).await;
+    let account = new_account(&user);
+
+    let linked = adaptor
+        .link_account(account.clone())
+        .await
+        .expect("link_account failed");
+    assert_eq!(linked.user_id, user.id, "link_account changed the user");
+
+    let found = adaptor
+        .get_user_by_account(provider_account_id(&account))
+        .await
+        .expect("get_user_by_account failed");
+    assert_eq!(
+        found.and_then(|u| u.id),
+        user.id,
+        "get_user_by_account did not find the user of a linked account"
+    );
+
+    let found = adaptor
+        .get_account(provider_account_id(&account))
+        .await
+        .expect("get_account failed")
+        .expect("get_account did not find a linked account");
+    assert_eq!(found.user_id, user.id);
+    let token = found.token.expect("get_account lost the token");
+    assert_eq!(token.access_token.as_deref(), Some("access_token"));
+    assert_eq!(token.refresh_token.as_deref(), Some("refresh_token"));
+    assert!(
+        token.expires_in.is_some_and(|e| e > 0 && e <= 3600),
+        "get_account lost the expiry of the token, got {:?}",
+        token.expires_in
+    );
+
+    let result = adaptor
+        .link_account(AdaptAccount {
+            id: Some(unique("account")),
+            ..account.clone()
+        })
+        .await;
+    assert!(
+        matches!(result, Err(AdaptorError::Conflict(_))),
+        "link_account of a linked account should conflict, got {:?}",
+        result
+    );
+
+    // Updates replace the token, and nothing else
+    let updated = adaptor
+        .update_account(AdaptAccount {
+            id: Some(unique("account")),
+            token: Some(Token {
+                access_token: Some("rotated_access_token".to_string()),
+                refresh_token: Some("rotated_refresh_token".to_string()),
+                expires_in: Some(7200),
+                ..Default::default()
+            }),
+            ..account.clone()
+        })
+        .await
+        .expect("update_account failed");
+    assert_eq!(updated.id, linked.id, "update_account changed the ID");
+    let found = adaptor
+        .get_account(provider_account_id(&account))
+        .await
+        .expect("get_account failed")
+        .expect("get_account did not find an updated account");
+    assert_eq!(found.id, linked.id, "update_account changed the ID");
+    let token = found.token.expect("update_account lost the token");
+    assert_eq!(token.access_token.as_deref(), Some("rotated_access_token"));
+    assert_eq!(
+        token.refresh_token.as_deref(),
+        Some("rotated_refresh_token")
+    );
+    assert!(
+        token.expires_in.is_some_and(|e| e > 3600 && e <= 7200),
+        "update_account lost the expiry of the token, got {:?}",
+        token.expires_in
+    );
+    let result = adaptor
+        .update_account(AdaptAccount {
+            provider_account_id: Some(unique("unknown")),
+            ..account.clone()
+        })
+        .await;
+    assert!(
+        matches!(result, Err(AdaptorError::NotFound(_))),
+        "update_account of an unknown account should not be found, got {:?}",
+        result
+    );
+
+    // Accounts without an ID are given one
+    let linked = adaptor
+        .link_account(AdaptAccount {
+            id: None,
+            ..new_account(&user)
+        })
+        .await
+        .expect("link_account without an ID failed");
+    assert!(
+        linked.id.is_some(),
+        "link_account did not give the account an ID"
+    );
+
+    // The accounts of a user are listed, and only theirs
+    let other = create_user(adaptor).await;
+    adaptor
+        .link_account(new_account(&other))
+        .await
+        .expect("link_account failed");
+    let accounts = adaptor
+        .get_accounts_by_user(user.id.clone().unwrap())
+        .await
+        .expect("get_accounts_by_user failed");
+    let mut listed: Vec<_> = accounts
+        .iter()
+        .map(|a| a.provider_account_id.clone())
+        .collect();
+    assert_eq!(listed.len(), 2, "get_accounts_by_user listed {:?}", listed);
+    assert!(listed.is_sorted(), "get_accounts_by_user is not ordered");
+    listed.retain(|id| id == &account.provider_account_id);
+    assert_eq!(listed.len(), 1, "get_accounts_by_user missed an account");
+    let accounts = adaptor
+        .get_accounts_by_user(unique("unknown"))
+        .await
+        .expect("get_accounts_by_user failed");
+    assert!(
+        accounts.is_empty(),
+        "get_accounts_by_user listed accounts of an unknown user"
+    );
+
+    let found = adaptor
+        .get_user_by_account(ProviderAccountId {
+            provider_id: PROVIDER_ID.to_string(),
+            provider_account_id: unique("unknown"),
+        })
+        .await
+        .expect("get_user_by_account failed");
+    assert!(
+        found.is_none(),
+        "get_user_by_account found an unknown account"
+    );
+
+    adaptor
+        .unlink_account(provider_account_id(&account))
+        .await
+        .expect("unlink_account failed");
+    let found = adaptor
+        .get_user_by_account(provider_account_id(&account))
+        .await
+        .expect("get_user_by_account failed");
+    assert!(
+        found.is_none(),
+        "get_user_by_account found an unlinked account"
+    );
+    let found = adaptor
+        .get_account(provider_account_id(&account))
+        .await
+        .expect("get_account failed");
+    assert!(found.is_none(), "get_account found an unlinked account");
+
+    // Unlinking leaves the user
+    let found = adaptor
+        .get_user(user.id.clone().unwrap())
+        .await
+        .expect("get_user failed");
+    assert!(found.is_some(), "unlink_account deleted the user");
+}
+
+/// Sessions lead to their user, are extended by updates, and expire
+pub async fn conformance_sessions<A: Adapt + ?Sized>(adaptor: &A) {
+    let user = create_user(adaptor).await;
+    let options = session_for(&user, 3600);
+
+    let session = adaptor
+        .create_session(options.clone())
+        .await
+        .expect("create_session failed");
+    assert_eq!(session.token, options.token);
+    assert!(!session.is_expired(), "create_session expired the session");
+
+    let session_user = adaptor
+        .get_session_and_user(options.token.clone())
+        .await
+        .expect("get_session_and_user failed")
+        .expect("get_session_and_user did not find the session");
+    assert_eq!(session_user.user.id, user.id);
+    assert!(
+        session_user.session.expires_in > 0 && session_user.session.expires_in <= 3600,
+        "get_session_and_user lost the expiry, got {}",
+        session_user.session.expires_in
+    );
+
+    let mut session = session_user.session;
+    session.expires_in = 7200;
+    let updated = adaptor
+        .update_session(session)
+        .await
+        .expect("update_session failed");
+    assert!(
+        updated.expires_in > 3600,
+        "update_session did not extend the session"
+    );
+
+    let found = adaptor
+        .get_session_and_user(unique("unknown"))
+        .await
+        .expect("get_session_and_user failed");
+    assert!(
+        found.is_none(),
+        "get_session_and_user found an unknown session"
+    );
+
+    adaptor
+        .delete_session(options.token.clone())
+        .await
+        .expect("delete_session failed");
+    let found = adaptor
+        .get_session_and_user(options.token)
+        .await
+        .expect("get_session_and_user failed");
+    assert!(
+        found.is_none(),
+        "get_session_and_user found a deleted session"
+    );
+
+    // An expired session is either gone or reported as expired
+    let options = session_for(&user, 0);
+    adaptor
+        .create_session(options.clone())
+        .await
+        .expect("create_session failed");
+    let found = adaptor
+        .get_session_and_user(options.token)
+        .await
+        .expect("get_session_and_user failed");
+    assert!(
+        found.is_none_or(|session_user| session_user.session.is_expired()),
+        "get_session_and_user returned an expired session as active"
+    );
+}
+
+/// Verification tokens can be used once, and only with the email they were created for
+pub async fn conformance_verification_tokens<A: Adapt + ?Sized>(adaptor: &A) {
+    let email = format!("{}@email.com", unique("john.doe"));
+    let token = unique("token");
+
+    adaptor
+        .create_verification_token(AdaptVerificationToken {
+            email: email.clone(),
+            token: token.clone(),
+            expires_in: 600,
+        })
+        .await
+        .expect("create_verification_token failed");
+
+    let use_token = |email: &str, token: &str| UseVerificationTokenOptions {
+        email: email.to_string(),
+        token: token.to_string(),
+    };
+
+    let used = adaptor
+        .use_verification_token(use_token("someone.else@email.com", &token))
+        .await
+        .expect("use_verification_token failed");
+    assert!(
+        used.is_none(),
+        "use_verification_token accepted the token for another email"
+    );
+
+    let used = adaptor
+        .use_verification_token(use_token(&email, &token))
+        .await
+        .expect("use_verification_token failed")
+        .expect("use_verification_token did not find the token");
+    assert_eq!(used.email, email);
+    assert_eq!(used.token, token);
+
+    let used = adaptor
+        .use_verification_token(use_token(&email, &token))
+        .await
+        .expect("use_verification_token failed");
+    assert!(used.is_none(), "use_verification_token used a token twice");
+}
+
+/// Deleting a user deletes its accounts and sessions
+pub async fn conformance_delete_user<A: Adapt + ?Sized>(adaptor: &A) {
+    let user = create_user(adaptor).await;
+    let id = user.id.clone().unwrap();
+    let account = new_account(&user);
+    adaptor
+        .link_account(account.clone())
+        .await
+        .expect("link_account failed");
+    let options = session_for(&user, 3600);
+    adaptor
+        .create_session(options.clone())
+        .await
+        .expect("create_session failed");
+
+    adaptor
+        .delete_user(id.clone())
+        .await
+        .expect("delete_user failed");
+
+    let found = adaptor.get_user(id).await.expect("get_user failed");
+    assert!(found.is_none(), "get_user found a deleted user");
+    let found = adaptor
+        .get_user_by_email(user.email.clone().unwrap())
+        .await
+        .expect("get_user_by_email failed");
+    assert!(found.is_none(), "get_user_by_email found a deleted user");
+    let found = adaptor
+        .get_user_by_account(provider_account_id(&account))
+        .await
+        .expect("get_user_by_account failed");
+    assert!(found.is_none(), "get_user_by_account found a deleted user");
+    let found = adaptor
+        .get_account(provider_account_id(&account))
+        .await
+        .expect("get_account failed");
+    assert!(found.is_none(), "delete_user left the accounts of the user");
+    let found = adaptor
+        .get_session_and_user(options.token)
+        .await
+        .expect("get_session_and_user failed");
+    assert!(found.is_none(), "delete_user left the sessions of the user");
+}
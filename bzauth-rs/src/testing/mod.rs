@@ -0,0 +1,5 @@
+//! Helpers for testing the crate's extension points in downstream crates
+
+pub mod conformance;
+
+pub use conformance::*; // Export the adaptor conformance suite
@@ -0,0 +1,155 @@
+use http::StatusCode;
+use oauth2::basic::BasicErrorResponseType;
+use oauth2::{RefreshToken, RequestTokenError};
+
+use crate::auth::Auth;
+use crate::contracts::account::Account;
+use crate::contracts::adapt::ProviderAccountId;
+use crate::contracts::token::Token;
+use crate::providers::error::ProviderError;
+use crate::tools::{CoreError, generators};
+
+/// Access tokens expiring within this many seconds are refreshed, so they do not expire in flight
+pub const ACCESS_TOKEN_EXPIRY_MARGIN: u64 = 60;
+
+/// Returns a valid access token of the user's account at the provider, refreshing it through the
+/// provider's token endpoint if it has expired. The rotated tokens are stored through the adaptor.
+///
+/// If the provider refuses the refresh token, the tokens of the account are cleared, flagging it
+/// until the user signs in with the provider again. Until then, and for accounts without a refresh
+/// token, [`ProviderError::ReauthorisationRequired`] is returned. Users with several accounts at
+/// the provider are refused with a conflict, as the account cannot be told.
+pub async fn get_provider_access_token(
+    auth: &Auth,
+    user_id: &str,
+    provider_id: &str,
+) -> Result<String, CoreError> {
+    let adaptor = auth.adaptor().ok_or_else(|| {
+        CoreError::new().with_message("An adaptor is required to keep provider tokens")
+    })?;
+    let provider = auth
+        .options
+        .providers
+        .iter()
+        .find(|p| p.id() == provider_id)
+        .ok_or_else(|| {
+            CoreError::new()
+                .with_message("Provider not found")
+                .with_status(StatusCode::NOT_FOUND.into())
+        })?;
+    let oauth2_provider = provider
+        .as_oauth2()
+        .ok_or_else(|| CoreError::new().with_message("Provider is not OAuth2"))?;
+
+    let mut accounts = adaptor
+        .get_accounts_by_user(user_id.to_string())
+        .await?
+        .into_iter()
+        .filter(|account| account.provider_id.as_deref() == Some(provider_id));
+    let account = accounts.next().ok_or_else(|| {
+        CoreError::new()
+            .with_message("Account not found")
+            .with_status(StatusCode::NOT_FOUND.into())
+    })?;
+    // Which of the user's accounts at the provider is meant cannot be told
+    if accounts.next().is_some() {
+        return Err(CoreError::new()
+            .with_message("The user has several accounts at the provider")
+            .with_status(StatusCode::CONFLICT.into()));
+    }
+    let token = account.token.clone().unwrap_or_default();
+
+    // Tokens without an expiry are used until the provider refuses them
+    let expiring = token
+        .expires_in
+        .is_some_and(|expires_in| expires_in <= ACCESS_TOKEN_EXPIRY_MARGIN);
+    if let Some(access_token) = token.access_token.clone().filter(|_| !expiring) {
+        return Ok(access_token);
+    }
+
+    let Some(refresh_token) = token.refresh_token.clone() else {
+        return Err(ProviderError::ReauthorisationRequired(
+            "The account has no refresh token".to_string(),
+        )
+        .into());
+    };
+
+    // OpenID Connect providers find their token endpoint through discovery
+    if let Some(oidc_provider) = oauth2_provider.as_oidc() {
+        oidc_provider.discover().await?;
+    }
+    let client = generators::generate_client(oauth2_provider)?;
+    let http_client = generators::TokenHttpClient(generators::generate_http_client()?);
+    let refresh_token = RefreshToken::new(refresh_token);
+    let token_response = client
+        .exchange_refresh_token(&refresh_token)
+        .request_async(&http_client)
+        .await;
+
+    let refreshed = match token_response {
+        Ok(token_response) => Token::from(token_response).replacing(token),
+        Err(RequestTokenError::ServerResponse(error))
+            if *error.error() == BasicErrorResponseType::InvalidGrant =>
+        {
+            // With rotating refresh tokens, a concurrent refresh may have used it first. Its
+            // tokens are kept then, rather than cleared
+            let stored_token = adaptor
+                .get_account(ProviderAccountId {
+                    provider_id: provider_id.to_string(),
+                    provider_account_id: account.provider_account_id.clone().unwrap_or_default(),
+                })
+                .await?
+                .and_then(|account| account.token);
+            let stored_refresh_token = stored_token
+                .as_ref()
+                .and_then(|token| token.refresh_token.as_ref());
+            if stored_refresh_token != Some(refresh_token.secret()) {
+                if let Some(access_token) = stored_token.and_then(|token| token.access_token) {
+                    return Ok(access_token);
+                }
+                return Err(ProviderError::ReauthorisationRequired(
+                    "The provider refused the refresh token".to_string(),
+                )
+                .into());
+            }
+
+            // The refresh token was revoked or has expired, so none of the tokens are of use
+            tracing::warn!(
+                "[access_token] Provider {} refused the refresh token of account {:?}",
+                provider_id,
+                account.id
+            );
+            adaptor
+                .update_account(Account {
+                    token: Some(Token {
+                        token_type: token.token_type,
+                        scope: token.scope,
+                        ..Default::default()
+                    }),
+                    ..account
+                })
+                .await?;
+            return Err(ProviderError::ReauthorisationRequired(
+                "The provider refused the refresh token".to_string(),
+            )
+            .into());
+        }
+        Err(error) => return Err(ProviderError::RefreshFailed(error.to_string()).into()),
+    };
+
+    let access_token = refreshed.access_token.clone().ok_or_else(|| {
+        ProviderError::RefreshFailed("The provider returned no access token".to_string())
+    })?;
+    tracing::debug!(
+        "[access_token] Refreshed the access token of account {:?}",
+        account.id
+    );
+    adaptor
+        .update_account(Account {
+            token: Some(refreshed),
+            ..account
+        })
+        .await?;
+
+    Ok(access_token)
+}
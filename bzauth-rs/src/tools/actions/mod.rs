@@ -1,5 +1,58 @@
+mod access_token;
 mod register;
+mod revocation;
+mod session;
 mod sign_in;
 
+use std::sync::Arc;
+
+pub use access_token::{ACCESS_TOKEN_EXPIRY_MARGIN, get_provider_access_token};
 pub use register::register;
+pub(crate) use revocation::try_revoke_account_tokens;
+pub use revocation::{delete_user, revoke_account_tokens, unlink_account};
+pub use session::{create_session, delete_session, get_session};
 pub use sign_in::sign_in;
+
+use crate::auth::Auth;
+use crate::tools::CoreError;
+use crate::tools::request::CoreRequest;
+use crate::tools::response::RequestPayload;
+
+/// Resolves where to send the user once signed in, through the redirect callback. The callback
+/// receives the requested URL and the origin of the application.
+pub(crate) async fn redirect_url<T: RequestPayload>(
+    request: &CoreRequest<T>,
+    auth: &Arc<Auth>,
+) -> Result<String, CoreError> {
+    let url = request.uri().to_string();
+    let base_url = request.extract_origin()?;
+
+    Ok(run_redirect_callback(auth, url, base_url).await)
+}
+
+/// Resolves a URL to send the user to through the redirect callback. Paths are resolved against
+/// the origin of the application first.
+pub(crate) async fn redirect_url_to<T: RequestPayload>(
+    request: &CoreRequest<T>,
+    auth: &Arc<Auth>,
+    url: String,
+) -> Result<String, CoreError> {
+    let base_url = request.extract_origin()?;
+    let url = match url.starts_with('/') && !url.starts_with("//") {
+        true => format!("{}{}", base_url, url),
+        false => url,
+    };
+
+    Ok(run_redirect_callback(auth, url, base_url).await)
+}
+
+async fn run_redirect_callback(auth: &Arc<Auth>, url: String, base_url: String) -> String {
+    let redirect_callback = auth
+        .options
+        .callbacks
+        .as_ref()
+        .map(|c| c.redirect.clone())
+        .unwrap_or_default();
+
+    redirect_callback(url, base_url).await
+}
@@ -2,7 +2,7 @@ use std::sync::Arc;
 
 use crate::auth::Auth;
 use crate::contracts::account::Account;
-use crate::contracts::adapt::{Adapt, CreateSessionOptions};
+use crate::contracts::adapt::Adapt;
 use crate::contracts::provide::Provide;
 use crate::contracts::user::User;
 use crate::tools::request::CoreRequest;
@@ -26,7 +26,7 @@ pub async fn register(
 
     // Check if email is already registered
     let user_by_email = if let Some(email) = user_email {
-        _adaptor.get_user_by_email(email.clone()).await
+        _adaptor.get_user_by_email(email.clone()).await?
     } else {
         None
     };
@@ -37,51 +37,30 @@ pub async fn register(
     }
 
     // Create user, link account, generate session, and redirect
-    let session_generated = "TODO";
+    let created_user = _adaptor.create_user(_user.clone()).await?;
+    tracing::debug!("[register] Created User: {:?}", created_user);
 
-    let _debug = _adaptor.create_user(_user.clone()).await;
-    tracing::debug!("[register] Created User: {:?}", _debug);
-
-    let _debug = _adaptor.link_account(_account.unwrap()).await;
+    let _account = _account.unwrap();
+    let _debug = _adaptor.link_account(_account.clone()).await?;
     tracing::debug!("[register] Linked Account: {:?}", _debug);
 
-    let _debug = _adaptor
-        .create_session(CreateSessionOptions {
-            token: session_generated.to_string(), // TODO: Generate a proper token
-            user_id: _user.id.clone().unwrap(),
-            expires_in: 3600, // TODO: Set appropriate expiration time from configuration
-        })
-        .await;
-    tracing::debug!("[register] Created Session: {:?}", _debug);
-
-    let mut cookies = _request.cookies().clone();
-    cookies.set("session_token", session_generated.to_string());
-
-    // TODO: Move this to an internal functionality of CoreResponse
-
-    // Infer the host from the request headers
-    let url = _request.uri().to_string();
-    let host = _request.headers().get("host").and_then(|h| h.to_str().ok());
-    if host.is_none() {
-        return Err(
-            CoreError::new().with_message("Failed to infer the host from the request headers")
-        );
-    }
-    let scheme = _request.uri().scheme_str().unwrap_or("http");
-    let base_url = format!("{}://{}", scheme, host.unwrap());
-
-    // Convert the host to a string
-    let redirect_callback = _auth
-        .options
-        .callbacks
-        .as_ref()
-        .map(|c| c.redirect.clone())
-        .unwrap_or_default();
+    let created_user = User {
+        id: created_user.id.or(_user.id),
+        ..created_user
+    };
+    let cookies = super::create_session(
+        &_request,
+        &_auth,
+        Some(_adaptor),
+        &created_user,
+        Some(&_account),
+    )
+    .await?;
 
-    let redirect_url = redirect_callback(url.clone(), base_url).await;
+    let redirect_url = super::redirect_url(&_request, &_auth).await?;
 
     // TODO: If a callback-url cookie is set, use that instead of redirecting to the home page
     Ok(CoreResponse::new()
         .with_redirect(redirect_url)
-        .with_cookies(cookies.clone()))
+        .with_cookies(cookies))
 }
@@ -0,0 +1,123 @@
+use crate::auth::Auth;
+use crate::contracts::account::Account;
+use crate::contracts::adapt::{Adapt, ProviderAccountId};
+use crate::providers::error::ProviderError;
+use crate::tools::{CoreError, generators};
+
+/// Revokes the refresh and access tokens of the account at its provider's revocation endpoint.
+/// Nothing is revoked if the provider has no revocation endpoint, or has been removed since.
+/// https://datatracker.ietf.org/doc/html/rfc7009
+pub async fn revoke_account_tokens(auth: &Auth, account: &Account) -> Result<(), CoreError> {
+    let Some(oauth2_provider) = auth
+        .options
+        .providers
+        .iter()
+        .find(|p| account.provider_id.as_deref() == Some(p.id().as_str()))
+        .and_then(|p| p.as_oauth2())
+    else {
+        return Ok(());
+    };
+
+    // OpenID Connect providers find their revocation endpoint through discovery
+    if let Some(oidc_provider) = oauth2_provider.as_oidc() {
+        oidc_provider.discover().await?;
+    }
+    let Some(endpoint) = oauth2_provider.revocation_endpoint() else {
+        return Ok(());
+    };
+
+    // The refresh token goes first, so no access token is issued with it in between. The access
+    // token is still revoked if the refresh token could not be
+    let token = account.token.clone().unwrap_or_default();
+    let tokens = [
+        (token.refresh_token, "refresh_token"),
+        (token.access_token, "access_token"),
+    ];
+    let http_client = generators::generate_http_client()?;
+    let mut result = Ok(());
+    for (token, token_type_hint) in tokens {
+        let Some(token) = token else {
+            continue;
+        };
+        let revoked = http_client
+            .post(endpoint.url())
+            .basic_auth(
+                oauth2_provider.client_id(),
+                Some(oauth2_provider.client_secret()),
+            )
+            .form(&[
+                ("token", token.as_str()),
+                ("token_type_hint", token_type_hint),
+            ])
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| ProviderError::RevocationFailed(e.to_string()));
+        result = result.and(revoked.map(|_| ()));
+    }
+    result?;
+    tracing::debug!(
+        "[revocation] Revoked the tokens of account {:?}",
+        account.id
+    );
+
+    Ok(())
+}
+
+/// Unlinks the account, then revokes its tokens at the provider unless
+/// [`TokenRevocationOptions::on_unlink`](crate::auth::TokenRevocationOptions::on_unlink) is off.
+/// A failed revocation is logged, as the account is already gone.
+pub async fn unlink_account(auth: &Auth, account: &Account) -> Result<(), CoreError> {
+    let adaptor = require_adaptor(auth)?;
+
+    adaptor
+        .unlink_account(ProviderAccountId {
+            provider_id: account.provider_id.clone().unwrap_or_default(),
+            provider_account_id: account.provider_account_id.clone().unwrap_or_default(),
+        })
+        .await?;
+
+    if auth.token_revocation_options().on_unlink() {
+        try_revoke_account_tokens(auth, account).await;
+    }
+    Ok(())
+}
+
+/// Deletes the user, then revokes the tokens of each of their accounts unless
+/// [`TokenRevocationOptions::on_delete_user`](crate::auth::TokenRevocationOptions::on_delete_user)
+/// is off. A failed revocation is logged, as the user is already gone.
+pub async fn delete_user(auth: &Auth, user_id: &str) -> Result<(), CoreError> {
+    let adaptor = require_adaptor(auth)?;
+
+    let accounts = match auth.token_revocation_options().on_delete_user() {
+        true => adaptor.get_accounts_by_user(user_id.to_string()).await?,
+        false => vec![],
+    };
+    adaptor.delete_user(user_id.to_string()).await?;
+    tracing::debug!("[revocation] Deleted user: {}", user_id);
+
+    for account in &accounts {
+        try_revoke_account_tokens(auth, account).await;
+    }
+    Ok(())
+}
+
+/// Revokes the tokens of the account, logging a failure instead of returning it
+pub(crate) async fn try_revoke_account_tokens(auth: &Auth, account: &Account) -> bool {
+    match revoke_account_tokens(auth, account).await {
+        Ok(()) => true,
+        Err(error) => {
+            tracing::warn!(
+                "[revocation] Failed to revoke the tokens of account {:?}: {}",
+                account.id,
+                error
+            );
+            false
+        }
+    }
+}
+
+fn require_adaptor(auth: &Auth) -> Result<&dyn Adapt, CoreError> {
+    auth.adaptor()
+        .ok_or_else(|| CoreError::new().with_message("An adaptor is required to manage accounts"))
+}
@@ -0,0 +1,217 @@
+use std::sync::Arc;
+
+use http::StatusCode;
+
+use crate::auth::{Auth, SessionStrategy};
+use crate::contracts::account::Account;
+use crate::contracts::adapt::{Adapt, CreateSessionOptions};
+use crate::contracts::session::{Session, SessionAccount};
+use crate::contracts::user::User;
+use crate::tools::CoreError;
+use crate::tools::cookie::{Cookie, Cookies, SameSite};
+use crate::tools::request::CoreRequest;
+use crate::tools::request_extractors::COOKIE_SESSION_TOKEN;
+use crate::tools::response::RequestPayload;
+use crate::tools::session_jwt;
+
+/// Creates a session for the user, returning the session cookie to set. With the database
+/// strategy the session is stored through the adaptor, otherwise it is held by the cookie itself.
+pub async fn create_session<T: RequestPayload>(
+    request: &CoreRequest<T>,
+    auth: &Arc<Auth>,
+    adaptor: Option<&dyn Adapt>,
+    user: &User,
+    account: Option<&Account>,
+) -> Result<Cookies, CoreError> {
+    let session_options = auth.session_options();
+    let max_age = session_options.max_age();
+
+    let token = match session_options.strategy() {
+        SessionStrategy::Database => {
+            let adaptor = adaptor.ok_or_else(|| {
+                CoreError::new().with_message("An adaptor is required for database sessions")
+            })?;
+            let user_id = user
+                .id
+                .clone()
+                .ok_or_else(|| CoreError::new().with_message("User has no ID"))?;
+
+            let session = adaptor
+                .create_session(CreateSessionOptions {
+                    token: session_options.generate_token(),
+                    user_id,
+                    expires_in: max_age.max(0) as u64,
+                })
+                .await?;
+            session.token
+        }
+        SessionStrategy::Jwt => {
+            let session = Session {
+                user: Some(user.clone()),
+                account: account.map(SessionAccount::from),
+                expires_at: Some(expires_at(max_age)),
+            };
+            encode_session(auth, &session)?
+        }
+    };
+    tracing::debug!("[session] Created session for user: {:?}", user.id);
+
+    let mut cookies = Cookies::new();
+    cookies.insert(session_cookie(request, token, max_age)?);
+    Ok(cookies)
+}
+
+/// Reads the session of the request. Expired sessions are cleared, and the expiry of a session last
+/// extended more than `update_age` ago is slid forward. The returned cookies must be set on the
+/// response, as they refresh or clear the session cookie.
+pub async fn get_session<T: RequestPayload>(
+    request: &CoreRequest<T>,
+    auth: &Arc<Auth>,
+) -> Result<(Option<Session>, Cookies), CoreError> {
+    let mut cookies = Cookies::new();
+    let Some(token) = request.extract_session_token() else {
+        return Ok((None, cookies));
+    };
+
+    let session_options = auth.session_options();
+    let max_age = session_options.max_age();
+
+    let session = match session_options.strategy() {
+        SessionStrategy::Database => {
+            let adaptor = auth.adaptor().ok_or_else(|| {
+                CoreError::new().with_message("An adaptor is required for database sessions")
+            })?;
+
+            let Some(mut session_user) = adaptor.get_session_and_user(token.clone()).await? else {
+                // Unknown token, most likely deleted on sign out
+                cookies.expire(COOKIE_SESSION_TOKEN);
+                return Ok((None, cookies));
+            };
+
+            if session_user.session.is_expired() {
+                tracing::debug!("[session] Session has expired");
+                adaptor.delete_session(token).await?;
+                cookies.expire(COOKIE_SESSION_TOKEN);
+                return Ok((None, cookies));
+            }
+
+            if session_options.is_due_for_update(session_user.session.expires_in) {
+                let mut session = session_user.session.clone();
+                session.expires_in = max_age.max(0) as u64;
+
+                session_user.session = adaptor.update_session(session).await?;
+                cookies.insert(session_cookie(request, token, max_age)?);
+                tracing::debug!("[session] Extended session expiry");
+            }
+
+            session_user.session.adapt_into(&Session {
+                user: Some(session_user.user),
+                ..Default::default()
+            })
+        }
+        SessionStrategy::Jwt => {
+            let session =
+                match session_jwt::decode(auth.secret(), session_options.jwt_encoding(), &token) {
+                    Ok(session) => session,
+                    Err(error) => {
+                        tracing::debug!("[session] Rejected session cookie: {}", error);
+                        cookies.expire(COOKIE_SESSION_TOKEN);
+                        return Ok((None, cookies));
+                    }
+                };
+
+            let now = chrono::Utc::now().timestamp() as u64;
+            let expires_in = session.expires_at.unwrap_or_default().saturating_sub(now);
+            if session_options.is_due_for_update(expires_in) {
+                let session = Session {
+                    expires_at: Some(expires_at(max_age)),
+                    ..session
+                };
+                let token = encode_session(auth, &session)?;
+                cookies.insert(session_cookie(request, token, max_age)?);
+                tracing::debug!("[session] Extended session expiry");
+                session
+            } else {
+                session
+            }
+        }
+    };
+
+    Ok((Some(session), cookies))
+}
+
+/// Ends the session of the request. With the database strategy the session is deleted through the
+/// adaptor, whereas a stateless session lives on until it expires, so clearing the cookie is all
+/// that can be done. Returns the session that was ended, if still valid, and the cookies to set.
+pub async fn delete_session<T: RequestPayload>(
+    request: &CoreRequest<T>,
+    auth: &Arc<Auth>,
+) -> Result<(Option<Session>, Cookies), CoreError> {
+    let mut cookies = Cookies::new();
+    let Some(token) = request.extract_session_token() else {
+        return Ok((None, cookies));
+    };
+
+    let session_options = auth.session_options();
+    let session = match session_options.strategy() {
+        SessionStrategy::Database => {
+            let adaptor = auth.adaptor().ok_or_else(|| {
+                CoreError::new().with_message("An adaptor is required for database sessions")
+            })?;
+
+            let session = adaptor
+                .get_session_and_user(token.clone())
+                .await?
+                .filter(|session_user| !session_user.session.is_expired())
+                .map(|session_user| {
+                    session_user.session.adapt_into(&Session {
+                        user: Some(session_user.user),
+                        ..Default::default()
+                    })
+                });
+            adaptor.delete_session(token).await?;
+            session
+        }
+        SessionStrategy::Jwt => {
+            session_jwt::decode(auth.secret(), session_options.jwt_encoding(), &token).ok()
+        }
+    };
+    tracing::debug!("[session] Ended session");
+
+    cookies.expire(COOKIE_SESSION_TOKEN);
+    Ok((session, cookies))
+}
+
+fn expires_at(max_age: i64) -> u64 {
+    (chrono::Utc::now().timestamp() + max_age).max(0) as u64
+}
+
+fn encode_session(auth: &Arc<Auth>, session: &Session) -> Result<String, CoreError> {
+    session_jwt::encode(
+        auth.secret(),
+        auth.session_options().jwt_encoding(),
+        session,
+    )
+    .map_err(|e| {
+        CoreError::new()
+            .with_message(e.to_string())
+            .with_status(StatusCode::INTERNAL_SERVER_ERROR.into())
+    })
+}
+
+/// The session cookie is hidden from scripts, and only sent over HTTPS when served over HTTPS.
+fn session_cookie<T: RequestPayload>(
+    request: &CoreRequest<T>,
+    token: String,
+    max_age: i64,
+) -> Result<Cookie, CoreError> {
+    let secure = request.extract_origin()?.starts_with("https://");
+
+    Ok(Cookie::new(COOKIE_SESSION_TOKEN.to_string())
+        .with_value(token)
+        .with_path("/".to_string())
+        .with_secure(secure)
+        .with_http_only(true)
+        .with_same_site(SameSite::Lax)
+        .with_max_age(max_age.clamp(0, i32::MAX as i64) as i32))
+}
@@ -11,32 +11,20 @@ use crate::tools::{CallbackRequest, CallbackResponse, CoreError};
 
 pub async fn sign_in(
     request: CoreRequest<CallbackRequest>,
-    _adapt_user: Option<User>,
-    _adapt_account: Option<Account>,
+    adapt_user: Option<User>,
+    adapt_account: Option<Account>,
     _provider: &dyn Provide,
-    _adaptor: &dyn Adapt,
+    adaptor: Option<&dyn Adapt>,
     auth: Arc<Auth>,
 ) -> Result<CoreResponse<CallbackResponse>, CoreError> {
-    // Infer the host from the request headers
-    let url = request.uri().to_string();
-    let host = request.headers().get("host").and_then(|h| h.to_str().ok());
-    if host.is_none() {
-        return Err(
-            CoreError::new().with_message("Failed to infer the host from the request headers")
-        );
-    }
+    let user =
+        adapt_user.ok_or_else(|| CoreError::new().with_message("User is required to sign in"))?;
+    let cookies =
+        super::create_session(&request, &auth, adaptor, &user, adapt_account.as_ref()).await?;
 
-    // Convert the host to a string
-    let host = host.unwrap().to_string();
+    let redirect_url = super::redirect_url(&request, &auth).await?;
 
-    let redirect_callback = auth.options.callbacks.as_ref().map(|c| c.redirect.as_ref());
-
-    if redirect_callback.is_none() {
-        return Err(CoreError::new().with_message("No redirect callback defined"));
-    }
-    let redirect_callback = redirect_callback.unwrap();
-
-    let redirect_url = redirect_callback(url.clone(), host).await;
-
-    Ok(CoreResponse::new().with_redirect(redirect_url))
+    Ok(CoreResponse::new()
+        .with_redirect(redirect_url)
+        .with_cookies(cookies))
 }
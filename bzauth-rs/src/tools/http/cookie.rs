@@ -211,44 +211,42 @@ impl FromStr for Cookie {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut cookie = Cookie::new("".to_string());
 
-        for part in s.split(';') {
-            let mut kv = part.split('=');
+        for (index, part) in s.split(';').enumerate() {
+            // Values may contain '=' (e.g. base64 padding), so only split on the first one
+            let mut kv = part.splitn(2, '=');
             let full_key = kv.next().unwrap_or("").trim().to_string();
             let value = kv.next().unwrap_or("").trim();
 
-            // Secure/Host cookies have a prefix: __Secure-Name=Value or __Host-Name=Value
-            let (key, secure, _host) = if full_key.to_lowercase().starts_with("__secure-") {
-                (
-                    full_key.trim_start_matches("__secure-").to_string(),
-                    true,
-                    false,
-                )
-            } else if full_key.starts_with("__host-") {
-                (
-                    full_key.trim_start_matches("__host-").to_string(),
-                    false,
-                    true,
-                )
-            } else {
-                (full_key.to_string(), false, false)
-            };
-
-            match key.to_lowercase().as_str() {
+            // The first pair is always the name and value of the cookie
+            if index == 0 {
+                // Secure/Host cookies have a prefix: __Secure-Name=Value or __Host-Name=Value
+                let lower_key = full_key.to_lowercase();
+                let (name, secure) = if lower_key.starts_with("__secure-") {
+                    (full_key["__secure-".len()..].to_string(), true)
+                } else if lower_key.starts_with("__host-") {
+                    (full_key["__host-".len()..].to_string(), true)
+                } else {
+                    (full_key, false)
+                };
+
+                cookie = Cookie::new(name)
+                    .with_value(value.to_string())
+                    .with_secure(secure);
+                continue;
+            }
+
+            // The rest are attributes
+            match full_key.to_lowercase().as_str() {
                 "path" => cookie = cookie.with_path(value.to_string()),
                 "domain" => cookie = cookie.with_domain(value.to_string()),
-                "httponly" => cookie = cookie.with_http_only(value.parse().unwrap_or(false)),
+                "secure" => cookie = cookie.with_secure(true),
+                "httponly" => cookie = cookie.with_http_only(true),
                 "samesite" => {
                     cookie = cookie.with_same_site(value.parse().unwrap_or(SameSite::Strict))
                 }
                 "expires" => cookie = cookie.with_expires(value.parse().unwrap_or(0)),
                 "max-age" => cookie = cookie.with_max_age(value.parse::<i32>().unwrap_or(0)),
-                _ => {
-                    cookie = cookie.with_value(value.to_string());
-                    if (secure) && !cookie.name.is_empty() {
-                        // If the cookie is secure or a host cookie, we set the secure flag
-                        cookie = cookie.with_secure(secure);
-                    }
-                }
+                _ => {}
             }
         }
         Ok(cookie)
@@ -299,10 +297,23 @@ impl Cookies {
         })
     }
 
+    pub fn insert(&mut self, cookie: Cookie) {
+        self.cookies.insert(cookie.name.clone(), cookie);
+    }
+
     pub fn remove(&mut self, name: &str) {
         self.cookies.remove(name);
     }
 
+    /// Replaces the cookie with an empty one that expires immediately, so the client drops it.
+    pub fn expire<K: Into<String>>(&mut self, name: K) {
+        let cookie = Cookie::new(name.into())
+            .with_value(String::new())
+            .with_path("/".to_string())
+            .with_max_age(0);
+        self.insert(cookie);
+    }
+
     pub fn extend(&mut self, other: Cookies) {
         for (name, cookie) in other.cookies {
             self.cookies.insert(name, cookie);
@@ -369,6 +380,12 @@ impl FromStr for Cookies {
             }
         }
 
+        // Finalize the last cookie
+        if let Some(partial_cookie) = currently_processing_cookie.take() {
+            let cookie = Cookie::from_str(&partial_cookie)?;
+            cookies.cookies.insert(cookie.name.clone(), cookie);
+        }
+
         Ok(cookies)
     }
 }
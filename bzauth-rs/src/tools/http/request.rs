@@ -83,6 +83,17 @@ impl<T> CoreRequest<T> {
         query
     }
 
+    /// Parses the body as a url-encoded form. Empty if there is no body or it is not a form.
+    pub fn form(&self) -> HashMap<String, String> {
+        let mut form = HashMap::new();
+        if let Some(body) = self.body.as_ref() {
+            for (key, value) in url::form_urlencoded::parse(body.as_bytes()) {
+                form.insert(key.to_string(), value.to_string());
+            }
+        }
+        form
+    }
+
     pub fn header(&self, key: HeaderName) -> Option<String> {
         self.headers
             .get(key)
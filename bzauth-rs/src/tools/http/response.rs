@@ -5,7 +5,7 @@ use http::{HeaderMap, StatusCode};
 use serde::Serialize;
 
 use super::request::CoreRequest;
-use crate::tools::Cookies;
+use crate::tools::{Cookies, CoreError};
 
 pub trait RequestPayload: Clone + Serialize {}
 impl<T> RequestPayload for T where T: Clone + Serialize {}
@@ -88,6 +88,16 @@ impl<T> CoreResponse<T> {
         }
     }
 
+    /// Creates a new CoreResponse from an error, using its status and the error as the payload.
+    /// Unlike returning the error directly, cookies can still be attached to the response.
+    pub fn from_error(error: CoreError) -> Self {
+        let mut response = CoreResponse::new()
+            .with_status(StatusCode::from_u16(error.status).unwrap_or(StatusCode::BAD_REQUEST))
+            .with_header::<_, String>(http::header::CONTENT_TYPE, "application/json".to_string());
+        response.set_body(error);
+        response
+    }
+
     /// Creates a new CoreResponse with a specified header. If reassigned, the header will be replaced.
     pub fn with_header<H, V>(self, key: H, value: String) -> Self
     where
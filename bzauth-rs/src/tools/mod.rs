@@ -11,8 +11,11 @@ pub mod util;
 pub use error::*;
 pub use http::cookie::*;
 pub use http::*;
+pub use routes::accounts::*;
 pub use routes::authorise::*;
 pub use routes::callback::*;
 pub use routes::csrf::*;
+pub use routes::logout::*;
+pub use routes::session::*;
 pub use util::try_async::*;
 pub use util::*; // Make the `TryFromAsync` trait available
@@ -0,0 +1,139 @@
+use std::sync::Arc;
+
+use http::StatusCode;
+use http::header::CACHE_CONTROL;
+use serde::{Deserialize, Serialize};
+
+use crate::auth::Auth;
+use crate::contracts::session::SessionAccount;
+use crate::tools::cookie::Cookies;
+use crate::tools::request::CoreRequest;
+use crate::tools::response::{CoreResponse, RequestPayload};
+use crate::tools::{CoreError, actions};
+
+/// The accounts linked to the current user, without their tokens
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AccountsResponse {
+    pub accounts: Vec<SessionAccount>,
+}
+
+/// The parameters accepted by `/accounts/{provider}/unlink`, from the query or a url-encoded form
+/// body
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UnlinkAccountRequest {
+    /// The account to unlink, when the user has several accounts at the provider
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider_account_id: Option<String>,
+    /// Where to send the user once unlinked. Without one, the remaining accounts are returned
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub callback_url: Option<String>,
+}
+
+impl UnlinkAccountRequest {
+    /// Reads the parameters from the request, with the form body taking precedence over the query.
+    pub fn from_request(request: &CoreRequest<UnlinkAccountRequest>) -> Self {
+        let mut params = request.query();
+        params.extend(request.form());
+
+        serde_json::to_value(params)
+            .and_then(serde_json::from_value)
+            .unwrap_or_default()
+    }
+}
+
+pub async fn accounts(
+    request: CoreRequest<()>,
+) -> Result<CoreResponse<AccountsResponse>, CoreError> {
+    let auth = request.extract_auth()?;
+    let adaptor = request.extract_adaptor()?;
+
+    let (user_id, cookies) = session_user_id(&request, &auth).await?;
+    let accounts = adaptor.get_accounts_by_user(user_id).await?;
+
+    // The accounts change on link and unlink, so they must never be served from a cache
+    Ok(CoreResponse::<AccountsResponse>::new()
+        .with_header::<_, String>(CACHE_CONTROL, "no-store".to_string())
+        .with_cookies(cookies)
+        .with_payload(AccountsResponse {
+            accounts: accounts.iter().map(SessionAccount::from).collect(),
+        }))
+}
+
+pub async fn unlink_account(
+    request: CoreRequest<UnlinkAccountRequest>,
+) -> Result<CoreResponse<AccountsResponse>, CoreError> {
+    let auth = request.extract_auth()?;
+    let adaptor = request.extract_adaptor()?;
+    let params = UnlinkAccountRequest::from_request(&request);
+
+    // Otherwise any site could unlink the user's accounts
+    request.check_csrf_token(auth.secret())?;
+
+    let (user_id, cookies) = session_user_id(&request, &auth).await?;
+    let provider_id = request.extract_provider_id()?;
+    let mut accounts = adaptor.get_accounts_by_user(user_id).await?;
+
+    // The provider may have been removed since, so the account is found among the user's own
+    let mut matching = accounts.iter().filter(|account| {
+        account.provider_id.as_deref() == Some(provider_id.as_str())
+            && params
+                .provider_account_id
+                .as_ref()
+                .is_none_or(|id| account.provider_account_id.as_ref() == Some(id))
+    });
+    let account = match (matching.next(), matching.next()) {
+        (Some(account), None) => account.clone(),
+        (Some(_), Some(_)) => {
+            return Err(CoreError::new()
+                .with_message("Several accounts are linked, choose one with provider_account_id")
+                .with_status(StatusCode::BAD_REQUEST.into()));
+        }
+        (None, _) => {
+            return Err(CoreError::new()
+                .with_message("Account not found")
+                .with_status(StatusCode::NOT_FOUND.into()));
+        }
+    };
+
+    // The user would have no way left to sign in
+    if accounts.len() <= 1 {
+        return Err(CoreError::new()
+            .with_message("Cannot unlink the last sign-in method")
+            .with_status(StatusCode::CONFLICT.into()));
+    }
+
+    // Its tokens are revoked at the provider too, unless turned off
+    actions::unlink_account(&auth, &account).await?;
+    accounts.retain(|a| a.id != account.id);
+    tracing::debug!("[accounts] Unlinked account: {:?}", account.id);
+
+    let response = CoreResponse::<AccountsResponse>::new().with_cookies(cookies);
+    match params.callback_url {
+        Some(callback_url) => {
+            let redirect_url = actions::redirect_url_to(&request, &auth, callback_url).await?;
+            Ok(response.with_redirect(redirect_url))
+        }
+        None => Ok(response.with_payload(AccountsResponse {
+            accounts: accounts.iter().map(SessionAccount::from).collect(),
+        })),
+    }
+}
+
+/// The ID of the signed-in user, and the cookies that refresh their session
+async fn session_user_id<T: RequestPayload>(
+    request: &CoreRequest<T>,
+    auth: &Arc<Auth>,
+) -> Result<(String, Cookies), CoreError> {
+    let (session, cookies) = actions::get_session(request, auth).await?;
+    let user_id = session
+        .and_then(|session| session.user)
+        .and_then(|user| user.id)
+        .ok_or_else(|| {
+            CoreError::new()
+                .with_message("Not signed in")
+                .with_status(StatusCode::UNAUTHORIZED.into())
+        })?;
+
+    Ok((user_id, cookies))
+}
@@ -1,15 +1,85 @@
-use oauth2::CsrfToken;
+use std::sync::Arc;
+
+use oauth2::{CsrfToken, PkceCodeChallenge, Scope};
 use serde::{Deserialize, Serialize};
 
-use crate::contracts::provide::ProviderType;
-use crate::tools::cookie::Cookies;
+use crate::auth::Auth;
+use crate::contracts::adapt::AdaptVerificationToken;
+use crate::contracts::provide::{ProviderOAuth2Check, ProviderType};
+use crate::providers::email::hash_verification_token;
+use crate::tools::cookie::{Cookie, Cookies, SameSite};
 use crate::tools::request::CoreRequest;
-use crate::tools::request_extractors::{COOKIE_CSRF_TOKEN, COOKIE_STATE};
+use crate::tools::request_extractors::{
+    COOKIE_CHECKS_MAX_AGE, COOKIE_LINK, COOKIE_NONCE, COOKIE_PKCE_VERIFIER, COOKIE_STATE,
+};
 use crate::tools::response::CoreResponse;
-use crate::tools::{CoreError, generators};
+use crate::tools::signing::SigningPurpose;
+use crate::tools::{CoreError, actions, generators, signing};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct AuthoriseRequest {}
+/// The parameters accepted by `/login/{provider}`, from the query or a url-encoded form body
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuthoriseRequest {
+    /// Extra scopes to request on top of the provider's scopes, separated by spaces
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    /// Whether the provider should prompt the user, e.g. `consent` or `select_account`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
+    /// A hint of the account to sign in with, usually an email address
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub login_hint: Option<String>,
+    /// Set to `offline` to ask for a refresh token (Google)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub access_type: Option<String>,
+    /// The address to send a sign-in link to (email providers)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    /// Set to `true` to link the account to the signed-in user rather than sign in (OAuth
+    /// providers)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub link: Option<String>,
+}
+
+impl AuthoriseRequest {
+    /// Reads the parameters from the request, with the form body taking precedence over the query.
+    pub fn from_request(request: &CoreRequest<AuthoriseRequest>) -> Self {
+        let mut params = request.query();
+        params.extend(request.form());
+
+        serde_json::to_value(params)
+            .and_then(serde_json::from_value)
+            .unwrap_or_default()
+    }
+
+    /// The extra scopes, in the order they were requested
+    pub fn scopes(&self) -> Vec<String> {
+        self.scope
+            .as_deref()
+            .unwrap_or_default()
+            .split([' ', ','])
+            .filter(|s| !s.is_empty())
+            .map(String::from)
+            .collect()
+    }
+
+    /// Whether the account is being linked to the signed-in user
+    pub fn is_link(&self) -> bool {
+        matches!(self.link.as_deref(), Some("true" | "1"))
+    }
+
+    /// The extra authorisation parameters to pass to the provider
+    pub fn extra_params(&self) -> Vec<(&'static str, String)> {
+        [
+            ("prompt", &self.prompt),
+            ("login_hint", &self.login_hint),
+            ("access_type", &self.access_type),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| value.clone().map(|value| (key, value)))
+        .collect()
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AuthoriseResponse {}
@@ -17,12 +87,26 @@ pub struct AuthoriseResponse {}
 pub async fn authorise(
     request: CoreRequest<AuthoriseRequest>,
 ) -> Result<CoreResponse<AuthoriseResponse>, CoreError> {
+    // Starting a sign-in changes state, so a form posted by another site is refused. GET is only
+    // routed in debug builds
+    if request.method().eq_ignore_ascii_case("POST") {
+        request.check_csrf_token(request.extract_auth()?.secret())?;
+    }
+
     // Extract the provider from the request
     let provider = request.extract_provider()?;
 
     // Handle the callback
     let provider_type = provider.provider_type();
 
+    // Only accounts at OAuth providers can be linked to a signed-in user
+    let is_oauth2 = matches!(provider_type, ProviderType::OAuth | ProviderType::OIDC);
+    if !is_oauth2 && AuthoriseRequest::from_request(&request).is_link() {
+        return Err(CoreError::new()
+            .with_message("Only OAuth accounts can be linked")
+            .with_status(http::StatusCode::BAD_REQUEST.into()));
+    }
+
     // Dispatch to the appropriate authorisation function based on the provider type
     match provider_type {
         ProviderType::OAuth => self::authorise_oauth2(request).await,
@@ -45,82 +129,175 @@ async fn authorise_oauth2(
         .as_oauth2()
         .ok_or_else(|| CoreError::new().with_message("Provider is not OAuth2"))?;
 
-    let client = generators::generate_client_from_auth(oauth2_provider)?;
-    // let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+    let client =
+        generators::generate_client_from_auth(oauth2_provider, &request.extract_auth_url()?)?;
+    let checks = oauth2_provider.checks();
+    let auth = request.extract_auth()?;
 
+    // The state doubles as the CSRF token sent to the provider
     let state = generators::generate_state();
-    let (authorisation_url, csrf_token) = client
-         // todo: manual csrf token
-         .authorize_url(CsrfToken::new_random)
-         // todo: add scopes
-        // .add_scopes(&oauth2_provider.scopes())
-        // .set_pkce_challenge(pkce_challenge)
-         .url();
+    let mut authorisation_request = client.authorize_url(|| CsrfToken::new(state.clone()));
+
+    // Request the provider's scopes, then any extras asked for by the client
+    let params = AuthoriseRequest::from_request(&request);
+    let mut scopes = oauth2_provider.scopes();
+    for scope in params.scopes() {
+        if !scopes.contains(&scope) {
+            scopes.push(scope);
+        }
+    }
+    authorisation_request = authorisation_request.add_scopes(scopes.into_iter().map(Scope::new));
+    for (key, value) in params.extra_params() {
+        authorisation_request = authorisation_request.add_extra_param(key, value);
+    }
+
+    // Create a PKCE challenge if the provider requires it, keeping the verifier for the callback
+    let pkce_verifier = if checks.contains(&ProviderOAuth2Check::PKCE) {
+        let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+        authorisation_request = authorisation_request.set_pkce_challenge(pkce_challenge);
+        Some(pkce_verifier)
+    } else {
+        None
+    };
+
+    // Bind the id_token to this sign-in with a nonce, if the provider requires one
+    let nonce = if checks.contains(&ProviderOAuth2Check::Nonce) {
+        let nonce = generators::generate_nonce();
+        authorisation_request = authorisation_request.add_extra_param("nonce", nonce.clone());
+        Some(nonce)
+    } else {
+        None
+    };
+
+    // Linking requires a signed-in user, who is remembered until the callback
+    let link_user_id = match params.is_link() {
+        true => Some(linking_user_id(&request, &auth).await?),
+        false => None,
+    };
+
+    let (authorisation_url, _) = authorisation_request.url();
 
     {
-        // Set the cookies in the response
+        // Set the cookies in the response. They must survive the cross-site redirect back from the
+        // provider, so they are Lax rather than Strict
+        let one_time_cookie = |name: &str, value: String| {
+            Cookie::new(name.to_string())
+                .with_value(value)
+                .with_path("/".to_string())
+                .with_http_only(true)
+                .with_same_site(SameSite::Lax)
+                .with_max_age(COOKIE_CHECKS_MAX_AGE)
+        };
+
         let mut cookies = Cookies::new();
-        cookies.set(COOKIE_STATE, state.clone());
-        cookies.set(COOKIE_CSRF_TOKEN, csrf_token.secret().to_string());
-        // TODO: Set the PKCE verifier cookie if needed
+        if checks.contains(&ProviderOAuth2Check::State) {
+            cookies.insert(one_time_cookie(
+                COOKIE_STATE,
+                signing::sign(auth.secret(), SigningPurpose::State, &state),
+            ));
+        }
+        if let Some(pkce_verifier) = pkce_verifier {
+            cookies.insert(one_time_cookie(
+                COOKIE_PKCE_VERIFIER,
+                pkce_verifier.secret().to_string(),
+            ));
+        }
+        if let Some(nonce) = nonce {
+            cookies.insert(one_time_cookie(COOKIE_NONCE, nonce));
+        }
+        // A link abandoned earlier must not turn this sign-in into a link
+        match link_user_id {
+            Some(user_id) => cookies.insert(one_time_cookie(
+                COOKIE_LINK,
+                signing::sign(auth.secret(), SigningPurpose::Link, &user_id),
+            )),
+            None => cookies.expire(COOKIE_LINK),
+        }
         response = response.with_cookies(cookies);
     }
 
     tracing::debug!("[authorise] Authorisation URL: {}", authorisation_url);
-    tracing::debug!(
-        "[authorise] State: {:?}",
-        response.cookies().get("state").map(|c| c.value)
-    );
-    tracing::debug!(
-        "[authorise] CSRF Token: {:?}",
-        response.cookies().get("csrf_token").map(|c| c.value)
-    );
+    tracing::debug!("[authorise] State: {}", state);
 
     // Redirect to the authorization URL
     Ok(response.with_redirect(authorisation_url.to_string()))
 }
 
-// ignore
+/// The ID of the signed-in user linking an account. Accounts are linked through the adaptor, so one
+/// is required.
+async fn linking_user_id(
+    request: &CoreRequest<AuthoriseRequest>,
+    auth: &Arc<Auth>,
+) -> Result<String, CoreError> {
+    request.extract_adaptor()?;
+
+    let (session, _) = actions::get_session(request, auth).await?;
+    session
+        .and_then(|session| session.user)
+        .and_then(|user| user.id)
+        .ok_or_else(|| {
+            CoreError::new()
+                .with_message("Sign in to link an account")
+                .with_status(http::StatusCode::UNAUTHORIZED.into())
+        })
+}
+
 async fn authorise_email(
     request: CoreRequest<AuthoriseRequest>,
 ) -> Result<CoreResponse<AuthoriseResponse>, CoreError> {
-    // Handle email provider authorisation
     let provider = request.extract_provider()?;
-    let provider_type = provider.provider_type();
+    let email_provider = provider
+        .as_email()
+        .ok_or_else(|| CoreError::new().with_message("Provider is not email"))?;
+    let adaptor = request.extract_adaptor()?;
+    let auth = request.extract_auth()?;
+
+    let params = AuthoriseRequest::from_request(&request);
+    let email = email_provider.normalize_email(params.email.as_deref().unwrap_or_default())?;
+
+    // Only a hash of the token is stored, the link holds the token itself
+    let token = generators::generate_verification_token();
+    adaptor
+        .create_verification_token(AdaptVerificationToken {
+            email: email.clone(),
+            token: hash_verification_token(&token, auth.secret()),
+            expires_in: email_provider.max_age(),
+        })
+        .await?;
 
-    // So far unsupported
-    CoreError::new()
-        .with_message(format!("Unsupported provider type: {}", provider_type))
-        .with_status(http::StatusCode::BAD_REQUEST.into())
-        .into()
+    let url = email_provider.sign_in_url(&request.extract_auth_url()?, &token, &email);
+    email_provider.send_sign_in_link(&email, &url).await?;
+    tracing::debug!("[authorise] Sent a sign-in link to: {}", email);
+
+    match email_provider.verify_request_url() {
+        Some(url) => {
+            let url = actions::redirect_url_to(&request, &auth, url.to_string()).await?;
+            Ok(CoreResponse::redirect(url))
+        }
+        None => Ok(CoreResponse::ok()),
+    }
 }
 
-// ignore
 async fn authorise_credentials(
     request: CoreRequest<AuthoriseRequest>,
 ) -> Result<CoreResponse<AuthoriseResponse>, CoreError> {
-    // Handle credentials provider authorisation
+    // The credentials are checked by the callback route. A temporary redirect keeps the method and
+    // the form, so a form posted here is posted there as is
     let provider = request.extract_provider()?;
-    let provider_type = provider.provider_type();
+    let callback_url = format!("{}/callback/{}", request.extract_auth_url()?, provider.id());
 
-    // So far unsupported
-    CoreError::new()
-        .with_message(format!("Unsupported provider type: {}", provider_type))
-        .with_status(http::StatusCode::BAD_REQUEST.into())
-        .into()
+    Ok(CoreResponse::redirect(callback_url).with_status(http::StatusCode::TEMPORARY_REDIRECT))
 }
 
-// ignore
 async fn authorise_oidc(
     request: CoreRequest<AuthoriseRequest>,
 ) -> Result<CoreResponse<AuthoriseResponse>, CoreError> {
-    // Handle OIDC provider authorisation
+    // Discover the issuer's endpoints, then continue as an OAuth2 flow
     let provider = request.extract_provider()?;
-    let provider_type = provider.provider_type();
+    let oidc_provider = provider
+        .as_oidc()
+        .ok_or_else(|| CoreError::new().with_message("Provider is not OpenID Connect"))?;
+    oidc_provider.discover().await?;
 
-    // So far unsupported
-    CoreError::new()
-        .with_message(format!("Unsupported provider type: {}", provider_type))
-        .with_status(http::StatusCode::BAD_REQUEST.into())
-        .into()
+    authorise_oauth2(request).await
 }
@@ -1,13 +1,26 @@
+use std::sync::Arc;
+
 use http::StatusCode;
-use oauth2::{AuthorizationCode, StandardTokenResponse, TokenResponse};
+use oauth2::{AuthorizationCode, PkceCodeVerifier, StandardTokenResponse, TokenResponse};
 use serde::{Deserialize, Serialize};
 
-use crate::auth::{SignInOptions, SignInResult};
-use crate::contracts::adapt::{AdaptAccount, AdaptUser, ProviderAccountId};
+use crate::auth::{AccountLinkingOptions, Auth, SignInOptions, SignInResult};
+use crate::contracts::adapt::{
+    Adapt, AdaptAccount, AdaptUser, ProviderAccountId, UseVerificationTokenOptions,
+};
 use crate::contracts::profile::Profile;
-use crate::contracts::provide::ProviderType;
+use crate::contracts::provide::{ProvideOidc, ProviderOAuth2Check, ProviderType};
 use crate::contracts::token::Token;
+use crate::providers::credentials::Credentials;
+use crate::providers::email::hash_verification_token;
+use crate::providers::error::ProviderError;
+use crate::tools::cookie::Cookies;
+use crate::tools::generators::Oauth2TokenResponse;
+use crate::tools::jose::{self, ClaimsValidation, IdTokenClaims};
 use crate::tools::request::CoreRequest;
+use crate::tools::request_extractors::{
+    COOKIE_LINK, COOKIE_NONCE, COOKIE_PKCE_VERIFIER, COOKIE_STATE,
+};
 use crate::tools::response::CoreResponse;
 use crate::tools::{CoreError, actions, generators};
 
@@ -31,6 +44,51 @@ pub struct CallbackRequest {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CallbackResponse {}
 
+/// Errors raised while validating the checks of an OAuth2 callback
+#[derive(Debug, Clone, PartialEq)]
+pub enum CallbackError {
+    /// The signed state cookie is missing or its signature is invalid
+    MissingState,
+    /// The state returned by the provider does not match the state cookie
+    StateMismatch,
+    /// The PKCE verifier cookie is missing
+    MissingPkceVerifier,
+    /// The nonce cookie is missing
+    MissingNonce,
+    /// The id_token is missing or its claims are invalid
+    InvalidIdToken(String),
+}
+
+impl std::fmt::Display for CallbackError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CallbackError::MissingState => write!(f, "Missing or invalid state cookie"),
+            CallbackError::StateMismatch => write!(f, "State does not match the state cookie"),
+            CallbackError::MissingPkceVerifier => write!(f, "Missing PKCE verifier"),
+            CallbackError::MissingNonce => write!(f, "Missing nonce cookie"),
+            CallbackError::InvalidIdToken(msg) => write!(f, "Invalid id_token: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for CallbackError {}
+
+impl From<CallbackError> for CoreError {
+    fn from(error: CallbackError) -> Self {
+        let status = match error {
+            CallbackError::MissingState
+            | CallbackError::MissingPkceVerifier
+            | CallbackError::MissingNonce => StatusCode::BAD_REQUEST,
+            CallbackError::StateMismatch => StatusCode::FORBIDDEN,
+            CallbackError::InvalidIdToken(_) => StatusCode::UNAUTHORIZED,
+        };
+
+        CoreError::new()
+            .with_message(error.to_string())
+            .with_status(status.into())
+    }
+}
+
 // Handle the callback
 pub async fn callback(
     request: CoreRequest<CallbackRequest>,
@@ -66,15 +124,39 @@ async fn callback_oauth2(
         .as_oauth2()
         .ok_or_else(|| CoreError::new().with_message("Provider is not OAuth2"))?;
 
+    // The state, verifier and nonce are single use, so always clear them from the client
+    let mut cleared_cookies = Cookies::new();
+    cleared_cookies.expire(COOKIE_STATE);
+    cleared_cookies.expire(COOKIE_PKCE_VERIFIER);
+    cleared_cookies.expire(COOKIE_NONCE);
+    cleared_cookies.expire(COOKIE_LINK);
+
+    // Validate the checks made during authorisation, rejecting the callback on failure
+    let CallbackChecks {
+        pkce_verifier,
+        nonce,
+    } = match validate_checks(&request, &oauth2_provider.checks()) {
+        Ok(checks) => checks,
+        Err(error) => {
+            tracing::debug!("[callback] Rejected callback: {}", error);
+            return Ok(CoreResponse::from_error(error.into()).with_cookies(cleared_cookies));
+        }
+    };
+
     // Extract the authorization code from the request
     let code = AuthorizationCode::new(request.extract_code()?);
     tracing::debug!("[callback] Code: {}", code.secret());
 
     // Exchange the authorization code for an access token
-    let client = generators::generate_client_from_auth(oauth2_provider)?;
-    let token_response = client
-        .exchange_code(code)
-        .request_async(&generators::generate_http_client()?)
+    let client =
+        generators::generate_client_from_auth(oauth2_provider, &request.extract_auth_url()?)?;
+    let mut token_request = client.exchange_code(code);
+    if let Some(pkce_verifier) = pkce_verifier {
+        token_request = token_request.set_pkce_verifier(pkce_verifier);
+    }
+    let http_client = generators::TokenHttpClient(generators::generate_http_client()?);
+    let token_response = token_request
+        .request_async(&http_client)
         .await
         .map_err(|e| {
             CoreError::new()
@@ -84,34 +166,67 @@ async fn callback_oauth2(
 
     tracing::debug!("[callback] Token: {:?}", token_response.access_token());
 
-    // Using the token response, we can now fetch the user's profile information
+    // For OpenID Connect, the id_token must be valid before anything else is trusted. OAuth2
+    // providers that are also issuers (e.g. Google) only have one if `openid` was requested
+    let id_token_expected = oauth2_provider.provider_type() == ProviderType::OIDC
+        || token_response.extra_fields().id_token.is_some();
+    let id_token_claims = match oauth2_provider.as_oidc() {
+        Some(oidc_provider) if id_token_expected => {
+            match verify_id_token(oidc_provider, &token_response, nonce.as_deref()).await {
+                Ok(claims) => Some((claims, oidc_provider.fetch_userinfo())),
+                Err(error) => {
+                    tracing::debug!("[callback] Rejected id_token: {}", error);
+                    return Ok(CoreResponse::from_error(error.into()).with_cookies(cleared_cookies));
+                }
+            }
+        }
+        _ => None,
+    };
+
+    // Using the token response, we can now fetch the user's profile information. For OpenID
+    // Connect, the verified id_token already holds it, saving the userinfo round-trip
     let profile_endpoint = oauth2_provider.profile_endpoint();
-    let profile_client = generators::generate_http_client()?;
-    let profile_response = profile_client
-        .get(profile_endpoint.url())
-        .bearer_auth(token_response.access_token().secret())
-        .send()
-        .await
-        .map_err(|e| {
-            CoreError::new()
-                .with_message(format!("Failed to fetch user info: (error={})", e))
-                .with_status(StatusCode::BAD_REQUEST.into())
-        })?
-        .json::<Profile>()// TODO: Change to a proper user info type
-        .await
-        .map_err(|e| {
-            CoreError::new()
-                .with_message(format!("Failed to parse user info: (error={})", e))
-                .with_status(StatusCode::BAD_REQUEST.into())
-        })?;
+    let profile_response = match id_token_claims {
+        Some((claims, fetch_userinfo)) if !fetch_userinfo || profile_endpoint.url().is_empty() => {
+            Profile::from(claims)
+        }
+        claims => {
+            let profile = oauth2_provider
+                .fetch_profile(token_response.access_token().secret())
+                .await?;
+
+            // The userinfo must describe the same user as the id_token
+            if let Some((claims, _)) = claims
+                && profile.sub.as_ref() != Some(&claims.sub)
+            {
+                let error = CallbackError::InvalidIdToken("Userinfo subject mismatch".to_string());
+                return Ok(CoreResponse::from_error(error.into()).with_cookies(cleared_cookies));
+            }
+
+            profile
+        }
+    };
 
     tracing::debug!("[callback] User Info: {:?}", profile_response);
 
-    // Here, the auth provider has given us a user profile
+    // Here, the auth provider has given us a user profile. The ID it resolves to is the user's ID
+    // at the provider, which is kept as the account's ID, while the user is given an ID of our own
+    let mut profile_user = oauth2_provider.get_profile(profile_response.clone());
+    let provider_account_id = profile_user
+        .id
+        .take()
+        .or_else(|| profile_response.sub.clone())
+        .or_else(|| profile_response.id.clone())
+        .filter(|id| !id.is_empty())
+        .ok_or_else(|| ProviderError::ProfileFailed("Profile has no user ID".to_string()))?;
+    // The provider's resolver knows where its profile says whether the email is verified (e.g.
+    // Discord's `verified`), so the standard claim only fills in when it does not
     let profile_user = {
-        let mut profile_user = oauth2_provider.get_profile(profile_response.clone());
         profile_user.id = Some(uuid::Uuid::new_v4().to_string());
         profile_user.email = profile_response.email.clone();
+        profile_user.email_verified = profile_user
+            .email_verified
+            .or(profile_response.email_verified);
         profile_user
     };
 
@@ -120,63 +235,354 @@ async fn callback_oauth2(
     let adapt_account_id = uuid::Uuid::new_v4().to_string();
     let adapt_provider_id = oauth2_provider.id().to_string();
     let adapt_provider_type = oauth2_provider.provider_type();
-    let adapt_account = AdaptAccount {
+    let mut adapt_account = AdaptAccount {
         id: Some(adapt_account_id.clone()),
         user_id: profile_user.id.clone(),
         provider_id: Some(adapt_provider_id.clone()),
         provider_type: adapt_provider_type,
-        provider_account_id: Some(adapt_account_id.clone()),
+        provider_account_id: Some(provider_account_id.clone()),
         token: Some(adapt_token),
     };
     tracing::debug!("[callback] Adapted Account: {:?}", adapt_account);
 
-    // Now we need to check if the user already exists in the database
-    let adaptor = request.extract_adaptor()?;
-    let adapt_user = adaptor
-        .get_user_by_account(ProviderAccountId {
-            provider_id: adapt_provider_id.clone(),
-            provider_account_id: adapt_account_id.clone(),
-        })
-        .await;
+    // A signed-in user started the flow to link the account to themselves, rather than sign in
+    let auth = request.extract_auth()?;
+    if let Some(user_id) = request.extract_link_cookie(auth.secret()) {
+        let response = link_account(&request, &auth, user_id, adapt_account)
+            .await
+            .unwrap_or_else(CoreResponse::from_error);
+        return Ok(response.with_cookies(cleared_cookies));
+    }
+
+    // Now we need to check if the user already exists in the database. Without an adaptor, users
+    // are not stored and the session alone (a JWT) holds who they are
+    let adaptor = request.extract_adaptor().ok();
+    let adapt_user = match adaptor {
+        Some(adaptor) => {
+            adaptor
+                .get_user_by_account(ProviderAccountId {
+                    provider_id: adapt_provider_id.clone(),
+                    provider_account_id: provider_account_id.clone(),
+                })
+                .await?
+        }
+        None => None,
+    };
+    if let Some(adapt_user) = &adapt_user {
+        adapt_account.user_id = adapt_user.id.clone();
+    }
     tracing::debug!("[callback] Adapted User: {:?}", adapt_user);
 
+    // A user with the same email may have signed up with another provider. The account is only
+    // linked to them if the linking policy allows it, otherwise registering fails
+    let linked_user = match (adaptor, &adapt_user, &profile_user.email) {
+        (Some(adaptor), None, Some(email)) => {
+            match adaptor.get_user_by_email(email.clone()).await? {
+                Some(existing_user) => {
+                    let options = AccountLinkingOptions {
+                        user: existing_user.clone(),
+                        account: AdaptAccount {
+                            user_id: existing_user.id.clone(),
+                            ..adapt_account.clone()
+                        },
+                        profile: Profile {
+                            email_verified: profile_user.email_verified,
+                            ..profile_response.clone()
+                        },
+                    };
+                    let allowed = auth.options.account_linking.allows(options).await;
+                    tracing::debug!(
+                        "[callback] Linking to existing user {:?}: {}",
+                        existing_user.id,
+                        allowed
+                    );
+                    allowed.then_some(existing_user)
+                }
+                None => None,
+            }
+        }
+        _ => None,
+    };
+    if let Some(linked_user) = &linked_user {
+        adapt_account.user_id = linked_user.id.clone();
+    }
+
     // Perform the user defined check to see if the user is allowed to sign in
-    let auth = request.extract_auth()?;
     if let Some(sign_in_check_response) = sign_in_check(
-        &adapt_user.clone().or(Some(*profile_user.clone())),
+        &adapt_user
+            .clone()
+            .or(linked_user.clone())
+            .or(Some(*profile_user.clone())),
         &adapt_account,
         &profile_response,
         auth.clone(),
     )
     .await
     {
-        return sign_in_check_response;
+        return sign_in_check_response.map(|response| response.with_cookies(cleared_cookies));
     }
 
     // If the user is already authorised, redirect them to the home page
-    if let Some(adapt_user) = adapt_user {
-        tracing::debug!("[callback] User already exists: {:?}", adapt_user);
-        actions::sign_in(
-            request.clone(),
-            Some(adapt_user),
-            Some(adapt_account),
-            &provider,
-            adaptor,
-            auth,
-        )
-        .await
+    let response = match (adaptor, adapt_user, linked_user) {
+        (_, Some(adapt_user), _) => {
+            tracing::debug!("[callback] User already exists: {:?}", adapt_user);
+            if let Some(adaptor) = adaptor {
+                update_account_token(adaptor, &adapt_account).await?;
+            }
+            actions::sign_in(
+                request.clone(),
+                Some(adapt_user),
+                Some(adapt_account),
+                &provider,
+                adaptor,
+                auth,
+            )
+            .await
+        }
+        (Some(adaptor), None, Some(linked_user)) => {
+            tracing::debug!("[callback] Linking account to user: {:?}", linked_user);
+            adaptor.link_account(adapt_account.clone()).await?;
+            // Only once linked, so the user keeps a way to sign in if linking fails
+            if profile_user.email_verified == Some(true) {
+                unlink_legacy_account(adaptor, &adapt_account).await;
+            }
+            actions::sign_in(
+                request.clone(),
+                Some(linked_user),
+                Some(adapt_account),
+                &provider,
+                Some(adaptor),
+                auth,
+            )
+            .await
+        }
+        (Some(adaptor), None, None) => {
+            tracing::debug!("[callback] Registering new user: {:?}", profile_user);
+            actions::register(
+                request.clone(),
+                Some(*profile_user),
+                Some(adapt_account),
+                &provider,
+                adaptor,
+                auth,
+            )
+            .await
+        }
+        (None, None, _) => {
+            tracing::debug!(
+                "[callback] Signing in without an adaptor: {:?}",
+                profile_user
+            );
+            actions::sign_in(
+                request.clone(),
+                Some(*profile_user),
+                Some(adapt_account),
+                &provider,
+                None,
+                auth,
+            )
+            .await
+        }
+    };
+
+    response.map(|response| response.with_cookies(cleared_cookies))
+}
+
+/// Links the account to the user who started the flow with `?link=true`, who must still be signed
+/// in. The account must not belong to another user already.
+async fn link_account(
+    request: &CoreRequest<CallbackRequest>,
+    auth: &Arc<Auth>,
+    user_id: String,
+    mut account: AdaptAccount,
+) -> Result<CoreResponse<CallbackResponse>, CoreError> {
+    let adaptor = request.extract_adaptor()?;
+
+    let (session, cookies) = actions::get_session(request, auth).await?;
+    let session_user_id = session.and_then(|s| s.user).and_then(|u| u.id);
+    if session_user_id.as_deref() != Some(user_id.as_str()) {
+        return Err(CoreError::new()
+            .with_message("Sign in to link an account")
+            .with_status(StatusCode::UNAUTHORIZED.into()));
+    }
+
+    let owner = adaptor
+        .get_user_by_account(ProviderAccountId {
+            provider_id: account.provider_id.clone().unwrap_or_default(),
+            provider_account_id: account.provider_account_id.clone().unwrap_or_default(),
+        })
+        .await?;
+    match owner {
+        Some(owner) if owner.id.as_deref() == Some(user_id.as_str()) => {
+            tracing::debug!("[callback] Account is already linked to user: {}", user_id);
+            update_account_token(adaptor, &account).await?;
+        }
+        Some(_) => {
+            return Err(CoreError::new()
+                .with_message("Account is already linked to another user")
+                .with_status(StatusCode::CONFLICT.into()));
+        }
+        None => {
+            tracing::debug!("[callback] Linking account to signed-in user: {}", user_id);
+            account.user_id = Some(user_id);
+            adaptor.link_account(account).await?;
+        }
+    }
+
+    let redirect_url = actions::redirect_url(request, auth).await?;
+    Ok(CoreResponse::new()
+        .with_redirect(redirect_url)
+        .with_cookies(cookies))
+}
+
+/// Stores the tokens just issued for an account that is already linked. Those the provider did not
+/// issue again, e.g. the refresh token, are kept.
+async fn update_account_token(
+    adaptor: &dyn Adapt,
+    account: &AdaptAccount,
+) -> Result<(), CoreError> {
+    let previous = adaptor
+        .get_account(ProviderAccountId {
+            provider_id: account.provider_id.clone().unwrap_or_default(),
+            provider_account_id: account.provider_account_id.clone().unwrap_or_default(),
+        })
+        .await?
+        .and_then(|previous| previous.token)
+        .unwrap_or_default();
+
+    adaptor
+        .update_account(AdaptAccount {
+            token: account.token.clone().map(|token| token.replacing(previous)),
+            ..account.clone()
+        })
+        .await?;
+    Ok(())
+}
+
+/// Accounts used to be linked with a random provider account ID, equal to their own ID, so returning
+/// users were never found by their account and had to be linked again by email. Once the new
+/// account is linked, the user's legacy account at the same provider is unlinked. Failures are
+/// logged, as the user can sign in either way.
+async fn unlink_legacy_account(adaptor: &dyn Adapt, account: &AdaptAccount) {
+    let user_id = account.user_id.clone().unwrap_or_default();
+    let legacy_account = match adaptor.get_accounts_by_user(user_id).await {
+        Ok(accounts) => accounts.into_iter().find(|legacy| {
+            legacy.provider_id == account.provider_id
+                && legacy.id.is_some()
+                && legacy.id != account.id
+                && legacy.provider_account_id == legacy.id
+        }),
+        Err(error) => {
+            tracing::warn!("[callback] Failed to find legacy accounts: {}", error);
+            return;
+        }
+    };
+    let Some(legacy_account) = legacy_account else {
+        return;
+    };
+
+    tracing::debug!(
+        "[callback] Unlinking legacy account {:?} of user {:?}",
+        legacy_account.id,
+        account.user_id
+    );
+    let unlinked = adaptor
+        .unlink_account(ProviderAccountId {
+            provider_id: legacy_account.provider_id.unwrap_or_default(),
+            provider_account_id: legacy_account.provider_account_id.unwrap_or_default(),
+        })
+        .await;
+    if let Err(error) = unlinked {
+        tracing::warn!("[callback] Failed to unlink legacy account: {}", error);
+    }
+}
+
+/// The values recovered from the one-time cookies set during authorisation
+struct CallbackChecks {
+    pkce_verifier: Option<PkceCodeVerifier>,
+    nonce: Option<String>,
+}
+
+/// Validates the state against the signed state cookie and recovers the PKCE verifier and nonce, as
+/// required by the provider's checks.
+fn validate_checks(
+    request: &CoreRequest<CallbackRequest>,
+    checks: &[ProviderOAuth2Check],
+) -> Result<CallbackChecks, CallbackError> {
+    if checks.contains(&ProviderOAuth2Check::State) {
+        let auth = request
+            .extract_auth()
+            .map_err(|_| CallbackError::MissingState)?;
+        let expected_state = request
+            .extract_state_cookie(auth.secret())
+            .map_err(|_| CallbackError::MissingState)?;
+        let state = request
+            .extract_state()
+            .map_err(|_| CallbackError::StateMismatch)?;
+
+        if state != expected_state {
+            return Err(CallbackError::StateMismatch);
+        }
+    }
+
+    let pkce_verifier = if checks.contains(&ProviderOAuth2Check::PKCE) {
+        let pkce_verifier = request
+            .extract_pkce_verifier()
+            .map_err(|_| CallbackError::MissingPkceVerifier)?;
+        Some(PkceCodeVerifier::new(pkce_verifier))
     } else {
-        tracing::debug!("[callback] Registering new user: {:?}", profile_user);
-        actions::register(
-            request.clone(),
-            Some(*profile_user),
-            Some(adapt_account),
-            &provider,
-            adaptor,
-            auth,
+        None
+    };
+
+    let nonce = if checks.contains(&ProviderOAuth2Check::Nonce) {
+        Some(
+            request
+                .extract_nonce()
+                .map_err(|_| CallbackError::MissingNonce)?,
         )
+    } else {
+        None
+    };
+
+    Ok(CallbackChecks {
+        pkce_verifier,
+        nonce,
+    })
+}
+
+/// Verifies the signature and claims of the id_token returned alongside the access token.
+async fn verify_id_token(
+    oidc_provider: &dyn ProvideOidc,
+    token_response: &Oauth2TokenResponse,
+    nonce: Option<&str>,
+) -> Result<IdTokenClaims, CallbackError> {
+    let id_token = token_response
+        .extra_fields()
+        .id_token
+        .as_deref()
+        .ok_or_else(|| CallbackError::InvalidIdToken("Missing id_token".to_string()))?;
+
+    // The metadata is cached, so this only reads the keys endpoint
+    let metadata = oidc_provider
+        .discover()
         .await
-    }
+        .map_err(|e| CallbackError::InvalidIdToken(e.to_string()))?;
+
+    let validation = ClaimsValidation {
+        issuers: oidc_provider.issuers(),
+        client_id: oidc_provider.client_id(),
+        nonce: nonce.map(String::from),
+        clock_skew: oidc_provider.clock_skew(),
+    };
+
+    jose::verify_id_token(
+        id_token,
+        &oidc_provider.jwks(),
+        &metadata.jwks_uri,
+        &validation,
+    )
+    .await
+    .map_err(|e| CallbackError::InvalidIdToken(e.to_string()))
 }
 
 async fn sign_in_check(
@@ -223,44 +629,244 @@ async fn sign_in_check(
     }
 }
 
-// ignore
 async fn callback_email(
     request: CoreRequest<CallbackRequest>,
 ) -> Result<CoreResponse<CallbackResponse>, CoreError> {
-    // Handle email provider callback
     let provider = request.extract_provider()?;
-    let provider_type = provider.provider_type();
+    let email_provider = provider
+        .as_email()
+        .ok_or_else(|| CoreError::new().with_message("Provider is not email"))?;
+    let adaptor = request.extract_adaptor()?;
+    let auth = request.extract_auth()?;
 
-    // So far unsupported
-    Err(CoreError::new()
-        .with_message(format!("Unsupported provider type: {}", provider_type))
-        .with_status(StatusCode::BAD_REQUEST.into()))
+    let query = request.query();
+    let (Some(token), Some(email)) = (query.get("token"), query.get("email")) else {
+        return Err(CoreError::new()
+            .with_message("Missing token or email")
+            .with_status(StatusCode::BAD_REQUEST.into()));
+    };
+    let email = email_provider.normalize_email(email)?;
+
+    // The token is deleted as it is used, so a link only signs in once
+    let verification_token = adaptor
+        .use_verification_token(UseVerificationTokenOptions {
+            email: email.clone(),
+            token: hash_verification_token(token, auth.secret()),
+        })
+        .await?
+        .filter(|token| token.expires_in > 0);
+    if verification_token.is_none() {
+        tracing::debug!("[callback] Rejected sign-in link for: {}", email);
+        return Err(CoreError::new()
+            .with_message("Invalid or expired sign-in link")
+            .with_status(StatusCode::UNAUTHORIZED.into()));
+    }
+
+    // Following the link proves the address belongs to the user
+    let profile = Profile {
+        email: Some(email.clone()),
+        email_verified: Some(true),
+        ..Default::default()
+    };
+    let existing_user = adaptor.get_user_by_email(email.clone()).await?;
+    let user = existing_user.clone().unwrap_or_else(|| AdaptUser {
+        id: Some(uuid::Uuid::new_v4().to_string()),
+        email: Some(email.clone()),
+        email_verified: Some(true),
+        ..Default::default()
+    });
+    let account = AdaptAccount {
+        id: None,
+        user_id: user.id.clone(),
+        provider_id: Some(provider.id()),
+        provider_type: ProviderType::Email,
+        provider_account_id: Some(email.clone()),
+        token: None,
+    };
+
+    // Perform the user defined check to see if the user is allowed to sign in
+    if let Some(sign_in_check_response) =
+        sign_in_check(&Some(user.clone()), &account, &profile, auth.clone()).await
+    {
+        return sign_in_check_response;
+    }
+
+    match existing_user {
+        Some(user) => {
+            // A user who signed up some other way can use the address from now on
+            let provider_account_id = ProviderAccountId {
+                provider_id: provider.id(),
+                provider_account_id: email,
+            };
+            if adaptor.get_account(provider_account_id).await?.is_none() {
+                adaptor.link_account(account.clone()).await?;
+            }
+            let user = match user.email_verified {
+                Some(true) => user,
+                _ => {
+                    adaptor
+                        .update_user(AdaptUser {
+                            email_verified: Some(true),
+                            ..user
+                        })
+                        .await?
+                }
+            };
+
+            tracing::debug!("[callback] Signing in by email: {:?}", user);
+            actions::sign_in(
+                request.clone(),
+                Some(user),
+                Some(account),
+                &provider,
+                Some(adaptor),
+                auth,
+            )
+            .await
+        }
+        None => {
+            tracing::debug!("[callback] Registering new user by email: {:?}", user);
+            actions::register(
+                request.clone(),
+                Some(user),
+                Some(account),
+                &provider,
+                adaptor,
+                auth,
+            )
+            .await
+        }
+    }
 }
 
-// ignore
 async fn callback_credentials(
     request: CoreRequest<CallbackRequest>,
 ) -> Result<CoreResponse<CallbackResponse>, CoreError> {
-    // Handle credentials provider callback
     let provider = request.extract_provider()?;
-    let provider_type = provider.provider_type();
+    let credentials_provider = provider
+        .as_credentials()
+        .ok_or_else(|| CoreError::new().with_message("Provider is not credentials"))?;
+    let auth = request.extract_auth()?;
 
-    // So far unsupported
-    Err(CoreError::new()
-        .with_message(format!("Unsupported provider type: {}", provider_type))
-        .with_status(StatusCode::BAD_REQUEST.into()))
+    // Credentials are only accepted from a form of the application
+    if !request.method().eq_ignore_ascii_case("POST") {
+        return Err(CoreError::new()
+            .with_message("Credentials must be posted")
+            .with_status(StatusCode::METHOD_NOT_ALLOWED.into()));
+    }
+    request.check_csrf_token(auth.secret())?;
+
+    let started = tokio::time::Instant::now();
+    let adaptor = request.extract_adaptor().ok();
+    let credentials = Credentials::from_form(request.form());
+    let username = credentials.username.clone();
+
+    let Some((user, account)) = credentials_provider.authorize(adaptor, credentials).await? else {
+        // Every failure takes as long, whether the user exists or not
+        tokio::time::sleep_until(started + credentials_provider.failure_delay()).await;
+        tracing::debug!("[callback] Rejected credentials for: {}", username);
+        return Err(CoreError::new()
+            .with_message("Invalid credentials")
+            .with_status(StatusCode::UNAUTHORIZED.into()));
+    };
+
+    // Perform the user defined check to see if the user is allowed to sign in
+    let profile = Profile {
+        id: user.id.clone(),
+        preferred_username: Some(username),
+        email: user.email.clone(),
+        ..Default::default()
+    };
+    if let Some(sign_in_check_response) =
+        sign_in_check(&Some(user.clone()), &account, &profile, auth.clone()).await
+    {
+        return sign_in_check_response;
+    }
+
+    // Users checked by the callback are stored on their first sign in, like those of other
+    // providers. Without an adaptor, the session alone (a JWT) holds who they are
+    let user = match adaptor {
+        Some(adaptor) if !credentials_provider.uses_password_store() => {
+            store_authorized_user(adaptor, user, &account).await?
+        }
+        _ => user,
+    };
+    let account = AdaptAccount {
+        user_id: user.id.clone(),
+        ..account
+    };
+
+    tracing::debug!("[callback] Signing in with credentials: {:?}", user);
+    actions::sign_in(
+        request.clone(),
+        Some(user),
+        Some(account),
+        &provider,
+        adaptor,
+        auth,
+    )
+    .await
+}
+
+/// Finds the stored user checked by the `authorize` callback, by their account, ID or email,
+/// storing them if there is none. Their account, named after their username, is linked on the way,
+/// so that they are found again even when the callback gives them no ID.
+async fn store_authorized_user(
+    adaptor: &dyn Adapt,
+    user: AdaptUser,
+    account: &AdaptAccount,
+) -> Result<AdaptUser, CoreError> {
+    let username = account
+        .provider_account_id
+        .clone()
+        .filter(|username| !username.is_empty());
+    if let Some(username) = username.clone()
+        && let Some(stored_user) = adaptor
+            .get_user_by_account(ProviderAccountId {
+                provider_id: account.provider_id.clone().unwrap_or_default(),
+                provider_account_id: username,
+            })
+            .await?
+    {
+        return Ok(stored_user);
+    }
+
+    let mut stored_user = match user.id.clone() {
+        Some(id) => adaptor.get_user(id).await?,
+        None => None,
+    };
+    if stored_user.is_none()
+        && let Some(email) = user.email.clone()
+    {
+        stored_user = adaptor.get_user_by_email(email).await?;
+    }
+    let stored_user = match stored_user {
+        Some(stored_user) => stored_user,
+        None => adaptor.create_user(user).await?,
+    };
+
+    if username.is_some() {
+        // The account holds no token, but adaptors may expect one
+        adaptor
+            .link_account(AdaptAccount {
+                user_id: stored_user.id.clone(),
+                token: Some(Token::default()),
+                ..account.clone()
+            })
+            .await?;
+    }
+    Ok(stored_user)
 }
 
-// ignore
 async fn callback_oidc(
     request: CoreRequest<CallbackRequest>,
 ) -> Result<CoreResponse<CallbackResponse>, CoreError> {
-    // Handle OIDC provider callback
+    // Discover the issuer's endpoints (usually cached), then continue as an OAuth2 flow
     let provider = request.extract_provider()?;
-    let provider_type = provider.provider_type();
+    let oidc_provider = provider
+        .as_oidc()
+        .ok_or_else(|| CoreError::new().with_message("Provider is not OpenID Connect"))?;
+    oidc_provider.discover().await?;
 
-    // So far unsupported
-    Err(CoreError::new()
-        .with_message(format!("Unsupported provider type: {}", provider_type))
-        .with_status(StatusCode::BAD_REQUEST.into()))
+    callback_oauth2(request).await
 }
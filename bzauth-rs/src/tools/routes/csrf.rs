@@ -1,7 +1,51 @@
-use crate::tools::CoreError;
+use http::header::CACHE_CONTROL;
+use serde::{Deserialize, Serialize};
+
+use crate::tools::cookie::{Cookie, Cookies, SameSite};
 use crate::tools::request::CoreRequest;
+use crate::tools::request_extractors::COOKIE_CSRF_TOKEN;
 use crate::tools::response::CoreResponse;
+use crate::tools::signing::SigningPurpose;
+use crate::tools::{CoreError, generators, signing};
+
+/// The token to submit with state-changing requests, as the `csrf_token` form field or the
+/// `X-CSRF-Token` header
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CsrfResponse {
+    pub csrf_token: String,
+}
+
+pub async fn csrf(request: CoreRequest<()>) -> Result<CoreResponse<CsrfResponse>, CoreError> {
+    let auth = request.extract_auth()?;
+
+    // Keep the token of a valid cookie, so that forms rendered earlier are still accepted
+    let mut cookies = Cookies::new();
+    let csrf_token = match request.extract_csrf_token(auth.secret()) {
+        Some(csrf_token) => csrf_token,
+        None => {
+            let csrf_token = generators::generate_csrf_token();
+            let secure = request.extract_origin()?.starts_with("https://");
+
+            // Only the signature ties the token to the cookie, so the secret never leaves the
+            // server. The cookie lasts as long as the browser session
+            cookies.insert(
+                Cookie::new(COOKIE_CSRF_TOKEN.to_string())
+                    .with_value(signing::sign(
+                        auth.secret(),
+                        SigningPurpose::Csrf,
+                        &csrf_token,
+                    ))
+                    .with_path("/".to_string())
+                    .with_secure(secure)
+                    .with_http_only(true)
+                    .with_same_site(SameSite::Lax),
+            );
+            csrf_token
+        }
+    };
 
-pub async fn csrf(_request: CoreRequest<()>) -> Result<CoreResponse, CoreError> {
-    todo!("Need to implement CSRF token generation and validation");
+    Ok(CoreResponse::<CsrfResponse>::new()
+        .with_header::<_, String>(CACHE_CONTROL, "no-store".to_string())
+        .with_cookies(cookies)
+        .with_payload(CsrfResponse { csrf_token }))
 }
@@ -0,0 +1,185 @@
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::auth::{Auth, SignOutOptions};
+use crate::contracts::account::Account;
+use crate::contracts::session::Session;
+use crate::contracts::token::Token;
+use crate::tools::request::CoreRequest;
+use crate::tools::response::CoreResponse;
+use crate::tools::{CoreError, actions};
+
+/// The parameters accepted by `/logout`, from the query or a url-encoded form body
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogoutRequest {
+    /// Where to send the user once signed out. Defaults to the origin of the application
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub callback_url: Option<String>,
+    /// The provider to sign out of as well, when the session does not record the provider it was
+    /// started with (database sessions)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+}
+
+impl LogoutRequest {
+    /// Reads the parameters from the request, with the form body taking precedence over the query.
+    pub fn from_request(request: &CoreRequest<LogoutRequest>) -> Self {
+        let mut params = request.query();
+        params.extend(request.form());
+
+        serde_json::to_value(params)
+            .and_then(serde_json::from_value)
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogoutResponse {}
+
+pub async fn logout(
+    request: CoreRequest<LogoutRequest>,
+) -> Result<CoreResponse<LogoutResponse>, CoreError> {
+    let auth = request.extract_auth()?;
+    let params = LogoutRequest::from_request(&request);
+
+    // Otherwise any site could sign the user out
+    request.check_csrf_token(auth.secret())?;
+
+    // End the session and clear its cookie, even if the session was no longer valid
+    let (session, cookies) = actions::delete_session(&request, &auth).await?;
+
+    // The provider the session was started with, which may have its own session and tokens
+    let provider_id = session
+        .as_ref()
+        .and_then(|s| s.account.as_ref())
+        .and_then(|a| a.provider_id.clone())
+        .or(params.provider.clone());
+    if let Some(provider_id) = provider_id
+        .as_ref()
+        .filter(|_| auth.token_revocation_options().on_sign_out())
+    {
+        revoke_session_tokens(&auth, session.as_ref(), provider_id).await;
+    }
+
+    if let Some(sign_out) = auth
+        .options
+        .callbacks
+        .as_ref()
+        .and_then(|c| c.sign_out.as_ref())
+    {
+        tracing::debug!("[logout] Running user defined sign out callback");
+        sign_out(SignOutOptions {
+            session: session.clone(),
+        })
+        .await;
+    }
+
+    let redirect_url = actions::redirect_url_to(
+        &request,
+        &auth,
+        params
+            .callback_url
+            .clone()
+            .unwrap_or_else(|| "/".to_string()),
+    )
+    .await?;
+
+    // Go through the issuer when it should end its session too, which redirects back afterwards
+    let redirect_url = match provider_id {
+        Some(provider_id) => end_session_url(&auth, &provider_id, &redirect_url)
+            .await
+            .unwrap_or(redirect_url),
+        None => redirect_url,
+    };
+
+    Ok(CoreResponse::new()
+        .with_redirect(redirect_url)
+        .with_cookies(cookies))
+}
+
+/// Revokes the tokens of the user's account at the provider, then clears them so they are not used
+/// again until the user signs in with the provider. Failures are logged, as the user is signed out
+/// regardless.
+async fn revoke_session_tokens(auth: &Auth, session: Option<&Session>, provider_id: &str) {
+    let Some(adaptor) = auth.adaptor() else {
+        return;
+    };
+    let Some(user_id) = session
+        .and_then(|s| s.user.as_ref())
+        .and_then(|u| u.id.clone())
+    else {
+        return;
+    };
+    let provider_account_id = session
+        .and_then(|s| s.account.as_ref())
+        .and_then(|a| a.provider_account_id.clone());
+
+    let account = match adaptor.get_accounts_by_user(user_id).await {
+        Ok(accounts) => accounts.into_iter().find(|account| {
+            account.provider_id.as_deref() == Some(provider_id)
+                && provider_account_id
+                    .as_ref()
+                    .is_none_or(|id| account.provider_account_id.as_ref() == Some(id))
+        }),
+        Err(error) => {
+            tracing::warn!("[logout] Failed to find the account to revoke: {}", error);
+            return;
+        }
+    };
+    let Some(account) = account.filter(|a| a.token.is_some()) else {
+        return;
+    };
+    if !actions::try_revoke_account_tokens(auth, &account).await {
+        return;
+    }
+
+    let token = account.token.clone().unwrap_or_default();
+    let cleared = Account {
+        token: Some(Token {
+            token_type: token.token_type,
+            scope: token.scope,
+            ..Default::default()
+        }),
+        ..account
+    };
+    if let Err(error) = adaptor.update_account(cleared).await {
+        tracing::warn!("[logout] Failed to clear the revoked tokens: {}", error);
+    }
+}
+
+/// Builds the URL of the issuer's `end_session_endpoint`, for OIDC RP-initiated logout. Returns
+/// `None` if the provider is not an OIDC provider with RP-initiated logout enabled, or the issuer
+/// does not support it.
+/// https://openid.net/specs/openid-connect-rpinitiated-1_0.html
+async fn end_session_url(
+    auth: &Auth,
+    provider_id: &str,
+    post_logout_redirect_uri: &str,
+) -> Option<String> {
+    let provider = auth
+        .options
+        .providers
+        .iter()
+        .find(|p| p.id() == provider_id)?;
+    let oidc_provider = provider.as_oidc().filter(|p| p.rp_initiated_logout())?;
+
+    let metadata = match oidc_provider.discover().await {
+        Ok(metadata) => metadata,
+        Err(error) => {
+            tracing::warn!("[logout] Failed to discover the issuer: {}", error);
+            return None;
+        }
+    };
+    let mut url = metadata
+        .end_session_endpoint
+        .and_then(|endpoint| Url::parse(&endpoint).ok())?;
+
+    // Without an id_token_hint, the client ID tells the issuer which client is signing out
+    url.query_pairs_mut()
+        .append_pair("client_id", &oidc_provider.client_id())
+        .append_pair("post_logout_redirect_uri", post_logout_redirect_uri);
+
+    tracing::debug!("[logout] Signing out of the issuer: {}", url);
+    Some(url.to_string())
+}
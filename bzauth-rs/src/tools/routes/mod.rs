@@ -1,3 +1,6 @@
+pub mod accounts;
 pub mod authorise;
 pub mod callback;
 pub mod csrf;
+pub mod logout;
+pub mod session;
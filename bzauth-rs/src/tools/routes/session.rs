@@ -0,0 +1,27 @@
+use http::header::CACHE_CONTROL;
+use serde::{Deserialize, Serialize};
+
+use crate::contracts::session::Session;
+use crate::tools::request::CoreRequest;
+use crate::tools::response::CoreResponse;
+use crate::tools::{CoreError, actions};
+
+/// The session of the current user, serialised as an empty object when signed out
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SessionResponse {
+    #[serde(flatten)]
+    pub session: Option<Session>,
+}
+
+pub async fn session(request: CoreRequest<()>) -> Result<CoreResponse<SessionResponse>, CoreError> {
+    let auth = request.extract_auth()?;
+
+    // The cookies refresh a sliding session, or clear one that is no longer valid
+    let (session, cookies) = actions::get_session(&request, &auth).await?;
+
+    // The session changes on sign in and out, so it must never be served from a cache
+    Ok(CoreResponse::<SessionResponse>::new()
+        .with_header::<_, String>(CACHE_CONTROL, "no-store".to_string())
+        .with_cookies(cookies)
+        .with_payload(SessionResponse { session }))
+}
@@ -1,45 +1,84 @@
-use oauth2::basic::BasicClient;
-use oauth2::{AuthUrl, ClientId, ClientSecret, EndpointNotSet, EndpointSet, RedirectUrl, TokenUrl};
+use oauth2::basic::{
+    BasicErrorResponse, BasicRevocationErrorResponse, BasicTokenIntrospectionResponse,
+    BasicTokenType,
+};
+use std::pin::Pin;
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use http::HeaderValue;
+use http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use oauth2::{
+    AsyncHttpClient, AuthUrl, Client, ClientId, ClientSecret, EndpointNotSet, EndpointSet,
+    ExtraTokenFields, HttpRequest, HttpResponse, RedirectUrl, StandardRevocableToken,
+    StandardTokenResponse, TokenUrl,
+};
 use rand::Rng;
 use rand::distr::Alphanumeric;
+use serde::{Deserialize, Serialize};
 
 use super::request_extractors::UtilError;
 use crate::contracts::provide::ProvideOAuth2;
 
-pub(crate) type Oauth2Client =
-    BasicClient<EndpointSet, EndpointNotSet, EndpointNotSet, EndpointNotSet, EndpointSet>;
+/// The extra fields of a token response. OpenID Connect providers return an id_token.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IdTokenFields {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id_token: Option<String>,
+}
+impl ExtraTokenFields for IdTokenFields {}
+
+pub(crate) type Oauth2TokenResponse = StandardTokenResponse<IdTokenFields, BasicTokenType>;
 
+pub(crate) type Oauth2Client = Client<
+    BasicErrorResponse,
+    Oauth2TokenResponse,
+    BasicTokenIntrospectionResponse,
+    StandardRevocableToken,
+    BasicRevocationErrorResponse,
+    EndpointSet,
+    EndpointNotSet,
+    EndpointNotSet,
+    EndpointNotSet,
+    EndpointSet,
+>;
+
+/// Creates the OAuth2 client for a provider. The redirect URL is derived from the public URL of the
+/// auth routes, and must match the one set in the provider's settings.
 pub fn generate_client_from_auth(
     oauth2_provider: &dyn ProvideOAuth2,
+    auth_url: &str,
 ) -> Result<Oauth2Client, UtilError> {
-    let auth_url = oauth2_provider.auth_endpoint().url();
-    let client_id = oauth2_provider.client_id();
-    let client_secret = oauth2_provider.client_secret();
-
-    // This should be the redirect URL you set in your provider settings
     let redirect_url = format!(
-        "http://localhost:3001/auth/callback/{provider}",
+        "{auth_url}/callback/{provider}",
         provider = oauth2_provider.id()
     );
+    let redirect_url = RedirectUrl::new(redirect_url.to_string())
+        .map_err(|_| UtilError::MissingProvider("Invalid redirect URL".to_string()))?;
+
+    Ok(generate_client(oauth2_provider)?.set_redirect_uri(redirect_url))
+}
+
+/// Creates the OAuth2 client for a provider, without a redirect URL. Enough for the grants that do
+/// not go through the browser, e.g. refreshing a token.
+pub fn generate_client(oauth2_provider: &dyn ProvideOAuth2) -> Result<Oauth2Client, UtilError> {
+    let auth_url = oauth2_provider.auth_endpoint().url();
+    let client_id = oauth2_provider.client_id();
+    let client_secret = oauth2_provider.client_secret();
 
     let token_url = oauth2_provider.token_endpoint().url();
 
     // Convert everything to oauth2 types
     let client_id = ClientId::new(client_id.to_string());
     let client_secret = ClientSecret::new(client_secret.to_string());
-    let redirect_url = RedirectUrl::new(redirect_url.to_string())
-        .map_err(|_| UtilError::MissingProvider("Invalid redirect URL".to_string()))?;
     let token_url = TokenUrl::new(token_url.to_string())
         .map_err(|_| UtilError::MissingProvider("Invalid token URL".to_string()))?;
     let auth_url = AuthUrl::new(auth_url.to_string())
         .map_err(|_| UtilError::MissingProvider("Invalid auth URL".to_string()))?;
-    let redirect_url = RedirectUrl::new(redirect_url.to_string())
-        .map_err(|_| UtilError::MissingProvider("Invalid redirect URL".to_string()))?;
 
-    let client = BasicClient::new(client_id)
+    let client: Oauth2Client = Client::new(client_id)
         .set_auth_uri(auth_url)
         .set_token_uri(token_url)
-        .set_redirect_uri(redirect_url)
         .set_client_secret(client_secret);
 
     Ok(client)
@@ -55,8 +94,101 @@ pub fn generate_state() -> String {
         .collect() // Collect into a String
 }
 
+pub fn generate_nonce() -> String {
+    // Same shape as the state, but kept separate as they protect different things
+    generate_state()
+}
+
+pub fn generate_secret() -> String {
+    // 64 random alphanumeric characters, well over the 256 bits needed for HMAC-SHA256
+    rand::rng()
+        .sample_iter(&Alphanumeric)
+        .take(64)
+        .map(char::from)
+        .collect()
+}
+
+pub fn generate_session_token() -> String {
+    // 32 random bytes, unguessable and URL safe
+    let bytes: [u8; 32] = rand::rng().random();
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+pub fn generate_verification_token() -> String {
+    // Same shape as the session token, but sent by email to prove the address belongs to the user
+    generate_session_token()
+}
+
+pub fn generate_csrf_token() -> String {
+    // Same shape as the session token, but kept separate as they protect different things
+    generate_session_token()
+}
+
+/// The user agent sent to providers. Some APIs (e.g. GitHub) refuse requests without one
+pub const USER_AGENT: &str = concat!("bzauth-rs/", env!("CARGO_PKG_VERSION"));
+
+/// The token response fields that are numbers, but arrive as strings in a form-encoded body
+const NUMERIC_TOKEN_FIELDS: [&str; 2] = ["expires_in", "refresh_token_expires_in"];
+
 pub fn generate_http_client() -> Result<reqwest::Client, UtilError> {
     reqwest::Client::builder()
+        .user_agent(USER_AGENT)
         .build()
         .map_err(|_| UtilError::MissingProvider("Failed to create HTTP client".to_string()))
 }
+
+/// The HTTP client of the token exchange. Some providers (e.g. GitHub) answer with a form-encoded
+/// body unless JSON is requested, or ignore the `Accept` header entirely, so such a body is
+/// converted to the JSON the OAuth2 client expects.
+pub(crate) struct TokenHttpClient(pub reqwest::Client);
+
+impl<'c> AsyncHttpClient<'c> for TokenHttpClient {
+    type Error = reqwest::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<HttpResponse, Self::Error>> + Send + 'c>>;
+
+    fn call(&'c self, request: HttpRequest) -> Self::Future {
+        Box::pin(send_token_request(&self.0, request))
+    }
+}
+
+async fn send_token_request(
+    http_client: &reqwest::Client,
+    request: HttpRequest,
+) -> Result<HttpResponse, reqwest::Error> {
+    let (parts, body) = request.into_parts();
+    let response = http_client
+        .request(parts.method, parts.uri.to_string())
+        .headers(parts.headers)
+        .body(body)
+        .send()
+        .await?;
+
+    let status = response.status();
+    let mut headers = response.headers().clone();
+    let mut body = response.bytes().await?.to_vec();
+
+    let form_encoded = headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/x-www-form-urlencoded"));
+    if form_encoded {
+        let fields = url::form_urlencoded::parse(&body)
+            .map(|(key, value)| {
+                let value = match value.parse::<u64>() {
+                    Ok(number) if NUMERIC_TOKEN_FIELDS.contains(&key.as_ref()) => number.into(),
+                    _ => serde_json::Value::String(value.into_owned()),
+                };
+                (key.into_owned(), value)
+            })
+            .collect::<serde_json::Map<_, _>>();
+
+        body = serde_json::to_vec(&fields).unwrap_or_default();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        headers.remove(CONTENT_LENGTH);
+    }
+
+    let mut http_response = HttpResponse::new(body);
+    *http_response.status_mut() = status;
+    *http_response.headers_mut() = headers;
+    Ok(http_response)
+}
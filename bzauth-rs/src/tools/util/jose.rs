@@ -0,0 +1,287 @@
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use jsonwebtoken::jwk::{Jwk, JwkSet};
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::contracts::profile::Profile;
+
+/// The signature algorithms accepted for an id_token. Symmetric and `none` algorithms are refused.
+pub const SUPPORTED_ALGORITHMS: [Algorithm; 3] =
+    [Algorithm::RS256, Algorithm::ES256, Algorithm::EdDSA];
+
+/// The default tolerated clock skew when checking `exp` and `iat` (in seconds)
+pub const DEFAULT_CLOCK_SKEW: i64 = 60;
+
+/// How long a fetched key set is used before it is fetched again
+pub const JWKS_MAX_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// The minimum time between two fetches, so unknown keys cannot be used to flood the issuer
+pub const JWKS_MIN_REFRESH: Duration = Duration::from_secs(30);
+
+/// The claims of an OpenID Connect id_token. The standard claims of the user are kept in `others`.
+/// https://openid.net/specs/openid-connect-core-1_0.html#IDToken
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    pub sub: String,
+    #[serde(deserialize_with = "one_or_many")]
+    pub aud: Vec<String>,
+    pub azp: Option<String>,
+    pub exp: i64,
+    pub iat: i64,
+    pub nonce: Option<String>,
+
+    #[serde(flatten)]
+    pub others: serde_json::Value,
+}
+
+/// The audience may be a single string or an array of strings
+fn one_or_many<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(one) => vec![one],
+        OneOrMany::Many(many) => many,
+    })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum JoseError {
+    Malformed(String),
+    UnsupportedAlgorithm(String),
+    UnknownKey(String),
+    InvalidSignature(String),
+    InvalidIssuer(String),
+    InvalidAudience(String),
+    InvalidAuthorizedParty(String),
+    Expired,
+    IssuedInFuture,
+    InvalidNonce,
+    Jwks(String),
+}
+
+impl std::fmt::Display for JoseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JoseError::Malformed(msg) => write!(f, "Malformed id_token: {}", msg),
+            JoseError::UnsupportedAlgorithm(alg) => write!(f, "Unsupported algorithm: {}", alg),
+            JoseError::UnknownKey(kid) => write!(f, "Unknown signing key: {}", kid),
+            JoseError::InvalidSignature(msg) => write!(f, "Invalid signature: {}", msg),
+            JoseError::InvalidIssuer(iss) => write!(f, "Invalid id_token issuer: {}", iss),
+            JoseError::InvalidAudience(aud) => write!(f, "Invalid id_token audience: {}", aud),
+            JoseError::InvalidAuthorizedParty(azp) => {
+                write!(f, "Invalid id_token authorized party: {}", azp)
+            }
+            JoseError::Expired => write!(f, "The id_token has expired"),
+            JoseError::IssuedInFuture => write!(f, "The id_token was issued in the future"),
+            JoseError::InvalidNonce => write!(f, "Invalid id_token nonce"),
+            JoseError::Jwks(msg) => write!(f, "Failed to fetch the signing keys: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for JoseError {}
+
+/// The expected values of the id_token claims
+#[derive(Debug, Clone)]
+pub struct ClaimsValidation {
+    /// The accepted issuers. Most providers have exactly one
+    pub issuers: Vec<String>,
+    pub client_id: String,
+    pub nonce: Option<String>,
+    /// The tolerated clock skew when checking `exp` and `iat` (in seconds)
+    pub clock_skew: i64,
+}
+
+impl Default for ClaimsValidation {
+    fn default() -> Self {
+        ClaimsValidation {
+            issuers: vec![],
+            client_id: String::new(),
+            nonce: None,
+            clock_skew: DEFAULT_CLOCK_SKEW,
+        }
+    }
+}
+
+impl ClaimsValidation {
+    /// Checks the issuer, audience, authorized party, lifetime and nonce of the claims.
+    pub fn validate(&self, claims: &IdTokenClaims) -> Result<(), JoseError> {
+        let issuer = claims.iss.trim_end_matches('/');
+        if !self
+            .issuers
+            .iter()
+            .any(|i| i.trim_end_matches('/') == issuer)
+        {
+            return Err(JoseError::InvalidIssuer(claims.iss.clone()));
+        }
+
+        if !claims.aud.contains(&self.client_id) {
+            return Err(JoseError::InvalidAudience(claims.aud.join(" ")));
+        }
+
+        // With several audiences, the token must say it was issued to us
+        match &claims.azp {
+            Some(azp) if azp != &self.client_id => {
+                return Err(JoseError::InvalidAuthorizedParty(azp.clone()));
+            }
+            None if claims.aud.len() > 1 => {
+                return Err(JoseError::InvalidAuthorizedParty("".to_string()));
+            }
+            _ => {}
+        }
+
+        let now = chrono::Utc::now().timestamp();
+        if claims.exp + self.clock_skew <= now {
+            return Err(JoseError::Expired);
+        }
+        if claims.iat - self.clock_skew > now {
+            return Err(JoseError::IssuedInFuture);
+        }
+
+        if let Some(nonce) = &self.nonce
+            && claims.nonce.as_ref() != Some(nonce)
+        {
+            return Err(JoseError::InvalidNonce);
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+struct CachedJwks {
+    jwks: Option<JwkSet>,
+    fetched_at: Option<Instant>,
+    pinned: bool,
+}
+
+/// A cache of an issuer's signing keys. The keys are fetched again once stale, or when a token is
+/// signed with an unknown key (the issuer has rotated its keys). Clones share the same cache.
+#[derive(Debug, Clone, Default)]
+pub struct JwksCache {
+    inner: Arc<RwLock<CachedJwks>>,
+}
+
+impl JwksCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a cache pinned to the given keys, which are never fetched again
+    pub fn from_jwks(jwks: JwkSet) -> Self {
+        JwksCache {
+            inner: Arc::new(RwLock::new(CachedJwks {
+                jwks: Some(jwks),
+                fetched_at: Some(Instant::now()),
+                pinned: true,
+            })),
+        }
+    }
+
+    /// Finds the key for a token, fetching the key set from `jwks_uri` if needed.
+    pub async fn find(&self, jwks_uri: &str, kid: Option<&str>) -> Result<Jwk, JoseError> {
+        let (key, refresh) = {
+            let cached = self
+                .inner
+                .read()
+                .map_err(|e| JoseError::Jwks(e.to_string()))?;
+            let age = cached.fetched_at.map(|t| t.elapsed());
+            let key = cached.jwks.as_ref().and_then(|jwks| find_key(jwks, kid));
+
+            let stale = age.is_none_or(|age| age >= JWKS_MAX_AGE);
+            let can_refresh = age.is_none_or(|age| age >= JWKS_MIN_REFRESH);
+            let refresh = !cached.pinned && can_refresh && (stale || key.is_none());
+            (key, refresh)
+        };
+
+        if !refresh {
+            return key.ok_or_else(|| JoseError::UnknownKey(kid.unwrap_or_default().to_string()));
+        }
+
+        tracing::debug!("[jose] Fetching signing keys: {}", jwks_uri);
+        let jwks = match fetch_jwks(jwks_uri).await {
+            Ok(jwks) => jwks,
+            // Keep using a known key if the issuer is briefly unavailable
+            Err(error) => return key.ok_or(error),
+        };
+        let key = find_key(&jwks, kid);
+
+        if let Ok(mut cached) = self.inner.write() {
+            cached.jwks = Some(jwks);
+            cached.fetched_at = Some(Instant::now());
+        }
+
+        key.ok_or_else(|| JoseError::UnknownKey(kid.unwrap_or_default().to_string()))
+    }
+}
+
+/// Finds a key by its ID, or the only key of the set if the token does not name one
+fn find_key(jwks: &JwkSet, kid: Option<&str>) -> Option<Jwk> {
+    match kid {
+        Some(kid) => jwks.find(kid).cloned(),
+        None if jwks.keys.len() == 1 => jwks.keys.first().cloned(),
+        None => None,
+    }
+}
+
+async fn fetch_jwks(jwks_uri: &str) -> Result<JwkSet, JoseError> {
+    reqwest::get(jwks_uri)
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| JoseError::Jwks(e.to_string()))?
+        .json::<JwkSet>()
+        .await
+        .map_err(|e| JoseError::Jwks(e.to_string()))
+}
+
+/// Verifies the signature of an id_token against the issuer's keys, then validates its claims.
+pub async fn verify_id_token(
+    token: &str,
+    jwks: &JwksCache,
+    jwks_uri: &str,
+    validation: &ClaimsValidation,
+) -> Result<IdTokenClaims, JoseError> {
+    let header =
+        jsonwebtoken::decode_header(token).map_err(|e| JoseError::Malformed(e.to_string()))?;
+    if !SUPPORTED_ALGORITHMS.contains(&header.alg) {
+        return Err(JoseError::UnsupportedAlgorithm(format!("{:?}", header.alg)));
+    }
+
+    let jwk = jwks.find(jwks_uri, header.kid.as_deref()).await?;
+    let key =
+        DecodingKey::from_jwk(&jwk).map_err(|e| JoseError::InvalidSignature(e.to_string()))?;
+
+    // Only the signature is checked here, the claims are validated below
+    let mut signature_validation = Validation::new(header.alg);
+    signature_validation.validate_exp = false;
+    signature_validation.validate_aud = false;
+    signature_validation.required_spec_claims.clear();
+
+    let claims = jsonwebtoken::decode::<IdTokenClaims>(token, &key, &signature_validation)
+        .map_err(|e| JoseError::InvalidSignature(e.to_string()))?
+        .claims;
+
+    validation.validate(&claims)?;
+    Ok(claims)
+}
+
+impl From<IdTokenClaims> for Profile {
+    fn from(claims: IdTokenClaims) -> Self {
+        // The standard claims share their names with the profile, the rest are kept in `others`
+        serde_json::to_value(claims)
+            .and_then(serde_json::from_value)
+            .unwrap_or_default()
+    }
+}
@@ -1,4 +1,7 @@
 pub mod awaitable;
 pub mod generators;
+pub mod jose;
 pub mod request_extractors;
+pub mod session_jwt;
+pub mod signing;
 pub mod try_async;
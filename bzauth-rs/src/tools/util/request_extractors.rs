@@ -1,6 +1,7 @@
 use std::sync::Arc;
 
 use super::generators::{Oauth2Client, generate_client_from_auth};
+use super::signing::{self, SigningPurpose};
 use crate::auth::Auth;
 use crate::contracts::adapt::Adapt;
 use crate::contracts::provide::Provide;
@@ -14,6 +15,7 @@ pub enum UtilError {
     MissingProvider(String),
     //
     ClientCreationFailed(String),
+    InvalidCsrfToken(String),
 }
 
 impl From<UtilError> for CoreError {
@@ -23,6 +25,9 @@ impl From<UtilError> for CoreError {
             UtilError::MissingProviderId(msg) => CoreError::new().with_message(msg),
             UtilError::MissingProvider(msg) => CoreError::new().with_message(msg),
             UtilError::ClientCreationFailed(msg) => CoreError::new().with_message(msg),
+            UtilError::InvalidCsrfToken(msg) => CoreError::new()
+                .with_message(msg)
+                .with_status(http::StatusCode::FORBIDDEN.into()),
         }
     }
 }
@@ -93,6 +98,106 @@ impl<T: RequestPayload> CoreRequest<T> {
         Ok(state)
     }
 
+    /// Extracts the signed state from the request cookies, returning the state if the signature is valid.
+    pub fn extract_state_cookie(&self, secret: &str) -> Result<String, UtilError> {
+        let signed_state = self
+            .cookies()
+            .get(COOKIE_STATE)
+            .and_then(|c| c.value)
+            .filter(|v| !v.is_empty())
+            .ok_or_else(|| UtilError::MissingAuth("Missing state cookie".to_string()))?;
+
+        signing::verify(secret, SigningPurpose::State, &signed_state)
+            .ok_or_else(|| UtilError::MissingAuth("Invalid state cookie signature".to_string()))
+    }
+
+    /// Extracts the PKCE verifier from the request cookies.
+    pub fn extract_pkce_verifier(&self) -> Result<String, UtilError> {
+        let verifier = self
+            .cookies()
+            .get(COOKIE_PKCE_VERIFIER)
+            .and_then(|c| c.value)
+            .filter(|v| !v.is_empty())
+            .ok_or_else(|| UtilError::MissingAuth("Missing PKCE verifier".to_string()))?;
+
+        Ok(verifier)
+    }
+
+    /// Extracts the OpenID Connect nonce from the request cookies.
+    pub fn extract_nonce(&self) -> Result<String, UtilError> {
+        let nonce = self
+            .cookies()
+            .get(COOKIE_NONCE)
+            .and_then(|c| c.value)
+            .filter(|v| !v.is_empty())
+            .ok_or_else(|| UtilError::MissingAuth("Missing nonce".to_string()))?;
+
+        Ok(nonce)
+    }
+
+    /// Extracts the ID of the user linking an account from the signed link cookie, if the signature
+    /// is valid.
+    pub fn extract_link_cookie(&self, secret: &str) -> Option<String> {
+        let signed_user_id = self
+            .cookies()
+            .get(COOKIE_LINK)
+            .and_then(|c| c.value)
+            .filter(|v| !v.is_empty())?;
+
+        signing::verify(secret, SigningPurpose::Link, &signed_user_id)
+    }
+
+    /// Extracts the CSRF token from the signed CSRF cookie, returning the token if the signature is
+    /// valid.
+    pub fn extract_csrf_token(&self, secret: &str) -> Option<String> {
+        let signed_token = self
+            .cookies()
+            .get(COOKIE_CSRF_TOKEN)
+            .and_then(|c| c.value)
+            .filter(|v| !v.is_empty())?;
+
+        signing::verify(secret, SigningPurpose::Csrf, &signed_token)
+    }
+
+    /// Checks the CSRF token submitted with the request, from the `csrf_token` form field or the
+    /// `X-CSRF-Token` header, against the CSRF cookie. A cross-site page can submit a form, but can
+    /// neither read the cookie nor the token bound to it.
+    pub fn check_csrf_token(&self, secret: &str) -> Result<(), UtilError> {
+        let submitted_token = self
+            .form()
+            .get(CSRF_TOKEN_FIELD)
+            .cloned()
+            .or_else(|| self.header(http::HeaderName::from_static(CSRF_TOKEN_HEADER)))
+            .filter(|v| !v.is_empty())
+            .ok_or_else(|| UtilError::InvalidCsrfToken("Missing CSRF token".to_string()))?;
+        let signed_token = self
+            .cookies()
+            .get(COOKIE_CSRF_TOKEN)
+            .and_then(|c| c.value)
+            .ok_or_else(|| UtilError::InvalidCsrfToken("Missing CSRF cookie".to_string()))?;
+
+        // Verifying the submitted token against the cookie's signature compares them in constant
+        // time, and rejects forged cookies at once
+        let (_, signature) = signed_token
+            .rsplit_once('.')
+            .ok_or_else(|| UtilError::InvalidCsrfToken("Invalid CSRF cookie".to_string()))?;
+        signing::verify(
+            secret,
+            SigningPurpose::Csrf,
+            &format!("{}.{}", submitted_token, signature),
+        )
+        .map(|_| ())
+        .ok_or_else(|| UtilError::InvalidCsrfToken("Invalid CSRF token".to_string()))
+    }
+
+    /// Extracts the session token from the request cookies.
+    pub fn extract_session_token(&self) -> Option<String> {
+        self.cookies()
+            .get(COOKIE_SESSION_TOKEN)
+            .and_then(|c| c.value)
+            .filter(|v| !v.is_empty())
+    }
+
     /// Extracts the OAuth2 client from the request.
     pub fn extract_oauth2_client(&self) -> Result<Oauth2Client, UtilError> {
         let provider = self.extract_provider()?;
@@ -101,12 +206,95 @@ impl<T: RequestPayload> CoreRequest<T> {
             .as_oauth2()
             .ok_or_else(|| UtilError::MissingProvider("Provider is not OAuth2".to_string()))?;
 
-        generate_client_from_auth(oauth2_provider)
+        generate_client_from_auth(oauth2_provider, &self.extract_auth_url()?)
+    }
+
+    /// Extracts the origin (scheme and host) the client used to reach the application.
+    ///
+    /// The configured base URL always wins. Otherwise the origin is inferred from the `Host`
+    /// header, or from the `Forwarded`/`X-Forwarded-*` headers if the proxy is trusted.
+    pub fn extract_origin(&self) -> Result<String, UtilError> {
+        let auth = self.extract_auth()?;
+        if let Some(base_url) = auth.base_url() {
+            return Ok(base_url.origin().ascii_serialization());
+        }
+
+        let (mut proto, mut host) = (None, None);
+        if auth.options.trust_proxy {
+            (proto, host) = self.forwarded();
+            proto = proto.or_else(|| self.last_header_value("x-forwarded-proto"));
+            host = host.or_else(|| self.last_header_value("x-forwarded-host"));
+        }
+
+        let host = host
+            .or_else(|| self.header(http::header::HOST))
+            .or_else(|| self.uri().authority().map(|a| a.to_string()))
+            .ok_or_else(|| {
+                UtilError::MissingAuth("Failed to infer the host from the request".to_string())
+            })?;
+        let proto = proto
+            .or_else(|| self.uri().scheme_str().map(String::from))
+            .unwrap_or_else(|| "http".to_string());
+
+        Ok(format!("{}://{}", proto, host))
+    }
+
+    /// Extracts the public URL of the auth routes, without a trailing slash.
+    pub fn extract_auth_url(&self) -> Result<String, UtilError> {
+        let auth = self.extract_auth()?;
+        if let Some(base_url) = auth.base_url() {
+            return Ok(base_url.as_str().trim_end_matches('/').to_string());
+        }
+
+        Ok(format!("{}{}", self.extract_origin()?, auth.base_path()))
+    }
+
+    /// Reads the proto and host from the RFC 7239 `Forwarded` header. Each proxy appends its own
+    /// element, so only the last one, added by the closest proxy, is trusted; the earlier ones may
+    /// come from the client.
+    fn forwarded(&self) -> (Option<String>, Option<String>) {
+        let Some(forwarded) = self.header(http::header::FORWARDED) else {
+            return (None, None);
+        };
+
+        let (mut proto, mut host) = (None, None);
+        let last_hop = forwarded.rsplit(',').next().unwrap_or_default();
+        for pair in last_hop.split(';') {
+            let Some((key, value)) = pair.split_once('=') else {
+                continue;
+            };
+            let value = value.trim().trim_matches('"').to_string();
+            match key.trim().to_ascii_lowercase().as_str() {
+                "proto" => proto = Some(value),
+                "host" => host = Some(value),
+                _ => {}
+            }
+        }
+
+        (proto, host)
+    }
+
+    /// Reads the last value of a comma-separated header. Proxies append to `X-Forwarded-*`, so the
+    /// first values may come from the client and only the last one is set by the closest proxy.
+    fn last_header_value(&self, key: &'static str) -> Option<String> {
+        self.header(http::HeaderName::from_static(key))
+            .and_then(|v| v.rsplit(',').next().map(|v| v.trim().to_string()))
+            .filter(|v| !v.is_empty())
     }
 }
 
 pub const COOKIE_STATE: &str = "state";
 pub const COOKIE_CSRF_TOKEN: &str = "csrf";
+/// Where the CSRF token is submitted: a form field, or a header for requests sent by scripts
+pub const CSRF_TOKEN_FIELD: &str = "csrf_token";
+pub const CSRF_TOKEN_HEADER: &str = "x-csrf-token";
 pub const COOKIE_PKCE: &str = "pkce";
 pub const COOKIE_PKCE_METHOD: &str = "pkce_method";
 pub const COOKIE_PKCE_VERIFIER: &str = "pkce_verifier";
+pub const COOKIE_NONCE: &str = "nonce";
+/// Set when a signed-in user links an account, holding their signed ID
+pub const COOKIE_LINK: &str = "link";
+pub const COOKIE_SESSION_TOKEN: &str = "session_token";
+
+/// How long the one-time check cookies survive between authorise and callback (in seconds)
+pub const COOKIE_CHECKS_MAX_AGE: i32 = 60 * 15;
@@ -0,0 +1,205 @@
+use aes_gcm::aead::{Aead, Payload};
+use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use hkdf::Hkdf;
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::contracts::session::{Session, SessionAccount};
+use crate::contracts::user::User;
+
+/// The protected header of an encrypted session: the key is used directly with AES-256-GCM
+const JWE_HEADER: &str = r#"{"alg":"dir","enc":"A256GCM"}"#;
+
+/// The HKDF info of the keys derived from the secret, so each use gets its own key
+const ENCRYPTION_KEY_INFO: &[u8] = b"bzauth-rs session encryption key";
+const SIGNING_KEY_INFO: &[u8] = b"bzauth-rs session signing key";
+
+/// How a session is protected in the session cookie
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum SessionJwtEncoding {
+    /// A JWE (dir, A256GCM): the session can be neither read nor forged without the secret
+    #[default]
+    Encrypted,
+    /// A JWS (HS256): the session cannot be forged, but can be read by the client
+    Signed,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SessionJwtError {
+    Malformed(String),
+    /// The token was not encrypted or signed with the secret, or was tampered with
+    InvalidToken,
+    Expired,
+}
+
+impl std::fmt::Display for SessionJwtError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SessionJwtError::Malformed(msg) => write!(f, "Malformed session token: {}", msg),
+            SessionJwtError::InvalidToken => write!(f, "Invalid session token"),
+            SessionJwtError::Expired => write!(f, "The session has expired"),
+        }
+    }
+}
+
+impl std::error::Error for SessionJwtError {}
+
+/// The claims of a session token
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SessionClaims {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    user: Option<User>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    account: Option<SessionAccount>,
+    iat: u64,
+    exp: u64,
+}
+
+/// Encodes a session into a token, valid until the session's `expires_at`.
+pub fn encode(
+    secret: &str,
+    encoding: SessionJwtEncoding,
+    session: &Session,
+) -> Result<String, SessionJwtError> {
+    let claims = SessionClaims {
+        user: session.user.clone(),
+        account: session.account.clone(),
+        iat: chrono::Utc::now().timestamp() as u64,
+        exp: session.expires_at.unwrap_or_default(),
+    };
+
+    match encoding {
+        SessionJwtEncoding::Encrypted => encrypt(secret, &claims),
+        SessionJwtEncoding::Signed => jsonwebtoken::encode(
+            &Header::new(Algorithm::HS256),
+            &claims,
+            &EncodingKey::from_secret(&derive_key(secret, SIGNING_KEY_INFO)),
+        )
+        .map_err(|e| SessionJwtError::Malformed(e.to_string())),
+    }
+}
+
+/// Decodes a session token produced by [encode] with the same secret and encoding. This needs no
+/// adaptor, so any service sharing the secret can validate sessions locally.
+pub fn decode(
+    secret: &str,
+    encoding: SessionJwtEncoding,
+    token: &str,
+) -> Result<Session, SessionJwtError> {
+    let claims = match encoding {
+        SessionJwtEncoding::Encrypted => decrypt(secret, token)?,
+        SessionJwtEncoding::Signed => {
+            let mut validation = Validation::new(Algorithm::HS256);
+            validation.validate_exp = false;
+            validation.required_spec_claims.clear();
+
+            jsonwebtoken::decode::<SessionClaims>(
+                token,
+                &DecodingKey::from_secret(&derive_key(secret, SIGNING_KEY_INFO)),
+                &validation,
+            )
+            .map_err(|_| SessionJwtError::InvalidToken)?
+            .claims
+        }
+    };
+
+    // Checked here for both encodings, without any leeway
+    if claims.exp <= chrono::Utc::now().timestamp() as u64 {
+        return Err(SessionJwtError::Expired);
+    }
+
+    Ok(Session {
+        user: claims.user,
+        account: claims.account,
+        expires_at: Some(claims.exp),
+    })
+}
+
+/// Derives a 256-bit key from the secret with HKDF-SHA256
+fn derive_key(secret: &str, info: &[u8]) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    Hkdf::<Sha256>::new(None, secret.as_bytes())
+        .expand(info, &mut key)
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+    key
+}
+
+/// Produces the compact serialization `header..iv.ciphertext.tag`, the encrypted key being empty
+/// with direct encryption
+fn encrypt(secret: &str, claims: &SessionClaims) -> Result<String, SessionJwtError> {
+    let plaintext =
+        serde_json::to_vec(claims).map_err(|e| SessionJwtError::Malformed(e.to_string()))?;
+    let header = URL_SAFE_NO_PAD.encode(JWE_HEADER);
+    let iv: [u8; 12] = rand::rng().random();
+
+    let cipher = Aes256Gcm::new(&derive_key(secret, ENCRYPTION_KEY_INFO).into());
+    let mut ciphertext = cipher
+        .encrypt(
+            Nonce::from_slice(&iv),
+            Payload {
+                msg: &plaintext,
+                aad: header.as_bytes(),
+            },
+        )
+        .map_err(|_| SessionJwtError::InvalidToken)?;
+
+    // The tag is appended to the ciphertext
+    let tag = ciphertext.split_off(ciphertext.len() - 16);
+
+    Ok(format!(
+        "{}..{}.{}.{}",
+        header,
+        URL_SAFE_NO_PAD.encode(iv),
+        URL_SAFE_NO_PAD.encode(ciphertext),
+        URL_SAFE_NO_PAD.encode(tag)
+    ))
+}
+
+fn decrypt(secret: &str, token: &str) -> Result<SessionClaims, SessionJwtError> {
+    let parts = token.split('.').collect::<Vec<_>>();
+    let [header, encrypted_key, iv, ciphertext, tag] = parts.as_slice() else {
+        return Err(SessionJwtError::Malformed("Expected 5 parts".to_string()));
+    };
+    if !encrypted_key.is_empty() {
+        return Err(SessionJwtError::Malformed(
+            "Unexpected encrypted key".to_string(),
+        ));
+    }
+
+    // Only the header this module produces is accepted, so the algorithm cannot be downgraded
+    let decoded_header = URL_SAFE_NO_PAD
+        .decode(header)
+        .map_err(|e| SessionJwtError::Malformed(e.to_string()))?;
+    if decoded_header != JWE_HEADER.as_bytes() {
+        return Err(SessionJwtError::Malformed("Unsupported header".to_string()));
+    }
+
+    let decode = |part: &str| {
+        URL_SAFE_NO_PAD
+            .decode(part)
+            .map_err(|e| SessionJwtError::Malformed(e.to_string()))
+    };
+    let iv = decode(iv)?;
+    if iv.len() != 12 {
+        return Err(SessionJwtError::Malformed("Invalid IV".to_string()));
+    }
+    let mut sealed = decode(ciphertext)?;
+    sealed.extend(decode(tag)?);
+
+    let cipher = Aes256Gcm::new(&derive_key(secret, ENCRYPTION_KEY_INFO).into());
+    let plaintext = cipher
+        .decrypt(
+            Nonce::from_slice(&iv),
+            Payload {
+                msg: &sealed,
+                aad: header.as_bytes(),
+            },
+        )
+        .map_err(|_| SessionJwtError::InvalidToken)?;
+
+    serde_json::from_slice(&plaintext).map_err(|e| SessionJwtError::Malformed(e.to_string()))
+}
@@ -1,9 +1,13 @@
+use bzauth_rs::adaptors::error::AdaptorError;
 use bzauth_rs::contracts::adapt::{
-    Adapt, AdaptAccount, AdaptSession, AdaptUser, AdaptVerificationToken, CreateSessionOptions,
-    ProviderAccountId, SessionUser, UseVerificationTokenOptions,
+    Adapt, AdaptAccount, AdaptResult, AdaptSession, AdaptUser, AdaptVerificationToken,
+    CreateSessionOptions, ProviderAccountId, SessionUser, UseVerificationTokenOptions,
 };
 
-use crate::mock::{JsonStore, JsonTableInsertQuery, JsonTableSelectQuery, JsonTableUpdateQuery};
+use crate::mock::{
+    JsonStore, JsonTableDeleteQuery, JsonTableInsertQuery, JsonTableSelectQuery,
+    JsonTableUpdateQuery,
+};
 
 pub struct MockAdaptor {
     store: JsonStore,
@@ -17,7 +21,20 @@ impl MockAdaptor {
 
 #[async_trait::async_trait]
 impl Adapt for MockAdaptor {
-    async fn create_user(&self, user: AdaptUser) -> AdaptUser {
+    async fn create_user(&self, mut user: AdaptUser) -> AdaptResult<AdaptUser> {
+        let id = user
+            .id
+            .get_or_insert_with(|| uuid::Uuid::new_v4().to_string())
+            .clone();
+        if self.get_user(id).await?.is_some() {
+            return Err(AdaptorError::Conflict("User already exists".to_string()));
+        }
+        if let Some(email) = user.email.clone()
+            && self.get_user_by_email(email).await?.is_some()
+        {
+            return Err(AdaptorError::Conflict("Email is already taken".to_string()));
+        }
+
         let query = JsonTableInsertQuery::new("users", user);
 
         let result = query.execute(&self.store);
@@ -25,34 +42,37 @@ impl Adapt for MockAdaptor {
             serde_json::from_value(result.first().unwrap().clone()).unwrap();
 
         // Return the created user
-        created_user
+        Ok(created_user)
     }
 
-    async fn get_user(&self, id: String) -> Option<AdaptUser> {
+    async fn get_user(&self, id: String) -> AdaptResult<Option<AdaptUser>> {
         let query = JsonTableSelectQuery::new("users").where_clause("id", id);
         let result = query.execute(&self.store);
 
         if result.is_empty() {
-            None
+            Ok(None)
         } else {
             let user: AdaptUser = serde_json::from_value(result.first().unwrap().clone()).unwrap();
-            Some(user)
+            Ok(Some(user))
         }
     }
 
-    async fn get_user_by_email(&self, email: String) -> Option<AdaptUser> {
+    async fn get_user_by_email(&self, email: String) -> AdaptResult<Option<AdaptUser>> {
         let query = JsonTableSelectQuery::new("users").where_clause("email", email);
         let result = query.execute(&self.store);
 
         if result.is_empty() {
-            None
+            Ok(None)
         } else {
             let user: AdaptUser = serde_json::from_value(result.first().unwrap().clone()).unwrap();
-            Some(user)
+            Ok(Some(user))
         }
     }
 
-    async fn get_user_by_account(&self, provider: ProviderAccountId) -> Option<AdaptUser> {
+    async fn get_user_by_account(
+        &self,
+        provider: ProviderAccountId,
+    ) -> AdaptResult<Option<AdaptUser>> {
         let query = JsonTableSelectQuery::new("accounts")
             .where_clause("provider_id", provider.provider_id)
             .where_clause("provider_account_id", provider.provider_account_id);
@@ -60,7 +80,7 @@ impl Adapt for MockAdaptor {
         let account = query.execute(&self.store);
 
         if account.is_empty() {
-            None
+            Ok(None)
         } else {
             let user_id = account.first().unwrap()["user_id"]
                 .as_str()
@@ -70,27 +90,35 @@ impl Adapt for MockAdaptor {
         }
     }
 
-    async fn update_user(&self, user: AdaptUser) -> AdaptUser {
+    async fn update_user(&self, user: AdaptUser) -> AdaptResult<AdaptUser> {
         let user_id = user.id.clone();
         let query = JsonTableUpdateQuery::new("users", user).where_clause("id", user_id);
 
         let result = query.execute(&self.store);
-        let updated_user: AdaptUser =
-            serde_json::from_value(result.first().unwrap().clone()).unwrap();
+        let updated_user = result
+            .first()
+            .ok_or_else(|| AdaptorError::NotFound("User does not exist".to_string()))?;
+        let updated_user: AdaptUser = serde_json::from_value(updated_user.clone()).unwrap();
 
         // Return the updated user
-        updated_user
+        Ok(updated_user)
     }
 
-    async fn delete_user(&self, id: String) -> () {
-        let query = JsonTableUpdateQuery::new("users", serde_json::json!({"deleted": true}))
-            .where_clause("id", id);
-
-        query.execute(&self.store);
-        ()
+    async fn delete_user(&self, id: String) -> AdaptResult<()> {
+        // Delete the user, along with its accounts and sessions
+        JsonTableDeleteQuery::new("users")
+            .where_clause("id", &id)
+            .execute(&self.store);
+        JsonTableDeleteQuery::new("accounts")
+            .where_clause("user_id", &id)
+            .execute(&self.store);
+        JsonTableDeleteQuery::new("sessions")
+            .where_clause("user_id", &id)
+            .execute(&self.store);
+        Ok(())
     }
 
-    async fn get_account(&self, provider: ProviderAccountId) -> Option<AdaptAccount> {
+    async fn get_account(&self, provider: ProviderAccountId) -> AdaptResult<Option<AdaptAccount>> {
         let query = JsonTableSelectQuery::new("accounts")
             .where_clause("provider_id", provider.provider_id)
             .where_clause("provider_account_id", provider.provider_account_id);
@@ -98,37 +126,75 @@ impl Adapt for MockAdaptor {
         let result = query.execute(&self.store);
 
         if result.is_empty() {
-            None
+            Ok(None)
         } else {
             let account: AdaptAccount =
                 serde_json::from_value(result.first().unwrap().clone()).unwrap();
-            Some(account)
+            Ok(Some(account))
         }
     }
 
-    async fn link_account(&self, account: AdaptAccount) -> Option<AdaptAccount> {
+    async fn get_accounts_by_user(&self, user_id: String) -> AdaptResult<Vec<AdaptAccount>> {
+        let query = JsonTableSelectQuery::new("accounts").where_clause("user_id", user_id);
+
+        let mut accounts: Vec<AdaptAccount> = query
+            .execute(&self.store)
+            .into_iter()
+            .map(|account| serde_json::from_value(account).unwrap())
+            .collect();
+        accounts.sort_by(|a, b| {
+            (&a.provider_id, &a.provider_account_id).cmp(&(&b.provider_id, &b.provider_account_id))
+        });
+        Ok(accounts)
+    }
+
+    async fn link_account(&self, mut account: AdaptAccount) -> AdaptResult<AdaptAccount> {
+        account
+            .id
+            .get_or_insert_with(|| uuid::Uuid::new_v4().to_string());
+        let provider = ProviderAccountId {
+            provider_id: account.provider_id.clone().unwrap_or_default(),
+            provider_account_id: account.provider_account_id.clone().unwrap_or_default(),
+        };
+        if self.get_account(provider).await?.is_some() {
+            return Err(AdaptorError::Conflict(
+                "Account is already linked".to_string(),
+            ));
+        }
+
         let query = JsonTableInsertQuery::new("accounts", account);
 
         let result = query.execute(&self.store);
-        if result.is_empty() {
-            None
-        } else {
-            let linked_account: AdaptAccount =
-                serde_json::from_value(result.first().unwrap().clone()).unwrap();
-            Some(linked_account)
-        }
+        let linked_account = result
+            .first()
+            .ok_or_else(|| AdaptorError::Other("Failed to link account".to_string()))?;
+        Ok(serde_json::from_value(linked_account.clone()).unwrap())
+    }
+
+    async fn update_account(&self, account: AdaptAccount) -> AdaptResult<AdaptAccount> {
+        // Only the token is updated
+        let query =
+            JsonTableUpdateQuery::new("accounts", serde_json::json!({ "token": account.token }))
+                .where_clause("provider_id", account.provider_id)
+                .where_clause("provider_account_id", account.provider_account_id);
+
+        let result = query.execute(&self.store);
+        let updated_account = result
+            .first()
+            .ok_or_else(|| AdaptorError::NotFound("Account does not exist".to_string()))?;
+        Ok(serde_json::from_value(updated_account.clone()).unwrap())
     }
 
-    async fn unlink_account(&self, provider: ProviderAccountId) -> () {
-        let query = JsonTableUpdateQuery::new("accounts", serde_json::json!({"deleted": true}))
+    async fn unlink_account(&self, provider: ProviderAccountId) -> AdaptResult<()> {
+        let query = JsonTableDeleteQuery::new("accounts")
             .where_clause("provider_id", provider.provider_id)
             .where_clause("provider_account_id", provider.provider_account_id);
 
         query.execute(&self.store);
-        ()
+        Ok(())
     }
 
-    async fn create_session(&self, options: CreateSessionOptions) -> Option<AdaptSession> {
+    async fn create_session(&self, options: CreateSessionOptions) -> AdaptResult<AdaptSession> {
         let session = AdaptSession {
             token: options.token,
             user_id: options.user_id,
@@ -138,51 +204,56 @@ impl Adapt for MockAdaptor {
         let query = JsonTableInsertQuery::new("sessions", session);
 
         let result = query.execute(&self.store);
-        if result.is_empty() {
-            None
-        } else {
-            let created_session: AdaptSession =
-                serde_json::from_value(result.first().unwrap().clone()).unwrap();
-            Some(created_session)
-        }
+        let created_session = result
+            .first()
+            .ok_or_else(|| AdaptorError::Other("Failed to create session".to_string()))?;
+        Ok(serde_json::from_value(created_session.clone()).unwrap())
     }
 
-    async fn get_session_and_user(&self, token: String) -> Option<SessionUser> {
+    async fn get_session_and_user(&self, token: String) -> AdaptResult<Option<SessionUser>> {
         let query = JsonTableSelectQuery::new("sessions").where_clause("token", token);
         let result = query.execute(&self.store);
 
         if result.is_empty() {
-            None
+            Ok(None)
         } else {
             let session: AdaptSession =
                 serde_json::from_value(result.first().unwrap().clone()).unwrap();
-            let user = self.get_user(session.user_id.clone()).await?;
+            let Some(user) = self.get_user(session.user_id.clone()).await? else {
+                return Ok(None);
+            };
             let session_user = SessionUser { session, user };
-            Some(session_user)
+            Ok(Some(session_user))
         }
     }
 
-    async fn update_session(&self, session: AdaptSession) -> AdaptSession {
+    async fn update_session(&self, session: AdaptSession) -> AdaptResult<AdaptSession> {
         let session_id = session.token.clone();
         let query =
             JsonTableUpdateQuery::new("sessions", session).where_clause("token", session_id);
 
         let result = query.execute(&self.store);
+        let updated_session = result
+            .first()
+            .ok_or_else(|| AdaptorError::NotFound("Session does not exist".to_string()))?;
         let updated_session: AdaptSession =
-            serde_json::from_value(result.first().unwrap().clone()).unwrap();
+            serde_json::from_value(updated_session.clone()).unwrap();
 
         // Return the updated session
-        updated_session
+        Ok(updated_session)
     }
 
-    async fn delete_session(&self, token: String) -> () {
-        let query = JsonTableUpdateQuery::new("sessions", serde_json::json!({"deleted": true}))
-            .where_clause("token", token);
+    async fn delete_session(&self, token: String) -> AdaptResult<()> {
+        let query = JsonTableDeleteQuery::new("sessions").where_clause("token", token);
 
         query.execute(&self.store);
+        Ok(())
     }
 
-    fn create_verification_token(&self, token: AdaptVerificationToken) -> AdaptVerificationToken {
+    async fn create_verification_token(
+        &self,
+        token: AdaptVerificationToken,
+    ) -> AdaptResult<AdaptVerificationToken> {
         let query = JsonTableInsertQuery::new("verification_tokens", token);
 
         let result = query.execute(&self.store);
@@ -190,25 +261,25 @@ impl Adapt for MockAdaptor {
             serde_json::from_value(result.first().unwrap().clone()).unwrap();
 
         // Return the created verification token
-        created_token
+        Ok(created_token)
     }
 
-    fn use_verification_token(
+    async fn use_verification_token(
         &self,
         options: UseVerificationTokenOptions,
-    ) -> Option<AdaptVerificationToken> {
-        // Delete the token from the store
-        let query =
-            JsonTableUpdateQuery::new("verification_tokens", serde_json::json!({"used": true}))
-                .where_clause("token", options.token);
+    ) -> AdaptResult<Option<AdaptVerificationToken>> {
+        // Delete the token from the store, so that it cannot be used again
+        let query = JsonTableDeleteQuery::new("verification_tokens")
+            .where_clause("email", options.email)
+            .where_clause("token", options.token);
 
         let result = query.execute(&self.store);
         if result.is_empty() {
-            None
+            Ok(None)
         } else {
             let used_token: AdaptVerificationToken =
                 serde_json::from_value(result.first().unwrap().clone()).unwrap();
-            Some(used_token)
+            Ok(Some(used_token))
         }
     }
 }
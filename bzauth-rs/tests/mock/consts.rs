@@ -2,3 +2,27 @@ pub const MOCK_AUTHORISE: &str = "authorize";
 pub const MOCK_CALLBACK: &str = "callback";
 pub const MOCK_TOKEN: &str = "token";
 pub const MOCK_PROFILE: &str = "profile";
+pub const MOCK_DISCOVERY: &str = ".well-known/openid-configuration";
+pub const MOCK_JWKS: &str = "jwks";
+pub const MOCK_END_SESSION: &str = "end_session";
+pub const MOCK_REVOKE: &str = "revoke";
+
+// The mock GitHub Enterprise Server, served under a path of the mock provider
+pub const MOCK_GITHUB: &str = "github";
+pub const MOCK_GITHUB_USER_ID: u64 = 1234567890;
+pub const MOCK_GITHUB_USER_LOGIN: &str = "octocat";
+pub const MOCK_GITHUB_USER_EMAIL: &str = "octocat@email.com";
+
+// The mock Discord API, served under a path of the mock provider
+pub const MOCK_DISCORD: &str = "discord";
+pub const MOCK_DISCORD_USER_ID: &str = "80351110224678912";
+pub const MOCK_DISCORD_USER_EMAIL: &str = "nelly@email.com";
+
+// Signing keys of the mock issuer, published in the JWKS under their key IDs
+pub const MOCK_JWKS_JSON: &str = include_str!("keys/jwks.json");
+pub const MOCK_JWKS_RSA_KID: &str = "mock-rsa";
+pub const MOCK_JWKS_RSA_PEM: &str = include_str!("keys/rsa.pem");
+pub const MOCK_JWKS_EC_KID: &str = "mock-ec";
+pub const MOCK_JWKS_EC_PEM: &str = include_str!("keys/ec.pem");
+pub const MOCK_JWKS_ED25519_KID: &str = "mock-ed25519";
+pub const MOCK_JWKS_ED25519_PEM: &str = include_str!("keys/ed25519.pem");
@@ -0,0 +1,181 @@
+//! A diesel adaptor over in-memory SQLite databases, created with the canonical schema
+
+use bzauth_rs::adaptors::diesel::{DieselAdapterOptions, DieselAdaptor};
+use diesel::SqliteConnection;
+use diesel::connection::SimpleConnection;
+use diesel::r2d2::{ConnectionManager, CustomizeConnection, Pool};
+
+pub mod schema {
+    diesel::table! {
+        users {
+            id -> Text,
+            name -> Nullable<Text>,
+            email -> Nullable<Text>,
+            email_verified -> Nullable<Timestamp>,
+            image -> Nullable<Text>,
+            created_at -> Timestamp,
+            updated_at -> Timestamp,
+        }
+    }
+
+    diesel::table! {
+        accounts {
+            id -> Text,
+            provider_id -> Text,
+            provider_account_id -> Text,
+            user_id -> Text,
+            provider_type -> Text,
+            refresh_token -> Nullable<Text>,
+            access_token -> Nullable<Text>,
+            expires_at -> Nullable<Timestamp>,
+            token_type -> Nullable<Text>,
+            scope -> Nullable<Text>,
+            id_token -> Nullable<Text>,
+            session_state -> Nullable<Text>,
+            created_at -> Timestamp,
+            updated_at -> Timestamp,
+        }
+    }
+
+    diesel::table! {
+        sessions {
+            id -> Text,
+            user_id -> Text,
+            token -> Text,
+            expires_at -> Timestamp,
+            created_at -> Timestamp,
+            updated_at -> Timestamp,
+        }
+    }
+
+    diesel::table! {
+        verification_tokens {
+            id -> Text,
+            email -> Text,
+            token -> Text,
+            expires_at -> Timestamp,
+            created_at -> Timestamp,
+            updated_at -> Timestamp,
+        }
+    }
+
+    diesel::joinable!(accounts -> users (user_id));
+    diesel::joinable!(sessions -> users (user_id));
+
+    diesel::allow_tables_to_appear_in_same_query!(users, accounts, sessions, verification_tokens);
+}
+
+pub mod models {
+    use chrono::NaiveDateTime;
+    use diesel::prelude::{Queryable, Selectable};
+
+    #[derive(Clone, Default, Queryable, Selectable)]
+    #[diesel(table_name = super::schema::users)]
+    #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+    pub struct User {
+        pub id: String,
+        pub name: Option<String>,
+        pub email: Option<String>,
+        pub email_verified: Option<NaiveDateTime>,
+        pub image: Option<String>,
+        pub created_at: NaiveDateTime,
+        pub updated_at: NaiveDateTime,
+    }
+
+    #[derive(Clone, Default, Queryable, Selectable)]
+    #[diesel(table_name = super::schema::accounts)]
+    #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+    pub struct Account {
+        pub id: String,
+        pub provider_id: String,
+        pub provider_account_id: String,
+        pub user_id: String,
+        pub provider_type: String,
+        pub refresh_token: Option<String>,
+        pub access_token: Option<String>,
+        pub expires_at: Option<NaiveDateTime>,
+        pub token_type: Option<String>,
+        pub scope: Option<String>,
+        pub id_token: Option<String>,
+        pub session_state: Option<String>,
+        pub created_at: NaiveDateTime,
+        pub updated_at: NaiveDateTime,
+    }
+
+    #[derive(Clone, Default, Queryable, Selectable)]
+    #[diesel(table_name = super::schema::sessions)]
+    #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+    pub struct Session {
+        pub id: String,
+        pub user_id: String,
+        pub token: String,
+        pub expires_at: NaiveDateTime,
+        pub created_at: NaiveDateTime,
+        pub updated_at: NaiveDateTime,
+    }
+
+    #[derive(Clone, Default, Queryable, Selectable)]
+    #[diesel(table_name = super::schema::verification_tokens)]
+    #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+    pub struct VerificationToken {
+        pub id: String,
+        pub email: String,
+        pub token: String,
+        pub expires_at: NaiveDateTime,
+    }
+}
+
+pub struct MockDieselAdaptor;
+bzauth_rs::adapt_diesel! {
+    MockDieselAdaptor,
+    SqliteConnection,
+    User = self::models::User,
+    UserTable = self::schema::users,
+    Account = self::models::Account,
+    AccountTable = self::schema::accounts,
+    Session = self::models::Session,
+    SessionTable = self::schema::sessions,
+    VerificationToken = self::models::VerificationToken,
+    VerificationTokenTable = self::schema::verification_tokens,
+}
+
+pub type MockDieselPool = Pool<ConnectionManager<SqliteConnection>>;
+
+pub type MockDieselStore = DieselAdaptor<
+    ConnectionManager<SqliteConnection>,
+    MockDieselAdaptor,
+    models::User,
+    models::Account,
+    models::Session,
+    models::VerificationToken,
+>;
+
+/// Every connection to :memory: is a new database, so the tables are created on connecting
+#[derive(Debug)]
+struct CreateTables;
+
+impl CustomizeConnection<SqliteConnection, diesel::r2d2::Error> for CreateTables {
+    fn on_acquire(&self, conn: &mut SqliteConnection) -> Result<(), diesel::r2d2::Error> {
+        conn.batch_execute(include_str!(
+            "../../migrations/sqlite/20250801000000_create_auth_tables.sql"
+        ))
+        .map_err(diesel::r2d2::Error::QueryError)
+    }
+}
+
+/// A pool of a single connection, which is the whole database
+pub fn diesel_pool() -> MockDieselPool {
+    Pool::builder()
+        .max_size(1)
+        .connection_timeout(std::time::Duration::from_secs(1))
+        .connection_customizer(Box::new(CreateTables))
+        .build(ConnectionManager::new(":memory:"))
+        .expect("Failed to create connection pool")
+}
+
+pub fn diesel_adaptor(pool: MockDieselPool) -> MockDieselStore {
+    DieselAdaptor::from_options(DieselAdapterOptions {
+        conn_pool: pool,
+        adaptor: MockDieselAdaptor,
+    })
+}
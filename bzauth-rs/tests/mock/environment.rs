@@ -16,12 +16,12 @@ pub mod axum_ {
         // Run the server in a separate task
         let server_handle = tokio::spawn(async move {
             if let Err(e) = server_future.await {
-                assert!(false, "Failed to start mock auth server: {}", e);
+                panic!("Failed to start mock auth server: {}", e);
             }
         });
         let runtime_handle = tokio::spawn(async move {
             if let Err(e) = runtime_future.await {
-                assert!(false, "Failed to start mock auth runtime: {}", e);
+                panic!("Failed to start mock auth runtime: {}", e);
             }
         });
 
@@ -0,0 +1,37 @@
+use bzauth_rs::auth::AuthOptions;
+use bzauth_rs::contracts::adapt::{Adapt, ProviderAccountId};
+use bzauth_rs::contracts::token::Token;
+
+use crate::mock::{
+    JsonStore, JsonTableSelectQuery, MockAdaptor, MockProvider, mock_github_provider,
+};
+
+/// The rows of a table of the store
+pub fn table(json_store: &JsonStore, name: &str) -> Vec<serde_json::Value> {
+    JsonTableSelectQuery::new(name).execute(json_store)
+}
+
+/// Signs in with the mock provider or GitHub, keeping the users in the store
+pub fn auth_options(json_store: &JsonStore) -> AuthOptions {
+    AuthOptions::new()
+        .add_provider(Box::new(MockProvider))
+        .add_provider(Box::new(mock_github_provider()))
+        .with_adaptor(Box::new(MockAdaptor::new(json_store.clone())))
+}
+
+/// The stored token of the account
+pub async fn stored_token(
+    json_store: &JsonStore,
+    provider_id: &str,
+    provider_account_id: &str,
+) -> Token {
+    MockAdaptor::new(json_store.clone())
+        .get_account(ProviderAccountId {
+            provider_id: provider_id.to_string(),
+            provider_account_id: provider_account_id.to_string(),
+        })
+        .await
+        .unwrap()
+        .and_then(|account| account.token)
+        .expect("The account has no token")
+}
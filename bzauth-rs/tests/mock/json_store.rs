@@ -48,11 +48,11 @@ impl JsonStoreInner {
     }
 
     pub(crate) fn save(&self, path: &str) -> Result<()> {
-        Self::save_to_file(&path, &self.data)
+        Self::save_to_file(path, &self.data)
     }
 
     pub(crate) fn load_from_file<P: AsRef<Path>>(path: &P) -> Result<Self> {
-        if std::fs::metadata(&path).is_err() {
+        if std::fs::metadata(path).is_err() {
             Self::create_file_and_load(path)
         } else {
             Self::open_file_and_load(path)
@@ -62,7 +62,7 @@ impl JsonStoreInner {
     fn create_file_and_load<P: AsRef<Path>>(path: &P) -> Result<Self> {
         let _ = Self::open_options_retries(
             &path,
-            &OpenOptions::new().create(true).write(true).read(true),
+            OpenOptions::new().create(true).write(true).read(true),
             10,
         )?;
 
@@ -78,7 +78,7 @@ impl JsonStoreInner {
     fn open_file_and_load<P: AsRef<Path>>(path: &P) -> Result<Self> {
         let _ = Self::open_options_retries(
             &path,
-            &OpenOptions::new().read(true).write(true).truncate(true),
+            OpenOptions::new().read(true).write(true).truncate(true),
             10,
         )?;
 
@@ -94,7 +94,7 @@ impl JsonStoreInner {
     ) -> Result<File> {
         let mut attempts = 0;
         loop {
-            match options.open(&path) {
+            match options.open(path) {
                 Ok(file) => return Ok(file),
                 Err(_) if attempts < retries => {
                     attempts += 1;
@@ -121,10 +121,9 @@ impl JsonStoreInner {
     }
 
     pub(crate) fn save_to_file(path: &str, data: &serde_json::Value) -> Result<()> {
-        let _ =
-            Self::open_options_retries(&path, &OpenOptions::new().write(true).create(true), 10)?;
+        let _ = Self::open_options_retries(&path, OpenOptions::new().write(true).create(true), 10)?;
 
-        std::fs::write(&path, data.to_string())
+        std::fs::write(path, data.to_string())
             .map_err(|e| format!("Failed to write to file: {}", e))?;
 
         Ok(())
@@ -433,19 +432,19 @@ impl JsonTableInterface {
         let mut data = store.get_data().unwrap_or_default();
         let mut updated = Vec::new();
 
-        if let Some(table) = data.get_mut(&query.table) {
-            if let Some(array) = table.as_array_mut() {
-                for item in array.iter_mut() {
-                    if query
-                        .where_clause
-                        .iter()
-                        .all(|(col, val)| item.get(col) == Some(val))
-                    {
-                        for (col, val) in query.values.as_object().unwrap().iter() {
-                            item[col] = val.clone();
-                        }
-                        updated.push(item.clone());
+        if let Some(table) = data.get_mut(&query.table)
+            && let Some(array) = table.as_array_mut()
+        {
+            for item in array.iter_mut() {
+                if query
+                    .where_clause
+                    .iter()
+                    .all(|(col, val)| item.get(col) == Some(val))
+                {
+                    for (col, val) in query.values.as_object().unwrap().iter() {
+                        item[col] = val.clone();
                     }
+                    updated.push(item.clone());
                 }
             }
         }
@@ -458,22 +457,20 @@ impl JsonTableInterface {
         let mut data = store.get_data().unwrap_or_default();
         let mut deleted = Vec::new();
 
-        if let Some(table) = data.get_mut(&query.table) {
-            if let Some(array) = table.as_array_mut() {
-                array.retain(|item| {
-                    let should_delete = query
-                        .where_clause
-                        .iter()
-                        .all(|(col, val)| item.get(col) == Some(val));
-
-                    should_delete
-                        .then(|| {
-                            deleted.push(item.clone());
-                            false
-                        })
-                        .unwrap_or(true)
-                });
-            }
+        if let Some(table) = data.get_mut(&query.table)
+            && let Some(array) = table.as_array_mut()
+        {
+            array.retain(|item| {
+                let should_delete = query
+                    .where_clause
+                    .iter()
+                    .all(|(col, val)| item.get(col) == Some(val));
+
+                if should_delete {
+                    deleted.push(item.clone());
+                }
+                !should_delete
+            });
         }
 
         store.set_data(data).unwrap();
@@ -2,9 +2,13 @@
 #![allow(dead_code)]
 
 mod adaptor;
-mod consts;
+pub mod consts;
+#[cfg(all(feature = "adapt_diesel", feature = "backend_sqlite"))]
+mod diesel;
+mod fixtures;
 mod json_store;
 mod provider;
+mod session;
 mod signals;
 
 pub mod environment;
@@ -12,6 +16,10 @@ pub mod provider_server;
 pub mod runtime;
 
 pub use adaptor::*;
+#[cfg(all(feature = "adapt_diesel", feature = "backend_sqlite"))]
+pub use diesel::*;
+pub use fixtures::*;
 pub use json_store::*;
 pub use provider::*;
+pub use session::*;
 pub use signals::*;
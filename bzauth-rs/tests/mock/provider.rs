@@ -2,10 +2,18 @@ use std::ops::Deref;
 
 use bzauth_rs::contracts::endpoint::Endpoint;
 use bzauth_rs::contracts::profile::Profile;
-use bzauth_rs::contracts::provide::{ProvideOAuth2, ProviderType, ProvidesProfile};
+use bzauth_rs::contracts::provide::{
+    ProvideOAuth2, ProviderOAuth2Check, ProviderType, ProvidesProfile,
+};
 use bzauth_rs::contracts::user::User;
+use bzauth_rs::providers::discord::DiscordProviderOptions;
+use bzauth_rs::providers::github::GithubProviderOptions;
+use bzauth_rs::providers::oidc::OidcProviderOptions;
+use bzauth_rs::providers::{DiscordProvider, GithubProvider, OidcProvider};
 
-use crate::mock::consts::{MOCK_AUTHORISE, MOCK_PROFILE, MOCK_TOKEN};
+use crate::mock::consts::{
+    MOCK_AUTHORISE, MOCK_DISCORD, MOCK_GITHUB, MOCK_PROFILE, MOCK_REVOKE, MOCK_TOKEN,
+};
 
 pub const MOCK_PROVIDER_NAME: &str = "MockProvider";
 pub const MOCK_PROVIDER_CLIENT_ID: &str = "mock_client_id";
@@ -14,6 +22,8 @@ pub const MOCK_PROVIDER_HOST: &str = "localhost";
 pub const MOCK_PROVIDER_PORT: u16 = 8081;
 pub const MOCK_PROVIDER_URL: &str = "http://localhost:8081";
 
+pub const MOCK_OIDC_PROVIDER_NAME: &str = "MockOidc";
+
 pub const MOCK_PROVIDER_USER_ID: &str = "mock_user_id";
 pub const MOCK_PROVIDER_USER_NAME: &str = "Mock User";
 pub const MOCK_PROVIDER_USER_EMAIL: &str = "mock_user@email.com";
@@ -54,6 +64,18 @@ impl ProvideOAuth2 for MockProvider {
     fn profile_endpoint(&self) -> Endpoint {
         format!("{}/{}", MOCK_PROVIDER_URL, MOCK_PROFILE).into()
     }
+
+    fn revocation_endpoint(&self) -> Option<Endpoint> {
+        Some(format!("{}/{}", MOCK_PROVIDER_URL, MOCK_REVOKE).into())
+    }
+
+    fn scopes(&self) -> Vec<String> {
+        vec!["read".to_string()]
+    }
+
+    fn checks(&self) -> Vec<ProviderOAuth2Check> {
+        vec![ProviderOAuth2Check::State, ProviderOAuth2Check::PKCE]
+    }
 }
 
 pub struct MockProfile(Profile);
@@ -78,7 +100,95 @@ impl ProvidesProfile for MockProvider {
             username: Some(MOCK_PROVIDER_USER_NAME.to_string()),
             email: Some(MOCK_PROVIDER_USER_EMAIL.to_string()),
             image: Some(MOCK_PROVIDER_USER_IMAGE.to_string()),
+            email_verified: None,
         };
         Box::new(user)
     }
 }
+
+/// A generic OpenID Connect provider, discovered from the mock provider server
+pub fn mock_oidc_provider() -> OidcProvider {
+    OidcProvider::from_options(OidcProviderOptions {
+        id: Some(MOCK_OIDC_PROVIDER_NAME.to_string()),
+        name: Some(MOCK_OIDC_PROVIDER_NAME.to_string()),
+        issuer: Some(MOCK_PROVIDER_URL.to_string()),
+        client_id: Some(MOCK_PROVIDER_CLIENT_ID.to_string()),
+        client_secret: Some(MOCK_PROVIDER_CLIENT_SECRET.to_string()),
+        ..Default::default()
+    })
+    .expect("Failed to create the mock OIDC provider")
+}
+
+/// A GitHub provider, pointed at the mock GitHub Enterprise Server
+pub fn mock_github_provider() -> GithubProvider {
+    GithubProvider::from_options(GithubProviderOptions {
+        client_id: Some(MOCK_PROVIDER_CLIENT_ID.to_string()),
+        client_secret: Some(MOCK_PROVIDER_CLIENT_SECRET.to_string()),
+        base_url: Some(format!("{}/{}", MOCK_PROVIDER_URL, MOCK_GITHUB)),
+        ..Default::default()
+    })
+    .expect("Failed to create the mock GitHub provider")
+}
+
+/// A Discord provider pointed at the mock Discord API. Discord's endpoints cannot be configured,
+/// so the profile is resolved by a real Discord provider
+#[derive(Debug, Clone)]
+pub struct MockDiscordProvider(DiscordProvider);
+
+pub fn mock_discord_provider() -> MockDiscordProvider {
+    let provider = DiscordProvider::from_options(DiscordProviderOptions {
+        client_id: Some(MOCK_PROVIDER_CLIENT_ID.to_string()),
+        client_secret: Some(MOCK_PROVIDER_CLIENT_SECRET.to_string()),
+        ..Default::default()
+    })
+    .expect("Failed to create the mock Discord provider");
+    MockDiscordProvider(provider)
+}
+
+impl ProvideOAuth2 for MockDiscordProvider {
+    fn id(&self) -> String {
+        self.0.id()
+    }
+
+    fn name(&self) -> String {
+        self.0.name()
+    }
+
+    fn provider_type(&self) -> ProviderType {
+        self.0.provider_type()
+    }
+
+    fn client_id(&self) -> String {
+        self.0.client_id()
+    }
+
+    fn client_secret(&self) -> String {
+        self.0.client_secret()
+    }
+
+    fn auth_endpoint(&self) -> Endpoint {
+        format!("{}/{}", MOCK_PROVIDER_URL, MOCK_AUTHORISE).into()
+    }
+
+    fn token_endpoint(&self) -> Endpoint {
+        format!("{}/{}", MOCK_PROVIDER_URL, MOCK_TOKEN).into()
+    }
+
+    fn profile_endpoint(&self) -> Endpoint {
+        format!("{}/{}/users/@me", MOCK_PROVIDER_URL, MOCK_DISCORD).into()
+    }
+
+    fn scopes(&self) -> Vec<String> {
+        self.0.scopes()
+    }
+
+    fn checks(&self) -> Vec<ProviderOAuth2Check> {
+        self.0.checks()
+    }
+}
+
+impl ProvidesProfile for MockDiscordProvider {
+    fn get_profile(&self, profile: Profile) -> Box<User> {
+        self.0.get_profile(profile)
+    }
+}
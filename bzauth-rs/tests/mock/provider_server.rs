@@ -1,21 +1,64 @@
 use oauth2::{EndpointNotSet, EndpointSet};
 
-use crate::mock::consts::{MOCK_AUTHORISE, MOCK_CALLBACK, MOCK_PROFILE, MOCK_TOKEN};
+use crate::mock::consts::{
+    MOCK_AUTHORISE, MOCK_CALLBACK, MOCK_DISCORD, MOCK_DISCORD_USER_EMAIL, MOCK_DISCORD_USER_ID,
+    MOCK_DISCOVERY, MOCK_END_SESSION, MOCK_GITHUB, MOCK_GITHUB_USER_EMAIL, MOCK_GITHUB_USER_ID,
+    MOCK_GITHUB_USER_LOGIN, MOCK_JWKS, MOCK_JWKS_JSON, MOCK_JWKS_RSA_KID, MOCK_JWKS_RSA_PEM,
+    MOCK_PROFILE, MOCK_REVOKE, MOCK_TOKEN,
+};
 use crate::mock::provider::{MOCK_PROVIDER_HOST, MOCK_PROVIDER_PORT};
 use crate::mock::{MOCK_PROVIDER_CLIENT_ID, MOCK_PROVIDER_CLIENT_SECRET};
 
+/// The nonce of the last authorisation request, echoed back in the id_token
+static LAST_NONCE: std::sync::Mutex<Option<String>> = std::sync::Mutex::new(None);
+
+/// The tokens revoked at the revocation endpoint, with their type hints
+static REVOKED_TOKENS: std::sync::Mutex<Vec<(String, String)>> = std::sync::Mutex::new(Vec::new());
+
+/// The tokens revoked so far, with their type hints, in the order they were revoked
+pub fn revoked_tokens() -> Vec<(String, String)> {
+    REVOKED_TOKENS.lock().unwrap().clone()
+}
+
+/// The token the revocation endpoint fails to revoke
+pub const MOCK_UNREVOCABLE_TOKEN: &str = "mock_unrevocable_token";
+
+/// The refresh token the token endpoint holds until the race is settled, then refuses
+pub const MOCK_RACED_REFRESH_TOKEN: &str = "mock_raced_refresh_token";
+
+static RACE_STARTED: tokio::sync::Notify = tokio::sync::Notify::const_new();
+static RACE_SETTLED: tokio::sync::Notify = tokio::sync::Notify::const_new();
+
+/// Waits until the token endpoint holds a refresh with [`MOCK_RACED_REFRESH_TOKEN`]
+pub async fn refresh_race_started() {
+    RACE_STARTED.notified().await;
+}
+
+/// Lets the token endpoint refuse the held refresh
+pub fn settle_refresh_race() {
+    RACE_SETTLED.notify_one();
+}
+
 pub mod axum_ {
 
-    use axum::Json;
-    use axum::response::Html;
+    use std::collections::HashMap;
+
+    use axum::extract::Query;
+    use axum::http::header::{AUTHORIZATION, CONTENT_TYPE, USER_AGENT};
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::response::{Html, IntoResponse, Response};
     use axum::routing::{Router, get, post};
+    use axum::{Form, Json};
+    use jsonwebtoken::{Algorithm, EncodingKey, Header};
 
     use super::*;
     use crate::mock::runtime::MOCK_AUTH_URL;
-    use crate::mock::{MOCK_PROVIDER_NAME, Signals};
+    use crate::mock::{MOCK_PROVIDER_NAME, MOCK_PROVIDER_URL, Signals};
 
-    async fn authorise() -> Html<String> {
+    async fn authorise(Query(query): Query<HashMap<String, String>>) -> Html<String> {
         println!("Mock Authorisation Endpoint Hit");
+        *LAST_NONCE.lock().unwrap() = query.get("nonce").cloned();
+
         format!(
             r#"
             <!DOCTYPE html>
@@ -33,10 +76,9 @@ pub mod axum_ {
                     // Simulate a redirect to the callback URL
                     let code = window.location.search.split('code=')[1];
                     let state = window.location.search.split('state=')[1];
-                    window.location.href = '{}?code=' + code + '&state=' + state;
+                    window.location.href = '{MOCK_AUTH_URL}/{MOCK_CALLBACK}/{MOCK_PROVIDER_NAME}?code=' + code + '&state=' + state;
                 </script>
-            </html>"#,
-            format!("{}/{}/{}", MOCK_AUTH_URL, MOCK_CALLBACK, MOCK_PROVIDER_NAME)
+            </html>"#
         )
         .to_string()
         .into()
@@ -46,16 +88,138 @@ pub mod axum_ {
         "This is a mock callback".to_string()
     }
 
-    async fn token() -> Json<serde_json::Value> {
+    async fn token(Form(form): Form<HashMap<String, String>>) -> Response {
         println!("Mock Token Endpoint Hit");
+
+        // The refresh tokens it issued are rotated, any other is refused
+        if form.get("grant_type").map(String::as_str) == Some("refresh_token") {
+            let refresh_token = form.get("refresh_token").map(String::as_str);
+            if refresh_token == Some(MOCK_RACED_REFRESH_TOKEN) {
+                RACE_STARTED.notify_one();
+                RACE_SETTLED.notified().await;
+            }
+            if !matches!(
+                refresh_token,
+                Some("mock_refresh_token" | "mock_rotated_refresh_token")
+            ) {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(serde_json::json!({
+                        "error": "invalid_grant",
+                        "error_description": "Unknown refresh token",
+                    })),
+                )
+                    .into_response();
+            }
+
+            return Json(serde_json::json!({
+                "access_token": "mock_refreshed_access_token",
+                "token_type": "Bearer",
+                "expires_in": 3600,
+                "refresh_token": "mock_rotated_refresh_token",
+            }))
+            .into_response();
+        }
+
+        // The mock provider is a public client, so it refuses exchanges without PKCE
+        if !form.contains_key("code_verifier") {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({
+                    "error": "invalid_request",
+                    "error_description": "Missing code_verifier",
+                })),
+            )
+                .into_response();
+        }
+
         Json(serde_json::json!({
             "access_token": "mock_access_token",
             "token_type": "Bearer",
             "expires_in": 3600,
             "refresh_token": "mock_refresh_token",
+            "id_token": id_token(),
+        }))
+        .into_response()
+    }
+
+    /// An id_token for the userinfo user, bound to the last nonce
+    fn id_token() -> String {
+        let now = chrono::Utc::now().timestamp();
+        let claims = serde_json::json!({
+            "iss": MOCK_PROVIDER_URL,
+            "sub": "1234567890",
+            "aud": MOCK_PROVIDER_CLIENT_ID,
+            "exp": now + 3600,
+            "iat": now,
+            "nonce": LAST_NONCE.lock().unwrap().clone(),
+            "name": "John Doe",
+            "email": "john.doe@email.com",
+        });
+
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = Some(MOCK_JWKS_RSA_KID.to_string());
+        let key = EncodingKey::from_rsa_pem(MOCK_JWKS_RSA_PEM.as_bytes()).unwrap();
+        jsonwebtoken::encode(&header, &claims, &key).unwrap()
+    }
+
+    async fn jwks() -> Json<serde_json::Value> {
+        println!("Mock JWKS Endpoint Hit");
+        Json(serde_json::from_str(MOCK_JWKS_JSON).unwrap())
+    }
+
+    async fn discovery() -> Json<serde_json::Value> {
+        println!("Mock Discovery Endpoint Hit");
+        Json(serde_json::json!({
+            "issuer": MOCK_PROVIDER_URL,
+            "authorization_endpoint": format!("{}/{}", MOCK_PROVIDER_URL, MOCK_AUTHORISE),
+            "token_endpoint": format!("{}/{}", MOCK_PROVIDER_URL, MOCK_TOKEN),
+            "userinfo_endpoint": format!("{}/{}", MOCK_PROVIDER_URL, MOCK_PROFILE),
+            "jwks_uri": format!("{}/{}", MOCK_PROVIDER_URL, MOCK_JWKS),
+            "end_session_endpoint": format!("{}/{}", MOCK_PROVIDER_URL, MOCK_END_SESSION),
+            "revocation_endpoint": format!("{}/{}", MOCK_PROVIDER_URL, MOCK_REVOKE),
+            "id_token_signing_alg_values_supported": ["RS256"],
         }))
     }
 
+    /// Revokes the token if the client authenticates, as in RFC 7009
+    async fn revoke(headers: HeaderMap, Form(form): Form<HashMap<String, String>>) -> Response {
+        use base64::Engine;
+        println!("Mock Revocation Endpoint Hit");
+
+        let credentials = base64::engine::general_purpose::STANDARD.encode(format!(
+            "{}:{}",
+            MOCK_PROVIDER_CLIENT_ID, MOCK_PROVIDER_CLIENT_SECRET
+        ));
+        if headers.get(AUTHORIZATION).and_then(|v| v.to_str().ok())
+            != Some(format!("Basic {}", credentials).as_str())
+        {
+            return (
+                StatusCode::UNAUTHORIZED,
+                Json(serde_json::json!({ "error": "invalid_client" })),
+            )
+                .into_response();
+        }
+
+        let Some(token) = form.get("token").cloned() else {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "error": "invalid_request" })),
+            )
+                .into_response();
+        };
+        if token == MOCK_UNREVOCABLE_TOKEN {
+            return StatusCode::SERVICE_UNAVAILABLE.into_response();
+        }
+
+        let token_type_hint = form.get("token_type_hint").cloned().unwrap_or_default();
+        REVOKED_TOKENS
+            .lock()
+            .unwrap()
+            .push((token, token_type_hint));
+        StatusCode::OK.into_response()
+    }
+
     async fn userinfo() -> Json<serde_json::Value> {
         println!("Mock Userinfo Endpoint Hit");
         Json(serde_json::json!({
@@ -65,12 +229,116 @@ pub mod axum_ {
         }))
     }
 
+    /// Like GitHub without `Accept: application/json`, the token is always form-encoded
+    async fn github_token(Form(form): Form<HashMap<String, String>>) -> Response {
+        println!("Mock GitHub Token Endpoint Hit");
+
+        if !form.contains_key("code") {
+            return (
+                [(CONTENT_TYPE, "application/x-www-form-urlencoded")],
+                "error=bad_verification_code",
+            )
+                .into_response();
+        }
+
+        (
+            [(CONTENT_TYPE, "application/x-www-form-urlencoded")],
+            "access_token=mock_github_token&expires_in=28800&scope=read%3Auser%2Cuser%3Aemail&token_type=bearer",
+        )
+            .into_response()
+    }
+
+    /// GitHub refuses API requests without a User-Agent header
+    fn github_api(headers: &HeaderMap, body: serde_json::Value) -> Response {
+        if !headers.contains_key(USER_AGENT) {
+            return (StatusCode::FORBIDDEN, "Missing User-Agent header").into_response();
+        }
+        if headers.get(AUTHORIZATION).and_then(|v| v.to_str().ok())
+            != Some("Bearer mock_github_token")
+        {
+            return StatusCode::UNAUTHORIZED.into_response();
+        }
+
+        Json(body).into_response()
+    }
+
+    async fn github_user(headers: HeaderMap) -> Response {
+        println!("Mock GitHub User Endpoint Hit");
+        // The user keeps their email private
+        github_api(
+            &headers,
+            serde_json::json!({
+                "id": MOCK_GITHUB_USER_ID,
+                "login": MOCK_GITHUB_USER_LOGIN,
+                "name": null,
+                "email": null,
+                "avatar_url": "https://avatars.githubusercontent.com/u/1234567890",
+                "html_url": format!("https://github.com/{}", MOCK_GITHUB_USER_LOGIN),
+            }),
+        )
+    }
+
+    async fn github_emails(headers: HeaderMap) -> Response {
+        println!("Mock GitHub Emails Endpoint Hit");
+        github_api(
+            &headers,
+            serde_json::json!([
+                { "email": "unverified@email.com", "primary": false, "verified": false },
+                { "email": MOCK_GITHUB_USER_EMAIL, "primary": true, "verified": true },
+                { "email": "secondary@email.com", "primary": false, "verified": true },
+            ]),
+        )
+    }
+
+    /// Discord says whether the email is verified with `verified`, not `email_verified`
+    async fn discord_user(headers: HeaderMap) -> Response {
+        println!("Mock Discord User Endpoint Hit");
+        if headers.get(AUTHORIZATION).and_then(|v| v.to_str().ok())
+            != Some("Bearer mock_access_token")
+        {
+            return StatusCode::UNAUTHORIZED.into_response();
+        }
+
+        Json(serde_json::json!({
+            "id": MOCK_DISCORD_USER_ID,
+            "username": "nelly",
+            "discriminator": "0",
+            "avatar": null,
+            "email": MOCK_DISCORD_USER_EMAIL,
+            "verified": true,
+        }))
+        .into_response()
+    }
+
     fn router() -> Router {
         Router::new()
             .route(format!("/{}", MOCK_AUTHORISE).as_str(), get(authorise))
             .route(format!("/{}", MOCK_CALLBACK).as_str(), get(callback))
             .route(format!("/{}", MOCK_TOKEN).as_str(), post(token))
             .route(format!("/{}", MOCK_PROFILE).as_str(), get(userinfo))
+            .route(format!("/{}", MOCK_DISCOVERY).as_str(), get(discovery))
+            .route(format!("/{}", MOCK_JWKS).as_str(), get(jwks))
+            .route(format!("/{}", MOCK_REVOKE).as_str(), post(revoke))
+            .route(
+                format!("/{}/users/@me", MOCK_DISCORD).as_str(),
+                get(discord_user),
+            )
+            .route(
+                format!("/{}/login/oauth/authorize", MOCK_GITHUB).as_str(),
+                get(authorise),
+            )
+            .route(
+                format!("/{}/login/oauth/access_token", MOCK_GITHUB).as_str(),
+                post(github_token),
+            )
+            .route(
+                format!("/{}/api/v3/user", MOCK_GITHUB).as_str(),
+                get(github_user),
+            )
+            .route(
+                format!("/{}/api/v3/user/emails", MOCK_GITHUB).as_str(),
+                get(github_emails),
+            )
     }
 
     pub async fn start(signals: Signals) -> Result<(), std::io::Error> {
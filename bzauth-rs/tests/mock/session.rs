@@ -0,0 +1,151 @@
+use reqwest::StatusCode;
+use reqwest::header::{CACHE_CONTROL, COOKIE, LOCATION, SET_COOKIE};
+
+use crate::mock::MOCK_PROVIDER_NAME;
+use crate::mock::runtime::MOCK_AUTH_URL;
+
+/// A client that does not follow redirects, so that they can be checked
+pub fn client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .expect("Failed to build client")
+}
+
+/// Signs in with the mock provider, returning the `Set-Cookie` headers of the callback
+pub async fn sign_in() -> Vec<String> {
+    let response = sign_in_with(MOCK_PROVIDER_NAME).await;
+    assert_eq!(
+        response.status(),
+        StatusCode::FOUND,
+        "Sign in did not redirect"
+    );
+
+    set_cookies(&response)
+}
+
+/// Goes through the login, authorisation and callback of a provider of the mock provider server,
+/// returning the response of the callback
+pub async fn sign_in_with(provider_id: &str) -> reqwest::Response {
+    let client = client();
+
+    let response = client
+        .get(format!("{}/login/{}", MOCK_AUTH_URL, provider_id))
+        .send()
+        .await
+        .expect("Failed to make request to auth server");
+    let location = response
+        .headers()
+        .get(LOCATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| reqwest::Url::parse(v).ok())
+        .expect("Login response has no location");
+    let state = location
+        .query_pairs()
+        .find(|(k, _)| k == "state")
+        .map(|(_, v)| v.to_string())
+        .expect("Login location has no state");
+
+    // Visit the provider's authorisation page, as the browser would, so that it keeps the nonce
+    let authorised = reqwest::get(location.clone())
+        .await
+        .expect("Failed to make request to provider");
+    assert!(
+        authorised.status().is_success(),
+        "Authorisation page failed"
+    );
+
+    let cookies = response
+        .headers()
+        .get_all(SET_COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .filter_map(|v| v.split(';').next())
+        .collect::<Vec<_>>()
+        .join("; ");
+
+    client
+        .get(format!("{}/callback/{}", MOCK_AUTH_URL, provider_id))
+        .query(&[("code", "mock_auth_code"), ("state", state.as_str())])
+        .header(COOKIE, cookies)
+        .send()
+        .await
+        .expect("Failed to make request to auth server")
+}
+
+/// The `Set-Cookie` headers of the response
+pub fn set_cookies(response: &reqwest::Response) -> Vec<String> {
+    response
+        .headers()
+        .get_all(SET_COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .map(String::from)
+        .collect()
+}
+
+/// Finds the session cookie among the `Set-Cookie` headers
+pub fn session_cookie(set_cookies: &[String]) -> String {
+    set_cookies
+        .iter()
+        .find(|c| c.starts_with("session_token="))
+        .cloned()
+        .unwrap_or_else(|| panic!("No session cookie was set: {:?}", set_cookies))
+}
+
+/// Requests `/session` with the given session cookie, returning the body and `Set-Cookie` headers
+pub async fn get_session(cookie: Option<&str>) -> (serde_json::Value, Vec<String>) {
+    let mut request = reqwest::Client::new().get(format!("{}/session", MOCK_AUTH_URL));
+    if let Some(cookie) = cookie {
+        let cookie = cookie.split(';').next().unwrap_or_default().to_string();
+        request = request.header(COOKIE, cookie);
+    }
+
+    let response = request
+        .send()
+        .await
+        .expect("Failed to make request to auth server");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response
+            .headers()
+            .get(CACHE_CONTROL)
+            .and_then(|v| v.to_str().ok()),
+        Some("no-store")
+    );
+
+    let set_cookies = response
+        .headers()
+        .get_all(SET_COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .map(String::from)
+        .collect();
+    let body = response.json().await.expect("Session is not JSON");
+    (body, set_cookies)
+}
+
+/// Fetches a CSRF token, returning it with the cookie it is bound to
+pub async fn csrf_token() -> (String, String) {
+    let response = reqwest::get(format!("{}/csrf", MOCK_AUTH_URL))
+        .await
+        .expect("Failed to make request to auth server");
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let cookie = response
+        .headers()
+        .get_all(SET_COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .find(|v| v.starts_with("csrf="))
+        .and_then(|v| v.split(';').next())
+        .map(String::from)
+        .expect("No CSRF cookie was set");
+    let body: serde_json::Value = response.json().await.expect("CSRF token is not JSON");
+    let token = body["csrf_token"]
+        .as_str()
+        .map(String::from)
+        .expect("No CSRF token was returned");
+
+    (token, cookie)
+}
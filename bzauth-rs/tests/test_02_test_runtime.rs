@@ -28,7 +28,7 @@ async fn test_runtime_server() {
     // Run the server in a separate task
     let server_handle = tokio::spawn(async move {
         if let Err(e) = server_future.await {
-            assert!(false, "Failed to start mock auth server: {}", e);
+            panic!("Failed to start mock auth server: {}", e);
         }
     });
 
@@ -4,8 +4,95 @@ use bzauth_rs::auth::AuthOptions;
 use bzauth_rs::runtimes::axum::AxumRuntimeOptions;
 use mock::runtime::MOCK_AUTH_URL;
 use mock::{JsonStore, JsonStoreTypes, MOCK_PROVIDER_NAME, MockAdaptor, MockProvider};
+use reqwest::header::{LOCATION, SET_COOKIE};
+use reqwest::{StatusCode, Url};
 use tempfile::NamedTempFile;
 
+/// Starts a login flow against the runtime, returning the provider redirect and the cookies set
+async fn start_login() -> (Url, String) {
+    start_login_with(&[]).await
+}
+
+/// Starts a login flow with extra query parameters
+async fn start_login_with(query: &[(&str, &str)]) -> (Url, String) {
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .expect("Failed to build client");
+
+    let response = client
+        .get(format!("{}/login/{}", MOCK_AUTH_URL, MOCK_PROVIDER_NAME))
+        .query(query)
+        .send()
+        .await
+        .expect("Failed to make request to auth server");
+    assert_eq!(
+        response.status(),
+        StatusCode::FOUND,
+        "Login did not redirect"
+    );
+
+    let location = response
+        .headers()
+        .get(LOCATION)
+        .and_then(|v| v.to_str().ok())
+        .expect("Login response has no location");
+    let location = Url::parse(location).expect("Login location is not a URL");
+
+    // Replay the cookies as a single Cookie header, dropping the attributes
+    let cookies = response
+        .headers()
+        .get_all(SET_COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .filter_map(|v| v.split(';').next())
+        .collect::<Vec<_>>()
+        .join("; ");
+
+    (location, cookies)
+}
+
+/// Reads the state sent to the provider from the login redirect
+fn state_of(location: &Url) -> String {
+    location
+        .query_pairs()
+        .find(|(k, _)| k == "state")
+        .map(|(_, v)| v.to_string())
+        .expect("Login location has no state")
+}
+
+/// Sends a callback to the runtime with the given state and cookies
+async fn send_callback(state: &str, cookies: &str) -> reqwest::Response {
+    reqwest::Client::new()
+        .get(format!("{}/callback/{}", MOCK_AUTH_URL, MOCK_PROVIDER_NAME))
+        .query(&[("code", "mock_auth_code"), ("state", state)])
+        .header(reqwest::header::COOKIE, cookies)
+        .send()
+        .await
+        .expect("Failed to make request to auth server")
+}
+
+/// Asserts that the one-time cookies are cleared by the response
+fn assert_cleared_cookies(response: &reqwest::Response) {
+    let set_cookies = response
+        .headers()
+        .get_all(SET_COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .collect::<Vec<_>>();
+
+    for name in ["state", "pkce_verifier"] {
+        assert!(
+            set_cookies
+                .iter()
+                .any(|c| c.starts_with(&format!("{}=;", name)) && c.contains("Max-Age=0")),
+            "Cookie {} was not cleared: {:?}",
+            name,
+            set_cookies
+        );
+    }
+}
+
 #[tokio::test]
 #[cfg_attr(
     not(feature = "test_sequential"),
@@ -95,11 +182,16 @@ async fn test_02_auth_server_callback() {
 
     // Start the mock auth server
     mock::environment::axum_::run(signals, options, || async {
+        // Start the login to receive the one-time cookies
+        let (location, cookies) = start_login().await;
+        let state = state_of(&location);
+
         // Simulate a callback request
         let client = reqwest::Client::new();
         let response = client
             .post(format!("{}/callback/{}", MOCK_AUTH_URL, MOCK_PROVIDER_NAME))
-            .query(&[("code", "mock_auth_code"), ("state", "mock_state")])
+            .query(&[("code", "mock_auth_code"), ("state", state.as_str())])
+            .header(reqwest::header::COOKIE, cookies)
             .send()
             .await
             .expect("Failed to make request to auth server");
@@ -127,3 +219,367 @@ async fn test_02_auth_server_callback() {
     })
     .await;
 }
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_03_auth_server_pkce() {
+    let signals = mock::Signals::new();
+
+    let tmpfile = NamedTempFile::new().expect("Failed to create temp file");
+    let path = tmpfile.path();
+
+    let json_store = JsonStore::new(&JsonStoreTypes::File(path));
+    let auth_options = AuthOptions::new()
+        .add_provider(Box::new(MockProvider))
+        .with_adaptor(Box::new(MockAdaptor::new(json_store)));
+    let options = AxumRuntimeOptions::new(auth_options);
+
+    mock::environment::axum_::run(signals, options, || async {
+        let (location, cookies) = start_login().await;
+
+        // The provider must receive an S256 challenge
+        let query = location.query_pairs().collect::<Vec<_>>();
+        assert!(
+            query
+                .iter()
+                .any(|(k, v)| k == "code_challenge_method" && v == "S256"),
+            "Missing S256 challenge method: {}",
+            location
+        );
+        assert!(
+            query.iter().any(|(k, _)| k == "code_challenge"),
+            "Missing code challenge: {}",
+            location
+        );
+
+        // The verifier must be kept for the callback
+        assert!(
+            cookies.contains("pkce_verifier="),
+            "Missing PKCE verifier cookie: {}",
+            cookies
+        );
+
+        // Without the verifier, the callback must be refused
+        let state_cookie = cookies
+            .split("; ")
+            .filter(|c| c.starts_with("state="))
+            .collect::<Vec<_>>()
+            .join("; ");
+        let response = send_callback(&state_of(&location), &state_cookie).await;
+        assert_eq!(
+            response.status(),
+            StatusCode::BAD_REQUEST,
+            "Callback without a PKCE verifier was not refused"
+        );
+    })
+    .await;
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_04_auth_server_state_mismatch() {
+    let signals = mock::Signals::new();
+
+    let tmpfile = NamedTempFile::new().expect("Failed to create temp file");
+    let path = tmpfile.path();
+
+    let json_store = JsonStore::new(&JsonStoreTypes::File(path));
+    let auth_options = AuthOptions::new()
+        .add_provider(Box::new(MockProvider))
+        .with_adaptor(Box::new(MockAdaptor::new(json_store)));
+    let options = AxumRuntimeOptions::new(auth_options);
+
+    mock::environment::axum_::run(signals, options, || async {
+        let (_, cookies) = start_login().await;
+
+        // A state that was not issued to this client must be refused
+        let response = send_callback("forged_state", &cookies).await;
+        assert_eq!(
+            response.status(),
+            StatusCode::FORBIDDEN,
+            "Callback with a mismatched state was not refused"
+        );
+        assert_cleared_cookies(&response);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_05_auth_server_state_cookie() {
+    let signals = mock::Signals::new();
+
+    let tmpfile = NamedTempFile::new().expect("Failed to create temp file");
+    let path = tmpfile.path();
+
+    let json_store = JsonStore::new(&JsonStoreTypes::File(path));
+    let auth_options = AuthOptions::new()
+        .add_provider(Box::new(MockProvider))
+        .with_adaptor(Box::new(MockAdaptor::new(json_store)));
+    let options = AxumRuntimeOptions::new(auth_options);
+
+    mock::environment::axum_::run(signals, options, || async {
+        let (location, cookies) = start_login().await;
+        let state = state_of(&location);
+
+        // The state cookie must be signed
+        let state_cookie = cookies
+            .split("; ")
+            .find_map(|c| c.strip_prefix("state="))
+            .expect("Missing state cookie");
+        assert!(
+            state_cookie != state && state_cookie.starts_with(&format!("{}.", state)),
+            "State cookie is not signed: {}",
+            state_cookie
+        );
+
+        // Without the state cookie, the callback must be refused
+        let response = send_callback(&state, "").await;
+        assert_eq!(
+            response.status(),
+            StatusCode::BAD_REQUEST,
+            "Callback without a state cookie was not refused"
+        );
+        assert_cleared_cookies(&response);
+
+        // A tampered state cookie must be refused too
+        let tampered = cookies
+            .split("; ")
+            .map(|c| match c.strip_prefix("state=") {
+                Some(_) => format!("state={}.invalid", state),
+                None => c.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("; ");
+        let response = send_callback(&state, &tampered).await;
+        assert_eq!(
+            response.status(),
+            StatusCode::BAD_REQUEST,
+            "Callback with a tampered state cookie was not refused"
+        );
+    })
+    .await;
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_06_auth_server_scopes() {
+    let signals = mock::Signals::new();
+
+    let tmpfile = NamedTempFile::new().expect("Failed to create temp file");
+    let path = tmpfile.path();
+
+    let json_store = JsonStore::new(&JsonStoreTypes::File(path));
+    let auth_options = AuthOptions::new()
+        .add_provider(Box::new(MockProvider))
+        .with_adaptor(Box::new(MockAdaptor::new(json_store)));
+    let options = AxumRuntimeOptions::new(auth_options);
+
+    mock::environment::axum_::run(signals, options, || async {
+        let param = |location: &Url, key: &str| {
+            location
+                .query_pairs()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v.to_string())
+        };
+
+        // By default, only the provider's scopes are requested
+        let (location, _) = start_login().await;
+        assert_eq!(param(&location, "scope").as_deref(), Some("read"));
+        assert_eq!(param(&location, "prompt"), None);
+
+        // Extra scopes are appended without duplicates, and the extra params are passed on
+        let (location, _) = start_login_with(&[
+            ("scope", "read write guilds"),
+            ("prompt", "consent"),
+            ("login_hint", "mock_user@email.com"),
+            ("access_type", "offline"),
+        ])
+        .await;
+        assert_eq!(
+            param(&location, "scope").as_deref(),
+            Some("read write guilds")
+        );
+        assert_eq!(param(&location, "prompt").as_deref(), Some("consent"));
+        assert_eq!(
+            param(&location, "login_hint").as_deref(),
+            Some("mock_user@email.com")
+        );
+        assert_eq!(param(&location, "access_type").as_deref(), Some("offline"));
+    })
+    .await;
+}
+
+/// Starts a login at the given path, returning the redirect URI sent to the provider
+async fn login_redirect_uri(path: &str, headers: &[(&str, &str)]) -> String {
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .expect("Failed to build client");
+
+    let mut request = client.get(format!(
+        "{}{}/login/{}",
+        MOCK_AUTH_URL, path, MOCK_PROVIDER_NAME
+    ));
+    for (key, value) in headers {
+        request = request.header(*key, *value);
+    }
+    let response = request
+        .send()
+        .await
+        .expect("Failed to make request to auth server");
+    assert_eq!(
+        response.status(),
+        StatusCode::FOUND,
+        "Login did not redirect"
+    );
+
+    let location = response
+        .headers()
+        .get(LOCATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| Url::parse(v).ok())
+        .expect("Login response has no location");
+    location
+        .query_pairs()
+        .find(|(k, _)| k == "redirect_uri")
+        .map(|(_, v)| v.to_string())
+        .expect("Login location has no redirect URI")
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_07_auth_server_inferred_url() {
+    let signals = mock::Signals::new();
+
+    let tmpfile = NamedTempFile::new().expect("Failed to create temp file");
+    let path = tmpfile.path();
+
+    let json_store = JsonStore::new(&JsonStoreTypes::File(path));
+    let auth_options = AuthOptions::new()
+        .add_provider(Box::new(MockProvider))
+        .with_adaptor(Box::new(MockAdaptor::new(json_store)));
+    let options = AxumRuntimeOptions::new(auth_options);
+
+    mock::environment::axum_::run(signals, options, || async {
+        let expected = format!("{}/callback/{}", MOCK_AUTH_URL, MOCK_PROVIDER_NAME);
+
+        // The origin is inferred from the host
+        assert_eq!(login_redirect_uri("", &[]).await, expected);
+
+        // The proxy headers are ignored unless the proxy is trusted
+        let headers = [
+            ("x-forwarded-proto", "https"),
+            ("x-forwarded-host", "evil.example.com"),
+        ];
+        assert_eq!(login_redirect_uri("", &headers).await, expected);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_08_auth_server_base_url() {
+    let signals = mock::Signals::new();
+
+    let tmpfile = NamedTempFile::new().expect("Failed to create temp file");
+    let path = tmpfile.path();
+
+    let json_store = JsonStore::new(&JsonStoreTypes::File(path));
+    let auth_options = AuthOptions::new()
+        .add_provider(Box::new(MockProvider))
+        .with_adaptor(Box::new(MockAdaptor::new(json_store)))
+        .with_base_url("https://app.example.com/api/auth/")
+        .with_trust_proxy(true);
+    let options = AxumRuntimeOptions::new(auth_options);
+
+    mock::environment::axum_::run(signals, options, || async {
+        // The routes are mounted at the base path, and the base URL wins over any proxy headers
+        let headers = [("x-forwarded-host", "evil.example.com")];
+        assert_eq!(
+            login_redirect_uri("/api/auth", &headers).await,
+            format!(
+                "https://app.example.com/api/auth/callback/{}",
+                MOCK_PROVIDER_NAME
+            )
+        );
+
+        // The routes are no longer at the root
+        let response = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .expect("Failed to build client")
+            .get(format!("{}/login/{}", MOCK_AUTH_URL, MOCK_PROVIDER_NAME))
+            .send()
+            .await
+            .expect("Failed to make request to auth server");
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_09_auth_server_trust_proxy() {
+    let signals = mock::Signals::new();
+
+    let tmpfile = NamedTempFile::new().expect("Failed to create temp file");
+    let path = tmpfile.path();
+
+    let json_store = JsonStore::new(&JsonStoreTypes::File(path));
+    let auth_options = AuthOptions::new()
+        .add_provider(Box::new(MockProvider))
+        .with_adaptor(Box::new(MockAdaptor::new(json_store)))
+        .with_trust_proxy(true);
+    let options = AxumRuntimeOptions::new(auth_options);
+
+    mock::environment::axum_::run(signals, options, || async {
+        let expected = format!("https://app.example.com/callback/{}", MOCK_PROVIDER_NAME);
+
+        // The closest proxy is used from the X-Forwarded-* headers
+        let headers = [
+            ("x-forwarded-proto", "http, https"),
+            ("x-forwarded-host", "app.example.com"),
+        ];
+        assert_eq!(login_redirect_uri("", &headers).await, expected);
+
+        // The values sent by the client come before the ones appended by the proxy
+        let headers = [
+            ("x-forwarded-proto", "https"),
+            ("x-forwarded-host", "evil.example.com, app.example.com"),
+        ];
+        assert_eq!(login_redirect_uri("", &headers).await, expected);
+
+        // The Forwarded header takes precedence
+        let headers = [
+            (
+                "forwarded",
+                "for=192.0.2.60;host=\"evil.example.com\", for=198.51.100.17;proto=https;host=\"app.example.com\"",
+            ),
+            ("x-forwarded-host", "other.example.com"),
+        ];
+        assert_eq!(login_redirect_uri("", &headers).await, expected);
+    })
+    .await;
+}
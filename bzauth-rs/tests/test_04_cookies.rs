@@ -0,0 +1,49 @@
+use bzauth_rs::tools::cookie::{Cookie, Cookies};
+
+#[test]
+fn test_parse_request_cookies() {
+    let cookies: Cookies = "state=abc; csrf=def; pkce_verifier=ghi=="
+        .parse()
+        .expect("Failed to parse cookies");
+
+    assert_eq!(
+        cookies.get("state").and_then(|c| c.value).as_deref(),
+        Some("abc")
+    );
+    assert_eq!(
+        cookies.get("csrf").and_then(|c| c.value).as_deref(),
+        Some("def")
+    );
+    assert_eq!(
+        cookies
+            .get("pkce_verifier")
+            .and_then(|c| c.value)
+            .as_deref(),
+        Some("ghi==")
+    );
+}
+
+#[test]
+fn test_parse_set_cookie() {
+    let cookie: Cookie = "__Secure-session=xyz; Path=/; Secure; HttpOnly; SameSite=Lax; Max-Age=60"
+        .parse()
+        .expect("Failed to parse cookie");
+
+    assert_eq!(cookie.name, "session");
+    assert_eq!(cookie.value.as_deref(), Some("xyz"));
+    assert_eq!(cookie.path.as_deref(), Some("/"));
+    assert!(cookie.secure);
+    assert!(cookie.http_only);
+    assert_eq!(cookie.max_age, Some(60));
+}
+
+#[test]
+fn test_expire_cookie() {
+    let mut cookies = Cookies::new();
+    cookies.set("pkce_verifier", "abc");
+    cookies.expire("pkce_verifier");
+
+    let cookie = cookies.get("pkce_verifier").expect("Cookie is missing");
+    assert_eq!(cookie.value.as_deref(), Some(""));
+    assert_eq!(cookie.max_age, Some(0));
+}
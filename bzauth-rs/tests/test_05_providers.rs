@@ -0,0 +1,128 @@
+use bzauth_rs::contracts::profile::Profile;
+use bzauth_rs::contracts::provide::{ProvideOAuth2, ProvidesProfile};
+use bzauth_rs::providers::discord::DiscordProviderOptions;
+use bzauth_rs::providers::github::GithubProviderOptions;
+use bzauth_rs::providers::google::GoogleProviderOptions;
+use bzauth_rs::providers::{DiscordProvider, GithubProvider, GoogleProvider};
+
+#[test]
+fn test_default_scopes() {
+    let google = GoogleProvider::from_options(GoogleProviderOptions {
+        client_id: Some("client_id".to_string()),
+        client_secret: Some("client_secret".to_string()),
+        ..Default::default()
+    })
+    .expect("Failed to create Google provider");
+    assert_eq!(google.scopes(), vec!["openid", "email", "profile"]);
+
+    let discord = DiscordProvider::from_options(DiscordProviderOptions {
+        client_id: Some("client_id".to_string()),
+        client_secret: Some("client_secret".to_string()),
+        ..Default::default()
+    })
+    .expect("Failed to create Discord provider");
+    assert_eq!(discord.scopes(), vec!["identify", "email"]);
+
+    let github = GithubProvider::from_options(GithubProviderOptions {
+        client_id: Some("client_id".to_string()),
+        client_secret: Some("client_secret".to_string()),
+        ..Default::default()
+    })
+    .expect("Failed to create GitHub provider");
+    assert_eq!(github.scopes(), vec!["read:user", "user:email"]);
+}
+
+#[test]
+fn test_configured_scopes() {
+    let discord = DiscordProvider::from_options(DiscordProviderOptions {
+        client_id: Some("client_id".to_string()),
+        client_secret: Some("client_secret".to_string()),
+        scopes: Some(vec![
+            "identify".to_string(),
+            "email".to_string(),
+            "guilds".to_string(),
+        ]),
+        ..Default::default()
+    })
+    .expect("Failed to create Discord provider");
+    assert_eq!(discord.scopes(), vec!["identify", "email", "guilds"]);
+
+    // The scopes are no longer smuggled into the authorisation endpoint
+    assert!(!discord.auth_endpoint().url().contains("scope"));
+}
+
+#[test]
+fn test_github_endpoints() {
+    let github = GithubProvider::from_options(GithubProviderOptions {
+        client_id: Some("client_id".to_string()),
+        client_secret: Some("client_secret".to_string()),
+        ..Default::default()
+    })
+    .expect("Failed to create GitHub provider");
+    assert_eq!(
+        github.auth_endpoint().url(),
+        "https://github.com/login/oauth/authorize"
+    );
+    assert_eq!(
+        github.token_endpoint().url(),
+        "https://github.com/login/oauth/access_token"
+    );
+    assert_eq!(
+        github.profile_endpoint().url(),
+        "https://api.github.com/user"
+    );
+    assert_eq!(
+        github.emails_endpoint().url(),
+        "https://api.github.com/user/emails"
+    );
+
+    // GitHub Enterprise Server serves the API under the instance
+    let enterprise = GithubProvider::from_options(GithubProviderOptions {
+        client_id: Some("client_id".to_string()),
+        client_secret: Some("client_secret".to_string()),
+        base_url: Some("https://github.example.com/".to_string()),
+        ..Default::default()
+    })
+    .expect("Failed to create GitHub provider");
+    assert_eq!(
+        enterprise.auth_endpoint().url(),
+        "https://github.example.com/login/oauth/authorize"
+    );
+    assert_eq!(
+        enterprise.token_endpoint().url(),
+        "https://github.example.com/login/oauth/access_token"
+    );
+    assert_eq!(
+        enterprise.profile_endpoint().url(),
+        "https://github.example.com/api/v3/user"
+    );
+    assert_eq!(
+        enterprise.emails_endpoint().url(),
+        "https://github.example.com/api/v3/user/emails"
+    );
+}
+
+#[test]
+fn test_github_profile() {
+    let github = GithubProvider::from_options(GithubProviderOptions {
+        client_id: Some("client_id".to_string()),
+        client_secret: Some("client_secret".to_string()),
+        ..Default::default()
+    })
+    .expect("Failed to create GitHub provider");
+
+    let user = github.get_profile(Profile {
+        id: Some("1234567890".to_string()),
+        preferred_username: Some("octocat".to_string()),
+        email: Some("octocat@email.com".to_string()),
+        picture: Some("https://avatars.githubusercontent.com/u/1234567890".to_string()),
+        ..Default::default()
+    });
+    assert_eq!(user.id.as_deref(), Some("1234567890"));
+    assert_eq!(user.username.as_deref(), Some("octocat"));
+    assert_eq!(user.email.as_deref(), Some("octocat@email.com"));
+    assert_eq!(
+        user.image.as_deref(),
+        Some("https://avatars.githubusercontent.com/u/1234567890")
+    );
+}
@@ -0,0 +1,249 @@
+mod mock;
+
+use bzauth_rs::auth::AuthOptions;
+use bzauth_rs::providers::OidcProvider;
+use bzauth_rs::providers::oidc::OidcProviderOptions;
+use bzauth_rs::runtimes::axum::AxumRuntimeOptions;
+use mock::runtime::MOCK_AUTH_URL;
+use mock::{
+    JsonStore, JsonStoreTypes, MOCK_OIDC_PROVIDER_NAME, MOCK_PROVIDER_CLIENT_ID,
+    MOCK_PROVIDER_CLIENT_SECRET, MOCK_PROVIDER_URL, MockAdaptor, mock_oidc_provider,
+};
+use reqwest::header::{LOCATION, SET_COOKIE};
+use reqwest::{StatusCode, Url};
+use tempfile::NamedTempFile;
+
+/// Starts a login flow with the OIDC provider, returning the provider redirect and the cookies set
+async fn start_login() -> (Url, String) {
+    let response = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .expect("Failed to build client")
+        .get(format!(
+            "{}/login/{}",
+            MOCK_AUTH_URL, MOCK_OIDC_PROVIDER_NAME
+        ))
+        .send()
+        .await
+        .expect("Failed to make request to auth server");
+    assert_eq!(
+        response.status(),
+        StatusCode::FOUND,
+        "Login did not redirect"
+    );
+
+    let location = response
+        .headers()
+        .get(LOCATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| Url::parse(v).ok())
+        .expect("Login response has no location");
+
+    // Replay the cookies as a single Cookie header, dropping the attributes
+    let cookies = response
+        .headers()
+        .get_all(SET_COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .filter_map(|v| v.split(';').next())
+        .collect::<Vec<_>>()
+        .join("; ");
+
+    (location, cookies)
+}
+
+/// Reads a query parameter of the login redirect
+fn param(location: &Url, key: &str) -> Option<String> {
+    location
+        .query_pairs()
+        .find(|(k, _)| k == key)
+        .map(|(_, v)| v.to_string())
+}
+
+/// Visits the provider's authorisation page, as the browser would
+async fn visit(location: &Url) {
+    let response = reqwest::get(location.clone())
+        .await
+        .expect("Failed to make request to provider");
+    assert!(response.status().is_success(), "Authorisation page failed");
+}
+
+/// Sends the provider's callback to the runtime
+async fn send_callback(location: &Url, cookies: &str) -> reqwest::Response {
+    let state = param(location, "state").expect("Login location has no state");
+    reqwest::Client::new()
+        .get(format!(
+            "{}/callback/{}",
+            MOCK_AUTH_URL, MOCK_OIDC_PROVIDER_NAME
+        ))
+        .query(&[("code", "mock_auth_code"), ("state", state.as_str())])
+        .header(reqwest::header::COOKIE, cookies)
+        .send()
+        .await
+        .expect("Failed to make request to auth server")
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_00_oidc_discovery() {
+    let signals = mock::Signals::new();
+
+    let tmpfile = NamedTempFile::new().expect("Failed to create temp file");
+    let path = tmpfile.path();
+
+    let json_store = JsonStore::new(&JsonStoreTypes::File(path));
+    let auth_options = AuthOptions::new()
+        .add_provider(Box::new(mock_oidc_provider()))
+        .with_adaptor(Box::new(MockAdaptor::new(json_store)));
+    let options = AxumRuntimeOptions::new(auth_options);
+
+    mock::environment::axum_::run(signals, options, || async {
+        let (location, cookies) = start_login().await;
+
+        // The authorisation endpoint comes from the discovery document
+        assert!(
+            location
+                .as_str()
+                .starts_with(&format!("{}/authorize?", MOCK_PROVIDER_URL)),
+            "Login did not redirect to the discovered endpoint: {}",
+            location
+        );
+        assert_eq!(
+            param(&location, "scope").as_deref(),
+            Some("openid email profile")
+        );
+
+        // The nonce is sent to the provider and kept for the callback
+        let nonce = param(&location, "nonce").expect("Login location has no nonce");
+        assert!(
+            cookies.contains(&format!("nonce={}", nonce)),
+            "Missing nonce cookie: {}",
+            cookies
+        );
+    })
+    .await;
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_01_oidc_callback() {
+    let signals = mock::Signals::new();
+
+    let tmpfile = NamedTempFile::new().expect("Failed to create temp file");
+    let path = tmpfile.path();
+
+    let json_store = JsonStore::new(&JsonStoreTypes::File(path));
+    let auth_options = AuthOptions::new()
+        .add_provider(Box::new(mock_oidc_provider()))
+        .with_adaptor(Box::new(MockAdaptor::new(json_store.clone())));
+    let options = AxumRuntimeOptions::new(auth_options);
+
+    mock::environment::axum_::run(signals, options, || async {
+        let (location, cookies) = start_login().await;
+        visit(&location).await;
+
+        let response = send_callback(&location, &cookies).await;
+        let status = response.status();
+        let text = response.text().await.expect("Failed to read response text");
+        assert!(
+            status.is_success(),
+            "Callback request failed:\n\tstatus: {}\n\tbody: {}",
+            status,
+            text
+        );
+
+        // The user is mapped from the standard claims
+        let data = json_store
+            .get_data()
+            .expect("Failed to get data from json store");
+        let data = serde_json::to_string(&data).unwrap();
+        assert!(
+            data.contains("john.doe@email.com"),
+            "User was not created: {}",
+            data
+        );
+    })
+    .await;
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_02_oidc_nonce_mismatch() {
+    let signals = mock::Signals::new();
+
+    let tmpfile = NamedTempFile::new().expect("Failed to create temp file");
+    let path = tmpfile.path();
+
+    let json_store = JsonStore::new(&JsonStoreTypes::File(path));
+    let auth_options = AuthOptions::new()
+        .add_provider(Box::new(mock_oidc_provider()))
+        .with_adaptor(Box::new(MockAdaptor::new(json_store)));
+    let options = AxumRuntimeOptions::new(auth_options);
+
+    mock::environment::axum_::run(signals, options, || async {
+        // The provider issues the id_token for another sign-in
+        let (location, cookies) = start_login().await;
+        let (other_location, _) = start_login().await;
+        visit(&other_location).await;
+
+        let response = send_callback(&location, &cookies).await;
+        assert_eq!(
+            response.status(),
+            StatusCode::UNAUTHORIZED,
+            "Callback with a mismatched nonce was not refused"
+        );
+    })
+    .await;
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_03_oidc_discovery_failure() {
+    let signals = mock::Signals::new();
+
+    let tmpfile = NamedTempFile::new().expect("Failed to create temp file");
+    let path = tmpfile.path();
+
+    let provider = OidcProvider::from_options(OidcProviderOptions {
+        id: Some(MOCK_OIDC_PROVIDER_NAME.to_string()),
+        issuer: Some(format!("{}/missing", MOCK_PROVIDER_URL)),
+        client_id: Some(MOCK_PROVIDER_CLIENT_ID.to_string()),
+        client_secret: Some(MOCK_PROVIDER_CLIENT_SECRET.to_string()),
+        ..Default::default()
+    })
+    .expect("Failed to create provider");
+
+    let json_store = JsonStore::new(&JsonStoreTypes::File(path));
+    let auth_options = AuthOptions::new()
+        .add_provider(Box::new(provider))
+        .with_adaptor(Box::new(MockAdaptor::new(json_store)));
+    let options = AxumRuntimeOptions::new(auth_options);
+
+    mock::environment::axum_::run(signals, options, || async {
+        let response = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .expect("Failed to build client")
+            .get(format!(
+                "{}/login/{}",
+                MOCK_AUTH_URL, MOCK_OIDC_PROVIDER_NAME
+            ))
+            .send()
+            .await
+            .expect("Failed to make request to auth server");
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+    })
+    .await;
+}
@@ -0,0 +1,184 @@
+mod mock;
+
+use bzauth_rs::contracts::profile::Profile;
+use bzauth_rs::tools::jose::{self, ClaimsValidation, JoseError, JwksCache};
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use mock::consts::{
+    MOCK_JWKS_EC_KID, MOCK_JWKS_EC_PEM, MOCK_JWKS_ED25519_KID, MOCK_JWKS_ED25519_PEM,
+    MOCK_JWKS_JSON, MOCK_JWKS_RSA_KID, MOCK_JWKS_RSA_PEM,
+};
+
+const ISSUER: &str = "https://issuer.example.com";
+const CLIENT_ID: &str = "client_id";
+const NONCE: &str = "nonce";
+
+fn jwks() -> JwksCache {
+    let jwks: JwkSet = serde_json::from_str(MOCK_JWKS_JSON).expect("Invalid JWKS");
+    JwksCache::from_jwks(jwks)
+}
+
+fn validation() -> ClaimsValidation {
+    ClaimsValidation {
+        issuers: vec![ISSUER.to_string()],
+        client_id: CLIENT_ID.to_string(),
+        nonce: Some(NONCE.to_string()),
+        ..Default::default()
+    }
+}
+
+fn claims() -> serde_json::Value {
+    let now = chrono::Utc::now().timestamp();
+    serde_json::json!({
+        "iss": ISSUER,
+        "sub": "subject",
+        "aud": CLIENT_ID,
+        "exp": now + 300,
+        "iat": now,
+        "nonce": NONCE,
+        "email": "user@example.com",
+        "email_verified": true,
+        "name": "User",
+    })
+}
+
+fn sign(alg: Algorithm, kid: &str, claims: &serde_json::Value) -> String {
+    let key = match alg {
+        Algorithm::RS256 => EncodingKey::from_rsa_pem(MOCK_JWKS_RSA_PEM.as_bytes()),
+        Algorithm::ES256 => EncodingKey::from_ec_pem(MOCK_JWKS_EC_PEM.as_bytes()),
+        Algorithm::EdDSA => EncodingKey::from_ed_pem(MOCK_JWKS_ED25519_PEM.as_bytes()),
+        _ => Ok(EncodingKey::from_secret(b"secret")),
+    }
+    .expect("Invalid signing key");
+
+    let mut header = Header::new(alg);
+    header.kid = Some(kid.to_string());
+    jsonwebtoken::encode(&header, claims, &key).expect("Failed to sign token")
+}
+
+async fn verify(
+    token: &str,
+    validation: &ClaimsValidation,
+) -> Result<jose::IdTokenClaims, JoseError> {
+    jose::verify_id_token(token, &jwks(), "", validation).await
+}
+
+#[tokio::test]
+async fn test_verify_supported_algorithms() {
+    for (alg, kid) in [
+        (Algorithm::RS256, MOCK_JWKS_RSA_KID),
+        (Algorithm::ES256, MOCK_JWKS_EC_KID),
+        (Algorithm::EdDSA, MOCK_JWKS_ED25519_KID),
+    ] {
+        let token = sign(alg, kid, &claims());
+        let claims = verify(&token, &validation())
+            .await
+            .unwrap_or_else(|e| panic!("Failed to verify {:?}: {}", alg, e));
+        assert_eq!(claims.sub, "subject");
+    }
+}
+
+#[tokio::test]
+async fn test_reject_signatures() {
+    // Tampered payload
+    let token = sign(Algorithm::RS256, MOCK_JWKS_RSA_KID, &claims());
+    let mut parts = token.split('.').map(String::from).collect::<Vec<_>>();
+    let mut forged = claims();
+    forged["sub"] = "someone_else".into();
+    parts[1] = sign(Algorithm::RS256, MOCK_JWKS_RSA_KID, &forged)
+        .split('.')
+        .nth(1)
+        .unwrap()
+        .to_string();
+    assert!(matches!(
+        verify(&parts.join("."), &validation()).await,
+        Err(JoseError::InvalidSignature(_))
+    ));
+
+    // Signed by another key than the one named
+    let token = sign(Algorithm::ES256, MOCK_JWKS_RSA_KID, &claims());
+    assert!(matches!(
+        verify(&token, &validation()).await,
+        Err(JoseError::InvalidSignature(_))
+    ));
+
+    // Symmetric algorithms are refused
+    let token = sign(Algorithm::HS256, MOCK_JWKS_RSA_KID, &claims());
+    assert!(matches!(
+        verify(&token, &validation()).await,
+        Err(JoseError::UnsupportedAlgorithm(_))
+    ));
+
+    // Unknown keys are refused
+    let token = sign(Algorithm::RS256, "unknown", &claims());
+    assert_eq!(
+        verify(&token, &validation()).await.unwrap_err(),
+        JoseError::UnknownKey("unknown".to_string())
+    );
+}
+
+#[tokio::test]
+async fn test_reject_claims() {
+    let now = chrono::Utc::now().timestamp();
+    let cases = [
+        ("iss", serde_json::json!("https://evil.example.com")),
+        ("aud", serde_json::json!("other_client")),
+        ("aud", serde_json::json!([CLIENT_ID, "other_client"])),
+        ("azp", serde_json::json!("other_client")),
+        ("exp", serde_json::json!(now - 120)),
+        ("iat", serde_json::json!(now + 120)),
+        ("nonce", serde_json::json!("other_nonce")),
+    ];
+
+    for (claim, value) in cases {
+        let mut claims = claims();
+        claims[claim] = value.clone();
+        let token = sign(Algorithm::RS256, MOCK_JWKS_RSA_KID, &claims);
+        assert!(
+            verify(&token, &validation()).await.is_err(),
+            "Accepted {}={}",
+            claim,
+            value
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_clock_skew() {
+    let now = chrono::Utc::now().timestamp();
+    let mut claims = claims();
+    claims["exp"] = (now - 30).into();
+    claims["iat"] = (now + 30).into();
+    let token = sign(Algorithm::RS256, MOCK_JWKS_RSA_KID, &claims);
+
+    // Within the default skew of a minute
+    assert!(verify(&token, &validation()).await.is_ok());
+
+    // Without any skew
+    let strict = ClaimsValidation {
+        clock_skew: 0,
+        ..validation()
+    };
+    assert_eq!(
+        verify(&token, &strict).await.unwrap_err(),
+        JoseError::Expired
+    );
+}
+
+#[tokio::test]
+async fn test_profile_from_claims() {
+    let token = sign(Algorithm::EdDSA, MOCK_JWKS_ED25519_KID, &claims());
+    let claims = verify(&token, &validation())
+        .await
+        .expect("Failed to verify token");
+
+    let profile = Profile::from(claims);
+    assert_eq!(profile.sub.as_deref(), Some("subject"));
+    assert_eq!(profile.email.as_deref(), Some("user@example.com"));
+    assert_eq!(profile.email_verified, Some(true));
+    assert_eq!(profile.name.as_deref(), Some("User"));
+    assert_eq!(
+        profile.others.get("iss").and_then(|v| v.as_str()),
+        Some(ISSUER)
+    );
+}
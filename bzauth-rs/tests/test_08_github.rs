@@ -0,0 +1,122 @@
+mod mock;
+
+use bzauth_rs::auth::AuthOptions;
+use bzauth_rs::runtimes::axum::AxumRuntimeOptions;
+use mock::consts::{MOCK_GITHUB, MOCK_GITHUB_USER_EMAIL, MOCK_GITHUB_USER_LOGIN};
+use mock::runtime::MOCK_AUTH_URL;
+use mock::{JsonStore, JsonStoreTypes, MOCK_PROVIDER_URL, MockAdaptor, mock_github_provider};
+use reqwest::header::{LOCATION, SET_COOKIE};
+use reqwest::{StatusCode, Url};
+use tempfile::NamedTempFile;
+
+/// Starts a login flow with GitHub, returning the provider redirect and the cookies set
+async fn start_login() -> (Url, String) {
+    let response = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .expect("Failed to build client")
+        .get(format!("{}/login/github", MOCK_AUTH_URL))
+        .send()
+        .await
+        .expect("Failed to make request to auth server");
+    assert_eq!(
+        response.status(),
+        StatusCode::FOUND,
+        "Login did not redirect"
+    );
+
+    let location = response
+        .headers()
+        .get(LOCATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| Url::parse(v).ok())
+        .expect("Login response has no location");
+
+    // Replay the cookies as a single Cookie header, dropping the attributes
+    let cookies = response
+        .headers()
+        .get_all(SET_COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .filter_map(|v| v.split(';').next())
+        .collect::<Vec<_>>()
+        .join("; ");
+
+    (location, cookies)
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_00_github_login() {
+    let signals = mock::Signals::new();
+
+    let tmpfile = NamedTempFile::new().expect("Failed to create temp file");
+    let path = tmpfile.path();
+
+    let json_store = JsonStore::new(&JsonStoreTypes::File(path));
+    let auth_options = AuthOptions::new()
+        .add_provider(Box::new(mock_github_provider()))
+        .with_adaptor(Box::new(MockAdaptor::new(json_store.clone())));
+    let options = AxumRuntimeOptions::new(auth_options);
+
+    mock::environment::axum_::run(signals, options, || async {
+        let (location, cookies) = start_login().await;
+
+        // The enterprise server is used rather than github.com
+        assert!(
+            location.as_str().starts_with(&format!(
+                "{}/{}/login/oauth/authorize?",
+                MOCK_PROVIDER_URL, MOCK_GITHUB
+            )),
+            "Login did not redirect to the enterprise server: {}",
+            location
+        );
+        let scope = location
+            .query_pairs()
+            .find(|(k, _)| k == "scope")
+            .map(|(_, v)| v.to_string());
+        assert_eq!(scope.as_deref(), Some("read:user user:email"));
+
+        // The form-encoded token is accepted, and the API is called with a User-Agent
+        let state = location
+            .query_pairs()
+            .find(|(k, _)| k == "state")
+            .map(|(_, v)| v.to_string())
+            .expect("Login location has no state");
+        let response = reqwest::Client::new()
+            .get(format!("{}/callback/github", MOCK_AUTH_URL))
+            .query(&[("code", "mock_auth_code"), ("state", state.as_str())])
+            .header(reqwest::header::COOKIE, cookies)
+            .send()
+            .await
+            .expect("Failed to make request to auth server");
+        let status = response.status();
+        let text = response.text().await.expect("Failed to read response text");
+        assert!(
+            status.is_success(),
+            "Callback request failed:\n\tstatus: {}\n\tbody: {}",
+            status,
+            text
+        );
+
+        // The private primary verified address is chosen
+        let data = json_store
+            .get_data()
+            .expect("Failed to get data from json store");
+        let data = serde_json::to_string(&data).unwrap();
+        assert!(
+            data.contains(MOCK_GITHUB_USER_EMAIL),
+            "User was not created with the primary email: {}",
+            data
+        );
+        assert!(
+            data.contains(MOCK_GITHUB_USER_LOGIN),
+            "User was not created with the login: {}",
+            data
+        );
+    })
+    .await;
+}
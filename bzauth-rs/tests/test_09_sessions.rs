@@ -0,0 +1,370 @@
+mod mock;
+
+use bzauth_rs::auth::{
+    AuthOptions, AuthSessionOptions, DEFAULT_SESSION_MAX_AGE, DEFAULT_SESSION_UPDATE_AGE,
+    SessionStrategy,
+};
+use bzauth_rs::contracts::session::{Session, SessionAccount};
+use bzauth_rs::contracts::user::User;
+use bzauth_rs::runtimes::axum::AxumRuntimeOptions;
+use bzauth_rs::tools::session_jwt::{self, SessionJwtEncoding, SessionJwtError};
+use mock::{
+    JsonStore, JsonStoreTypes, MOCK_PROVIDER_NAME, MockAdaptor, MockProvider, get_session,
+    session_cookie, sign_in,
+};
+use tempfile::NamedTempFile;
+
+#[test]
+fn test_session_options() {
+    let options = AuthSessionOptions::default();
+    assert_eq!(options.strategy(), SessionStrategy::Database);
+    assert_eq!(options.max_age(), DEFAULT_SESSION_MAX_AGE);
+    assert_eq!(options.update_age(), DEFAULT_SESSION_UPDATE_AGE);
+
+    // Tokens are random, and long enough not to be guessed
+    let token = options.generate_token();
+    assert!(token.len() >= 43, "Session token is too short: {}", token);
+    assert_ne!(token, options.generate_token());
+
+    let options = AuthSessionOptions {
+        generate_session: Some(|| "custom".to_string()),
+        ..Default::default()
+    };
+    assert_eq!(options.generate_token(), "custom");
+}
+
+#[test]
+fn test_session_sliding() {
+    let options = AuthSessionOptions {
+        max_age: Some(3600),
+        update_age: Some(600),
+        ..Default::default()
+    };
+
+    // Fresh sessions are left alone, until update_age has passed
+    assert!(!options.is_due_for_update(3600));
+    assert!(!options.is_due_for_update(3001));
+    assert!(options.is_due_for_update(3000));
+    assert!(options.is_due_for_update(1));
+
+    // An update_age of 0 extends the session on every use
+    let options = AuthSessionOptions {
+        max_age: Some(3600),
+        update_age: Some(0),
+        ..Default::default()
+    };
+    assert!(options.is_due_for_update(3600));
+}
+
+const SECRET: &str = "session_secret";
+
+fn jwt_session(expires_in: i64) -> Session {
+    Session {
+        user: Some(User {
+            id: Some("user_id".to_string()),
+            email: Some("user@example.com".to_string()),
+            ..Default::default()
+        }),
+        account: Some(SessionAccount {
+            provider_id: Some("github".to_string()),
+            provider_account_id: Some("1234567890".to_string()),
+            ..Default::default()
+        }),
+        expires_at: Some((chrono::Utc::now().timestamp() + expires_in) as u64),
+    }
+}
+
+#[test]
+fn test_session_jwt() {
+    for encoding in [SessionJwtEncoding::Encrypted, SessionJwtEncoding::Signed] {
+        let session = jwt_session(3600);
+        let token = session_jwt::encode(SECRET, encoding, &session).expect("Failed to encode");
+
+        let decoded = session_jwt::decode(SECRET, encoding, &token).expect("Failed to decode");
+        let user = decoded.user.expect("Session has no user");
+        assert_eq!(user.id.as_deref(), Some("user_id"));
+        assert_eq!(user.email.as_deref(), Some("user@example.com"));
+        assert_eq!(decoded.account, session.account);
+        assert_eq!(decoded.expires_at, session.expires_at);
+
+        // Another secret can neither read nor forge it
+        assert_eq!(
+            session_jwt::decode("other_secret", encoding, &token).unwrap_err(),
+            SessionJwtError::InvalidToken
+        );
+
+        // Expired sessions are refused
+        let token = session_jwt::encode(SECRET, encoding, &jwt_session(-1)).unwrap();
+        assert_eq!(
+            session_jwt::decode(SECRET, encoding, &token).unwrap_err(),
+            SessionJwtError::Expired
+        );
+    }
+
+    // The encrypted session is a JWE, unreadable without the secret
+    let token =
+        session_jwt::encode(SECRET, SessionJwtEncoding::Encrypted, &jwt_session(3600)).unwrap();
+    assert_eq!(token.split('.').count(), 5);
+    assert!(!token.contains("user_id"));
+
+    // Tampering with the ciphertext is detected
+    let mut parts = token.split('.').map(String::from).collect::<Vec<_>>();
+    parts[3] = parts[3].chars().rev().collect();
+    assert!(session_jwt::decode(SECRET, SessionJwtEncoding::Encrypted, &parts.join(".")).is_err());
+
+    // One encoding is not accepted as the other
+    let signed =
+        session_jwt::encode(SECRET, SessionJwtEncoding::Signed, &jwt_session(3600)).unwrap();
+    assert!(session_jwt::decode(SECRET, SessionJwtEncoding::Encrypted, &signed).is_err());
+    assert!(session_jwt::decode(SECRET, SessionJwtEncoding::Signed, &token).is_err());
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_00_session_cookie() {
+    let signals = mock::Signals::new();
+
+    let tmpfile = NamedTempFile::new().expect("Failed to create temp file");
+    let path = tmpfile.path();
+
+    let json_store = JsonStore::new(&JsonStoreTypes::File(path));
+    let auth_options = AuthOptions::new()
+        .add_provider(Box::new(MockProvider))
+        .with_adaptor(Box::new(MockAdaptor::new(json_store.clone())))
+        .with_session(AuthSessionOptions {
+            max_age: Some(3600),
+            ..Default::default()
+        });
+    let options = AxumRuntimeOptions::new(auth_options);
+
+    mock::environment::axum_::run(signals, options, || async {
+        let cookie = session_cookie(&sign_in().await);
+
+        // The cookie is hidden from scripts and lives as long as the session
+        assert!(
+            cookie.contains("HttpOnly"),
+            "Cookie is not HttpOnly: {}",
+            cookie
+        );
+        assert!(
+            cookie.contains("SameSite=Lax"),
+            "Cookie is not Lax: {}",
+            cookie
+        );
+        assert!(cookie.contains("Path=/"), "Cookie has no path: {}", cookie);
+        assert!(
+            cookie.contains("Max-Age=3600"),
+            "Cookie has no max age: {}",
+            cookie
+        );
+
+        // Served over plain HTTP, so it cannot be Secure
+        assert!(!cookie.contains("Secure"), "Cookie is Secure: {}", cookie);
+
+        // The token references the stored session
+        let token = cookie
+            .split(';')
+            .next()
+            .and_then(|c| c.strip_prefix("session_token="))
+            .expect("Cookie has no token");
+        assert_ne!(token, "TODO");
+
+        let data = json_store
+            .get_data()
+            .expect("Failed to get data from json store");
+        let sessions = data["sessions"]
+            .as_array()
+            .expect("No sessions were stored");
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0]["token"], token);
+        assert_eq!(sessions[0]["expires_in"], 3600);
+        assert_eq!(sessions[0]["user_id"], data["users"][0]["id"]);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_01_session_secure_cookie() {
+    let signals = mock::Signals::new();
+
+    let tmpfile = NamedTempFile::new().expect("Failed to create temp file");
+    let path = tmpfile.path();
+
+    let json_store = JsonStore::new(&JsonStoreTypes::File(path));
+    let auth_options = AuthOptions::new()
+        .add_provider(Box::new(MockProvider))
+        .with_adaptor(Box::new(MockAdaptor::new(json_store)))
+        .with_base_url("https://app.example.com");
+    let options = AxumRuntimeOptions::new(auth_options);
+
+    mock::environment::axum_::run(signals, options, || async {
+        // Served over HTTPS, so the cookie is never sent in the clear
+        let cookie = session_cookie(&sign_in().await);
+        assert!(
+            cookie.contains("Secure"),
+            "Cookie is not Secure: {}",
+            cookie
+        );
+        assert!(
+            cookie.contains(&format!("Max-Age={}", DEFAULT_SESSION_MAX_AGE)),
+            "Cookie has no max age: {}",
+            cookie
+        );
+    })
+    .await;
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_02_session_jwt_without_adaptor() {
+    let signals = mock::Signals::new();
+
+    let auth_options = AuthOptions::new()
+        .add_provider(Box::new(MockProvider))
+        .with_secret(SECRET)
+        .with_session(AuthSessionOptions {
+            strategy: Some(SessionStrategy::Jwt),
+            ..Default::default()
+        });
+    let options = AxumRuntimeOptions::new(auth_options);
+
+    mock::environment::axum_::run(signals, options, || async {
+        let cookie = session_cookie(&sign_in().await);
+        assert!(
+            cookie.contains("HttpOnly"),
+            "Cookie is not HttpOnly: {}",
+            cookie
+        );
+
+        // The cookie holds the session itself, which any service with the secret can decode
+        let token = cookie
+            .split(';')
+            .next()
+            .and_then(|c| c.strip_prefix("session_token="))
+            .expect("Cookie has no token");
+        let session = session_jwt::decode(SECRET, SessionJwtEncoding::Encrypted, token)
+            .expect("Failed to decode the session cookie");
+
+        let user = session.user.expect("Session has no user");
+        assert_eq!(user.email.as_deref(), Some("john.doe@email.com"));
+        let account = session.account.expect("Session has no account");
+        assert_eq!(account.provider_id.as_deref(), Some(MOCK_PROVIDER_NAME));
+
+        let expires_in = session.expires_at.unwrap() as i64 - chrono::Utc::now().timestamp();
+        assert!((DEFAULT_SESSION_MAX_AGE - 60..=DEFAULT_SESSION_MAX_AGE).contains(&expires_in));
+    })
+    .await;
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_03_session_endpoint() {
+    let signals = mock::Signals::new();
+
+    let tmpfile = NamedTempFile::new().expect("Failed to create temp file");
+    let path = tmpfile.path();
+
+    let json_store = JsonStore::new(&JsonStoreTypes::File(path));
+    let auth_options = AuthOptions::new()
+        .add_provider(Box::new(MockProvider))
+        .with_adaptor(Box::new(MockAdaptor::new(json_store)))
+        .with_session(AuthSessionOptions {
+            max_age: Some(3600),
+            ..Default::default()
+        });
+    let options = AxumRuntimeOptions::new(auth_options);
+
+    mock::environment::axum_::run(signals, options, || async {
+        // Signed out
+        let (body, set_cookies) = get_session(None).await;
+        assert_eq!(body, serde_json::json!({}));
+        assert!(set_cookies.is_empty());
+
+        // Signed in
+        let cookie = session_cookie(&sign_in().await);
+        let (body, set_cookies) = get_session(Some(&cookie)).await;
+        assert_eq!(body["user"]["email"], "john.doe@email.com");
+        let expires_in = body["expires_at"].as_i64().expect("Session has no expiry")
+            - chrono::Utc::now().timestamp();
+        assert!((3540..=3600).contains(&expires_in));
+
+        // A fresh session is not extended
+        assert!(
+            set_cookies.is_empty(),
+            "Cookies were set: {:?}",
+            set_cookies
+        );
+
+        // An unknown session is cleared
+        let (body, set_cookies) = get_session(Some("session_token=unknown")).await;
+        assert_eq!(body, serde_json::json!({}));
+        let cookie = session_cookie(&set_cookies);
+        assert!(
+            cookie.contains("Max-Age=0"),
+            "Cookie was not cleared: {}",
+            cookie
+        );
+    })
+    .await;
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_04_session_endpoint_sliding() {
+    let signals = mock::Signals::new();
+
+    let auth_options = AuthOptions::new()
+        .add_provider(Box::new(MockProvider))
+        .with_secret(SECRET)
+        .with_session(AuthSessionOptions {
+            strategy: Some(SessionStrategy::Jwt),
+            max_age: Some(3600),
+            update_age: Some(0),
+            ..Default::default()
+        });
+    let options = AxumRuntimeOptions::new(auth_options);
+
+    mock::environment::axum_::run(signals, options, || async {
+        let cookie = session_cookie(&sign_in().await);
+        let (body, set_cookies) = get_session(Some(&cookie)).await;
+        assert_eq!(body["user"]["email"], "john.doe@email.com");
+        assert_eq!(body["account"]["provider_id"], MOCK_PROVIDER_NAME);
+
+        // The session is extended on every use, so the cookie is refreshed
+        let refreshed = session_cookie(&set_cookies);
+        assert!(
+            refreshed.contains("Max-Age=3600"),
+            "Cookie was not refreshed: {}",
+            refreshed
+        );
+        let (body, _) = get_session(Some(&refreshed)).await;
+        assert_eq!(body["user"]["email"], "john.doe@email.com");
+
+        // A forged session is cleared
+        let (body, set_cookies) = get_session(Some("session_token=forged")).await;
+        assert_eq!(body, serde_json::json!({}));
+        let cookie = session_cookie(&set_cookies);
+        assert!(
+            cookie.contains("Max-Age=0"),
+            "Cookie was not cleared: {}",
+            cookie
+        );
+    })
+    .await;
+}
@@ -0,0 +1,281 @@
+mod mock;
+
+use std::sync::{Arc, Mutex};
+
+use bzauth_rs::auth::{AuthOptions, AuthSessionOptions, SessionStrategy, SignOutOptions};
+use bzauth_rs::providers::OidcProvider;
+use bzauth_rs::providers::oidc::OidcProviderOptions;
+use bzauth_rs::runtimes::axum::AxumRuntimeOptions;
+use mock::consts::MOCK_END_SESSION;
+use mock::runtime::MOCK_AUTH_URL;
+use mock::{
+    JsonStore, JsonStoreTypes, MOCK_OIDC_PROVIDER_NAME, MOCK_PROVIDER_CLIENT_ID,
+    MOCK_PROVIDER_CLIENT_SECRET, MOCK_PROVIDER_URL, MockAdaptor, MockProvider, client, csrf_token,
+    get_session, session_cookie, sign_in,
+};
+use reqwest::header::{COOKIE, LOCATION, SET_COOKIE};
+use reqwest::{StatusCode, Url};
+use tempfile::NamedTempFile;
+
+/// Posts to `/logout` with the given cookie and form, along with a CSRF token, returning the
+/// redirect and `Set-Cookie` headers
+async fn logout(cookie: Option<&str>, form: &[(&str, &str)]) -> (String, Vec<String>) {
+    let (csrf_token, csrf_cookie) = csrf_token().await;
+    let mut form = form.to_vec();
+    form.push(("csrf_token", &csrf_token));
+
+    let mut cookies = vec![csrf_cookie];
+    if let Some(cookie) = cookie {
+        cookies.push(cookie.split(';').next().unwrap_or_default().to_string());
+    }
+
+    let request = client()
+        .post(format!("{}/logout", MOCK_AUTH_URL))
+        .header(COOKIE, cookies.join("; "))
+        .form(&form);
+
+    let response = request
+        .send()
+        .await
+        .expect("Failed to make request to auth server");
+    assert_eq!(
+        response.status(),
+        StatusCode::FOUND,
+        "Logout did not redirect"
+    );
+
+    let location = response
+        .headers()
+        .get(LOCATION)
+        .and_then(|v| v.to_str().ok())
+        .map(String::from)
+        .expect("Logout response has no location");
+    let set_cookies = response
+        .headers()
+        .get_all(SET_COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .map(String::from)
+        .collect();
+    (location, set_cookies)
+}
+
+/// An OIDC provider signing out of the mock issuer too
+fn rp_initiated_logout_provider() -> OidcProvider {
+    OidcProvider::from_options(OidcProviderOptions {
+        id: Some(MOCK_OIDC_PROVIDER_NAME.to_string()),
+        issuer: Some(MOCK_PROVIDER_URL.to_string()),
+        client_id: Some(MOCK_PROVIDER_CLIENT_ID.to_string()),
+        client_secret: Some(MOCK_PROVIDER_CLIENT_SECRET.to_string()),
+        rp_initiated_logout: Some(true),
+        ..Default::default()
+    })
+    .expect("Failed to create the OIDC provider")
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_00_logout() {
+    let signals = mock::Signals::new();
+
+    let tmpfile = NamedTempFile::new().expect("Failed to create temp file");
+    let path = tmpfile.path();
+
+    // Record the sessions passed to the sign out callback
+    let signed_out = Arc::new(Mutex::new(Vec::<SignOutOptions>::new()));
+    let signed_out_callback = signed_out.clone();
+
+    let json_store = JsonStore::new(&JsonStoreTypes::File(path));
+    let auth_options = AuthOptions::new()
+        .add_provider(Box::new(MockProvider))
+        .with_adaptor(Box::new(MockAdaptor::new(json_store.clone())))
+        .with_sign_out_callback(Arc::new(move |options| {
+            signed_out_callback.lock().unwrap().push(options);
+            Box::pin(async {})
+        }));
+    let options = AxumRuntimeOptions::new(auth_options);
+
+    mock::environment::axum_::run(signals, options, || async {
+        let cookie = session_cookie(&sign_in().await);
+        let (location, set_cookies) = logout(Some(&cookie), &[]).await;
+
+        // Back to the origin, with the cookie cleared
+        assert_eq!(location, format!("{}/", MOCK_AUTH_URL));
+        let cleared = session_cookie(&set_cookies);
+        assert!(
+            cleared.contains("Max-Age=0"),
+            "Cookie was not cleared: {}",
+            cleared
+        );
+
+        // The session is gone, so the old cookie no longer works
+        let data = json_store.get_data().expect("Failed to get data");
+        assert_eq!(data["sessions"].as_array().map(|s| s.len()), Some(0));
+        let (body, _) = get_session(Some(&cookie)).await;
+        assert_eq!(body, serde_json::json!({}));
+
+        // The callback was given the session that was ended
+        {
+            let signed_out = signed_out.lock().unwrap();
+            assert_eq!(signed_out.len(), 1);
+            let user = signed_out[0].session.as_ref().and_then(|s| s.user.clone());
+            assert_eq!(
+                user.and_then(|u| u.email).as_deref(),
+                Some("john.doe@email.com")
+            );
+        }
+
+        // Signing out again is harmless, and honours the callback URL
+        let (location, _) = logout(Some(&cookie), &[("callback_url", "/signed-out")]).await;
+        assert_eq!(location, format!("{}/signed-out", MOCK_AUTH_URL));
+        assert!(signed_out.lock().unwrap()[1].session.is_none());
+
+        // Other origins are refused by the default redirect callback
+        let (location, _) = logout(None, &[("callback_url", "https://evil.example.com")]).await;
+        assert_eq!(location, MOCK_AUTH_URL);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_01_logout_requires_post() {
+    let signals = mock::Signals::new();
+
+    let auth_options = AuthOptions::new().add_provider(Box::new(MockProvider));
+    let options = AxumRuntimeOptions::new(auth_options);
+
+    mock::environment::axum_::run(signals, options, || async {
+        // A link or an image must not be able to sign the user out
+        let response = reqwest::get(format!("{}/logout", MOCK_AUTH_URL))
+            .await
+            .expect("Failed to make request to auth server");
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_02_logout_jwt() {
+    let signals = mock::Signals::new();
+
+    let auth_options = AuthOptions::new()
+        .add_provider(Box::new(MockProvider))
+        .with_session(AuthSessionOptions {
+            strategy: Some(SessionStrategy::Jwt),
+            ..Default::default()
+        });
+    let options = AxumRuntimeOptions::new(auth_options);
+
+    mock::environment::axum_::run(signals, options, || async {
+        // The mock provider is not an OIDC provider, so the user stays signed in there
+        let cookie = session_cookie(&sign_in().await);
+        let (location, set_cookies) = logout(Some(&cookie), &[]).await;
+        assert_eq!(location, format!("{}/", MOCK_AUTH_URL));
+        assert!(session_cookie(&set_cookies).contains("Max-Age=0"));
+    })
+    .await;
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_03_rp_initiated_logout() {
+    let signals = mock::Signals::new();
+
+    let auth_options = AuthOptions::new().add_provider(Box::new(rp_initiated_logout_provider()));
+    let options = AxumRuntimeOptions::new(auth_options);
+
+    mock::environment::axum_::run(signals, options, || async {
+        let (location, _) = logout(
+            None,
+            &[
+                ("provider", MOCK_OIDC_PROVIDER_NAME),
+                ("callback_url", "/signed-out"),
+            ],
+        )
+        .await;
+
+        // Through the issuer, which redirects back once signed out
+        let location = Url::parse(&location).expect("Invalid location");
+        assert_eq!(
+            location.as_str().split('?').next(),
+            Some(format!("{}/{}", MOCK_PROVIDER_URL, MOCK_END_SESSION).as_str())
+        );
+        let param = |key: &str| {
+            location
+                .query_pairs()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v.to_string())
+        };
+        assert_eq!(param("client_id").as_deref(), Some(MOCK_PROVIDER_CLIENT_ID));
+        assert_eq!(
+            param("post_logout_redirect_uri"),
+            Some(format!("{}/signed-out", MOCK_AUTH_URL))
+        );
+
+        // Unknown providers are ignored
+        let (location, _) = logout(None, &[("provider", "unknown")]).await;
+        assert_eq!(location, format!("{}/", MOCK_AUTH_URL));
+    })
+    .await;
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_04_rp_initiated_logout_disabled() {
+    let signals = mock::Signals::new();
+
+    // RP-initiated logout is opt-in
+    let auth_options = AuthOptions::new().add_provider(Box::new(mock::mock_oidc_provider()));
+    let options = AxumRuntimeOptions::new(auth_options);
+
+    mock::environment::axum_::run(signals, options, || async {
+        let (location, _) = logout(None, &[("provider", MOCK_OIDC_PROVIDER_NAME)]).await;
+        assert_eq!(location, format!("{}/", MOCK_AUTH_URL));
+    })
+    .await;
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_05_logout_refuses_lookalike_origins() {
+    let signals = mock::Signals::new();
+
+    let auth_options = AuthOptions::new().add_provider(Box::new(MockProvider));
+    let options = AxumRuntimeOptions::new(auth_options);
+
+    mock::environment::axum_::run(signals, options, || async {
+        // URLs starting with the origin, but leading to another host
+        for callback_url in [
+            format!("{}.evil.example.com/", MOCK_AUTH_URL),
+            format!("{}@evil.example.com/", MOCK_AUTH_URL),
+        ] {
+            let (location, _) = logout(None, &[("callback_url", &callback_url)]).await;
+            assert_eq!(location, MOCK_AUTH_URL, "Redirected to {}", callback_url);
+        }
+
+        // Paths stay on the origin, however the browser reads them
+        let (location, _) = logout(None, &[("callback_url", "/\\evil.example.com")]).await;
+        assert_eq!(Url::parse(&location).unwrap().host_str(), Some("localhost"));
+    })
+    .await;
+}
@@ -0,0 +1,224 @@
+mod mock;
+
+use bzauth_rs::auth::AuthOptions;
+use bzauth_rs::runtimes::axum::AxumRuntimeOptions;
+use mock::runtime::MOCK_AUTH_URL;
+use mock::{MOCK_PROVIDER_NAME, MOCK_PROVIDER_URL, MockProvider, client, csrf_token};
+use reqwest::StatusCode;
+use reqwest::header::{CACHE_CONTROL, COOKIE, LOCATION, SET_COOKIE};
+
+/// Posts a form to the auth server with the given cookie and headers
+async fn post(
+    path: &str,
+    cookie: Option<&str>,
+    headers: &[(&'static str, &str)],
+    form: &[(&str, &str)],
+) -> reqwest::Response {
+    let mut request = client()
+        .post(format!("{}{}", MOCK_AUTH_URL, path))
+        .form(form);
+    if let Some(cookie) = cookie {
+        request = request.header(COOKIE, cookie);
+    }
+    for (key, value) in headers {
+        request = request.header(*key, *value);
+    }
+
+    request
+        .send()
+        .await
+        .expect("Failed to make request to auth server")
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_00_csrf_token() {
+    let signals = mock::Signals::new();
+
+    let auth_options = AuthOptions::new().add_provider(Box::new(MockProvider));
+    let options = AxumRuntimeOptions::new(auth_options);
+
+    mock::environment::axum_::run(signals, options, || async {
+        let response = reqwest::get(format!("{}/csrf", MOCK_AUTH_URL))
+            .await
+            .expect("Failed to make request to auth server");
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response
+                .headers()
+                .get(CACHE_CONTROL)
+                .and_then(|v| v.to_str().ok()),
+            Some("no-store")
+        );
+
+        // The cookie is hidden from scripts, which read the token from the body instead
+        let cookie = response
+            .headers()
+            .get(SET_COOKIE)
+            .and_then(|v| v.to_str().ok())
+            .map(String::from)
+            .expect("No CSRF cookie was set");
+        assert!(cookie.starts_with("csrf="), "Unexpected cookie: {}", cookie);
+        assert!(
+            cookie.contains("HttpOnly"),
+            "Cookie is not HttpOnly: {}",
+            cookie
+        );
+        assert!(
+            cookie.contains("SameSite=Lax"),
+            "Cookie is not Lax: {}",
+            cookie
+        );
+        assert!(cookie.contains("Path=/"), "Cookie has no path: {}", cookie);
+
+        let body: serde_json::Value = response.json().await.expect("Token is not JSON");
+        let token = body["csrf_token"].as_str().expect("No token was returned");
+        assert!(token.len() >= 43, "Token is too short: {}", token);
+
+        // The cookie only holds the token and its signature
+        let cookie = cookie.split(';').next().unwrap().to_string();
+        assert!(cookie.starts_with(&format!("csrf={}.", token)));
+
+        // The token of a valid cookie is kept
+        let response = client()
+            .get(format!("{}/csrf", MOCK_AUTH_URL))
+            .header(COOKIE, &cookie)
+            .send()
+            .await
+            .expect("Failed to make request to auth server");
+        assert!(response.headers().get(SET_COOKIE).is_none());
+        let body: serde_json::Value = response.json().await.expect("Token is not JSON");
+        assert_eq!(body["csrf_token"], token);
+
+        // A forged cookie is replaced
+        let response = client()
+            .get(format!("{}/csrf", MOCK_AUTH_URL))
+            .header(COOKIE, "csrf=forged.c2lnbmF0dXJl")
+            .send()
+            .await
+            .expect("Failed to make request to auth server");
+        assert!(response.headers().get(SET_COOKIE).is_some());
+        let body: serde_json::Value = response.json().await.expect("Token is not JSON");
+        assert_ne!(body["csrf_token"], "forged");
+    })
+    .await;
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_01_csrf_logout() {
+    let signals = mock::Signals::new();
+
+    let auth_options = AuthOptions::new().add_provider(Box::new(MockProvider));
+    let options = AxumRuntimeOptions::new(auth_options);
+
+    mock::environment::axum_::run(signals, options, || async {
+        let (token, cookie) = csrf_token().await;
+        let (other_token, _) = csrf_token().await;
+
+        // Without the token, or without the cookie it is bound to
+        let response = post("/logout", Some(&cookie), &[], &[]).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = post("/logout", None, &[], &[("csrf_token", &token)]).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        // With a token bound to another cookie
+        let response = post(
+            "/logout",
+            Some(&cookie),
+            &[],
+            &[("csrf_token", &other_token)],
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        // With a cookie forged for the token
+        let forged = format!("csrf={}.c2lnbmF0dXJl", token);
+        let response = post("/logout", Some(&forged), &[], &[("csrf_token", &token)]).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        // As a form field, or a header for scripts
+        let response = post("/logout", Some(&cookie), &[], &[("csrf_token", &token)]).await;
+        assert_eq!(response.status(), StatusCode::FOUND);
+        let response = post("/logout", Some(&cookie), &[("x-csrf-token", &token)], &[]).await;
+        assert_eq!(response.status(), StatusCode::FOUND);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_02_csrf_login() {
+    let signals = mock::Signals::new();
+
+    let auth_options = AuthOptions::new().add_provider(Box::new(MockProvider));
+    let options = AxumRuntimeOptions::new(auth_options);
+
+    mock::environment::axum_::run(signals, options, || async {
+        let path = format!("/login/{}", MOCK_PROVIDER_NAME);
+
+        // A form posted by another site cannot start a sign-in
+        let response = post(&path, None, &[], &[]).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let (token, cookie) = csrf_token().await;
+        let response = post(&path, Some(&cookie), &[], &[("csrf_token", &token)]).await;
+        assert_eq!(response.status(), StatusCode::FOUND);
+        let location = response
+            .headers()
+            .get(LOCATION)
+            .and_then(|v| v.to_str().ok())
+            .expect("Login response has no location");
+        assert!(
+            location.starts_with(MOCK_PROVIDER_URL),
+            "Login did not redirect to the provider: {}",
+            location
+        );
+    })
+    .await;
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_03_csrf_refuses_other_signed_values() {
+    let signals = mock::Signals::new();
+
+    let auth_options = AuthOptions::new().add_provider(Box::new(MockProvider));
+    let options = AxumRuntimeOptions::new(auth_options);
+
+    mock::environment::axum_::run(signals, options, || async {
+        // The state cookie is signed with the same secret, for another purpose
+        let response = client()
+            .get(format!("{}/login/{}", MOCK_AUTH_URL, MOCK_PROVIDER_NAME))
+            .send()
+            .await
+            .expect("Failed to make request to auth server");
+        let signed_state = response
+            .headers()
+            .get_all(SET_COOKIE)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .filter_map(|v| v.split(';').next())
+            .find_map(|v| v.strip_prefix("state="))
+            .map(String::from)
+            .expect("No state cookie was set");
+        let (state, _) = signed_state.rsplit_once('.').unwrap();
+
+        let cookie = format!("csrf={}", signed_state);
+        let response = post("/logout", Some(&cookie), &[], &[("csrf_token", state)]).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    })
+    .await;
+}
@@ -0,0 +1,162 @@
+mod mock;
+
+use bzauth_rs::adaptors::error::AdaptorError;
+use bzauth_rs::auth::AuthOptions;
+use bzauth_rs::contracts::adapt::{
+    Adapt, AdaptAccount, AdaptResult, AdaptSession, AdaptUser, AdaptVerificationToken,
+    CreateSessionOptions, ProviderAccountId, SessionUser, UseVerificationTokenOptions,
+};
+use bzauth_rs::runtimes::axum::AxumRuntimeOptions;
+use bzauth_rs::tools::CoreError;
+use mock::MockProvider;
+use mock::runtime::MOCK_AUTH_URL;
+use reqwest::StatusCode;
+use reqwest::header::COOKIE;
+
+/// An adaptor whose store can never be reached, like a database that is down
+struct UnavailableAdaptor;
+
+fn unavailable<T>() -> AdaptResult<T> {
+    Err(AdaptorError::Unavailable(
+        "connection refused by db.internal:5432".to_string(),
+    ))
+}
+
+#[async_trait::async_trait]
+impl Adapt for UnavailableAdaptor {
+    async fn create_user(&self, _user: AdaptUser) -> AdaptResult<AdaptUser> {
+        unavailable()
+    }
+    async fn get_user(&self, _id: String) -> AdaptResult<Option<AdaptUser>> {
+        unavailable()
+    }
+    async fn get_user_by_email(&self, _email: String) -> AdaptResult<Option<AdaptUser>> {
+        unavailable()
+    }
+    async fn get_user_by_account(
+        &self,
+        _provider: ProviderAccountId,
+    ) -> AdaptResult<Option<AdaptUser>> {
+        unavailable()
+    }
+    async fn update_user(&self, _user: AdaptUser) -> AdaptResult<AdaptUser> {
+        unavailable()
+    }
+    async fn delete_user(&self, _id: String) -> AdaptResult<()> {
+        unavailable()
+    }
+    async fn get_account(&self, _provider: ProviderAccountId) -> AdaptResult<Option<AdaptAccount>> {
+        unavailable()
+    }
+    async fn get_accounts_by_user(&self, _user_id: String) -> AdaptResult<Vec<AdaptAccount>> {
+        unavailable()
+    }
+    async fn link_account(&self, _account: AdaptAccount) -> AdaptResult<AdaptAccount> {
+        unavailable()
+    }
+    async fn update_account(&self, _account: AdaptAccount) -> AdaptResult<AdaptAccount> {
+        unavailable()
+    }
+    async fn unlink_account(&self, _provider: ProviderAccountId) -> AdaptResult<()> {
+        unavailable()
+    }
+    async fn create_session(&self, _options: CreateSessionOptions) -> AdaptResult<AdaptSession> {
+        unavailable()
+    }
+    async fn get_session_and_user(&self, _token: String) -> AdaptResult<Option<SessionUser>> {
+        unavailable()
+    }
+    async fn update_session(&self, _session: AdaptSession) -> AdaptResult<AdaptSession> {
+        unavailable()
+    }
+    async fn delete_session(&self, _token: String) -> AdaptResult<()> {
+        unavailable()
+    }
+    async fn create_verification_token(
+        &self,
+        _token: AdaptVerificationToken,
+    ) -> AdaptResult<AdaptVerificationToken> {
+        unavailable()
+    }
+    async fn use_verification_token(
+        &self,
+        _options: UseVerificationTokenOptions,
+    ) -> AdaptResult<Option<AdaptVerificationToken>> {
+        unavailable()
+    }
+}
+
+#[test]
+fn test_00_adaptor_error_status() {
+    let cases = [
+        (AdaptorError::NotFound("user 1".to_string()), 404),
+        (AdaptorError::Conflict("users.email".to_string()), 409),
+        (AdaptorError::Unavailable("pool timed out".to_string()), 503),
+        (AdaptorError::Other("disk I/O error".to_string()), 500),
+    ];
+
+    for (error, status) in cases {
+        assert_eq!(error.status().as_u16(), status);
+
+        // The details of the store are logged, not returned to the client
+        let message = error.message().to_string();
+        let core_error = CoreError::from(error);
+        assert_eq!(core_error.status, status);
+        assert!(
+            !core_error.message.contains(&message),
+            "Store details leaked: {}",
+            core_error.message
+        );
+    }
+}
+
+#[test]
+#[cfg(feature = "adapt_diesel")]
+fn test_01_diesel_error() {
+    use diesel::result::Error;
+
+    assert!(matches!(
+        AdaptorError::from(Error::NotFound),
+        AdaptorError::NotFound(_)
+    ));
+    assert!(matches!(
+        AdaptorError::from(Error::RollbackTransaction),
+        AdaptorError::Other(_)
+    ));
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_02_adaptor_unavailable() {
+    let signals = mock::Signals::new();
+
+    let auth_options = AuthOptions::new()
+        .add_provider(Box::new(MockProvider))
+        .with_adaptor(Box::new(UnavailableAdaptor));
+    let options = AxumRuntimeOptions::new(auth_options);
+
+    mock::environment::axum_::run(signals, options, || async {
+        // The handler fails instead of crashing the request
+        let response = reqwest::Client::new()
+            .get(format!("{}/session", MOCK_AUTH_URL))
+            .header(COOKIE, "session_token=token")
+            .send()
+            .await
+            .expect("Failed to make request to auth server");
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let body: serde_json::Value = response.json().await.expect("Error is not JSON");
+        assert_eq!(body["status"], 503);
+        assert_eq!(body["message"], "Service unavailable");
+
+        // Without a session, the store is not needed
+        let response = reqwest::get(format!("{}/session", MOCK_AUTH_URL))
+            .await
+            .expect("Failed to make request to auth server");
+        assert_eq!(response.status(), StatusCode::OK);
+    })
+    .await;
+}
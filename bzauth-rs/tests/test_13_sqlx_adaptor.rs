@@ -0,0 +1,269 @@
+#![cfg(all(feature = "adapt_sqlx", feature = "backend_sqlite"))]
+
+mod mock;
+
+use bzauth_rs::adaptors::error::AdaptorError;
+use bzauth_rs::adaptors::sqlx::{SqlxAdapterOptions, SqlxAdaptor};
+use bzauth_rs::auth::AuthOptions;
+use bzauth_rs::contracts::adapt::{
+    Adapt, AdaptAccount, AdaptUser, AdaptVerificationToken, CreateSessionOptions,
+    ProviderAccountId, UseVerificationTokenOptions,
+};
+use bzauth_rs::contracts::provide::ProviderType;
+use bzauth_rs::contracts::token::Token;
+use bzauth_rs::runtimes::axum::AxumRuntimeOptions;
+use mock::{MockProvider, get_session, session_cookie, sign_in};
+use sqlx::Sqlite;
+use sqlx::sqlite::SqlitePoolOptions;
+
+/// An adaptor over a fresh in-memory database, with its tables created
+async fn adaptor() -> SqlxAdaptor<Sqlite> {
+    // Every connection to :memory: is a new database, so the pool keeps to one
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .expect("Failed to open the database");
+    let adaptor = SqlxAdaptor::from_options(SqlxAdapterOptions { pool });
+    adaptor.migrate().await.expect("Failed to migrate");
+    adaptor
+}
+
+fn user(id: &str, email: &str) -> AdaptUser {
+    AdaptUser {
+        id: Some(id.to_string()),
+        username: Some("John Doe".to_string()),
+        email: Some(email.to_string()),
+        image: None,
+        email_verified: None,
+    }
+}
+
+fn account(user_id: &str, provider_account_id: &str) -> AdaptAccount {
+    AdaptAccount {
+        id: None,
+        user_id: Some(user_id.to_string()),
+        provider_id: Some("github".to_string()),
+        provider_type: ProviderType::OAuth,
+        provider_account_id: Some(provider_account_id.to_string()),
+        token: Some(Token {
+            access_token: Some("access_token".to_string()),
+            expires_in: Some(3600),
+            ..Default::default()
+        }),
+    }
+}
+
+fn provider_account_id(provider_account_id: &str) -> ProviderAccountId {
+    ProviderAccountId {
+        provider_id: "github".to_string(),
+        provider_account_id: provider_account_id.to_string(),
+    }
+}
+
+#[tokio::test]
+async fn test_00_sqlx_users() {
+    let adaptor = adaptor().await;
+
+    // Migrations already applied are skipped
+    adaptor.migrate().await.expect("Failed to migrate again");
+
+    let created = adaptor
+        .create_user(user("user_1", "john.doe@email.com"))
+        .await
+        .expect("Failed to create user");
+    assert_eq!(created.id.as_deref(), Some("user_1"));
+
+    let found = adaptor.get_user("user_1".to_string()).await.unwrap();
+    assert_eq!(found.and_then(|u| u.username).as_deref(), Some("John Doe"));
+    let found = adaptor
+        .get_user_by_email("john.doe@email.com".to_string())
+        .await
+        .unwrap();
+    assert_eq!(found.and_then(|u| u.id).as_deref(), Some("user_1"));
+    assert!(
+        adaptor
+            .get_user("unknown".to_string())
+            .await
+            .unwrap()
+            .is_none()
+    );
+
+    // Emails are unique
+    let error = adaptor
+        .create_user(user("user_2", "john.doe@email.com"))
+        .await
+        .unwrap_err();
+    assert!(matches!(error, AdaptorError::Conflict(_)), "{:?}", error);
+
+    let updated = adaptor
+        .update_user(AdaptUser {
+            username: Some("Jane Doe".to_string()),
+            ..user("user_1", "jane.doe@email.com")
+        })
+        .await
+        .unwrap();
+    assert_eq!(updated.username.as_deref(), Some("Jane Doe"));
+    assert_eq!(updated.email.as_deref(), Some("jane.doe@email.com"));
+
+    let error = adaptor
+        .update_user(user("unknown", "unknown@email.com"))
+        .await
+        .unwrap_err();
+    assert!(matches!(error, AdaptorError::NotFound(_)), "{:?}", error);
+}
+
+#[tokio::test]
+async fn test_01_sqlx_accounts() {
+    let adaptor = adaptor().await;
+    adaptor
+        .create_user(user("user_1", "john.doe@email.com"))
+        .await
+        .unwrap();
+
+    let linked = adaptor
+        .link_account(account("user_1", "12345"))
+        .await
+        .expect("Failed to link account");
+    assert!(linked.id.is_some());
+    let token = linked.token.expect("Account has no token");
+    assert_eq!(token.access_token.as_deref(), Some("access_token"));
+    assert!(token.expires_in.is_some_and(|e| e > 3500 && e <= 3600));
+
+    let found = adaptor
+        .get_user_by_account(provider_account_id("12345"))
+        .await
+        .unwrap();
+    assert_eq!(found.and_then(|u| u.id).as_deref(), Some("user_1"));
+    let found = adaptor
+        .get_account(provider_account_id("12345"))
+        .await
+        .unwrap();
+    assert_eq!(found.and_then(|a| a.user_id).as_deref(), Some("user_1"));
+
+    // An account belongs to a single user, which must exist
+    let error = adaptor
+        .link_account(account("user_1", "12345"))
+        .await
+        .unwrap_err();
+    assert!(matches!(error, AdaptorError::Conflict(_)), "{:?}", error);
+    let error = adaptor
+        .link_account(account("unknown", "67890"))
+        .await
+        .unwrap_err();
+    assert!(matches!(error, AdaptorError::Conflict(_)), "{:?}", error);
+
+    adaptor
+        .unlink_account(provider_account_id("12345"))
+        .await
+        .unwrap();
+    let found = adaptor
+        .get_user_by_account(provider_account_id("12345"))
+        .await
+        .unwrap();
+    assert!(found.is_none());
+}
+
+#[tokio::test]
+async fn test_02_sqlx_sessions() {
+    let adaptor = adaptor().await;
+    adaptor
+        .create_user(user("user_1", "john.doe@email.com"))
+        .await
+        .unwrap();
+    adaptor
+        .link_account(account("user_1", "12345"))
+        .await
+        .unwrap();
+
+    let session = adaptor
+        .create_session(CreateSessionOptions {
+            token: "token".to_string(),
+            user_id: "user_1".to_string(),
+            expires_in: 60,
+        })
+        .await
+        .expect("Failed to create session");
+    assert!(!session.is_expired());
+
+    let session_user = adaptor
+        .get_session_and_user("token".to_string())
+        .await
+        .unwrap()
+        .expect("Session was not found");
+    assert_eq!(session_user.user.id.as_deref(), Some("user_1"));
+
+    let mut session = session_user.session;
+    session.expires_in = 3600;
+    let session = adaptor.update_session(session).await.unwrap();
+    assert!(session.expires_in > 60);
+
+    // Deleting the user deletes its sessions and accounts
+    adaptor.delete_user("user_1".to_string()).await.unwrap();
+    let session_user = adaptor
+        .get_session_and_user("token".to_string())
+        .await
+        .unwrap();
+    assert!(session_user.is_none());
+    let account = adaptor
+        .get_account(provider_account_id("12345"))
+        .await
+        .unwrap();
+    assert!(account.is_none());
+}
+
+#[tokio::test]
+async fn test_03_sqlx_verification_tokens() {
+    let adaptor = adaptor().await;
+
+    let token = adaptor
+        .create_verification_token(AdaptVerificationToken {
+            email: "john.doe@email.com".to_string(),
+            token: "token".to_string(),
+            expires_in: 600,
+        })
+        .await
+        .expect("Failed to create token");
+    assert_eq!(token.token, "token");
+
+    let use_token = || UseVerificationTokenOptions {
+        email: "john.doe@email.com".to_string(),
+        token: "token".to_string(),
+    };
+
+    // A token can only be used once
+    let used = adaptor.use_verification_token(use_token()).await.unwrap();
+    assert_eq!(used.map(|t| t.email).as_deref(), Some("john.doe@email.com"));
+    let used = adaptor.use_verification_token(use_token()).await.unwrap();
+    assert!(used.is_none());
+}
+
+#[tokio::test]
+async fn test_04_sqlx_unavailable() {
+    let adaptor = adaptor().await;
+    adaptor.options.pool.close().await;
+
+    let error = adaptor.get_user("user_1".to_string()).await.unwrap_err();
+    assert!(matches!(error, AdaptorError::Unavailable(_)), "{:?}", error);
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_05_sqlx_sign_in() {
+    let signals = mock::Signals::new();
+
+    let auth_options = AuthOptions::new()
+        .add_provider(Box::new(MockProvider))
+        .with_adaptor(adaptor().await.into());
+    let options = AxumRuntimeOptions::new(auth_options);
+
+    mock::environment::axum_::run(signals, options, || async {
+        let cookie = session_cookie(&sign_in().await);
+        let (body, _) = get_session(Some(&cookie)).await;
+        assert_eq!(body["user"]["email"], "john.doe@email.com");
+    })
+    .await;
+}
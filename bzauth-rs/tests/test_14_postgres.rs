@@ -0,0 +1,290 @@
+#![cfg(all(feature = "backend_postgres", feature = "testing"))]
+//! These tests need a Postgres server, given by `BZAUTH_TEST_POSTGRES_URL`
+//! (e.g. `postgres://postgres@localhost:5432`), and are skipped without one. Each test creates a
+//! database of its own on the server.
+
+use bzauth_rs::adaptors::error::AdaptorError;
+use bzauth_rs::contracts::adapt::{Adapt, AdaptAccount, AdaptUser};
+use bzauth_rs::contracts::provide::ProviderType;
+use bzauth_rs::contracts::token::Token;
+use bzauth_rs::testing::adaptor_conformance;
+
+const POSTGRES_URL_VAR: &str = "BZAUTH_TEST_POSTGRES_URL";
+
+/// The URL of the Postgres server, or `None` if the test should be skipped
+fn server_url() -> Option<String> {
+    let url = std::env::var(POSTGRES_URL_VAR).ok();
+    if url.is_none() {
+        eprintln!("Skipping, {} is not set", POSTGRES_URL_VAR);
+    }
+    url.map(|url| url.trim_end_matches('/').to_string())
+}
+
+fn database_name(prefix: &str) -> String {
+    format!("{}_{}", prefix, uuid::Uuid::new_v4().simple())
+}
+
+/// Runs the conformance suite over a fresh database, then checks that Postgres' unique violations
+/// are reported as conflicts
+async fn exercise(adaptor: &dyn Adapt) {
+    adaptor_conformance(adaptor).await;
+
+    let user = AdaptUser {
+        id: Some(uuid::Uuid::new_v4().to_string()),
+        email: Some(format!("{}@email.com", uuid::Uuid::new_v4().simple())),
+        ..Default::default()
+    };
+    adaptor.create_user(user.clone()).await.unwrap();
+    let error = adaptor
+        .create_user(AdaptUser {
+            id: Some(uuid::Uuid::new_v4().to_string()),
+            ..user.clone()
+        })
+        .await
+        .unwrap_err();
+    assert!(matches!(error, AdaptorError::Conflict(_)), "{:?}", error);
+
+    let account = AdaptAccount {
+        id: Some(uuid::Uuid::new_v4().to_string()),
+        user_id: user.id.clone(),
+        provider_id: Some("github".to_string()),
+        provider_type: ProviderType::OAuth,
+        provider_account_id: Some(uuid::Uuid::new_v4().to_string()),
+        token: Some(Token::default()),
+    };
+    adaptor.link_account(account.clone()).await.unwrap();
+    let error = adaptor
+        .link_account(AdaptAccount {
+            id: Some(uuid::Uuid::new_v4().to_string()),
+            ..account
+        })
+        .await
+        .unwrap_err();
+    assert!(matches!(error, AdaptorError::Conflict(_)), "{:?}", error);
+}
+
+#[cfg(feature = "adapt_sqlx")]
+#[tokio::test]
+async fn test_00_sqlx_postgres() {
+    use bzauth_rs::adaptors::sqlx::{SqlxAdapterOptions, SqlxAdaptor};
+    use sqlx::{Connection, PgConnection, PgPool};
+
+    let Some(url) = server_url() else {
+        return;
+    };
+    let name = database_name("bzauth_sqlx");
+    let mut conn = PgConnection::connect(&format!("{}/postgres", url))
+        .await
+        .expect("Failed to connect to Postgres");
+    sqlx::query(&format!("CREATE DATABASE {}", name))
+        .execute(&mut conn)
+        .await
+        .expect("Failed to create the database");
+
+    let pool = PgPool::connect(&format!("{}/{}", url, name))
+        .await
+        .expect("Failed to connect to the database");
+    let adaptor = SqlxAdaptor::from_options(SqlxAdapterOptions { pool });
+    adaptor.migrate().await.expect("Failed to migrate");
+    adaptor.migrate().await.expect("Failed to migrate again");
+
+    exercise(&adaptor).await;
+
+    // The server may not have ended the closed connections yet
+    adaptor.options.pool.close().await;
+    sqlx::query(&format!("DROP DATABASE {} WITH (FORCE)", name))
+        .execute(&mut conn)
+        .await
+        .expect("Failed to drop the database");
+}
+
+#[cfg(feature = "adapt_diesel")]
+mod diesel_postgres {
+    pub mod schema {
+        diesel::table! {
+            users {
+                id -> Text,
+                name -> Nullable<Text>,
+                email -> Nullable<Text>,
+                email_verified -> Nullable<Timestamp>,
+                image -> Nullable<Text>,
+                created_at -> Timestamp,
+                updated_at -> Timestamp,
+            }
+        }
+
+        diesel::table! {
+            accounts {
+                id -> Text,
+                provider_id -> Text,
+                provider_account_id -> Text,
+                user_id -> Text,
+                provider_type -> Text,
+                refresh_token -> Nullable<Text>,
+                access_token -> Nullable<Text>,
+                expires_at -> Nullable<Timestamp>,
+                token_type -> Nullable<Text>,
+                scope -> Nullable<Text>,
+                id_token -> Nullable<Text>,
+                session_state -> Nullable<Text>,
+                created_at -> Timestamp,
+                updated_at -> Timestamp,
+            }
+        }
+
+        // Time zone aware, unlike the other tables
+        diesel::table! {
+            sessions {
+                id -> Text,
+                user_id -> Text,
+                token -> Text,
+                expires_at -> Timestamptz,
+                created_at -> Timestamptz,
+                updated_at -> Timestamptz,
+            }
+        }
+
+        diesel::table! {
+            verification_tokens {
+                id -> Text,
+                email -> Text,
+                token -> Text,
+                expires_at -> Timestamp,
+                created_at -> Timestamp,
+                updated_at -> Timestamp,
+            }
+        }
+
+        diesel::joinable!(accounts -> users (user_id));
+        diesel::joinable!(sessions -> users (user_id));
+
+        diesel::allow_tables_to_appear_in_same_query!(
+            users,
+            accounts,
+            sessions,
+            verification_tokens
+        );
+    }
+
+    pub mod models {
+        use chrono::{DateTime, NaiveDateTime, Utc};
+        use diesel::prelude::{Queryable, Selectable};
+
+        #[derive(Clone, Default, Queryable, Selectable)]
+        #[diesel(table_name = super::schema::users)]
+        #[diesel(check_for_backend(diesel::pg::Pg))]
+        pub struct User {
+            pub id: String,
+            pub name: Option<String>,
+            pub email: Option<String>,
+            pub email_verified: Option<NaiveDateTime>,
+            pub image: Option<String>,
+            pub created_at: NaiveDateTime,
+            pub updated_at: NaiveDateTime,
+        }
+
+        #[derive(Clone, Default, Queryable, Selectable)]
+        #[diesel(table_name = super::schema::accounts)]
+        #[diesel(check_for_backend(diesel::pg::Pg))]
+        pub struct Account {
+            pub id: String,
+            pub provider_id: String,
+            pub provider_account_id: String,
+            pub user_id: String,
+            pub provider_type: String,
+            pub refresh_token: Option<String>,
+            pub access_token: Option<String>,
+            pub expires_at: Option<NaiveDateTime>,
+            pub token_type: Option<String>,
+            pub scope: Option<String>,
+            pub id_token: Option<String>,
+            pub session_state: Option<String>,
+            pub created_at: NaiveDateTime,
+            pub updated_at: NaiveDateTime,
+        }
+
+        #[derive(Clone, Default, Queryable, Selectable)]
+        #[diesel(table_name = super::schema::sessions)]
+        #[diesel(check_for_backend(diesel::pg::Pg))]
+        pub struct Session {
+            pub id: String,
+            pub user_id: String,
+            pub token: String,
+            pub expires_at: DateTime<Utc>,
+            pub created_at: DateTime<Utc>,
+            pub updated_at: DateTime<Utc>,
+        }
+
+        #[derive(Clone, Default, Queryable, Selectable)]
+        #[diesel(table_name = super::schema::verification_tokens)]
+        #[diesel(check_for_backend(diesel::pg::Pg))]
+        pub struct VerificationToken {
+            pub id: String,
+            pub email: String,
+            pub token: String,
+            pub expires_at: NaiveDateTime,
+        }
+    }
+
+    use diesel::PgConnection;
+
+    pub struct PgDieselAdaptor;
+    bzauth_rs::adapt_diesel! {
+        PgDieselAdaptor,
+        PgConnection,
+        User = self::models::User,
+        UserTable = self::schema::users,
+        Account = self::models::Account,
+        AccountTable = self::schema::accounts,
+        Session = self::models::Session,
+        SessionTable = self::schema::sessions,
+        VerificationToken = self::models::VerificationToken,
+        VerificationTokenTable = self::schema::verification_tokens,
+    }
+}
+
+#[cfg(feature = "adapt_diesel")]
+#[tokio::test]
+async fn test_01_diesel_postgres() {
+    use bzauth_rs::adaptors::diesel::{DieselAdapterOptions, DieselAdaptor};
+    use diesel::connection::SimpleConnection;
+    use diesel::r2d2::{ConnectionManager, Pool};
+    use diesel::{Connection, PgConnection, RunQueryDsl};
+    use diesel_postgres::PgDieselAdaptor;
+
+    let Some(url) = server_url() else {
+        return;
+    };
+    let name = database_name("bzauth_diesel");
+    let mut conn = PgConnection::establish(&format!("{}/postgres", url))
+        .expect("Failed to connect to Postgres");
+    diesel::sql_query(format!("CREATE DATABASE {}", name))
+        .execute(&mut conn)
+        .expect("Failed to create the database");
+
+    let manager = ConnectionManager::<PgConnection>::new(format!("{}/{}", url, name));
+    let pool = Pool::builder()
+        .max_size(2)
+        .build(manager)
+        .expect("Failed to create connection pool");
+    pool.get()
+        .expect("Failed to get connection from pool")
+        .batch_execute(concat!(
+            include_str!("../migrations/postgres/20250801000000_create_auth_tables.sql"),
+            "ALTER TABLE sessions ALTER COLUMN expires_at TYPE TIMESTAMPTZ, \
+             ALTER COLUMN created_at TYPE TIMESTAMPTZ, \
+             ALTER COLUMN updated_at TYPE TIMESTAMPTZ;"
+        ))
+        .expect("Failed to create the tables");
+
+    let adaptor = DieselAdaptor::from_options(DieselAdapterOptions {
+        conn_pool: pool,
+        adaptor: PgDieselAdaptor,
+    });
+    exercise(&adaptor).await;
+
+    drop(adaptor);
+    diesel::sql_query(format!("DROP DATABASE {} WITH (FORCE)", name))
+        .execute(&mut conn)
+        .expect("Failed to drop the database");
+}
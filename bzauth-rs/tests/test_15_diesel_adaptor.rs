@@ -0,0 +1,67 @@
+#![cfg(all(feature = "adapt_diesel", feature = "backend_sqlite"))]
+
+mod mock;
+
+use std::time::Duration;
+
+use bzauth_rs::contracts::adapt::{Adapt, AdaptUser, CreateSessionOptions};
+use mock::{diesel_adaptor, diesel_pool};
+
+#[tokio::test]
+async fn test_00_diesel_sessions() {
+    let adaptor = diesel_adaptor(diesel_pool());
+
+    let user = adaptor
+        .create_user(AdaptUser {
+            id: Some("user_1".to_string()),
+            username: Some("John Doe".to_string()),
+            email: Some("john.doe@email.com".to_string()),
+            image: None,
+            email_verified: None,
+        })
+        .await
+        .expect("Failed to create user");
+    assert_eq!(user.id.as_deref(), Some("user_1"));
+
+    adaptor
+        .create_session(CreateSessionOptions {
+            token: "token".to_string(),
+            user_id: "user_1".to_string(),
+            expires_in: 60,
+        })
+        .await
+        .expect("Failed to create session");
+    let session_user = adaptor
+        .get_session_and_user("token".to_string())
+        .await
+        .unwrap()
+        .expect("Session was not found");
+    assert_eq!(
+        session_user.user.email.as_deref(),
+        Some("john.doe@email.com")
+    );
+
+    adaptor.delete_user("user_1".to_string()).await.unwrap();
+    let session_user = adaptor
+        .get_session_and_user("token".to_string())
+        .await
+        .unwrap();
+    assert!(session_user.is_none());
+}
+
+// The test runtime has a single thread, which a blocking query would hold
+#[tokio::test]
+async fn test_01_diesel_does_not_block() {
+    let pool = diesel_pool();
+    let adaptor = diesel_adaptor(pool.clone());
+
+    // The operation waits for the only connection, which is handed back on the same thread
+    let held = pool.get().expect("Failed to get connection from pool");
+    let release = async move {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        drop(held);
+    };
+
+    let (user, _) = tokio::join!(adaptor.get_user("user_1".to_string()), release);
+    assert!(user.expect("The connection was never released").is_none());
+}
@@ -0,0 +1,343 @@
+#![cfg(feature = "adapt_memory")]
+
+mod mock;
+
+use bzauth_rs::adaptors::error::AdaptorError;
+use bzauth_rs::adaptors::memory::{MemoryAdapterOptions, MemoryAdaptor};
+use bzauth_rs::auth::AuthOptions;
+use bzauth_rs::contracts::adapt::{
+    Adapt, AdaptAccount, AdaptUser, AdaptVerificationToken, CreateSessionOptions,
+    ProviderAccountId, UseVerificationTokenOptions,
+};
+use bzauth_rs::contracts::provide::ProviderType;
+use bzauth_rs::contracts::token::Token;
+use bzauth_rs::runtimes::axum::AxumRuntimeOptions;
+use mock::{MockProvider, get_session, session_cookie, sign_in};
+
+fn user(id: &str, email: &str) -> AdaptUser {
+    AdaptUser {
+        id: Some(id.to_string()),
+        username: Some("John Doe".to_string()),
+        email: Some(email.to_string()),
+        image: None,
+        email_verified: None,
+    }
+}
+
+fn account(user_id: &str, provider_account_id: &str) -> AdaptAccount {
+    AdaptAccount {
+        id: None,
+        user_id: Some(user_id.to_string()),
+        provider_id: Some("github".to_string()),
+        provider_type: ProviderType::OAuth,
+        provider_account_id: Some(provider_account_id.to_string()),
+        token: Some(Token {
+            access_token: Some("access_token".to_string()),
+            expires_in: Some(3600),
+            ..Default::default()
+        }),
+    }
+}
+
+fn provider_account_id(provider_account_id: &str) -> ProviderAccountId {
+    ProviderAccountId {
+        provider_id: "github".to_string(),
+        provider_account_id: provider_account_id.to_string(),
+    }
+}
+
+fn session(token: &str, expires_in: u64) -> CreateSessionOptions {
+    CreateSessionOptions {
+        token: token.to_string(),
+        user_id: "user_1".to_string(),
+        expires_in,
+    }
+}
+
+#[tokio::test]
+async fn test_00_memory_users() {
+    let adaptor = MemoryAdaptor::new();
+
+    adaptor
+        .create_user(user("user_1", "john.doe@email.com"))
+        .await
+        .expect("Failed to create user");
+    let found = adaptor
+        .get_user_by_email("john.doe@email.com".to_string())
+        .await
+        .unwrap();
+    assert_eq!(found.and_then(|u| u.id).as_deref(), Some("user_1"));
+
+    // Users without an ID are given one
+    let created = adaptor
+        .create_user(AdaptUser {
+            id: None,
+            ..user("", "jane.doe@email.com")
+        })
+        .await
+        .unwrap();
+    assert!(created.id.is_some());
+
+    // Emails are unique, including when changed
+    let error = adaptor
+        .create_user(user("user_2", "john.doe@email.com"))
+        .await
+        .unwrap_err();
+    assert!(matches!(error, AdaptorError::Conflict(_)), "{:?}", error);
+    let error = adaptor
+        .update_user(user("user_1", "jane.doe@email.com"))
+        .await
+        .unwrap_err();
+    assert!(matches!(error, AdaptorError::Conflict(_)), "{:?}", error);
+
+    // The email index follows the user
+    adaptor
+        .update_user(user("user_1", "johnny.doe@email.com"))
+        .await
+        .unwrap();
+    let found = adaptor
+        .get_user_by_email("john.doe@email.com".to_string())
+        .await
+        .unwrap();
+    assert!(found.is_none());
+    let found = adaptor
+        .get_user_by_email("johnny.doe@email.com".to_string())
+        .await
+        .unwrap();
+    assert_eq!(found.and_then(|u| u.id).as_deref(), Some("user_1"));
+
+    let error = adaptor
+        .update_user(user("unknown", "unknown@email.com"))
+        .await
+        .unwrap_err();
+    assert!(matches!(error, AdaptorError::NotFound(_)), "{:?}", error);
+}
+
+#[tokio::test]
+async fn test_01_memory_accounts() {
+    let adaptor = MemoryAdaptor::new();
+    adaptor
+        .create_user(user("user_1", "john.doe@email.com"))
+        .await
+        .unwrap();
+
+    let linked = adaptor
+        .link_account(account("user_1", "12345"))
+        .await
+        .expect("Failed to link account");
+    assert!(linked.id.is_some());
+    let expires_in = linked.token.and_then(|t| t.expires_in);
+    assert!(expires_in.is_some_and(|e| e > 3500 && e <= 3600));
+
+    let found = adaptor
+        .get_user_by_account(provider_account_id("12345"))
+        .await
+        .unwrap();
+    assert_eq!(found.and_then(|u| u.id).as_deref(), Some("user_1"));
+
+    // An account belongs to a single user, which must exist
+    let error = adaptor
+        .link_account(account("user_1", "12345"))
+        .await
+        .unwrap_err();
+    assert!(matches!(error, AdaptorError::Conflict(_)), "{:?}", error);
+    let error = adaptor
+        .link_account(account("unknown", "67890"))
+        .await
+        .unwrap_err();
+    assert!(matches!(error, AdaptorError::Conflict(_)), "{:?}", error);
+
+    adaptor
+        .unlink_account(provider_account_id("12345"))
+        .await
+        .unwrap();
+    let found = adaptor
+        .get_account(provider_account_id("12345"))
+        .await
+        .unwrap();
+    assert!(found.is_none());
+}
+
+#[tokio::test]
+async fn test_02_memory_sessions() {
+    let adaptor = MemoryAdaptor::new();
+    adaptor
+        .create_user(user("user_1", "john.doe@email.com"))
+        .await
+        .unwrap();
+    adaptor
+        .link_account(account("user_1", "12345"))
+        .await
+        .unwrap();
+
+    adaptor.create_session(session("token", 60)).await.unwrap();
+    let session_user = adaptor
+        .get_session_and_user("token".to_string())
+        .await
+        .unwrap()
+        .expect("Session was not found");
+    assert_eq!(session_user.user.id.as_deref(), Some("user_1"));
+
+    let mut updated = session_user.session;
+    updated.expires_in = 3600;
+    let updated = adaptor.update_session(updated).await.unwrap();
+    assert!(updated.expires_in > 60);
+
+    // Deleting the user deletes its sessions and accounts
+    adaptor.delete_user("user_1".to_string()).await.unwrap();
+    let session_user = adaptor
+        .get_session_and_user("token".to_string())
+        .await
+        .unwrap();
+    assert!(session_user.is_none());
+    let found = adaptor
+        .get_account(provider_account_id("12345"))
+        .await
+        .unwrap();
+    assert!(found.is_none());
+}
+
+#[tokio::test]
+async fn test_03_memory_eviction() {
+    let adaptor = MemoryAdaptor::new();
+    adaptor
+        .create_user(user("user_1", "john.doe@email.com"))
+        .await
+        .unwrap();
+
+    adaptor.create_session(session("expired", 0)).await.unwrap();
+    adaptor.create_session(session("active", 60)).await.unwrap();
+    adaptor
+        .create_verification_token(AdaptVerificationToken {
+            email: "john.doe@email.com".to_string(),
+            token: "expired".to_string(),
+            expires_in: 0,
+        })
+        .await
+        .unwrap();
+
+    // Expired records are not found, and are gone by the next change
+    let session_user = adaptor
+        .get_session_and_user("expired".to_string())
+        .await
+        .unwrap();
+    assert!(session_user.is_none());
+    adaptor.delete_session("unknown".to_string()).await.unwrap();
+
+    let snapshot = adaptor.snapshot().unwrap();
+    let tokens: Vec<_> = snapshot.sessions.iter().map(|s| s.token.as_str()).collect();
+    assert_eq!(tokens, vec!["active"]);
+    assert!(snapshot.verification_tokens.is_empty());
+
+    // A token that was used or expired cannot be used
+    let use_token = |token: &str| UseVerificationTokenOptions {
+        email: "john.doe@email.com".to_string(),
+        token: token.to_string(),
+    };
+    adaptor
+        .create_verification_token(AdaptVerificationToken {
+            email: "john.doe@email.com".to_string(),
+            token: "token".to_string(),
+            expires_in: 600,
+        })
+        .await
+        .unwrap();
+    let used = adaptor
+        .use_verification_token(use_token("token"))
+        .await
+        .unwrap();
+    assert!(used.is_some_and(|t| t.expires_in > 590));
+    let used = adaptor
+        .use_verification_token(use_token("token"))
+        .await
+        .unwrap();
+    assert!(used.is_none());
+    let used = adaptor
+        .use_verification_token(use_token("expired"))
+        .await
+        .unwrap();
+    assert!(used.is_none());
+}
+
+#[tokio::test]
+async fn test_04_memory_snapshot() {
+    let dir = tempfile::tempdir().expect("Failed to create a temporary directory");
+    let options = MemoryAdapterOptions {
+        snapshot_path: Some(dir.path().join("store.json")),
+    };
+
+    let adaptor = MemoryAdaptor::from_options(options.clone()).expect("Failed to open the store");
+    adaptor
+        .create_user(user("user_1", "john.doe@email.com"))
+        .await
+        .unwrap();
+    adaptor
+        .link_account(account("user_1", "12345"))
+        .await
+        .unwrap();
+    adaptor.create_session(session("token", 60)).await.unwrap();
+    drop(adaptor);
+
+    // The snapshot holds tokens, so only its owner can read it
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        let metadata = std::fs::metadata(dir.path().join("store.json")).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+    }
+
+    // The store survives a restart, indexes included
+    let adaptor = MemoryAdaptor::from_options(options).expect("Failed to reopen the store");
+    let found = adaptor
+        .get_user_by_email("john.doe@email.com".to_string())
+        .await
+        .unwrap();
+    assert_eq!(found.and_then(|u| u.id).as_deref(), Some("user_1"));
+    let found = adaptor
+        .get_user_by_account(provider_account_id("12345"))
+        .await
+        .unwrap();
+    assert_eq!(found.and_then(|u| u.id).as_deref(), Some("user_1"));
+    let session_user = adaptor
+        .get_session_and_user("token".to_string())
+        .await
+        .unwrap()
+        .expect("Session was not found");
+    assert!(session_user.session.expires_in > 50);
+
+    // A corrupt snapshot is refused rather than overwritten
+    let path = dir.path().join("corrupt.json");
+    std::fs::write(&path, "not json").unwrap();
+    let result = MemoryAdaptor::from_options(MemoryAdapterOptions {
+        snapshot_path: Some(path),
+    });
+    assert!(matches!(result, Err(AdaptorError::Other(_))));
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_05_memory_sign_in() {
+    let signals = mock::Signals::new();
+
+    let adaptor = MemoryAdaptor::new();
+    let auth_options = AuthOptions::new()
+        .add_provider(Box::new(MockProvider))
+        .with_adaptor(adaptor.clone().into());
+    let options = AxumRuntimeOptions::new(auth_options);
+
+    mock::environment::axum_::run(signals, options, || async move {
+        let cookie = session_cookie(&sign_in().await);
+        let (body, _) = get_session(Some(&cookie)).await;
+        assert_eq!(body["user"]["email"], "john.doe@email.com");
+
+        // Clones share the store
+        let snapshot = adaptor.snapshot().unwrap();
+        assert_eq!(snapshot.users.len(), 1);
+        assert_eq!(snapshot.sessions.len(), 1);
+    })
+    .await;
+}
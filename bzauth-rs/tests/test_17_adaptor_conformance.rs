@@ -0,0 +1,44 @@
+#![cfg(feature = "testing")]
+
+mod mock;
+
+use bzauth_rs::testing::adaptor_conformance;
+use mock::{JsonStore, JsonStoreTypes, MockAdaptor};
+
+#[tokio::test]
+async fn test_00_mock_adaptor_conformance() {
+    let adaptor = MockAdaptor::new(JsonStore::new(&JsonStoreTypes::Memory));
+    adaptor_conformance(&adaptor).await;
+}
+
+#[cfg(all(feature = "adapt_diesel", feature = "backend_sqlite"))]
+#[tokio::test]
+async fn test_01_diesel_sqlite_conformance() {
+    let adaptor = mock::diesel_adaptor(mock::diesel_pool());
+    adaptor_conformance(&adaptor).await;
+}
+
+#[cfg(all(feature = "adapt_sqlx", feature = "backend_sqlite"))]
+#[tokio::test]
+async fn test_02_sqlx_sqlite_conformance() {
+    use bzauth_rs::adaptors::sqlx::{SqlxAdapterOptions, SqlxAdaptor};
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    // Every connection to :memory: is a new database, so the pool keeps to one
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .expect("Failed to open the database");
+    let adaptor = SqlxAdaptor::from_options(SqlxAdapterOptions { pool });
+    adaptor.migrate().await.expect("Failed to migrate");
+    adaptor_conformance(&adaptor).await;
+}
+
+#[cfg(feature = "adapt_memory")]
+#[tokio::test]
+async fn test_03_memory_conformance() {
+    use bzauth_rs::adaptors::memory::MemoryAdaptor;
+
+    adaptor_conformance(&MemoryAdaptor::new()).await;
+}
@@ -0,0 +1,353 @@
+mod mock;
+
+use std::sync::Arc;
+
+use bzauth_rs::auth::{AuthOptions, SignInResult};
+use bzauth_rs::awaitable;
+use bzauth_rs::contracts::provide::Provide;
+use bzauth_rs::providers::email::{
+    EmailMessage, EmailProvider, EmailProviderOptions, FileTransport, MemoryTransport,
+};
+use bzauth_rs::runtimes::axum::AxumRuntimeOptions;
+use mock::runtime::MOCK_AUTH_URL;
+use mock::{
+    JsonStore, JsonStoreTypes, MockAdaptor, MockProvider, client, csrf_token, get_session,
+    session_cookie, sign_in, table,
+};
+use reqwest::StatusCode;
+use reqwest::header::{COOKIE, LOCATION, SET_COOKIE};
+
+const SENDER: &str = "no-reply@example.com";
+
+/// Asks for a sign-in link to be sent to the address
+async fn request_link(email: &str) -> reqwest::Response {
+    let (token, cookie) = csrf_token().await;
+    client()
+        .post(format!("{}/login/email", MOCK_AUTH_URL))
+        .header(COOKIE, cookie)
+        .form(&[("email", email), ("csrf_token", token.as_str())])
+        .send()
+        .await
+        .expect("Failed to make request to auth server")
+}
+
+/// Follows a sign-in link
+async fn follow_link(url: &str) -> reqwest::Response {
+    client()
+        .get(url)
+        .send()
+        .await
+        .expect("Failed to make request to auth server")
+}
+
+/// Requests a sign-in link and returns the link that was sent
+async fn sign_in_link(transport: &MemoryTransport, email: &str) -> String {
+    let response = request_link(email).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    transport
+        .last_message_to(email)
+        .map(|message| message.url)
+        .expect("No sign-in link was sent")
+}
+
+fn set_cookies(response: &reqwest::Response) -> Vec<String> {
+    response
+        .headers()
+        .get_all(SET_COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .map(String::from)
+        .collect()
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_00_email_sign_in() {
+    let signals = mock::Signals::new();
+
+    let transport = MemoryTransport::new();
+    let json_store = JsonStore::new(&JsonStoreTypes::Memory);
+    let auth_options = AuthOptions::new()
+        .add_provider(Box::new(EmailProvider::new(transport.clone(), SENDER)))
+        .with_adaptor(Box::new(MockAdaptor::new(json_store.clone())));
+    let options = AxumRuntimeOptions::new(auth_options);
+
+    mock::environment::axum_::run(signals, options, || async move {
+        // The address is normalised before anything is sent
+        let response = request_link(" John.Doe@Email.com ").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let message = transport
+            .last_message_to("john.doe@email.com")
+            .expect("No sign-in link was sent");
+        assert_eq!(message.from, SENDER);
+        assert!(message.text.contains(&message.url));
+        assert!(
+            message
+                .url
+                .starts_with(&format!("{}/callback/email?", MOCK_AUTH_URL)),
+            "Unexpected link: {}",
+            message.url
+        );
+
+        // Only a hash of the token is stored
+        let link = reqwest::Url::parse(&message.url).unwrap();
+        let token = link
+            .query_pairs()
+            .find(|(k, _)| k == "token")
+            .map(|(_, v)| v.to_string())
+            .expect("Link has no token");
+        let tokens = table(&json_store, "verification_tokens");
+        assert_eq!(tokens.len(), 1);
+        assert_ne!(tokens[0]["token"], token);
+
+        // The link creates the user and signs them in
+        let response = follow_link(&message.url).await;
+        assert_eq!(response.status(), StatusCode::FOUND);
+        let cookie = session_cookie(&set_cookies(&response));
+        let (body, _) = get_session(Some(&cookie)).await;
+        assert_eq!(body["user"]["email"], "john.doe@email.com");
+        let user_id = body["user"]["id"].clone();
+
+        // A link only works once
+        let response = follow_link(&message.url).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        // A returning user is signed in as the same user
+        let url = sign_in_link(&transport, "john.doe@email.com").await;
+        let response = follow_link(&url).await;
+        assert_eq!(response.status(), StatusCode::FOUND);
+        let cookie = session_cookie(&set_cookies(&response));
+        let (body, _) = get_session(Some(&cookie)).await;
+        assert_eq!(body["user"]["id"], user_id);
+        assert_eq!(table(&json_store, "users").len(), 1);
+        assert_eq!(table(&json_store, "accounts").len(), 1);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_01_email_existing_user() {
+    let signals = mock::Signals::new();
+
+    let transport = MemoryTransport::new();
+    let json_store = JsonStore::new(&JsonStoreTypes::Memory);
+    let auth_options = AuthOptions::new()
+        .add_provider(Box::new(MockProvider))
+        .add_provider(Box::new(EmailProvider::new(transport.clone(), SENDER)))
+        .with_adaptor(Box::new(MockAdaptor::new(json_store.clone())));
+    let options = AxumRuntimeOptions::new(auth_options);
+
+    mock::environment::axum_::run(signals, options, || async move {
+        let cookie = session_cookie(&sign_in().await);
+        let (body, _) = get_session(Some(&cookie)).await;
+        let user_id = body["user"]["id"].clone();
+
+        // The address of a user who signed up with another provider signs in as them
+        let url = sign_in_link(&transport, "john.doe@email.com").await;
+        let response = follow_link(&url).await;
+        assert_eq!(response.status(), StatusCode::FOUND);
+        let cookie = session_cookie(&set_cookies(&response));
+        let (body, _) = get_session(Some(&cookie)).await;
+        assert_eq!(body["user"]["id"], user_id);
+
+        let accounts = table(&json_store, "accounts");
+        assert_eq!(accounts.len(), 2);
+        assert!(
+            accounts
+                .iter()
+                .any(|a| a["provider_id"] == "email" && a["user_id"] == user_id),
+            "Email account was not linked: {:?}",
+            accounts
+        );
+    })
+    .await;
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_02_email_rejected() {
+    let signals = mock::Signals::new();
+
+    let transport = MemoryTransport::new();
+    let expired = EmailProvider::from_options(EmailProviderOptions {
+        transport: Some(Arc::new(transport.clone())),
+        from: Some(SENDER.to_string()),
+        max_age: Some(0),
+        ..Default::default()
+    })
+    .unwrap();
+    let auth_options = AuthOptions::new()
+        .add_provider(Box::new(expired))
+        .with_adaptor(Box::new(MockAdaptor::new(JsonStore::new(
+            &JsonStoreTypes::Memory,
+        ))));
+    let options = AxumRuntimeOptions::new(auth_options);
+
+    mock::environment::axum_::run(signals, options, || async move {
+        // Not an address, or more than one
+        for email in [
+            "",
+            "john.doe",
+            "@email.com",
+            "john@doe@email.com",
+            "a@b.com, c@d.com",
+        ] {
+            let response = request_link(email).await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", email);
+        }
+        assert!(transport.messages().is_empty());
+
+        // Without the CSRF token, any site could send links
+        let response = client()
+            .post(format!("{}/login/email", MOCK_AUTH_URL))
+            .form(&[("email", "john.doe@email.com")])
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert!(transport.messages().is_empty());
+
+        // An expired link is refused
+        let url = sign_in_link(&transport, "john.doe@email.com").await;
+        let response = follow_link(&url).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        // And so is a forged one
+        let response = follow_link(&format!(
+            "{}/callback/email?token=forged&email=john.doe%40email.com",
+            MOCK_AUTH_URL
+        ))
+        .await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_03_email_sign_in_callback() {
+    let signals = mock::Signals::new();
+
+    let transport = MemoryTransport::new();
+    let json_store = JsonStore::new(&JsonStoreTypes::Memory);
+    let auth_options = AuthOptions::new()
+        .add_provider(Box::new(
+            EmailProvider::from_options(EmailProviderOptions {
+                transport: Some(Arc::new(transport.clone())),
+                from: Some(SENDER.to_string()),
+                verify_request_url: Some("/check-your-inbox".to_string()),
+                ..Default::default()
+            })
+            .unwrap(),
+        ))
+        .with_adaptor(Box::new(MockAdaptor::new(json_store.clone())))
+        .with_callback(Arc::new(|options| {
+            let email = options.user.and_then(|u| u.email).unwrap_or_default();
+            match email.ends_with("@example.com") {
+                true => awaitable!(SignInResult::Success),
+                false => awaitable!(SignInResult::Error("Not allowed".to_string())),
+            }
+        }));
+    let options = AxumRuntimeOptions::new(auth_options);
+
+    mock::environment::axum_::run(signals, options, || async move {
+        // The user is sent on to the configured page once the link is sent
+        let response = request_link("jane.doe@email.com").await;
+        assert_eq!(response.status(), StatusCode::FOUND);
+        assert_eq!(
+            response
+                .headers()
+                .get(LOCATION)
+                .and_then(|v| v.to_str().ok()),
+            Some("http://localhost:8080/check-your-inbox")
+        );
+
+        // The sign in callback runs before the user is created
+        let url = transport
+            .last_message_to("jane.doe@email.com")
+            .map(|message| message.url)
+            .expect("No sign-in link was sent");
+        let response = follow_link(&url).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert!(table(&json_store, "users").is_empty());
+
+        let response = request_link("jane.doe@example.com").await;
+        assert_eq!(response.status(), StatusCode::FOUND);
+        let url = transport
+            .last_message_to("jane.doe@example.com")
+            .map(|message| message.url)
+            .expect("No sign-in link was sent");
+        let response = follow_link(&url).await;
+        assert_eq!(response.status(), StatusCode::FOUND);
+        assert_eq!(table(&json_store, "users").len(), 1);
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_04_email_file_transport() {
+    let dir = tempfile::tempdir().expect("Failed to create a temporary directory");
+    let outbox = dir.path().join("outbox");
+    let provider = EmailProvider::new(FileTransport::new(&outbox), SENDER);
+    assert_eq!(provider.id(), "email");
+
+    let url = provider.sign_in_url("https://app.example.com/auth", "token", "a+b@example.com");
+    assert_eq!(
+        url,
+        "https://app.example.com/auth/callback/email?token=token&email=a%2Bb%40example.com"
+    );
+    provider
+        .send_sign_in_link("a+b@example.com", &url)
+        .await
+        .expect("Failed to send the link");
+
+    let files: Vec<_> = std::fs::read_dir(&outbox)
+        .expect("The outbox was not created")
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    assert_eq!(files.len(), 1);
+    let message: EmailMessage =
+        serde_json::from_str(&std::fs::read_to_string(&files[0]).unwrap()).unwrap();
+    assert_eq!(message.to, "a+b@example.com");
+    assert_eq!(message.url, url);
+    assert_eq!(message.subject, "Sign in to app.example.com");
+
+    // The link is escaped in the HTML
+    assert!(message.html.contains("token=token&amp;email="));
+
+    // Nothing is written when the outbox cannot be created
+    let blocked = dir.path().join("blocked");
+    std::fs::write(&blocked, "").unwrap();
+    let provider = EmailProvider::new(FileTransport::new(blocked.join("outbox")), SENDER);
+    let error = provider
+        .send_sign_in_link("a+b@example.com", &url)
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("blocked"), "{}", error);
+
+    // A provider needs a transport
+    let error = EmailProvider::from_options(EmailProviderOptions {
+        from: Some(SENDER.to_string()),
+        ..Default::default()
+    })
+    .err()
+    .expect("A provider was created without a transport");
+    assert!(
+        error.to_string().contains("transport is required"),
+        "{}",
+        error
+    );
+}
@@ -0,0 +1,295 @@
+mod mock;
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use bzauth_rs::auth::{AuthOptions, SignInResult};
+use bzauth_rs::awaitable;
+use bzauth_rs::contracts::adapt::{Adapt, AdaptUser, ProviderAccountId};
+use bzauth_rs::contracts::user::User;
+use bzauth_rs::providers::credentials::{
+    Credentials, CredentialsProvider, CredentialsProviderOptions, PasswordParams,
+};
+use bzauth_rs::runtimes::axum::AxumRuntimeOptions;
+use mock::runtime::MOCK_AUTH_URL;
+use mock::{
+    JsonStore, JsonStoreTypes, JsonTableSelectQuery, MockAdaptor, client, csrf_token, get_session,
+    session_cookie, set_cookies,
+};
+use reqwest::StatusCode;
+use reqwest::header::{COOKIE, LOCATION};
+
+const FAILURE_DELAY: Duration = Duration::from_millis(300);
+
+/// A cheap password hash, as the default cost is slow in debug builds
+fn provider_options() -> CredentialsProviderOptions {
+    CredentialsProviderOptions {
+        password_params: Some(PasswordParams::new(256, 1, 1, None).unwrap()),
+        failure_delay: Some(FAILURE_DELAY),
+        ..Default::default()
+    }
+}
+
+/// Posts the credentials to the path, along with a valid CSRF token
+async fn post_credentials(path: &str, username: &str, password: &str) -> reqwest::Response {
+    let (token, cookie) = csrf_token().await;
+    client()
+        .post(format!("{}{}", MOCK_AUTH_URL, path))
+        .header(COOKIE, cookie)
+        .form(&[
+            ("username", username),
+            ("password", password),
+            ("csrf_token", token.as_str()),
+        ])
+        .send()
+        .await
+        .expect("Failed to make request to auth server")
+}
+
+async fn sign_in_with(username: &str, password: &str) -> reqwest::Response {
+    post_credentials("/callback/credentials", username, password).await
+}
+
+async fn create_user(adaptor: &dyn Adapt, id: &str, email: &str) {
+    adaptor
+        .create_user(AdaptUser {
+            id: Some(id.to_string()),
+            username: None,
+            email: Some(email.to_string()),
+            ..Default::default()
+        })
+        .await
+        .expect("Failed to create user");
+}
+
+#[tokio::test]
+async fn test_00_credentials_passwords() {
+    let provider = CredentialsProvider::from_options(provider_options());
+
+    let hash = provider.hash_password("correct horse").unwrap();
+    assert!(hash.starts_with("$argon2id$"), "Unexpected hash: {}", hash);
+    assert!(provider.verify_password("correct horse", &hash));
+    assert!(!provider.verify_password("battery staple", &hash));
+    assert!(!provider.verify_password("correct horse", "not a hash"));
+
+    // Salted, so the same password never hashes the same
+    assert_ne!(provider.hash_password("correct horse").unwrap(), hash);
+
+    // The password is never printed
+    let credentials = Credentials {
+        username: "john".to_string(),
+        password: "correct horse".to_string(),
+        ..Default::default()
+    };
+    assert!(!format!("{:?}", credentials).contains("correct horse"));
+
+    // A username belongs to a single user, and a password can be changed
+    let adaptor = MockAdaptor::new(JsonStore::new(&JsonStoreTypes::Memory));
+    create_user(&adaptor, "user_1", "john.doe@email.com").await;
+    create_user(&adaptor, "user_2", "jane.doe@email.com").await;
+    provider
+        .set_password(&adaptor, "user_1", "john", "first")
+        .await
+        .unwrap();
+    provider
+        .set_password(&adaptor, "user_1", "john", "second")
+        .await
+        .unwrap();
+    let error = provider
+        .set_password(&adaptor, "user_2", "john", "third")
+        .await
+        .unwrap_err();
+    assert_eq!(error.status, 409);
+
+    let account = adaptor
+        .get_account(ProviderAccountId {
+            provider_id: "credentials".to_string(),
+            provider_account_id: "john".to_string(),
+        })
+        .await
+        .unwrap()
+        .expect("Account was not linked");
+    let hash = account.token.and_then(|t| t.access_token).unwrap();
+    assert!(provider.verify_password("second", &hash));
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_01_credentials_password_store() {
+    let signals = mock::Signals::new();
+
+    let json_store = JsonStore::new(&JsonStoreTypes::Memory);
+    let adaptor = MockAdaptor::new(json_store.clone());
+    let provider = CredentialsProvider::from_options(provider_options());
+    create_user(&adaptor, "user_1", "john.doe@email.com").await;
+    provider
+        .set_password(&adaptor, "user_1", "john", "correct horse")
+        .await
+        .unwrap();
+
+    let auth_options = AuthOptions::new()
+        .add_provider(Box::new(provider))
+        .with_adaptor(Box::new(adaptor));
+    let options = AxumRuntimeOptions::new(auth_options);
+
+    mock::environment::axum_::run(signals, options, || async {
+        let response = sign_in_with("john", "correct horse").await;
+        assert_eq!(response.status(), StatusCode::FOUND);
+        let cookie = session_cookie(&set_cookies(&response));
+        let (body, _) = get_session(Some(&cookie)).await;
+        assert_eq!(body["user"]["id"], "user_1");
+
+        // A wrong password and an unknown user fail alike, and take as long
+        for (username, password) in [("john", "battery staple"), ("jane", "correct horse")] {
+            let started = Instant::now();
+            let response = sign_in_with(username, password).await;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+            assert!(started.elapsed() >= FAILURE_DELAY);
+            assert!(set_cookies(&response).is_empty());
+            let body = response.text().await.unwrap();
+            assert!(body.contains("Invalid credentials"), "{}", body);
+        }
+
+        // The credentials are only accepted from a form of the application
+        let response = client()
+            .post(format!("{}/callback/credentials", MOCK_AUTH_URL))
+            .form(&[("username", "john"), ("password", "correct horse")])
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = client()
+            .get(format!(
+                "{}/callback/credentials?username=john&password=correct+horse",
+                MOCK_AUTH_URL
+            ))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+
+        // The login route passes the form on to the callback route
+        let response = post_credentials("/login/credentials", "john", "correct horse").await;
+        assert_eq!(response.status(), StatusCode::TEMPORARY_REDIRECT);
+        assert_eq!(
+            response
+                .headers()
+                .get(LOCATION)
+                .and_then(|v| v.to_str().ok()),
+            Some("http://localhost:8080/callback/credentials")
+        );
+    })
+    .await;
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_02_credentials_authorize() {
+    let signals = mock::Signals::new();
+
+    let json_store = JsonStore::new(&JsonStoreTypes::Memory);
+    let provider = CredentialsProvider::from_options(CredentialsProviderOptions {
+        authorize: Some(Arc::new(|credentials: Credentials| {
+            let user = (credentials.password == "secret").then(|| User {
+                id: Some(format!("ldap:{}", credentials.username)),
+                username: Some(credentials.username.clone()),
+                email: Some(format!("{}@example.com", credentials.username)),
+                ..Default::default()
+            });
+            awaitable!(user)
+        })),
+        ..provider_options()
+    });
+    let auth_options = AuthOptions::new()
+        .add_provider(Box::new(provider))
+        .with_adaptor(Box::new(MockAdaptor::new(json_store.clone())))
+        .with_callback(Arc::new(|options| {
+            let username = options.user.and_then(|u| u.username).unwrap_or_default();
+            match username.as_str() {
+                "mallory" => awaitable!(SignInResult::Error("Blocked".to_string())),
+                _ => awaitable!(SignInResult::Success),
+            }
+        }));
+    let options = AxumRuntimeOptions::new(auth_options);
+
+    mock::environment::axum_::run(signals, options, || async {
+        // The user is stored on their first sign in, and found again after
+        for _ in 0..2 {
+            let response = sign_in_with("john", "secret").await;
+            assert_eq!(response.status(), StatusCode::FOUND);
+            let cookie = session_cookie(&set_cookies(&response));
+            let (body, _) = get_session(Some(&cookie)).await;
+            assert_eq!(body["user"]["id"], "ldap:john");
+        }
+        let users = JsonTableSelectQuery::new("users").execute(&json_store);
+        assert_eq!(users.len(), 1);
+
+        let started = Instant::now();
+        let response = sign_in_with("john", "wrong").await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert!(started.elapsed() >= FAILURE_DELAY);
+
+        // The sign in callback still runs
+        let response = sign_in_with("mallory", "secret").await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let users = JsonTableSelectQuery::new("users").execute(&json_store);
+        assert_eq!(users.len(), 1);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_03_credentials_authorize_without_id() {
+    let signals = mock::Signals::new();
+
+    // The callback only vouches for the username, leaving the ID to the adaptor
+    let json_store = JsonStore::new(&JsonStoreTypes::Memory);
+    let provider = CredentialsProvider::from_options(CredentialsProviderOptions {
+        authorize: Some(Arc::new(|credentials: Credentials| {
+            let user = (credentials.password == "secret").then(|| User {
+                id: None,
+                username: Some(credentials.username.clone()),
+                email: Some(format!("{}@example.com", credentials.username)),
+                ..Default::default()
+            });
+            awaitable!(user)
+        })),
+        ..provider_options()
+    });
+    let auth_options = AuthOptions::new()
+        .add_provider(Box::new(provider))
+        .with_adaptor(Box::new(MockAdaptor::new(json_store.clone())));
+    let options = AxumRuntimeOptions::new(auth_options);
+
+    mock::environment::axum_::run(signals, options, || async {
+        // The user is found by their username after the first sign in, rather than stored again
+        let mut user_ids = Vec::new();
+        for _ in 0..2 {
+            let response = sign_in_with("john", "secret").await;
+            assert_eq!(response.status(), StatusCode::FOUND);
+            let cookie = session_cookie(&set_cookies(&response));
+            let (body, _) = get_session(Some(&cookie)).await;
+            user_ids.push(body["user"]["id"].clone());
+        }
+        assert!(user_ids[0].is_string(), "{:?}", user_ids);
+        assert_eq!(user_ids[0], user_ids[1]);
+
+        let users = JsonTableSelectQuery::new("users").execute(&json_store);
+        assert_eq!(users.len(), 1);
+        let accounts = JsonTableSelectQuery::new("accounts").execute(&json_store);
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(accounts[0]["provider_account_id"], "john");
+        assert_eq!(accounts[0]["user_id"], user_ids[0]);
+    })
+    .await;
+}
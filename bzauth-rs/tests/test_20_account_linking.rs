@@ -0,0 +1,251 @@
+mod mock;
+
+use std::sync::{Arc, Mutex};
+
+use bzauth_rs::auth::{AccountLinking, AccountLinkingOptions, AuthOptions};
+use bzauth_rs::awaitable;
+use bzauth_rs::providers::email::{EmailProvider, MemoryTransport};
+use bzauth_rs::runtimes::axum::AxumRuntimeOptions;
+use mock::consts::{MOCK_DISCORD_USER_EMAIL, MOCK_GITHUB_USER_EMAIL};
+use mock::runtime::MOCK_AUTH_URL;
+use mock::{
+    JsonStore, JsonStoreTypes, MOCK_PROVIDER_NAME, MockAdaptor, MockProvider, client, csrf_token,
+    get_session, mock_discord_provider, mock_github_provider, session_cookie, set_cookies,
+    sign_in_with, table,
+};
+use reqwest::StatusCode;
+use reqwest::header::COOKIE;
+
+const SENDER: &str = "no-reply@example.com";
+
+/// The email of the mock provider's profile, which does not say whether it is verified
+const MOCK_PROVIDER_EMAIL: &str = "john.doe@email.com";
+
+fn auth_options(json_store: &JsonStore, transport: &MemoryTransport) -> AuthOptions {
+    AuthOptions::new()
+        .add_provider(Box::new(MockProvider))
+        .add_provider(Box::new(mock_github_provider()))
+        .add_provider(Box::new(mock_discord_provider()))
+        .add_provider(Box::new(EmailProvider::new(transport.clone(), SENDER)))
+        .with_adaptor(Box::new(MockAdaptor::new(json_store.clone())))
+}
+
+/// Signs in with a sign-in link, which verifies the email, returning the ID of the user
+async fn sign_up_by_email(transport: &MemoryTransport, email: &str) -> serde_json::Value {
+    let (token, cookie) = csrf_token().await;
+    let response = client()
+        .post(format!("{}/login/email", MOCK_AUTH_URL))
+        .header(COOKIE, cookie)
+        .form(&[("email", email), ("csrf_token", token.as_str())])
+        .send()
+        .await
+        .expect("Failed to make request to auth server");
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let url = transport
+        .last_message_to(email)
+        .map(|message| message.url)
+        .expect("No sign-in link was sent");
+    let response = client()
+        .get(url)
+        .send()
+        .await
+        .expect("Failed to make request to auth server");
+    assert_eq!(response.status(), StatusCode::FOUND);
+
+    session_user_id(&response).await
+}
+
+async fn session_user_id(response: &reqwest::Response) -> serde_json::Value {
+    let cookie = session_cookie(&set_cookies(response));
+    let (body, _) = get_session(Some(&cookie)).await;
+    body["user"]["id"].clone()
+}
+
+/// The accounts of the provider
+fn accounts_of(json_store: &JsonStore, provider_id: &str) -> Vec<serde_json::Value> {
+    table(json_store, "accounts")
+        .into_iter()
+        .filter(|a| a["provider_id"] == provider_id)
+        .collect()
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_00_account_linking_never() {
+    let signals = mock::Signals::new();
+
+    let transport = MemoryTransport::new();
+    let json_store = JsonStore::new(&JsonStoreTypes::Memory);
+    let options = AxumRuntimeOptions::new(auth_options(&json_store, &transport));
+
+    mock::environment::axum_::run(signals, options, || async move {
+        sign_up_by_email(&transport, MOCK_GITHUB_USER_EMAIL).await;
+
+        // Even verified on both sides, the email is taken by default
+        let response = sign_in_with("github").await;
+        assert_ne!(response.status(), StatusCode::FOUND);
+        let body = response.text().await.unwrap();
+        assert!(body.contains("Email is already registered"), "{}", body);
+
+        assert_eq!(table(&json_store, "users").len(), 1);
+        assert!(accounts_of(&json_store, "github").is_empty());
+    })
+    .await;
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_01_account_linking_verified_email() {
+    let signals = mock::Signals::new();
+
+    let transport = MemoryTransport::new();
+    let json_store = JsonStore::new(&JsonStoreTypes::Memory);
+    let auth_options =
+        auth_options(&json_store, &transport).with_account_linking(AccountLinking::VerifiedEmail);
+    let options = AxumRuntimeOptions::new(auth_options);
+
+    mock::environment::axum_::run(signals, options, || async move {
+        // GitHub verified the primary email, and so did the sign-in link
+        let user_id = sign_up_by_email(&transport, MOCK_GITHUB_USER_EMAIL).await;
+        let response = sign_in_with("github").await;
+        assert_eq!(response.status(), StatusCode::FOUND);
+        assert_eq!(session_user_id(&response).await, user_id);
+
+        let accounts = accounts_of(&json_store, "github");
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(accounts[0]["user_id"], user_id);
+        assert_eq!(table(&json_store, "users").len(), 1);
+
+        // The mock provider does not say the email is verified, so it is not linked
+        sign_up_by_email(&transport, MOCK_PROVIDER_EMAIL).await;
+        let response = sign_in_with(MOCK_PROVIDER_NAME).await;
+        assert_ne!(response.status(), StatusCode::FOUND);
+        assert!(accounts_of(&json_store, MOCK_PROVIDER_NAME).is_empty());
+        assert_eq!(table(&json_store, "users").len(), 2);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_02_account_linking_records_verification() {
+    let signals = mock::Signals::new();
+
+    let transport = MemoryTransport::new();
+    let json_store = JsonStore::new(&JsonStoreTypes::Memory);
+    let auth_options =
+        auth_options(&json_store, &transport).with_account_linking(AccountLinking::VerifiedEmail);
+    let options = AxumRuntimeOptions::new(auth_options);
+
+    mock::environment::axum_::run(signals, options, || async move {
+        // Users record whether their provider verified the email
+        let response = sign_in_with(MOCK_PROVIDER_NAME).await;
+        assert_eq!(response.status(), StatusCode::FOUND);
+        let users = table(&json_store, "users");
+        assert_eq!(users.len(), 1);
+        assert!(users[0]["email_verified"].is_null(), "{:?}", users);
+
+        // A sign-in link proves the email, so it signs in as them and verifies it
+        let user_id = sign_up_by_email(&transport, MOCK_PROVIDER_EMAIL).await;
+        assert_eq!(user_id, users[0]["id"]);
+        let users = table(&json_store, "users");
+        assert_eq!(users[0]["email_verified"], true);
+
+        // A GitHub user has a verified email
+        let response = sign_in_with("github").await;
+        assert_eq!(response.status(), StatusCode::FOUND);
+        let users = table(&json_store, "users");
+        let github_user = users
+            .iter()
+            .find(|u| u["email"] == MOCK_GITHUB_USER_EMAIL)
+            .expect("GitHub user was not created");
+        assert_eq!(github_user["email_verified"], true);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_03_account_linking_custom() {
+    let signals = mock::Signals::new();
+
+    let transport = MemoryTransport::new();
+    let json_store = JsonStore::new(&JsonStoreTypes::Memory);
+    let asked: Arc<Mutex<Vec<AccountLinkingOptions>>> = Arc::new(Mutex::new(Vec::new()));
+    let asked_by_callback = asked.clone();
+    let auth_options = auth_options(&json_store, &transport).with_account_linking(
+        AccountLinking::Custom(Arc::new(move |options: AccountLinkingOptions| {
+            asked_by_callback.lock().unwrap().push(options.clone());
+            let allowed = options.account.provider_id.as_deref() == Some(MOCK_PROVIDER_NAME);
+            awaitable!(allowed)
+        })),
+    );
+    let options = AxumRuntimeOptions::new(auth_options);
+
+    mock::environment::axum_::run(signals, options, || async move {
+        // The callback trusts the mock provider, although it does not verify emails
+        let user_id = sign_up_by_email(&transport, MOCK_PROVIDER_EMAIL).await;
+        let response = sign_in_with(MOCK_PROVIDER_NAME).await;
+        assert_eq!(response.status(), StatusCode::FOUND);
+        assert_eq!(session_user_id(&response).await, user_id);
+
+        let accounts = accounts_of(&json_store, MOCK_PROVIDER_NAME);
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(accounts[0]["user_id"], user_id);
+
+        // It is asked about the existing user, with the account as it would be linked
+        let options = asked.lock().unwrap().pop().expect("Callback was not run");
+        assert_eq!(options.user.id.as_deref(), user_id.as_str());
+        assert_eq!(options.account.user_id, options.user.id);
+        assert_eq!(options.profile.email.as_deref(), Some(MOCK_PROVIDER_EMAIL));
+
+        // But not GitHub
+        sign_up_by_email(&transport, MOCK_GITHUB_USER_EMAIL).await;
+        let response = sign_in_with("github").await;
+        assert_ne!(response.status(), StatusCode::FOUND);
+        assert!(accounts_of(&json_store, "github").is_empty());
+    })
+    .await;
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_04_account_linking_discord_verified_email() {
+    let signals = mock::Signals::new();
+
+    let transport = MemoryTransport::new();
+    let json_store = JsonStore::new(&JsonStoreTypes::Memory);
+    let auth_options =
+        auth_options(&json_store, &transport).with_account_linking(AccountLinking::VerifiedEmail);
+    let options = AxumRuntimeOptions::new(auth_options);
+
+    mock::environment::axum_::run(signals, options, || async move {
+        // Discord verified the email under its own `verified` field, and so did the sign-in link
+        let user_id = sign_up_by_email(&transport, MOCK_DISCORD_USER_EMAIL).await;
+        let response = sign_in_with("discord").await;
+        assert_eq!(response.status(), StatusCode::FOUND);
+        assert_eq!(session_user_id(&response).await, user_id);
+
+        let accounts = accounts_of(&json_store, "discord");
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(accounts[0]["user_id"], user_id);
+        assert_eq!(table(&json_store, "users").len(), 1);
+    })
+    .await;
+}
@@ -0,0 +1,193 @@
+mod mock;
+
+use bzauth_rs::auth::{AccountLinking, AuthOptions};
+use bzauth_rs::contracts::adapt::{Adapt, AdaptAccount, AdaptUser};
+use bzauth_rs::contracts::provide::ProviderType;
+use bzauth_rs::contracts::token::Token;
+use bzauth_rs::runtimes::axum::AxumRuntimeOptions;
+use mock::consts::{MOCK_DISCORD_USER_EMAIL, MOCK_DISCORD_USER_ID, MOCK_GITHUB_USER_ID};
+use mock::{
+    JsonStore, JsonStoreTypes, MOCK_OIDC_PROVIDER_NAME, MOCK_PROVIDER_NAME, MOCK_PROVIDER_USER_ID,
+    MockAdaptor, MockProvider, get_session, mock_discord_provider, mock_github_provider,
+    mock_oidc_provider, session_cookie, set_cookies, sign_in_with, table,
+};
+use reqwest::StatusCode;
+
+/// The email of the mock provider's profile
+const MOCK_PROVIDER_EMAIL: &str = "john.doe@email.com";
+
+/// Signs in with the provider, returning the ID of the signed in user
+async fn sign_in_as(provider_id: &str) -> serde_json::Value {
+    let response = sign_in_with(provider_id).await;
+    let status = response.status();
+    let cookies = set_cookies(&response);
+    assert_eq!(
+        status,
+        StatusCode::FOUND,
+        "Sign in failed: {}",
+        response.text().await.unwrap_or_default()
+    );
+
+    let (body, _) = get_session(Some(&session_cookie(&cookies))).await;
+    body["user"]["id"].clone()
+}
+
+/// Signs in twice with each provider, checking that the same user is signed in again and that
+/// their account is keyed by their ID at the provider
+async fn assert_returning_users(json_store: &JsonStore, expected: &[(&str, String)]) {
+    for (index, (provider_id, provider_account_id)) in expected.iter().enumerate() {
+        // The user is registered on their first sign in, and found again after
+        let user_id = sign_in_as(provider_id).await;
+        assert_eq!(sign_in_as(provider_id).await, user_id, "{}", provider_id);
+        assert_eq!(table(json_store, "users").len(), index + 1);
+
+        // The account is keyed by the user's ID at the provider, not by ours
+        let accounts: Vec<_> = table(json_store, "accounts")
+            .into_iter()
+            .filter(|a| a["provider_id"] == *provider_id)
+            .collect();
+        assert_eq!(accounts.len(), 1, "{}: {:?}", provider_id, accounts);
+        assert_eq!(accounts[0]["provider_account_id"], **provider_account_id);
+        assert_eq!(accounts[0]["user_id"], user_id);
+        assert_ne!(accounts[0]["id"], accounts[0]["provider_account_id"]);
+        assert_ne!(user_id, **provider_account_id);
+    }
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_00_returning_users() {
+    let signals = mock::Signals::new();
+
+    let json_store = JsonStore::new(&JsonStoreTypes::Memory);
+    let auth_options = AuthOptions::new()
+        .add_provider(Box::new(MockProvider))
+        .add_provider(Box::new(mock_github_provider()))
+        .with_adaptor(Box::new(MockAdaptor::new(json_store.clone())));
+    let options = AxumRuntimeOptions::new(auth_options);
+
+    mock::environment::axum_::run(signals, options, || async move {
+        let expected = [
+            (MOCK_PROVIDER_NAME, MOCK_PROVIDER_USER_ID.to_string()),
+            ("github", MOCK_GITHUB_USER_ID.to_string()),
+        ];
+        assert_returning_users(&json_store, &expected).await;
+    })
+    .await;
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_01_returning_users_oidc() {
+    let signals = mock::Signals::new();
+
+    let json_store = JsonStore::new(&JsonStoreTypes::Memory);
+    let auth_options = AuthOptions::new()
+        .add_provider(Box::new(mock_oidc_provider()))
+        .with_adaptor(Box::new(MockAdaptor::new(json_store.clone())));
+    let options = AxumRuntimeOptions::new(auth_options);
+
+    mock::environment::axum_::run(signals, options, || async move {
+        // The subject of the id_token
+        let expected = [(MOCK_OIDC_PROVIDER_NAME, "1234567890".to_string())];
+        assert_returning_users(&json_store, &expected).await;
+    })
+    .await;
+}
+
+/// Creates a user with a verified email, and an account at the provider linked under a random
+/// provider account ID, equal to its own ID
+async fn seed_legacy_account(adaptor: &MockAdaptor, provider_id: &str, email: &str) {
+    adaptor
+        .create_user(AdaptUser {
+            id: Some("user_1".to_string()),
+            email: Some(email.to_string()),
+            email_verified: Some(true),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    adaptor
+        .link_account(AdaptAccount {
+            id: Some("legacy".to_string()),
+            user_id: Some("user_1".to_string()),
+            provider_id: Some(provider_id.to_string()),
+            provider_type: ProviderType::OAuth,
+            provider_account_id: Some("legacy".to_string()),
+            token: Some(Token::default()),
+        })
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_02_returning_users_legacy_account() {
+    let signals = mock::Signals::new();
+
+    let json_store = JsonStore::new(&JsonStoreTypes::Memory);
+    let adaptor = MockAdaptor::new(json_store.clone());
+    seed_legacy_account(&adaptor, "discord", MOCK_DISCORD_USER_EMAIL).await;
+
+    let auth_options = AuthOptions::new()
+        .add_provider(Box::new(mock_discord_provider()))
+        .with_adaptor(Box::new(adaptor))
+        .with_account_linking(AccountLinking::VerifiedEmail);
+    let options = AxumRuntimeOptions::new(auth_options);
+
+    mock::environment::axum_::run(signals, options, || async move {
+        // Discord verified the email, so the account replaces the legacy one
+        assert_eq!(sign_in_as("discord").await, "user_1");
+        let accounts = table(&json_store, "accounts");
+        assert_eq!(accounts.len(), 1, "{:?}", accounts);
+        assert_eq!(accounts[0]["provider_account_id"], MOCK_DISCORD_USER_ID);
+        assert_eq!(accounts[0]["user_id"], "user_1");
+
+        // From then on, they are found by their account
+        assert_eq!(sign_in_as("discord").await, "user_1");
+        assert_eq!(table(&json_store, "users").len(), 1);
+        assert_eq!(table(&json_store, "accounts").len(), 1);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_03_legacy_account_unverified_email() {
+    let signals = mock::Signals::new();
+
+    let json_store = JsonStore::new(&JsonStoreTypes::Memory);
+    let adaptor = MockAdaptor::new(json_store.clone());
+    seed_legacy_account(&adaptor, MOCK_PROVIDER_NAME, MOCK_PROVIDER_EMAIL).await;
+
+    let auth_options = AuthOptions::new()
+        .add_provider(Box::new(MockProvider))
+        .with_adaptor(Box::new(adaptor))
+        .with_account_linking(AccountLinking::VerifiedEmail);
+    let options = AxumRuntimeOptions::new(auth_options);
+
+    mock::environment::axum_::run(signals, options, || async move {
+        // Anyone can claim an email the provider did not verify, so it cannot reach the user
+        let response = sign_in_with(MOCK_PROVIDER_NAME).await;
+        assert_ne!(response.status(), StatusCode::FOUND);
+
+        let accounts = table(&json_store, "accounts");
+        assert_eq!(accounts.len(), 1, "{:?}", accounts);
+        assert_eq!(accounts[0]["id"], "legacy");
+        assert_eq!(accounts[0]["provider_account_id"], "legacy");
+        assert_eq!(table(&json_store, "users").len(), 1);
+    })
+    .await;
+}
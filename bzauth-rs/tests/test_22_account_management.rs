@@ -0,0 +1,305 @@
+mod mock;
+
+use bzauth_rs::runtimes::axum::AxumRuntimeOptions;
+use mock::runtime::MOCK_AUTH_URL;
+use mock::{
+    JsonStore, JsonStoreTypes, MOCK_PROVIDER_NAME, auth_options, client, csrf_token, get_session,
+    session_cookie, set_cookies, sign_in_with, table,
+};
+use reqwest::StatusCode;
+use reqwest::header::{COOKIE, LOCATION, SET_COOKIE};
+
+/// Signs in with the provider, returning the session cookie and the ID of the user
+async fn sign_in_as(provider_id: &str) -> (String, serde_json::Value) {
+    let response = sign_in_with(provider_id).await;
+    assert_eq!(response.status(), StatusCode::FOUND);
+    let cookie = session_cookie(&set_cookies(&response));
+    let cookie = cookie.split(';').next().unwrap_or_default().to_string();
+
+    let (body, _) = get_session(Some(&cookie)).await;
+    (cookie, body["user"]["id"].clone())
+}
+
+/// Goes through the login, authorisation and callback of the provider with `?link=true`, as the
+/// user of the session cookie, returning the response of the callback
+async fn link_with(provider_id: &str, session_cookie: &str) -> reqwest::Response {
+    let login_url = format!("{}/login/{}?link=true", MOCK_AUTH_URL, provider_id);
+    authorise(provider_id, &login_url, session_cookie, |_| {}).await
+}
+
+/// Goes through the login at `login_url`, then the authorisation and callback of the provider,
+/// sending `cookie` along. The cookies set by the login can be altered before the callback.
+async fn authorise(
+    provider_id: &str,
+    login_url: &str,
+    cookie: &str,
+    alter_cookies: impl FnOnce(&mut Vec<String>),
+) -> reqwest::Response {
+    let response = client()
+        .get(login_url)
+        .header(COOKIE, cookie)
+        .send()
+        .await
+        .expect("Failed to make request to auth server");
+    assert!(response.status().is_redirection(), "Login did not redirect");
+    let location = response
+        .headers()
+        .get(LOCATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| reqwest::Url::parse(v).ok())
+        .expect("Login response has no location");
+    let state = location
+        .query_pairs()
+        .find(|(k, _)| k == "state")
+        .map(|(_, v)| v.to_string())
+        .expect("Login location has no state");
+
+    let authorised = reqwest::get(location.clone())
+        .await
+        .expect("Failed to make request to provider");
+    assert!(authorised.status().is_success());
+
+    let mut cookies = response
+        .headers()
+        .get_all(SET_COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .filter_map(|v| v.split(';').next())
+        .filter(|v| !v.ends_with('='))
+        .map(String::from)
+        .collect::<Vec<_>>();
+    alter_cookies(&mut cookies);
+    cookies.push(cookie.to_string());
+
+    client()
+        .get(format!("{}/callback/{}", MOCK_AUTH_URL, provider_id))
+        .query(&[("code", "mock_auth_code"), ("state", state.as_str())])
+        .header(COOKIE, cookies.join("; "))
+        .send()
+        .await
+        .expect("Failed to make request to auth server")
+}
+
+/// Requests `/accounts` with the session cookie
+async fn get_accounts(session_cookie: &str) -> reqwest::Response {
+    client()
+        .get(format!("{}/accounts", MOCK_AUTH_URL))
+        .header(COOKIE, session_cookie)
+        .send()
+        .await
+        .expect("Failed to make request to auth server")
+}
+
+/// Posts to `/accounts/{provider}/unlink` with the session cookie, the form and a CSRF token
+async fn unlink(
+    provider_id: &str,
+    session_cookie: &str,
+    form: &[(&str, &str)],
+) -> reqwest::Response {
+    let (token, csrf_cookie) = csrf_token().await;
+    let mut form = form.to_vec();
+    form.push(("csrf_token", &token));
+    client()
+        .post(format!("{}/accounts/{}/unlink", MOCK_AUTH_URL, provider_id))
+        .header(COOKIE, format!("{}; {}", session_cookie, csrf_cookie))
+        .form(&form)
+        .send()
+        .await
+        .expect("Failed to make request to auth server")
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_00_link_account() {
+    let signals = mock::Signals::new();
+
+    let json_store = JsonStore::new(&JsonStoreTypes::Memory);
+    let options = AxumRuntimeOptions::new(auth_options(&json_store));
+
+    mock::environment::axum_::run(signals, options, || async move {
+        let (cookie, user_id) = sign_in_as(MOCK_PROVIDER_NAME).await;
+
+        // The GitHub account is attached to the signed-in user, whose email differs
+        let response = link_with("github", &cookie).await;
+        assert_eq!(response.status(), StatusCode::FOUND);
+        assert_eq!(table(&json_store, "users").len(), 1);
+        let accounts = table(&json_store, "accounts");
+        assert_eq!(accounts.len(), 2);
+        assert!(accounts.iter().all(|a| a["user_id"] == user_id));
+
+        // Both are listed, without their tokens
+        let response = get_accounts(&cookie).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = response.json().await.unwrap();
+        let accounts = body["accounts"].as_array().unwrap();
+        assert_eq!(accounts.len(), 2);
+        assert_eq!(accounts[0]["provider_id"], MOCK_PROVIDER_NAME);
+        assert_eq!(accounts[1]["provider_id"], "github");
+        assert!(accounts.iter().all(|a| a.get("token").is_none()));
+
+        // Linking again changes nothing, and GitHub now signs in as the same user
+        let response = link_with("github", &cookie).await;
+        assert_eq!(response.status(), StatusCode::FOUND);
+        assert_eq!(table(&json_store, "accounts").len(), 2);
+        assert_eq!(sign_in_as("github").await.1, user_id);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_01_link_account_refused() {
+    let signals = mock::Signals::new();
+
+    let json_store = JsonStore::new(&JsonStoreTypes::Memory);
+    let options = AxumRuntimeOptions::new(auth_options(&json_store));
+
+    mock::environment::axum_::run(signals, options, || async move {
+        // Linking requires a session
+        let response = client()
+            .get(format!("{}/login/github?link=true", MOCK_AUTH_URL))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let response = get_accounts("").await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        // The GitHub account belongs to another user
+        let (_, github_user_id) = sign_in_as("github").await;
+        let (cookie, _) = sign_in_as(MOCK_PROVIDER_NAME).await;
+        let response = link_with("github", &cookie).await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let body = response.text().await.unwrap();
+        assert!(body.contains("already linked to another user"), "{}", body);
+
+        let accounts = table(&json_store, "accounts");
+        let github_account = accounts
+            .iter()
+            .find(|a| a["provider_id"] == "github")
+            .unwrap();
+        assert_eq!(github_account["user_id"], github_user_id);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_02_unlink_account() {
+    let signals = mock::Signals::new();
+
+    let json_store = JsonStore::new(&JsonStoreTypes::Memory);
+    let options = AxumRuntimeOptions::new(auth_options(&json_store));
+
+    mock::environment::axum_::run(signals, options, || async move {
+        let (cookie, user_id) = sign_in_as(MOCK_PROVIDER_NAME).await;
+
+        // The only account is the last way to sign in
+        let response = unlink(MOCK_PROVIDER_NAME, &cookie, &[]).await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        assert_eq!(table(&json_store, "accounts").len(), 1);
+
+        let response = link_with("github", &cookie).await;
+        assert_eq!(response.status(), StatusCode::FOUND);
+
+        // A form without a CSRF token is refused
+        let response = client()
+            .post(format!("{}/accounts/github/unlink", MOCK_AUTH_URL))
+            .header(COOKIE, &cookie)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let response = unlink("unknown", &cookie, &[]).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        // The remaining accounts are returned
+        let response = unlink("github", &cookie, &[]).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(body["accounts"].as_array().unwrap().len(), 1);
+        assert_eq!(body["accounts"][0]["provider_id"], MOCK_PROVIDER_NAME);
+
+        let accounts = table(&json_store, "accounts");
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(accounts[0]["user_id"], user_id);
+
+        // GitHub now signs up a new user
+        assert_ne!(sign_in_as("github").await.1, user_id);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_03_link_cookie_refuses_state() {
+    let signals = mock::Signals::new();
+
+    let json_store = JsonStore::new(&JsonStoreTypes::Memory);
+    let options = AxumRuntimeOptions::new(auth_options(&json_store));
+
+    mock::environment::axum_::run(signals, options, || async move {
+        // The signed state is replayed as a link cookie, which is signed for another purpose
+        let login_url = format!("{}/login/{}", MOCK_AUTH_URL, MOCK_PROVIDER_NAME);
+        let response = authorise(MOCK_PROVIDER_NAME, &login_url, "", |cookies| {
+            let signed_state = cookies
+                .iter()
+                .find_map(|c| c.strip_prefix("state="))
+                .map(String::from)
+                .expect("No state cookie was set");
+            cookies.push(format!("link={}", signed_state));
+        })
+        .await;
+
+        // So the callback signs the user in rather than linking an account
+        assert_eq!(response.status(), StatusCode::FOUND);
+        assert_eq!(table(&json_store, "users").len(), 1);
+        assert_eq!(table(&json_store, "accounts").len(), 1);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_04_unlink_refuses_other_origins() {
+    let signals = mock::Signals::new();
+
+    let json_store = JsonStore::new(&JsonStoreTypes::Memory);
+    let options = AxumRuntimeOptions::new(auth_options(&json_store));
+
+    mock::environment::axum_::run(signals, options, || async move {
+        let (cookie, _) = sign_in_as(MOCK_PROVIDER_NAME).await;
+
+        // URLs starting with the origin, but leading to another host, go back to the origin
+        for callback_url in [
+            format!("{}.evil.example.com/", MOCK_AUTH_URL),
+            format!("{}@evil.example.com/", MOCK_AUTH_URL),
+        ] {
+            let response = link_with("github", &cookie).await;
+            assert_eq!(response.status(), StatusCode::FOUND);
+
+            let response = unlink("github", &cookie, &[("callback_url", &callback_url)]).await;
+            assert_eq!(response.status(), StatusCode::FOUND);
+            let location = response.headers().get(LOCATION).unwrap().to_str().unwrap();
+            assert_eq!(location, MOCK_AUTH_URL, "Redirected to {}", callback_url);
+        }
+        assert_eq!(table(&json_store, "accounts").len(), 1);
+    })
+    .await;
+}
@@ -0,0 +1,320 @@
+mod mock;
+
+use bzauth_rs::auth::Auth;
+use bzauth_rs::contracts::adapt::{Adapt, AdaptAccount, AdaptUser, ProviderAccountId};
+use bzauth_rs::contracts::provide::ProviderType;
+use bzauth_rs::contracts::token::Token;
+use bzauth_rs::runtimes::axum::AxumRuntimeOptions;
+use mock::consts::MOCK_GITHUB_USER_ID;
+use mock::provider_server::{MOCK_RACED_REFRESH_TOKEN, refresh_race_started, settle_refresh_race};
+use mock::{
+    JsonStore, JsonStoreTypes, MOCK_PROVIDER_NAME, MOCK_PROVIDER_USER_ID, MockAdaptor,
+    auth_options, sign_in, stored_token,
+};
+use reqwest::StatusCode;
+
+const USER_ID: &str = "user_1";
+
+/// Creates a user with an account at the mock provider holding the token
+async fn seed_account(json_store: &JsonStore, token: Token) {
+    let adaptor = MockAdaptor::new(json_store.clone());
+    adaptor
+        .create_user(AdaptUser {
+            id: Some(USER_ID.to_string()),
+            email: Some("john.doe@email.com".to_string()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    adaptor
+        .link_account(AdaptAccount {
+            id: Some("account_1".to_string()),
+            user_id: Some(USER_ID.to_string()),
+            provider_id: Some(MOCK_PROVIDER_NAME.to_string()),
+            provider_type: ProviderType::OAuth,
+            provider_account_id: Some(MOCK_PROVIDER_USER_ID.to_string()),
+            token: Some(token),
+        })
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_00_provider_access_token_refresh() {
+    let signals = mock::Signals::new();
+
+    let json_store = JsonStore::new(&JsonStoreTypes::Memory);
+    let auth = Auth::from_options(auth_options(&json_store));
+    let options = AxumRuntimeOptions::new(auth_options(&json_store));
+
+    mock::environment::axum_::run(signals, options, || async move {
+        seed_account(
+            &json_store,
+            Token {
+                access_token: Some("stored_access_token".to_string()),
+                refresh_token: Some("mock_refresh_token".to_string()),
+                expires_in: Some(3600),
+                scope: Some("openid".to_string()),
+                ..Default::default()
+            },
+        )
+        .await;
+
+        // A valid token is returned as is
+        let access_token = auth
+            .get_provider_access_token(USER_ID, MOCK_PROVIDER_NAME)
+            .await
+            .unwrap();
+        assert_eq!(access_token, "stored_access_token");
+
+        // An expiring one is refreshed, and the rotated tokens are stored
+        let adaptor = MockAdaptor::new(json_store.clone());
+        let mut account = adaptor
+            .get_account(ProviderAccountId {
+                provider_id: MOCK_PROVIDER_NAME.to_string(),
+                provider_account_id: MOCK_PROVIDER_USER_ID.to_string(),
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        account.token.as_mut().unwrap().expires_in = Some(30);
+        adaptor.update_account(account).await.unwrap();
+
+        let access_token = auth
+            .get_provider_access_token(USER_ID, MOCK_PROVIDER_NAME)
+            .await
+            .unwrap();
+        assert_eq!(access_token, "mock_refreshed_access_token");
+        let token = stored_token(&json_store, MOCK_PROVIDER_NAME, MOCK_PROVIDER_USER_ID).await;
+        assert_eq!(
+            token.access_token.as_deref(),
+            Some("mock_refreshed_access_token")
+        );
+        assert_eq!(
+            token.refresh_token.as_deref(),
+            Some("mock_rotated_refresh_token")
+        );
+        assert_eq!(token.expires_in, Some(3600));
+        // The scope was not issued again, so it is kept
+        assert_eq!(token.scope.as_deref(), Some("openid"));
+
+        // Accounts and providers that do not exist are not found
+        let error = auth
+            .get_provider_access_token(USER_ID, "github")
+            .await
+            .unwrap_err();
+        assert_eq!(error.status, StatusCode::NOT_FOUND.as_u16());
+        let error = auth
+            .get_provider_access_token(USER_ID, "unknown")
+            .await
+            .unwrap_err();
+        assert_eq!(error.status, StatusCode::NOT_FOUND.as_u16());
+    })
+    .await;
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_01_provider_access_token_refused() {
+    let signals = mock::Signals::new();
+
+    let json_store = JsonStore::new(&JsonStoreTypes::Memory);
+    let auth = Auth::from_options(auth_options(&json_store));
+    let options = AxumRuntimeOptions::new(auth_options(&json_store));
+
+    mock::environment::axum_::run(signals, options, || async move {
+        seed_account(
+            &json_store,
+            Token {
+                access_token: Some("stored_access_token".to_string()),
+                refresh_token: Some("revoked_refresh_token".to_string()),
+                expires_in: Some(0),
+                ..Default::default()
+            },
+        )
+        .await;
+
+        // The provider refuses the refresh token, so the account is flagged
+        let error = auth
+            .get_provider_access_token(USER_ID, MOCK_PROVIDER_NAME)
+            .await
+            .unwrap_err();
+        assert_eq!(error.status, StatusCode::UNAUTHORIZED.as_u16());
+        assert!(
+            error.message.contains("Reauthorisation required"),
+            "{}",
+            error
+        );
+        let token = stored_token(&json_store, MOCK_PROVIDER_NAME, MOCK_PROVIDER_USER_ID).await;
+        assert!(token.access_token.is_none());
+        assert!(token.refresh_token.is_none());
+
+        // It stays so without asking the provider again
+        let error = auth
+            .get_provider_access_token(USER_ID, MOCK_PROVIDER_NAME)
+            .await
+            .unwrap_err();
+        assert_eq!(error.status, StatusCode::UNAUTHORIZED.as_u16());
+
+        // Until the user signs in with the provider again
+        sign_in().await;
+        let access_token = auth
+            .get_provider_access_token(USER_ID, MOCK_PROVIDER_NAME)
+            .await
+            .unwrap();
+        assert_eq!(access_token, "mock_access_token");
+    })
+    .await;
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_02_returning_sign_in_keeps_refresh_token() {
+    let signals = mock::Signals::new();
+
+    let json_store = JsonStore::new(&JsonStoreTypes::Memory);
+    let options = AxumRuntimeOptions::new(auth_options(&json_store));
+
+    mock::environment::axum_::run(signals, options, || async move {
+        // GitHub does not issue refresh tokens
+        let response = mock::sign_in_with("github").await;
+        assert_eq!(response.status(), StatusCode::FOUND);
+        let github_account_id = MOCK_GITHUB_USER_ID.to_string();
+
+        let adaptor = MockAdaptor::new(json_store.clone());
+        let mut account = adaptor
+            .get_account(ProviderAccountId {
+                provider_id: "github".to_string(),
+                provider_account_id: github_account_id.clone(),
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        account.token = Some(Token {
+            access_token: Some("stale_access_token".to_string()),
+            refresh_token: Some("kept_refresh_token".to_string()),
+            ..Default::default()
+        });
+        adaptor.update_account(account).await.unwrap();
+
+        // Signing in again stores the new access token, and keeps the refresh token
+        let response = mock::sign_in_with("github").await;
+        assert_eq!(response.status(), StatusCode::FOUND);
+        let token = stored_token(&json_store, "github", &github_account_id).await;
+        assert_eq!(token.access_token.as_deref(), Some("mock_github_token"));
+        assert_eq!(token.refresh_token.as_deref(), Some("kept_refresh_token"));
+    })
+    .await;
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_03_refused_refresh_keeps_concurrently_rotated_tokens() {
+    let signals = mock::Signals::new();
+
+    let json_store = JsonStore::new(&JsonStoreTypes::Memory);
+    let auth = std::sync::Arc::new(Auth::from_options(auth_options(&json_store)));
+    let options = AxumRuntimeOptions::new(auth_options(&json_store));
+
+    mock::environment::axum_::run(signals, options, || async move {
+        seed_account(
+            &json_store,
+            Token {
+                access_token: Some("stored_access_token".to_string()),
+                refresh_token: Some(MOCK_RACED_REFRESH_TOKEN.to_string()),
+                expires_in: Some(0),
+                ..Default::default()
+            },
+        )
+        .await;
+
+        let refresh = tokio::spawn({
+            let auth = auth.clone();
+            async move {
+                auth.get_provider_access_token(USER_ID, MOCK_PROVIDER_NAME)
+                    .await
+            }
+        });
+
+        // Another refresh rotates the tokens while the provider holds this one
+        refresh_race_started().await;
+        let adaptor = MockAdaptor::new(json_store.clone());
+        let mut account = adaptor
+            .get_account(ProviderAccountId {
+                provider_id: MOCK_PROVIDER_NAME.to_string(),
+                provider_account_id: MOCK_PROVIDER_USER_ID.to_string(),
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        account.token = Some(Token {
+            access_token: Some("winner_access_token".to_string()),
+            refresh_token: Some("mock_rotated_refresh_token".to_string()),
+            expires_in: Some(3600),
+            ..Default::default()
+        });
+        adaptor.update_account(account).await.unwrap();
+        settle_refresh_race();
+
+        // The refused refresh returns the rotated access token and keeps the tokens
+        let access_token = refresh.await.unwrap().unwrap();
+        assert_eq!(access_token, "winner_access_token");
+        let token = stored_token(&json_store, MOCK_PROVIDER_NAME, MOCK_PROVIDER_USER_ID).await;
+        assert_eq!(token.access_token.as_deref(), Some("winner_access_token"));
+        assert_eq!(
+            token.refresh_token.as_deref(),
+            Some("mock_rotated_refresh_token")
+        );
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_04_provider_access_token_ambiguous_account() {
+    let json_store = JsonStore::new(&JsonStoreTypes::Memory);
+    let auth = Auth::from_options(auth_options(&json_store));
+
+    seed_account(
+        &json_store,
+        Token {
+            access_token: Some("stored_access_token".to_string()),
+            expires_in: Some(3600),
+            ..Default::default()
+        },
+    )
+    .await;
+    MockAdaptor::new(json_store.clone())
+        .link_account(AdaptAccount {
+            id: Some("account_2".to_string()),
+            user_id: Some(USER_ID.to_string()),
+            provider_id: Some(MOCK_PROVIDER_NAME.to_string()),
+            provider_type: ProviderType::OAuth,
+            provider_account_id: Some("second_account".to_string()),
+            token: Some(Token {
+                access_token: Some("other_access_token".to_string()),
+                ..Default::default()
+            }),
+        })
+        .await
+        .unwrap();
+
+    // Which of the two accounts is meant cannot be told
+    let error = auth
+        .get_provider_access_token(USER_ID, MOCK_PROVIDER_NAME)
+        .await
+        .unwrap_err();
+    assert_eq!(error.status, StatusCode::CONFLICT.as_u16());
+}
@@ -0,0 +1,229 @@
+mod mock;
+
+use bzauth_rs::auth::{Auth, TokenRevocationOptions};
+use bzauth_rs::contracts::adapt::{Adapt, AdaptAccount, AdaptUser, ProviderAccountId};
+use bzauth_rs::contracts::provide::ProviderType;
+use bzauth_rs::contracts::token::Token;
+use bzauth_rs::runtimes::axum::AxumRuntimeOptions;
+use mock::provider_server::{MOCK_UNREVOCABLE_TOKEN, revoked_tokens};
+use mock::runtime::MOCK_AUTH_URL;
+use mock::{
+    JsonStore, JsonStoreTypes, MOCK_PROVIDER_NAME, MOCK_PROVIDER_USER_ID, MockAdaptor,
+    auth_options, client, csrf_token, session_cookie, sign_in, stored_token,
+};
+use reqwest::StatusCode;
+use reqwest::header::COOKIE;
+
+const USER_ID: &str = "user_1";
+
+/// Creates a user with an account at the mock provider holding the tokens, and a GitHub account
+async fn seed_accounts(json_store: &JsonStore, access_token: &str, refresh_token: &str) {
+    let adaptor = MockAdaptor::new(json_store.clone());
+    adaptor
+        .create_user(AdaptUser {
+            id: Some(USER_ID.to_string()),
+            email: Some("john.doe@email.com".to_string()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    for (provider_id, provider_account_id) in [
+        (MOCK_PROVIDER_NAME, MOCK_PROVIDER_USER_ID),
+        ("github", "github_user_id"),
+    ] {
+        adaptor
+            .link_account(AdaptAccount {
+                id: Some(format!("{}_account", provider_id)),
+                user_id: Some(USER_ID.to_string()),
+                provider_id: Some(provider_id.to_string()),
+                provider_type: ProviderType::OAuth,
+                provider_account_id: Some(provider_account_id.to_string()),
+                token: Some(Token {
+                    access_token: Some(access_token.to_string()),
+                    refresh_token: Some(refresh_token.to_string()),
+                    ..Default::default()
+                }),
+            })
+            .await
+            .unwrap();
+    }
+}
+
+fn mock_account() -> ProviderAccountId {
+    ProviderAccountId {
+        provider_id: MOCK_PROVIDER_NAME.to_string(),
+        provider_account_id: MOCK_PROVIDER_USER_ID.to_string(),
+    }
+}
+
+/// The revocations of the token so far, by type hint
+fn revocations(token: &str) -> Vec<String> {
+    revoked_tokens()
+        .into_iter()
+        .filter(|(revoked, _)| revoked == token)
+        .map(|(_, token_type_hint)| token_type_hint)
+        .collect()
+}
+
+/// Posts to `/logout` with the session cookie and a CSRF token, signing out of the mock provider
+async fn logout(session_cookie: &str) {
+    let (token, csrf_cookie) = csrf_token().await;
+    let response = client()
+        .post(format!("{}/logout", MOCK_AUTH_URL))
+        .header(COOKIE, format!("{}; {}", session_cookie, csrf_cookie))
+        .form(&[
+            ("csrf_token", token.as_str()),
+            ("provider", MOCK_PROVIDER_NAME),
+        ])
+        .send()
+        .await
+        .expect("Failed to make request to auth server");
+    assert_eq!(response.status(), StatusCode::FOUND);
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_00_revoke_on_unlink() {
+    let signals = mock::Signals::new();
+
+    let json_store = JsonStore::new(&JsonStoreTypes::Memory);
+    let auth = Auth::from_options(auth_options(&json_store));
+    let options = AxumRuntimeOptions::new(auth_options(&json_store));
+
+    mock::environment::axum_::run(signals, options, || async move {
+        seed_accounts(&json_store, "unlink_access_token", "unlink_refresh_token").await;
+
+        // The refresh token is revoked first, then the access token
+        auth.unlink_account(mock_account()).await.unwrap();
+        assert_eq!(revocations("unlink_refresh_token"), ["refresh_token"]);
+        assert_eq!(revocations("unlink_access_token"), ["access_token"]);
+        let adaptor = MockAdaptor::new(json_store.clone());
+        assert!(adaptor.get_account(mock_account()).await.unwrap().is_none());
+
+        // GitHub has no revocation endpoint, so it is only unlinked
+        let error = auth
+            .unlink_account(ProviderAccountId {
+                provider_id: "github".to_string(),
+                provider_account_id: "unknown".to_string(),
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(error.status, StatusCode::NOT_FOUND.as_u16());
+        auth.unlink_account(ProviderAccountId {
+            provider_id: "github".to_string(),
+            provider_account_id: "github_user_id".to_string(),
+        })
+        .await
+        .unwrap();
+        assert_eq!(revocations("unlink_access_token").len(), 1);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_01_revoke_on_delete_user() {
+    let signals = mock::Signals::new();
+
+    let json_store = JsonStore::new(&JsonStoreTypes::Memory);
+    let auth = Auth::from_options(auth_options(&json_store));
+    let options = AxumRuntimeOptions::new(auth_options(&json_store));
+
+    mock::environment::axum_::run(signals, options, || async move {
+        // The provider fails to revoke the refresh token, but the access token is revoked and the
+        // user deleted regardless
+        seed_accounts(&json_store, "deleted_access_token", MOCK_UNREVOCABLE_TOKEN).await;
+
+        auth.delete_user(USER_ID).await.unwrap();
+        assert!(revocations(MOCK_UNREVOCABLE_TOKEN).is_empty());
+        assert_eq!(revocations("deleted_access_token"), ["access_token"]);
+        let adaptor = MockAdaptor::new(json_store.clone());
+        assert!(
+            adaptor
+                .get_user(USER_ID.to_string())
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert!(adaptor.get_account(mock_account()).await.unwrap().is_none());
+
+        // Revocation can be turned off
+        let auth = Auth::from_options(auth_options(&json_store).with_token_revocation(
+            TokenRevocationOptions {
+                on_delete_user: Some(false),
+                ..Default::default()
+            },
+        ));
+        seed_accounts(&json_store, "kept_access_token", "kept_refresh_token").await;
+
+        auth.delete_user(USER_ID).await.unwrap();
+        assert!(revocations("kept_access_token").is_empty());
+        assert!(revocations("kept_refresh_token").is_empty());
+    })
+    .await;
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_02_revoke_on_sign_out() {
+    let signals = mock::Signals::new();
+
+    let json_store = JsonStore::new(&JsonStoreTypes::Memory);
+    let options = AxumRuntimeOptions::new(auth_options(&json_store).with_token_revocation(
+        TokenRevocationOptions {
+            on_sign_out: Some(true),
+            ..Default::default()
+        },
+    ));
+
+    mock::environment::axum_::run(signals, options, || async move {
+        let revoked = revocations("mock_refresh_token").len();
+        let cookie = session_cookie(&sign_in().await);
+        let cookie = cookie.split(';').next().unwrap_or_default().to_string();
+
+        // The tokens are revoked, and cleared until the user signs in again
+        logout(&cookie).await;
+        assert_eq!(revocations("mock_refresh_token").len(), revoked + 1);
+        let token = stored_token(&json_store, MOCK_PROVIDER_NAME, MOCK_PROVIDER_USER_ID).await;
+        assert!(token.access_token.is_none());
+        assert!(token.refresh_token.is_none());
+
+        sign_in().await;
+        let token = stored_token(&json_store, MOCK_PROVIDER_NAME, MOCK_PROVIDER_USER_ID).await;
+        assert_eq!(token.access_token.as_deref(), Some("mock_access_token"));
+    })
+    .await;
+}
+
+#[tokio::test]
+#[cfg_attr(
+    not(feature = "test_sequential"),
+    ignore = "this test cannot run in parallel"
+)]
+async fn test_03_sign_out_keeps_tokens_by_default() {
+    let signals = mock::Signals::new();
+
+    let json_store = JsonStore::new(&JsonStoreTypes::Memory);
+    let options = AxumRuntimeOptions::new(auth_options(&json_store));
+
+    mock::environment::axum_::run(signals, options, || async move {
+        let revoked = revocations("mock_refresh_token").len();
+        let cookie = session_cookie(&sign_in().await);
+        let cookie = cookie.split(';').next().unwrap_or_default().to_string();
+
+        logout(&cookie).await;
+        assert_eq!(revocations("mock_refresh_token").len(), revoked);
+        let token = stored_token(&json_store, MOCK_PROVIDER_NAME, MOCK_PROVIDER_USER_ID).await;
+        assert_eq!(token.refresh_token.as_deref(), Some("mock_refresh_token"));
+    })
+    .await;
+}